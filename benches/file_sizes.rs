@@ -0,0 +1,35 @@
+//! Benchmarks of the computation of directory sizes, over a generated directory
+//! Run with `cargo bench --bench file_sizes`
+
+use std::fs;
+use std::path::Path;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use broot::file_sizes::Size;
+use broot::task_sync::TaskLifetime;
+
+// 20 directories of 10 subdirectories, each one holding 20 files of 1 to 20 KB
+fn fill(root: &Path) {
+    for i in 0..20 {
+        for j in 0..10 {
+            let dir = root.join(format!("dir_{}", i)).join(format!("sub_{}", j));
+            fs::create_dir_all(&dir).unwrap();
+            for k in 0..20 {
+                fs::write(dir.join(format!("file_{}.bin", k)), vec![0u8; 1024 * (k + 1)]).unwrap();
+            }
+        }
+    }
+}
+
+fn file_sizes(c: &mut Criterion) {
+    let dir = tempfile::tempdir().unwrap();
+    fill(dir.path());
+    let root = dir.path().to_path_buf();
+    c.bench_function("size of a 4000 files directory", |b| {
+        b.iter(|| Size::compute_dir(&root, &TaskLifetime::unlimited()))
+    });
+}
+
+criterion_group!(benches, file_sizes);
+criterion_main!(benches);
@@ -0,0 +1,45 @@
+//! Benchmarks of pattern matching, over a synthetic corpus of names
+//! Run with `cargo bench --bench patterns`
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use broot::patterns::Pattern;
+
+const WORDS: &[&str] = &[
+    "main", "tree", "build", "config", "readme", "test", "lib", "screen", "status", "verb",
+];
+const EXTENSIONS: &[&str] = &["rs", "toml", "md", "txt", "json", "png"];
+
+// 100k names looking like real ones, always the same
+fn corpus() -> Vec<String> {
+    (0..100_000)
+        .map(|i| {
+            format!(
+                "{}_{}_{}.{}",
+                WORDS[i % WORDS.len()],
+                WORDS[(i / 7) % WORDS.len()],
+                i,
+                EXTENSIONS[i % EXTENSIONS.len()],
+            )
+        })
+        .collect()
+}
+
+fn find_all(pattern: &Pattern, names: &[String]) -> usize {
+    names.iter().filter(|name| pattern.find(name).is_some()).count()
+}
+
+fn patterns(c: &mut Criterion) {
+    let names = corpus();
+    c.bench_function("fuzzy find over 100k names", |b| {
+        let pattern = Pattern::fuzzy("trbd");
+        b.iter(|| find_all(black_box(&pattern), &names))
+    });
+    c.bench_function("regex find over 100k names", |b| {
+        let pattern = Pattern::regex("tree.*\\.rs$", "").unwrap();
+        b.iter(|| find_all(black_box(&pattern), &names))
+    });
+}
+
+criterion_group!(benches, patterns);
+criterion_main!(benches);
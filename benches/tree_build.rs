@@ -0,0 +1,53 @@
+//! Benchmarks of the tree builder, over a generated directory
+//! Run with `cargo bench --bench tree_build`
+
+use std::fs;
+use std::path::Path;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use broot::patterns::Pattern;
+use broot::task_sync::TaskLifetime;
+use broot::tree_build::TreeBuilder;
+use broot::tree_options::TreeOptions;
+
+// a hierarchy of 10*10 directories, each one holding 30 files
+fn fill(root: &Path) {
+    for i in 0..10 {
+        for j in 0..10 {
+            let dir = root.join(format!("dir_{}", i)).join(format!("sub_{}", j));
+            fs::create_dir_all(&dir).unwrap();
+            for k in 0..30 {
+                fs::write(dir.join(format!("file_{}_{}_{}.txt", i, j, k)), "").unwrap();
+            }
+        }
+    }
+}
+
+fn build(root: &Path, options: &TreeOptions) {
+    let builder = TreeBuilder::from(root.to_path_buf(), options.clone(), 40).unwrap();
+    builder.build(&TaskLifetime::unlimited()).unwrap();
+}
+
+fn tree_build(c: &mut Criterion) {
+    let dir = tempfile::tempdir().unwrap();
+    fill(dir.path());
+    let root = dir.path().to_path_buf();
+    c.bench_function("build without pattern", |b| {
+        let options = TreeOptions::new();
+        b.iter(|| build(&root, &options))
+    });
+    c.bench_function("build with fuzzy pattern", |b| {
+        let mut options = TreeOptions::new();
+        options.pattern = Pattern::fuzzy("f27");
+        b.iter(|| build(&root, &options))
+    });
+    c.bench_function("build with regex pattern", |b| {
+        let mut options = TreeOptions::new();
+        options.pattern = Pattern::regex("_2[0-9]\\.", "").unwrap();
+        b.iter(|| build(&root, &options))
+    });
+}
+
+criterion_group!(benches, tree_build);
+criterion_main!(benches);
@@ -0,0 +1,86 @@
+//! Embeds what's known of the build in the binary, for `--version`
+//!  and `:version`:
+//! - the git commit, or the BROOT_COMMIT env variable when building
+//!   outside of a git repository (eg from a source archive)
+//! - the date, taken from SOURCE_DATE_EPOCH when it's set, so that
+//!   builds can be reproduced
+//! - the enabled cargo features
+
+use std::env;
+use std::path::Path;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn git_commit() -> Option<String> {
+    let output = Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .current_dir(env::var("CARGO_MANIFEST_DIR").ok()?)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let commit = String::from_utf8(output.stdout).ok()?.trim().to_string();
+    if commit.is_empty() {
+        None
+    } else {
+        Some(commit)
+    }
+}
+
+// the yyyy-mm-dd date of the given number of days since the epoch
+//  (see http://howardhinnant.github.io/date_algorithms.html#civil_from_days)
+fn civil_date(days: i64) -> String {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+fn build_date() -> String {
+    let secs = match env::var("SOURCE_DATE_EPOCH").ok().and_then(|s| s.parse().ok()) {
+        Some(secs) => secs,
+        None => SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or(0),
+    };
+    civil_date(secs.div_euclid(86_400))
+}
+
+fn features() -> String {
+    let mut features: Vec<String> = env::vars()
+        .filter_map(|(k, _)| {
+            k.strip_prefix("CARGO_FEATURE_")
+                .map(|feature| feature.to_lowercase().replace('_', "-"))
+        })
+        .collect();
+    if features.is_empty() {
+        return "none".to_string();
+    }
+    features.sort();
+    features.join(",")
+}
+
+fn main() {
+    let commit = git_commit()
+        .or_else(|| env::var("BROOT_COMMIT").ok())
+        .unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=BROOT_COMMIT={}", commit);
+    println!("cargo:rustc-env=BROOT_BUILD_DATE={}", build_date());
+    println!("cargo:rustc-env=BROOT_FEATURES={}", features());
+    println!("cargo:rerun-if-env-changed=BROOT_COMMIT");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+    // without a git repository, nothing else changes the build info
+    println!("cargo:rerun-if-changed=build.rs");
+    if Path::new(".git/HEAD").exists() {
+        println!("cargo:rerun-if-changed=.git/HEAD");
+        println!("cargo:rerun-if-changed=.git/refs");
+    }
+}
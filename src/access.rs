@@ -0,0 +1,98 @@
+//! What the current user may do with the files: read them, or list
+//!  and enter the directories. It's asked to access(2) with the
+//!  effective ids, which, contrary to a check of the mode, takes the
+//!  ACLs and the privileges of root into account.
+
+use std::ffi::CString;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+
+// tell whether access(2) grants the rights (a combination of R_OK,
+//  W_OK and X_OK). A path which can't be checked, for example a
+//  dangling symlink, is assumed allowed, so that no false lock is shown
+fn is_granted(path: &Path, rights: libc::c_int) -> bool {
+    let c_path = match CString::new(path.as_os_str().as_bytes()) {
+        Ok(c_path) => c_path,
+        Err(_) => {
+            return true;
+        }
+    };
+    if unsafe { libc::faccessat(libc::AT_FDCWD, c_path.as_ptr(), rights, libc::AT_EACCESS) } == 0 {
+        return true;
+    }
+    io::Error::last_os_error().raw_os_error() != Some(libc::EACCES)
+}
+
+/// tell whether the file can be read, or the directory listed and entered.
+/// Symlinks are followed
+pub fn can_open(path: &Path, is_dir: bool) -> bool {
+    if is_dir {
+        is_granted(path, libc::R_OK | libc::X_OK)
+    } else {
+        is_granted(path, libc::R_OK)
+    }
+}
+
+/// tell why the directory can't be opened, if it can't
+pub fn dir_denial(path: &Path) -> Option<&'static str> {
+    if !is_granted(path, libc::X_OK) {
+        Some("you're not allowed to enter it")
+    } else if !is_granted(path, libc::R_OK) {
+        Some("you're not allowed to list its content")
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod access_tests {
+    use super::*;
+    use std::fs;
+    use std::os::unix::fs::{symlink, PermissionsExt};
+    use tempfile::tempdir;
+
+    #[test]
+    fn denials_follow_the_modes() {
+        let dir = tempdir().unwrap();
+        let with_mode = |name: &str, mode: u32, is_dir: bool| {
+            let path = dir.path().join(name);
+            if is_dir {
+                fs::create_dir(&path).unwrap();
+            } else {
+                fs::write(&path, "").unwrap();
+            }
+            fs::set_permissions(&path, fs::Permissions::from_mode(mode)).unwrap();
+            path
+        };
+        let file = with_mode("file", 0o644, false);
+        let open_dir = with_mode("open", 0o755, true);
+        assert!(can_open(&file, false));
+        assert!(can_open(&open_dir, true));
+        assert_eq!(dir_denial(&open_dir), None);
+        let locked_file = with_mode("locked_file", 0o000, false);
+        let unenterable = with_mode("unenterable", 0o600, true);
+        let unlistable = with_mode("unlistable", 0o300, true);
+        // root is granted everything but the execution of a file
+        if unsafe { libc::geteuid() } == 0 {
+            assert!(can_open(&locked_file, false));
+            assert!(can_open(&unenterable, true));
+            assert_eq!(dir_denial(&unlistable), None);
+        } else {
+            assert!(!can_open(&locked_file, false));
+            assert!(!can_open(&unenterable, true));
+            assert_eq!(dir_denial(&unenterable), Some("you're not allowed to enter it"));
+            assert!(!can_open(&unlistable, true));
+            assert_eq!(dir_denial(&unlistable), Some("you're not allowed to list its content"));
+        }
+        // a path which can't be checked isn't shown locked
+        let dangling = dir.path().join("dangling");
+        symlink(dir.path().join("missing"), &dangling).unwrap();
+        assert!(can_open(&dangling, false));
+        assert_eq!(dir_denial(&dangling), None);
+        // the modes are restored for the removal of the directory
+        for path in &[&unenterable, &unlistable] {
+            fs::set_permissions(path, fs::Permissions::from_mode(0o755)).unwrap();
+        }
+    }
+}
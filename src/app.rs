@@ -6,43 +6,77 @@
 //! - a pop to get back to the previous one
 //! - an operation which keeps the state
 //! - a request to quit broot
-//! - a request to launch an executable (thus leaving broot)
-use std::io::{self, stdin, Write};
+//! - a request to launch an executable (thus leaving broot, now or later)
+//! - a request to show the terminal under broot
+use std::io::{self, stderr, Stderr, Write};
+use std::mem;
+use std::path::{Path, PathBuf};
 use std::result::Result;
-use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::{mpsc, Arc};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, TryRecvError};
+use std::sync::Arc;
 use std::thread;
+use std::time::Duration;
+use directories::UserDirs;
+use termion::event::Event;
 use termion::input::TermRead;
+use termion::raw::{IntoRawMode, RawTerminal};
 
 use crate::app_context::AppContext;
 use crate::browser_states::BrowserState;
-use crate::commands::Command;
+use crate::commands::{Action, Command};
 use crate::errors::ProgramError;
 use crate::errors::TreeBuildError;
-use crate::external::Launchable;
+use crate::external::{self, Launchable};
 use crate::input::Input;
-use crate::screens::Screen;
+use crate::job_control;
+use crate::screens::{self, Screen};
+use crate::selection_export::SelectionExport;
 use crate::skin::Skin;
+use crate::special_paths;
 use crate::spinner::Spinner;
 use crate::status::Status;
+use crate::term_events::{self, StdinReader, TermEvent, TermEvents};
 use crate::task_sync::TaskLifetime;
 
+/// how long the main loop waits for an event before checking
+///  again whether there's something to do
+const INPUT_TICK: Duration = Duration::from_millis(20);
+
 /// Result of applying a command to a state
 pub enum AppStateCmdResult {
     Quit,
+    QuitWithExport, // a verb wrote what the shell must do after broot
     Keep,
-    Launch(Launchable),
+    Launch {
+        launchable: Launchable,
+        quit: bool, // when false, the launchable is queued and broot goes on
+    },
+    LaunchAndStay(Launchable), // broot waits for the end of the program, if any
     DisplayError(String),
+    DisplayMessage(String), // an information for the status
+    DisplayTransient(String), // an information not hiding an error, cleared on next event
     NewState(Box<dyn AppState>, Command),
     PopStateAndReapply, // the state asks the command be executed on a previous state
     PopState,
     RefreshState,
+    ShowTerminal, // broot's screen is left until a key is hit
 }
 
 impl AppStateCmdResult {
+    /// tell, until the next event, that the key does nothing
+    pub fn unbound_key(key: &str) -> AppStateCmdResult {
+        AppStateCmdResult::DisplayTransient(format!("key {} is not bound — see :help", key))
+    }
     pub fn verb_not_found(text: &str) -> AppStateCmdResult {
         AppStateCmdResult::DisplayError(format!("verb not found: {:?}", &text))
     }
+    pub fn disabled_in_picker(key: &str) -> AppStateCmdResult {
+        AppStateCmdResult::DisplayError(format!(
+            "{} is disabled in picker mode, where <enter> picks the selection",
+            key
+        ))
+    }
     pub fn from_optional_state(
         os: Result<Option<BrowserState>, TreeBuildError>,
         cmd: Command,
@@ -55,6 +89,16 @@ impl AppStateCmdResult {
     }
 }
 
+// the path, written with a '~' for the home directory, eg "~/work/proj"
+fn tilde_path(path: &Path) -> String {
+    let home = UserDirs::new().map(|user_dirs| user_dirs.home_dir().to_path_buf());
+    match home.as_ref().and_then(|home| path.strip_prefix(home).ok()) {
+        Some(sub) if sub.as_os_str().is_empty() => "~".to_string(),
+        Some(sub) => format!("~/{}", sub.to_string_lossy()),
+        None => path.to_string_lossy().to_string(),
+    }
+}
+
 // a whole application state, stackable to allow reverting
 //  to a previous one
 pub trait AppState {
@@ -64,6 +108,8 @@ pub trait AppState {
         screen: &mut Screen,
         con: &AppContext,
     ) -> io::Result<AppStateCmdResult>;
+    /// ask for what's displayed to be read again, as a pending task
+    ///  coalescing the requests, and return the command of the state
     fn refresh(
         &mut self,
         screen: &Screen,
@@ -74,20 +120,44 @@ pub trait AppState {
     fn display(&mut self, screen: &mut Screen, con: &AppContext) -> io::Result<()>;
     fn write_status(&self, screen: &mut Screen, cmd: &Command, con: &AppContext) -> io::Result<()>;
     fn write_flags(&self, screen: &mut Screen, con: &AppContext) -> io::Result<()>;
+    /// check, when it's time to, whether the files changed since they were
+    ///  read. Return true when the flags must be written again
+    fn check_outdated(&mut self, _con: &AppContext) -> bool {
+        false
+    }
+    fn selected_path(&self) -> Option<PathBuf>;
+    /// the root of the displayed tree, for the states showing one
+    fn tree_root(&self) -> Option<PathBuf> {
+        None
+    }
+    /// adapt to a new size of the screen, before it's drawn again
+    fn resize(&mut self, _screen: &Screen) {}
 }
 
 pub struct App {
     states: Vec<Box<dyn AppState>>, // stack: the last one is current
+    inputs: Vec<Command>, // the inputs of the states below the current one, restored on pop
     quitting: bool,
-    launch_at_end: Option<Launchable>, // what must be launched after end
+    launch_at_end: Vec<Launchable>, // what must be launched after end, in order
+    cd_root: Option<PathBuf>, // where the shell must go after end, with cd_on_quit
+    shown_terminal: Option<RawTerminal<Stderr>>, // raw while showing the terminal, so that any key brings broot back
+    keys_paused: Arc<AtomicBool>, // while a program launched by broot has the terminal
+    selection_export: Option<SelectionExport>, // the writing of the selection, with --selection-file
+    launch_warning: Option<String>, // kept in the status until the first key
 }
 
 impl App {
     pub fn new() -> App {
         App {
             states: Vec::new(),
+            inputs: Vec::new(),
             quitting: false,
-            launch_at_end: None,
+            launch_at_end: Vec::new(),
+            cd_root: None,
+            shown_terminal: None,
+            keys_paused: Arc::new(AtomicBool::new(false)),
+            selection_export: None,
+            launch_warning: None,
         }
     }
 
@@ -95,6 +165,52 @@ impl App {
         self.states.push(new_state);
     }
 
+    /// the number of states in the stack
+    #[cfg(test)]
+    pub fn nb_states(&self) -> usize {
+        self.states.len()
+    }
+
+    /// the path selected in the current state, if any
+    pub fn selected_path(&self) -> Option<PathBuf> {
+        self.states.last().and_then(|state| state.selected_path())
+    }
+
+    // adapt all the states to the new size of the screen, the ones
+    //  below the current one being shown again when it's popped
+    fn resize_states(&mut self, screen: &Screen) {
+        for state in self.states.iter_mut() {
+            state.resize(screen);
+        }
+    }
+
+    // write the selection in the file given with --selection-file, if
+    //  it changed, when it's stable enough or with force. An error is
+    //  told once, as the export is then stopped
+    fn export_selection(&mut self, screen: &mut Screen, force: bool) -> io::Result<()> {
+        let selection = self.selected_path();
+        if let Some(export) = &mut self.selection_export {
+            export.note(selection);
+            if let Err(e) = export.write(force) {
+                screen.write_status_err(&format!(
+                    "the selection can't be written in {:?}: {}",
+                    export.path(),
+                    e
+                ))?;
+            }
+        }
+        Ok(())
+    }
+
+    // quit without a verb telling what comes next: the shell will go to
+    //  the last displayed root when it's asked in conf
+    fn quit_normally(&mut self, con: &AppContext) {
+        self.quitting = true;
+        if con.cd_on_quit && !con.launch_args.picker {
+            self.cd_root = self.states.iter().rev().find_map(|state| state.tree_root());
+        }
+    }
+
     fn mut_state(&mut self) -> &mut Box<dyn AppState> {
         match self.states.last_mut() {
             Some(s) => s,
@@ -112,6 +228,15 @@ impl App {
         }
     }
 
+    // write the status of the current state, unless the launch
+    //  warnings must still be read
+    fn write_status(&self, screen: &mut Screen, cmd: &Command, con: &AppContext) -> io::Result<()> {
+        match &self.launch_warning {
+            Some(warning) => screen.write_status_err(warning),
+            None => self.state().write_status(screen, cmd, con),
+        }
+    }
+
     /// execute all the pending tasks until there's none remaining or
     ///  the allowed lifetime is expired (usually when the user typed a new key)
     fn do_pending_tasks(
@@ -124,7 +249,7 @@ impl App {
         let has_task = self.state().has_pending_tasks();
         if has_task {
             loop {
-                self.state().write_status(screen, &cmd, con)?;
+                self.write_status(screen, &cmd, con)?;
                 screen.write_spinner(true)?;
                 self.mut_state().display(screen, con)?;
                 if tl.is_expired() {
@@ -141,6 +266,85 @@ impl App {
         Ok(())
     }
 
+    /// do one step of the pending tasks of the current state, if any,
+    ///  then display it. A step stops when the lifetime expires.
+    fn do_pending_task_step(
+        &mut self,
+        cmd: &Command,
+        screen: &mut Screen,
+        con: &AppContext,
+        tl: TaskLifetime,
+    ) -> io::Result<()> {
+        if self.state().has_pending_tasks() {
+            self.write_status(screen, &cmd, con)?;
+            screen.write_spinner(true)?;
+            let nb_statuses = screen.nb_statuses;
+            self.mut_state().do_pending_task(screen, &tl);
+            if !self.state().has_pending_tasks() {
+                screen.write_spinner(false)?;
+                // the status of the tasks is replaced, unless the last
+                //  step told its outcome
+                if screen.nb_statuses == nb_statuses {
+                    self.write_status(screen, cmd, con)?;
+                }
+            }
+            self.mut_state().display(screen, con)?;
+        }
+        Ok(())
+    }
+
+    // show the root of the current state (or of the one below, for a
+    //  state without tree, like the help) in the title of the terminal
+    fn update_title(&self, screen: &mut Screen) -> io::Result<()> {
+        match self.states.iter().rev().find_map(|state| state.tree_root()) {
+            Some(root) => screen.set_title(&format!("broot: {}", tilde_path(&root))),
+            None => Ok(()),
+        }
+    }
+
+    // give the screen the names of the roots of the stacked states, for
+    //  the breadcrumb drawn at the end of the root line, if it's wanted
+    fn update_stack_crumbs(&self, screen: &mut Screen, con: &AppContext) {
+        screen.stack_crumbs = if con.stack_breadcrumb {
+            self.states
+                .iter()
+                .filter_map(|state| state.tree_root())
+                .map(|root| match root.file_name() {
+                    Some(name) => name.to_string_lossy().to_string(),
+                    None => root.to_string_lossy().to_string(),
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+    }
+
+    /// draw everything again, eg when broot gets its screen back
+    fn redraw(&mut self, cmd: &Command, screen: &mut Screen, con: &AppContext) -> io::Result<()> {
+        self.update_title(screen)?;
+        self.update_stack_crumbs(screen, con);
+        screen.write_input(cmd)?;
+        self.write_status(screen, cmd, con)?;
+        self.state().write_flags(screen, con)?;
+        self.mut_state().display(screen, con)
+    }
+
+    /// give the terminal back to the shell and stop, then take
+    ///  it again when broot is continued (eg with `fg`)
+    fn stop_and_continue(
+        &mut self,
+        cmd: &Command,
+        screen: &mut Screen,
+        con: &AppContext,
+    ) -> io::Result<()> {
+        if self.shown_terminal.take().is_none() {
+            screen.suspend()?;
+        }
+        job_control::stop();
+        screen.resume()?;
+        self.redraw(cmd, screen, con)
+    }
+
     /// apply a command, and returns a command, which may be the same (modified or not)
     ///  or a new one.
     /// This normally mutates self
@@ -152,42 +356,91 @@ impl App {
     ) -> io::Result<Command> {
         let mut cmd = cmd;
         debug!("action: {:?}", &cmd.action);
-        screen.read_size()?;
+        if let Action::Suspend = cmd.action {
+            self.stop_and_continue(&cmd, screen, con)?;
+            return Ok(cmd);
+        }
+        if screen.read_size()? {
+            self.resize_states(screen);
+        }
         screen.write_input(&cmd)?;
         self.state().write_flags(screen, con)?;
         match self.mut_state().apply(&mut cmd, screen, con)? {
             AppStateCmdResult::Quit => {
                 debug!("cmd result quit");
-                self.quitting = true;
+                self.quit_normally(con);
             }
-            AppStateCmdResult::Launch(launchable) => {
-                self.launch_at_end = Some(launchable);
+            AppStateCmdResult::QuitWithExport => {
                 self.quitting = true;
             }
+            AppStateCmdResult::Launch { launchable, quit } => {
+                self.launch_at_end.push(launchable);
+                if quit {
+                    self.quitting = true;
+                } else {
+                    // the verb is done, the pattern is kept
+                    cmd = cmd.pattern_input();
+                    let n = self.launch_at_end.len();
+                    screen.write_status_text(&format!(
+                        "{} launch{} queued, to be done when broot quits",
+                        n,
+                        if n == 1 { "" } else { "es" },
+                    ))?;
+                }
+            }
+            AppStateCmdResult::LaunchAndStay(launchable) => {
+                let needs_terminal = launchable.needs_terminal();
+                if needs_terminal {
+                    self.keys_paused.store(true, Ordering::SeqCst);
+                    screen.suspend()?;
+                }
+                let executed = launchable.execute_staying();
+                if needs_terminal {
+                    screen.resume()?;
+                    self.keys_paused.store(false, Ordering::SeqCst);
+                }
+                screen.stderr.invalidate();
+                cmd = self.mut_state().refresh(screen, con);
+                match executed {
+                    Ok(()) => self.state().write_status(screen, &cmd, con)?,
+                    Err(e) => screen.write_status_err(&e.to_string())?,
+                }
+            }
             AppStateCmdResult::NewState(boxed_state, new_cmd) => {
+                screen.stderr.invalidate();
+                self.inputs.push(cmd.pattern_input());
                 self.push(boxed_state);
                 cmd = new_cmd;
                 self.state().write_status(screen, &cmd, con)?;
             }
             AppStateCmdResult::RefreshState => {
+                // an external program may have written on the screen
+                screen.stderr.invalidate();
                 cmd = self.mut_state().refresh(screen, con);
             }
             AppStateCmdResult::PopState => {
                 if self.states.len() == 1 {
                     debug!("quitting on last pop state");
-                    self.quitting = true;
+                    self.quit_normally(con);
                 } else {
                     self.states.pop();
+                    screen.stderr.invalidate();
+                    // the state is refreshed, in case the files changed, but
+                    //  its input is the one the user left, not a rebuilt one
                     cmd = self.mut_state().refresh(screen, con);
+                    if let Some(input) = self.inputs.pop() {
+                        cmd = input;
+                    }
                     self.state().write_status(screen, &cmd, con)?;
                 }
             }
             AppStateCmdResult::PopStateAndReapply => {
                 if self.states.len() == 1 {
                     debug!("quitting on last pop state");
-                    self.quitting = true;
+                    self.quit_normally(con);
                 } else {
                     self.states.pop();
+                    self.inputs.pop();
                     debug!("about to reapply {:?}", &cmd);
                     return self.apply_command(cmd, screen, con);
                 }
@@ -195,93 +448,305 @@ impl App {
             AppStateCmdResult::DisplayError(txt) => {
                 screen.write_status_err(&txt)?;
             }
+            AppStateCmdResult::DisplayMessage(txt) => {
+                screen.write_status_text(&txt)?;
+            }
+            AppStateCmdResult::DisplayTransient(txt) => {
+                screen.write_status_transient(&txt)?;
+            }
             AppStateCmdResult::Keep => {
                 self.state().write_status(screen, &cmd, con)?;
             }
+            AppStateCmdResult::ShowTerminal => {
+                screen.suspend()?;
+                self.shown_terminal = Some(stderr().into_raw_mode()?);
+                // the verb isn't kept in the input, when broot comes back
+                return Ok(cmd.pattern_input());
+            }
         }
+        self.update_title(screen)?;
+        self.update_stack_crumbs(screen, con);
         screen.write_input(&cmd)?;
         self.state().write_flags(screen, con)?;
         Ok(cmd)
     }
 
-    /// This is the main loop of the application
-    pub fn run(mut self, con: &AppContext, skin: Skin) -> Result<Option<Launchable>, ProgramError> {
-        let mut screen = Screen::new(skin)?;
+    /// run the application in the terminal, reading the keys on stdin
+    pub fn run(&mut self, con: &AppContext, skin: Skin) -> Result<Vec<Launchable>, ProgramError> {
+        let mut screen = Screen::new(skin, con.launch_args.height)?;
+        screen.two_line_status = con.two_line_status;
+        screen.terminal_title = con.terminal_title;
+        job_control::catch_stop_signal();
+        self.run_with_events(con, screen, StdinReader.events(), term_events::stdin_is_ready, false)
+    }
+
+    /// run the application with the given events instead of the keys
+    ///  typed on stdin, eg on a headless screen to test it.
+    /// An event is only applied when the tasks started by the previous
+    ///  ones are done, so that the run doesn't depend on timing.
+    #[cfg(test)]
+    pub fn run_scripted(
+        &mut self,
+        con: &AppContext,
+        screen: Screen,
+        events: Vec<Event>,
+    ) -> Result<Vec<Launchable>, ProgramError> {
+        self.run_with_events(con, screen, events.into_iter().map(Ok), |_| true, true)
+    }
 
+    /// run the application until it quits. When the terminal goes
+    ///  away, the loop stops and the pending tasks are dropped
+    fn run_with_events<E, R>(
+        &mut self,
+        con: &AppContext,
+        mut screen: Screen,
+        events: E,
+        is_ready: R,
+        wait_for_tasks: bool,
+    ) -> Result<Vec<Launchable>, ProgramError>
+    where
+        E: Iterator<Item = io::Result<Event>> + Send + 'static,
+        R: Fn(Duration) -> bool + Send + 'static,
+    {
+        let quitting = Arc::new(AtomicBool::new(false));
+        let result = self.run_loop(con, &mut screen, events, is_ready, wait_for_tasks, &quitting);
+        // the thread reading the events must stop, however the loop ended
+        quitting.store(true, Ordering::SeqCst);
+        // the loss is noticed either when drawing or when reading the keys
+        let lost = screen.stderr.is_lost()
+            || match &result {
+                Err(ProgramError::Io { source }) => screens::is_terminal_loss(source),
+                _ => false,
+            };
+        match result {
+            Err(e) if lost => {
+                debug!("loop stopped by {}", e);
+                // the states are dropped, with their pending tasks
+                self.states.clear();
+                self.inputs.clear();
+                Err(ProgramError::TerminalLost)
+            }
+            result => result,
+        }
+    }
+
+    /// This is the main loop of the application
+    fn run_loop<E, R>(
+        &mut self,
+        con: &AppContext,
+        screen: &mut Screen,
+        events: E,
+        is_ready: R,
+        wait_for_tasks: bool,
+        quitting: &Arc<AtomicBool>,
+    ) -> Result<Vec<Launchable>, ProgramError>
+    where
+        E: Iterator<Item = io::Result<Event>> + Send + 'static,
+        R: Fn(Duration) -> bool + Send + 'static,
+    {
         // create the initial state
-        if let Some(bs) = BrowserState::new(
+        let mut options = con.launch_args.tree_options.clone();
+        special_paths::apply_at_launch(&con.special_paths, &con.launch_args.root, &mut options);
+        if let Some(mut bs) = BrowserState::new(
             con.launch_args.root.clone(),
-            con.launch_args.tree_options.clone(),
-            &screen,
+            options,
+            screen,
             &TaskLifetime::unlimited(),
         )? {
+            if let Some(path) = &con.launch_args.selection {
+                bs.select_path(path, screen);
+            }
             self.push(Box::new(bs));
         } else {
             unreachable!();
         }
 
+        self.selection_export = con.launch_args.selection_file.clone().map(SelectionExport::new);
         let mut cmd = Command::new();
 
         // if some commands were passed to the application
         //  we execute them before even starting listening for keys
         for arg_cmd in &con.launch_args.commands {
             cmd = (*arg_cmd).clone();
-            cmd = self.apply_command(cmd, &mut screen, con)?;
-            self.do_pending_tasks(&cmd, &mut screen, con, TaskLifetime::unlimited())?;
+            cmd = self.apply_command(cmd, screen, con)?;
+            self.do_pending_tasks(&cmd, screen, con, TaskLifetime::unlimited())?;
             if self.quitting {
-                return Ok(self.launch_at_end.take());
+                return self.end(con);
             }
         }
 
         // we listen for keys in a separate thread so that we can go on listening
         // when a long search is running, and interrupt it if needed
-        let keys = stdin().keys();
-        let (tx_keys, rx_keys) = mpsc::channel();
-        let (tx_quit, rx_quit) = mpsc::channel();
-        let cmd_count = Arc::new(AtomicUsize::new(0));
-        let key_count = Arc::clone(&cmd_count);
+        let (tx_events, rx_events) = mpsc::channel();
+        let read_count = Arc::new(AtomicUsize::new(0)); // events read on stdin
+        let event_count = Arc::clone(&read_count);
+        let input_quitting = Arc::clone(quitting);
+        let input_paused = Arc::clone(&self.keys_paused);
         thread::spawn(move || {
-            for c in keys {
-                key_count.fetch_add(1, Ordering::SeqCst);
-                // we send the command to the receiver in the
-                //  main event loop
-                tx_keys.send(c).unwrap();
-                let quit = rx_quit.recv().unwrap();
-                if quit {
-                    // cleanly quitting this thread is necessary
-                    //  to ensure stdin is properly closed when
-                    //  we launch an external application in the same
-                    //  terminal
+            let mut events = TermEvents::new(events);
+            loop {
+                // an event is read only once it's there, so that stdin is
+                //  left to the programs launched by broot, when it quits
+                //  or while they run
+                if input_quitting.load(Ordering::SeqCst) {
+                    return;
+                }
+                if input_paused.load(Ordering::SeqCst) {
+                    thread::sleep(INPUT_TICK);
+                    continue;
+                }
+                if !is_ready(INPUT_TICK)
+                    || input_paused.load(Ordering::SeqCst)
+                    || input_quitting.load(Ordering::SeqCst)
+                {
+                    continue;
+                }
+                let e = match events.next() {
+                    Some(e) => e,
+                    None => return,
+                };
+                event_count.fetch_add(1, Ordering::SeqCst);
+                if tx_events.send(e).is_err() {
                     return;
                 }
             }
         });
 
+        self.update_title(screen)?;
+        self.update_stack_crumbs(screen, con);
         screen.write_input(&cmd)?;
-        screen.write_status_text("Hit <esc> to quit, '?' for help, or some letters to search")?;
-        self.state().write_flags(&mut screen, con)?;
+        match &con.launch_args.tree_options.path_list {
+            _ if !con.launch_warnings.is_empty() => {
+                self.launch_warning = Some(format!("Warning: {}", con.launch_warnings.join(", ")));
+                self.write_status(screen, &cmd, con)?;
+            }
+            // the state tells about the paths which couldn't be used
+            Some(path_list) if path_list.nb_dropped > 0 => {
+                self.state().write_status(screen, &cmd, con)?;
+            }
+            _ if con.launch_args.picker => {
+                screen.write_status_text(&format!(
+                    "Hit {} to pick the selection, {} to cancel, or some letters to search",
+                    con.verb_store.hint_for("open"),
+                    con.verb_store.hint_for("back"),
+                ))?;
+            }
+            // the user may have forgotten the shell was launched by broot
+            _ if con.nesting_depth > 1 => {
+                screen.write_status_text(&format!(
+                    "You're already inside a broot-launched shell ({} broots running), hit {} to quit this one",
+                    con.nesting_depth,
+                    con.verb_store.hint_for("back"),
+                ))?;
+            }
+            _ => {
+                screen.write_status_text(&format!(
+                    "Hit {} to quit, {} for help, or some letters to search",
+                    con.verb_store.hint_for("back"),
+                    con.verb_store.hint_for("help"),
+                ))?;
+            }
+        }
+        self.state().write_flags(screen, con)?;
+        self.mut_state().display(screen, con)?;
         loop {
-            if !self.quitting {
-                self.do_pending_tasks(&cmd, &mut screen, con, TaskLifetime::new(&cmd_count))?;
+            if job_control::take_stop_request() {
+                self.stop_and_continue(&cmd, screen, con)?;
             }
-            let c = match rx_keys.recv() {
-                Ok(c) => c,
-                Err(_) => {
-                    // this is how we quit the application,
-                    // when the input thread is properly closed
-                    break;
+            // when there's some work to do, we do it in steps, checking
+            //  for a new event between them
+            let event = if self.state().has_pending_tasks() {
+                if wait_for_tasks {
+                    // the scripted events wait for the end of the tasks
+                    None
+                } else {
+                    match rx_events.try_recv() {
+                        Ok(e) => Some(e),
+                        Err(TryRecvError::Empty) => None,
+                        Err(TryRecvError::Disconnected) => break,
+                    }
+                }
+            } else {
+                match rx_events.recv_timeout(INPUT_TICK) {
+                    Ok(e) => Some(e),
+                    Err(RecvTimeoutError::Timeout) => None,
+                    Err(RecvTimeoutError::Disconnected) => break,
                 }
             };
-            cmd.add_key(c?);
-            cmd = self.apply_command(cmd, &mut screen, con)?;
-            tx_quit.send(self.quitting).unwrap();
+            match event {
+                Some(e) => {
+                    let e = e?;
+                    if let Some(raw) = self.shown_terminal.take() {
+                        // the key only brings broot back
+                        drop(raw);
+                        screen.resume()?;
+                        self.redraw(&cmd, screen, con)?;
+                        continue;
+                    }
+                    self.launch_warning = None;
+                    match e {
+                        TermEvent::Event(e) => cmd.add_event(e, &con.chords),
+                        TermEvent::Paste(text) => cmd.add_paste(&text),
+                    }
+                    cmd = self.apply_command(cmd, screen, con)?;
+                    if self.quitting {
+                        break;
+                    }
+                    if self.shown_terminal.is_none() {
+                        self.mut_state().display(screen, con)?;
+                    }
+                    self.export_selection(screen, false)?;
+                }
+                None if self.shown_terminal.is_some() => {}
+                None => {
+                    // the terminal may be resized without any key typed
+                    if screen.read_size()? {
+                        self.resize_states(screen);
+                        self.redraw(&cmd, screen, con)?;
+                    }
+                    if cmd.end_expired_chord() {
+                        cmd = self.apply_command(cmd, screen, con)?;
+                        self.mut_state().display(screen, con)?;
+                    } else {
+                        let tl = if wait_for_tasks {
+                            TaskLifetime::unlimited()
+                        } else {
+                            TaskLifetime::new(&read_count)
+                        };
+                        self.do_pending_task_step(&cmd, screen, con, tl)?;
+                        if !self.state().has_pending_tasks() && self.mut_state().check_outdated(con) {
+                            self.state().write_flags(screen, con)?;
+                            screen.stderr.flush()?;
+                        }
+                    }
+                    self.export_selection(screen, false)?;
+                }
+            }
         }
-        Ok(self.launch_at_end.take())
+        quitting.store(true, Ordering::SeqCst);
+        // the last selection is written, even if broot quits before it's stable
+        self.export_selection(screen, true)?;
+        self.end(con)
+    }
+
+    // what must be done once broot has quit: the cd for the shell is
+    //  exported, and the launchables are returned to be executed. A
+    //  picker quitting without printing a path is an error
+    fn end(&mut self, con: &AppContext) -> Result<Vec<Launchable>, ProgramError> {
+        if let Some(root) = self.cd_root.take() {
+            external::export_cd(&root, con)?;
+        }
+        if con.launch_args.picker && self.launch_at_end.is_empty() {
+            return Err(ProgramError::NothingPicked);
+        }
+        Ok(mem::replace(&mut self.launch_at_end, Vec::new()))
     }
 }
 
 impl Drop for App {
     fn drop(&mut self) {
-        io::stderr().flush().unwrap();
+        if let Err(e) = io::stderr().flush() {
+            warn!("error while flushing stderr: {}", e);
+        }
     }
 }
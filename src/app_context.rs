@@ -1,9 +1,43 @@
+use std::path::{Path, PathBuf};
+
 use crate::cli::AppLaunchArgs;
+use crate::commands::Chords;
+use crate::internals::Internal;
+use crate::special_paths::SpecialPath;
 use crate::verb_store::VerbStore;
+use crate::verbs::Verb;
+use crate::yank::PasteConflict;
 
 /// The immutable container that can be passed around to provide
 /// the configuration things
 pub struct AppContext {
     pub launch_args: AppLaunchArgs,
+    pub launch_warnings: Vec<String>, // about the ignored launch arguments, told on start
     pub verb_store: VerbStore,
+    pub file_opener: Option<Verb>, // replaces the OS opener when defined in conf
+    pub enter_on_file: Internal,   // what <enter> does on a file: OpenLeave or OpenStay
+    pub paste_conflict: PasteConflict,
+    pub chords: Chords,
+    pub same_depth_jump_to_parent: bool, // whether next_same_depth may leave the parent
+    pub launch_dir: PathBuf,  // the current directory when broot was launched
+    pub relative_paths: bool, // whether the paths given to the shell are relative to launch_dir
+    pub cd_on_quit: bool, // whether the shell goes to the last root when broot quits normally
+    pub two_line_status: bool, // whether long errors may borrow the row above the status
+    pub terminal_title: bool,  // whether the title of the terminal tells the root
+    pub stack_breadcrumb: bool, // whether the roots of the stacked states end the root line
+    pub nesting_depth: usize,  // the number of broots running one in another, this one included
+    pub watch_changes: bool,   // whether the root is checked for changes made outside broot
+    pub special_paths: Vec<SpecialPath>, // the options of some roots
+}
+
+impl AppContext {
+    /// the directory the paths printed or given to the shell are made
+    ///  relative to, if they are
+    pub fn relative_base(&self) -> Option<&Path> {
+        if self.relative_paths {
+            Some(&self.launch_dir)
+        } else {
+            None
+        }
+    }
 }
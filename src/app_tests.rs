@@ -0,0 +1,1406 @@
+//! These tests drive the whole application, from the keys to the
+//!  launched program, on a headless screen and a known set of files.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Write};
+use std::os::unix::fs::{symlink, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use tempfile::{tempdir, TempDir};
+use termion::event::{Event, Key};
+
+use crate::app::{App, AppState, AppStateCmdResult};
+use crate::app_context::AppContext;
+use crate::browser_states::BrowserState;
+use crate::cli::AppLaunchArgs;
+use crate::commands::{Chords, Command};
+use crate::conf::Conf;
+use crate::errors::ProgramError;
+use crate::external::Launchable;
+use crate::help_states::HelpState;
+use crate::path_list::PathList;
+use crate::screens::Screen;
+use crate::skin::{Palette, PaletteSource, Skin};
+use crate::task_sync::TaskLifetime;
+use crate::tree_options::{SizeBarBase, TreeOptions};
+use crate::verb_invocation::VerbInvocation;
+use crate::verb_store::VerbStore;
+use crate::verbs::Verb;
+use crate::yank::PasteConflict;
+
+// the tree of the tests:
+//  alpha/a.txt
+//  alpha/b.txt
+//  beta/nested/deep.md
+//  gamma.txt
+//  link_to_alpha -> alpha
+fn fixture() -> (TempDir, PathBuf) {
+    let dir = tempdir().unwrap();
+    let root = dir.path().canonicalize().unwrap();
+    fs::create_dir_all(root.join("alpha")).unwrap();
+    fs::write(root.join("alpha/a.txt"), "a").unwrap();
+    fs::write(root.join("alpha/b.txt"), "b").unwrap();
+    fs::create_dir_all(root.join("beta/nested")).unwrap();
+    fs::write(root.join("beta/nested/deep.md"), "deep").unwrap();
+    fs::write(root.join("gamma.txt"), "gamma").unwrap();
+    symlink(root.join("alpha"), root.join("link_to_alpha")).unwrap();
+    (dir, root)
+}
+
+fn context(root: &Path, conf: &str) -> AppContext {
+    let conf_dir = tempdir().unwrap();
+    let conf_path = conf_dir.path().join("conf.toml");
+    fs::write(&conf_path, conf).unwrap();
+    let conf = Conf::from_file(&conf_path).unwrap();
+    let mut verb_store = VerbStore::new();
+    verb_store.init(&conf);
+    AppContext {
+        launch_args: AppLaunchArgs {
+            root: root.to_path_buf(),
+            selection: None,
+            file_export_path: None,
+            cmd_export_path: None,
+            tree_options: TreeOptions::new(),
+            commands: Vec::new(),
+            install: false,
+            height: None,
+            profile: None,
+            skin_palette: None,
+            completions: None,
+            picker: false,
+            selection_file: None,
+        },
+        launch_warnings: Vec::new(),
+        verb_store,
+        file_opener: conf.file_opener.clone().map(|execution| {
+            Verb::create_external("open", None, execution, None, false, true, false).unwrap()
+        }),
+        enter_on_file: conf.enter_on_file,
+        paste_conflict: PasteConflict::Ask,
+        chords: Chords::default(),
+        same_depth_jump_to_parent: false,
+        launch_dir: root.to_path_buf(),
+        relative_paths: false,
+        cd_on_quit: conf.cd_on_quit,
+        two_line_status: false,
+        terminal_title: conf.terminal_title,
+        stack_breadcrumb: conf.stack_breadcrumb,
+        nesting_depth: 1,
+        watch_changes: conf.watch_changes,
+        special_paths: conf.special_paths,
+    }
+}
+
+// the events of typing the given keys, where '\n' is <enter>,
+//  '\x1b' is <esc> and '↓' is <down>
+fn keys(typed: &str) -> Vec<Event> {
+    typed
+        .chars()
+        .map(|c| match c {
+            '\x1b' => Event::Key(Key::Esc),
+            '↓' => Event::Key(Key::Down),
+            c => Event::Key(Key::Char(c)),
+        })
+        .collect()
+}
+
+// run broot on the fixture, returning the app for inspection,
+//  with what it would launch
+fn run(root: &Path, conf: &str, typed: &str) -> (App, Vec<Launchable>) {
+    run_events(root, conf, keys(typed))
+}
+
+fn run_events(root: &Path, conf: &str, events: Vec<Event>) -> (App, Vec<Launchable>) {
+    let con = context(root, conf);
+    let skin = Skin::create(Palette::Dark, PaletteSource::Default, HashMap::new());
+    let screen = Screen::headless(skin, 80, 30, Box::new(Vec::new()));
+    let mut app = App::new();
+    let launchables = app.run_scripted(&con, screen, events).unwrap();
+    (app, launchables)
+}
+
+#[test]
+fn fuzzy_search_narrows_to_the_best_match() {
+    let (_dir, root) = fixture();
+    let (app, launchables) = run(&root, "", "dpmd");
+    assert_eq!(app.selected_path(), Some(root.join("beta/nested/deep.md")));
+    assert_eq!(app.nb_states(), 1);
+    assert!(launchables.is_empty());
+}
+
+#[test]
+fn enter_on_a_directory_focuses_it() {
+    let (_dir, root) = fixture();
+    let (app, _) = run(&root, "", "nested\n");
+    assert_eq!(app.nb_states(), 2);
+    assert_eq!(app.selected_path(), Some(root.join("beta/nested")));
+}
+
+#[test]
+fn back_pops_the_state() {
+    let (_dir, root) = fixture();
+    let (app, _) = run(&root, "", "nested\n:back\n");
+    assert_eq!(app.nb_states(), 1);
+    // the previous state is as it was left
+    assert_eq!(app.selected_path(), Some(root.join("beta/nested")));
+}
+
+#[test]
+fn escape_is_staged() {
+    let (_dir, root) = fixture();
+    // (the first line below the root is the parent line)
+    let (app, _) = run(&root, "", "↓↓");
+    assert_eq!(app.selected_path(), Some(root.join("alpha")));
+    // the first <esc> removes the filter, restoring the selection
+    let (app, _) = run(&root, "", "↓↓gam");
+    assert_eq!(app.selected_path(), Some(root.join("gamma.txt")));
+    let (app, _) = run(&root, "", "↓↓gam\x1b");
+    assert_eq!(app.selected_path(), Some(root.join("alpha")));
+    // the second one selects the root
+    let (app, _) = run(&root, "", "↓↓gam\x1b\x1b");
+    assert_eq!(app.selected_path(), Some(root.clone()));
+    assert_eq!(app.nb_states(), 1);
+    // and the third one quits, launching nothing: the next keys are ignored
+    let (app, launchables) = run(&root, "", "↓↓gam\x1b\x1b\x1bnested\n");
+    assert_eq!(app.nb_states(), 1);
+    assert!(launchables.is_empty());
+    // after a focus, it goes back to the previous state
+    let (app, _) = run(&root, "", "nested\n\x1b");
+    assert_eq!(app.nb_states(), 1);
+}
+
+#[test]
+fn custom_verb_is_substituted() {
+    let (_dir, root) = fixture();
+    let conf = r#"
+        [[verbs]]
+        invocation = "say {word}"
+        execution = "/bin/echo {file} {word}"
+        env = { GIT_PAGER = "cat" }
+    "#;
+    let (_, launchables) = run(&root, conf, "gam:say hello\n");
+    match &launchables[..] {
+        [Launchable::Program { exe, args, env, .. }] => {
+            assert_eq!(exe, "/bin/echo");
+            assert_eq!(*env, vec![("GIT_PAGER".to_string(), "cat".to_string())]);
+            assert_eq!(
+                *args,
+                vec![root.join("gamma.txt").to_string_lossy().to_string(), "hello".to_string()],
+            );
+        }
+        other => panic!("unexpected launchable: {:?}", other),
+    }
+}
+
+#[test]
+fn missing_program_isnt_launched() {
+    let (_dir, root) = fixture();
+    let conf = r#"
+        [[verbs]]
+        invocation = "try"
+        execution = "surely-not-a-program {file}"
+    "#;
+    let (app, launchables) = run(&root, conf, "gam:try\n");
+    assert!(launchables.is_empty());
+    assert_eq!(app.selected_path(), Some(root.join("gamma.txt")));
+    // unless the launch is forced
+    let (_, launchables) = run(&root, conf, "gam:try!\n");
+    match &launchables[..] {
+        [Launchable::Program { exe, .. }] => assert_eq!(exe, "surely-not-a-program"),
+        other => panic!("unexpected launchable: {:?}", other),
+    }
+}
+
+#[test]
+fn enter_on_file_may_stay_in_broot() {
+    let (_dir, root) = fixture();
+    let conf = r#"
+        file_opener = "touch {file}.opened"
+    "#;
+    let (_, launchables) = run(&root, conf, "gam\n");
+    match &launchables[..] {
+        [Launchable::Program { exe, .. }] => assert_eq!(exe, "touch"),
+        other => panic!("unexpected launchable: {:?}", other),
+    }
+    let conf = r#"
+        file_opener = "touch {file}.opened"
+        enter_on_file = "open_stay"
+    "#;
+    let (app, launchables) = run(&root, conf, "gam\n");
+    assert!(launchables.is_empty());
+    assert!(root.join("gamma.txt.opened").exists());
+    assert_eq!(app.selected_path(), Some(root.join("gamma.txt")));
+    // :open_leave still quits
+    let (_, launchables) = run(&root, conf, "gam:open_leave\n");
+    assert_eq!(launchables.len(), 1);
+}
+
+#[test]
+fn launches_are_queued() {
+    let (_dir, root) = fixture();
+    let conf = r#"
+        file_opener = "touch {file}.opened"
+    "#;
+    let (app, launchables) = run(&root, conf, "gam:open_later\n\x1bdeep:open_later\n");
+    assert_eq!(app.nb_states(), 1);
+    assert_eq!(launchables.len(), 2);
+    // nothing is opened before broot quits
+    assert!(!root.join("gamma.txt.opened").exists());
+    // a launch leaving broot comes after the queued ones
+    let typed = "gam:open_later\n\x1bb.txt:open_leave\n";
+    let (_, launchables) = run(&root, conf, typed);
+    let files: Vec<&str> = launchables
+        .iter()
+        .map(|launchable| match launchable {
+            Launchable::Program { args, .. } => args[0].rsplit('/').next().unwrap(),
+            other => panic!("unexpected launchable: {:?}", other),
+        })
+        .collect();
+    assert_eq!(files, vec!["gamma.txt.opened", "b.txt.opened"]);
+}
+
+#[test]
+fn hint_labels_select_lines() {
+    let (_dir, root) = fixture();
+    // the labels start at the parent line: "a" is .., "s" is alpha
+    let (app, _) = run(&root, "", "'s");
+    assert_eq!(app.selected_path(), Some(root.join("alpha")));
+    assert_eq!(app.nb_states(), 1);
+    // an uppercase label opens the line
+    let (app, _) = run(&root, "", "'S");
+    assert_eq!(app.nb_states(), 2);
+    // the letters typed after the label are the input again
+    let (app, _) = run(&root, "", "'sgam");
+    assert_eq!(app.selected_path(), Some(root.join("gamma.txt")));
+    // <esc> leaves the hint mode without moving the selection
+    let (app, _) = run(&root, "", "'\x1bgam");
+    assert_eq!(app.selected_path(), Some(root.join("gamma.txt")));
+}
+
+#[test]
+fn hot_key_keeps_the_pattern() {
+    let (_dir, root) = fixture();
+    let conf = r#"
+        [[verbs]]
+        invocation = "hidden"
+        key = "alt-h"
+        execution = ":toggle_hidden"
+    "#;
+    let mut events = keys("gam");
+    events.push(Event::Key(Key::Alt('h')));
+    let (app, _) = run_events(&root, conf, events);
+    // the toggle made a new state, still filtered
+    assert_eq!(app.nb_states(), 2);
+    assert_eq!(app.selected_path(), Some(root.join("gamma.txt")));
+    // a key bound to nothing changes nothing
+    let mut events = keys("gam");
+    events.push(Event::Key(Key::Alt('x')));
+    let (app, _) = run_events(&root, conf, events);
+    assert_eq!(app.nb_states(), 1);
+    assert_eq!(app.selected_path(), Some(root.join("gamma.txt")));
+}
+
+#[test]
+fn flags_menu_toggles_or_lets_the_key_through() {
+    let (_dir, root) = fixture();
+    let mut events = vec![Event::Key(Key::Ctrl('f'))];
+    events.extend(keys("h"));
+    let (app, _) = run_events(&root, "", events);
+    assert_eq!(app.nb_states(), 2);
+    // a char which isn't in the menu starts the pattern
+    let mut events = vec![Event::Key(Key::Ctrl('f'))];
+    events.extend(keys("b.txt"));
+    let (app, _) = run_events(&root, "", events);
+    assert_eq!(app.nb_states(), 1);
+    assert_eq!(app.selected_path(), Some(root.join("alpha/b.txt")));
+}
+
+#[test]
+fn dangerous_verbs_are_confirmed() {
+    let (_dir, root) = fixture();
+    let conf = r#"
+        [[verbs]]
+        invocation = "trash"
+        key = "ctrl-t"
+        execution = "rm {file}"
+        leave_broot = false
+        confirm = true
+    "#;
+    // any key other than 'y' cancels, leaving the input and the selection
+    let (app, _) = run(&root, conf, "gam:trash\nn");
+    assert!(root.join("gamma.txt").exists());
+    assert_eq!(app.selected_path(), Some(root.join("gamma.txt")));
+    let (app, _) = run(&root, conf, "gam:trash\n\x1b");
+    assert!(root.join("gamma.txt").exists());
+    assert_eq!(app.nb_states(), 1);
+    // the built-in rm is confirmed too
+    run(&root, conf, "gam:rm\n");
+    assert!(root.join("gamma.txt").exists());
+    run(&root, conf, "gam:trash\ny");
+    assert!(!root.join("gamma.txt").exists());
+    // as well as a hot key
+    let mut events = keys("b.txt");
+    events.push(Event::Key(Key::Ctrl('t')));
+    run_events(&root, conf, events.clone());
+    assert!(root.join("alpha/b.txt").exists());
+    events.extend(keys("y"));
+    run_events(&root, conf, events);
+    assert!(!root.join("alpha/b.txt").exists());
+}
+
+#[test]
+fn recursive_chmod_is_confirmed() {
+    let (_dir, root) = fixture();
+    let mode = |path: &str| fs::metadata(root.join(path)).unwrap().permissions().mode() & 0o777;
+    let original_mode = mode("alpha/a.txt");
+    // without -R, only the directory is changed, at once
+    run(&root, "", "alpha:chmod 700\n");
+    assert_eq!(mode("alpha"), 0o700);
+    assert_eq!(mode("alpha/a.txt"), original_mode);
+    // the confirmation tells the command
+    let con = context(&root, "");
+    let skin = Skin::create(Palette::Dark, PaletteSource::Default, HashMap::new());
+    let output = Output::default();
+    let screen = Screen::headless(skin, 80, 30, Box::new(output.clone()));
+    App::new().run_scripted(&con, screen, keys("alpha:chmod -R 750\n")).unwrap();
+    let written = String::from_utf8(output.0.borrow().clone()).unwrap();
+    assert!(written.contains("confirm: :chmod -R 750 on "));
+    assert_eq!(mode("alpha/a.txt"), original_mode);
+    // and nothing is done unless it's confirmed
+    run(&root, "", "alpha:chmod -R 750\nn");
+    assert_eq!(mode("alpha/a.txt"), original_mode);
+    run(&root, "", "alpha:chmod -R 750\ny");
+    assert_eq!(mode("alpha"), 0o750);
+    assert_eq!(mode("alpha/a.txt"), 0o750);
+}
+
+#[test]
+fn paste_bang_overwrites() {
+    let (_dir, root) = fixture();
+    fs::write(root.join("beta/nested/gamma.txt"), "old").unwrap();
+    // without the '!', the existing file is kept
+    run(&root, "", "gam:cp\n\x1bnested:paste\n");
+    assert_eq!(fs::read_to_string(root.join("beta/nested/gamma.txt")).unwrap(), "old");
+    run(&root, "", "gam:cp\n\x1bnested:paste!\ny");
+    assert_eq!(fs::read_to_string(root.join("beta/nested/gamma.txt")).unwrap(), "gamma");
+}
+
+#[test]
+fn hints_tell_the_bound_keys() {
+    let (_dir, root) = fixture();
+    let conf = r#"
+        enter_on_file = "open_stay"
+        [[verbs]]
+        invocation = "ts"
+        key = "ctrl-t"
+        execution = ":total_search"
+        [[verbs]]
+        invocation = "redo"
+        key = "F5"
+        execution = ":refresh"
+    "#;
+    let con = context(&root, conf);
+    let verbs = &con.verb_store;
+    assert_eq!(verbs.hint_for("back"), "<esc>");
+    assert_eq!(verbs.hint_for("cd"), "<alt-enter>");
+    assert_eq!(verbs.hint_for("help"), "'?'");
+    assert_eq!(verbs.hint_for("open_stay"), "<enter>");
+    assert_eq!(verbs.hint_for("open_leave"), "':open_leave'");
+    assert_eq!(verbs.hint_for("ts"), "<ctrl-t>");
+    // the key of a verb of the conf calls the internal too
+    assert_eq!(verbs.hint_for("total_search"), "<ctrl-t>");
+    // F5 moved from the built-in to the verb of the conf, which refreshes too
+    assert_eq!(verbs.binding_for("refresh").unwrap().to_string(), "F5");
+    assert_eq!(verbs.binding_for("redo").unwrap().to_string(), "F5");
+    // as well as the keys parsed by the input, even when termion doesn't
+    assert_eq!(verbs.hint_for("previous_same_depth"), "<alt-up>");
+    let keys: Vec<String> = verbs
+        .bindings_for("next_same_depth")
+        .iter()
+        .map(|key| key.hint())
+        .collect();
+    assert_eq!(keys, vec!["<alt-down>", "'}'"]);
+    assert!(help_row(&con, 120, " previous_same_depth ").contains(" alt-up { "));
+    let action_keys: Vec<String> = verbs
+        .action_keys()
+        .iter()
+        .map(|(key, action)| format!("{}:{}", key, action))
+        .collect();
+    assert_eq!(
+        action_keys,
+        vec!["ctrl-f:flags_menu", "ctrl-z:suspend", "home:select_first", "end:select_last"],
+    );
+    let con = context(&root, "");
+    assert_eq!(con.verb_store.binding_for("refresh").unwrap().to_string(), "F5");
+}
+
+#[test]
+fn rebound_key_is_told_for_its_new_verb_only() {
+    let (_dir, root) = fixture();
+    let conf = r#"
+        [[verbs]]
+        invocation = "hid"
+        key = "F5"
+        execution = ":toggle_hidden"
+    "#;
+    let con = context(&root, conf);
+    let verbs = &con.verb_store;
+    assert_eq!(verbs.binding_for("refresh"), None);
+    assert_eq!(verbs.hint_for("refresh"), "':refresh'");
+    assert_eq!(verbs.binding_for("hid").unwrap().to_string(), "F5");
+    let refresh = &verbs.verbs[verbs.index_of("refresh")];
+    assert!(!refresh.description.as_ref().unwrap().contains("F5"));
+    assert!(!help_row(&con, 120, " refresh ").contains("F5"));
+    assert!(help_row(&con, 120, " hid ").contains("F5"));
+    // without the conf, F5 refreshes
+    let con = context(&root, "");
+    assert!(help_row(&con, 120, " refresh ").contains("F5"));
+}
+
+#[test]
+fn verb_descriptions_are_shown_while_typing() {
+    let (_dir, root) = fixture();
+    let conf = r#"
+        [[verbs]]
+        invocation = "tidy"
+        execution = "cat {file}"
+        description = "fix the indentation of the file, keeping its blank lines and comments"
+        [[verbs]]
+        invocation = "tig"
+        execution = "cat"
+    "#;
+    let con = context(&root, conf);
+    let status = |key: &str| {
+        let invocation = VerbInvocation::from(key);
+        con.verb_store.edition_status(&invocation, &root.join("gamma.txt"), &root, None)
+    };
+    let tidy = status("tidy").unwrap();
+    assert!(tidy.starts_with("Hit <enter> to tidy : fix the indentation of the file, keeping"));
+    assert!(tidy.contains("will run `cat "));
+    assert_eq!(
+        status("ti").unwrap(),
+        "Possible verbs: tidy (fix the indentation of the file…), tig",
+    );
+}
+
+// the row of the help containing the given text, without its styles
+fn help_row(con: &AppContext, width: u16, text: &str) -> String {
+    let skin = Skin::create(Palette::Dark, PaletteSource::Default, HashMap::new());
+    let output = Output::default();
+    let mut screen = Screen::headless(skin, width, 200, Box::new(output.clone()));
+    HelpState::new(&screen).display(&mut screen, con).unwrap();
+    let written = String::from_utf8(output.0.borrow().clone()).unwrap();
+    let row_start = regex::Regex::new(r"\x1b\[\d+;1H").unwrap();
+    let styles = regex::Regex::new(r"\x1b\[[\d;]*[A-Za-z]").unwrap();
+    let row = row_start
+        .split(&written)
+        .map(|row| styles.replace_all(row, "").to_string())
+        .find(|row| row.contains(text))
+        .unwrap();
+    row
+}
+
+#[test]
+fn verb_descriptions_are_cut_in_the_help() {
+    let (_dir, root) = fixture();
+    let conf = r#"
+        [[verbs]]
+        invocation = "tidy"
+        execution = "cat {file}"
+        description = "fix the indentation of the file, keeping its blank lines and comments"
+    "#;
+    let con = context(&root, conf);
+    let tidy_row = |width: u16| help_row(&con, width, " tidy ");
+    let row = tidy_row(80);
+    assert!(row.chars().count() < 80, "{:?}", row);
+    assert!(row.contains("│ fix the indentation of "), "{:?}", row);
+    assert!(row.ends_with('…'), "{:?}", row);
+    let row = tidy_row(200);
+    assert!(row.ends_with("keeping its blank lines and comments"), "{:?}", row);
+}
+
+// what's written on the headless screen, kept for inspection
+#[derive(Clone, Default)]
+struct Output(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn deep_paths_fit_in_the_width() {
+    let dir = tempdir().unwrap();
+    let root = dir.path().canonicalize().unwrap();
+    let mut path = root.clone();
+    for i in 0..100 {
+        path.push(format!("d{}", i));
+    }
+    fs::create_dir_all(&path).unwrap();
+    fs::write(path.join("leaf.txt"), "").unwrap();
+    let con = context(&root, "");
+    let skin = Skin::create(Palette::Dark, PaletteSource::Default, HashMap::new());
+    let output = Output::default();
+    // (high enough for the whole branch to be kept)
+    let screen = Screen::headless(skin, 80, 120, Box::new(output.clone()));
+    let mut app = App::new();
+    app.run_scripted(&con, screen, keys("leaf")).unwrap();
+    assert_eq!(app.selected_path(), Some(path.join("leaf.txt")));
+    // every row starts with a cursor move, and the text written
+    //  after it, without the styles, must fit in the screen
+    let written = String::from_utf8(output.0.borrow().clone()).unwrap();
+    let goto = regex::Regex::new(r"\x1b\[\d+;\d+H").unwrap();
+    let csi = regex::Regex::new(r"\x1b\[[0-9;?]*[A-Za-z]").unwrap();
+    let mut collapsed = 0;
+    for row in goto.split(&written) {
+        let text = csi.replace_all(row, "");
+        assert!(text.chars().count() <= 80, "too long row: {:?}", text);
+        if text.starts_with("…  ") {
+            collapsed += 1;
+        }
+    }
+    assert!(collapsed > 0);
+}
+
+#[test]
+fn special_path_options_applied_on_focus() {
+    let (_dir, root) = fixture();
+    let conf = format!(
+        "[[special_paths]]\npath = \"{}/beta\"\nname = \"betas\"\nshow_dates = true\n",
+        root.to_string_lossy(),
+    );
+    let con = context(&root, &conf);
+    let skin = Skin::create(Palette::Dark, PaletteSource::Default, HashMap::new());
+    let output = Output::default();
+    let screen = Screen::headless(skin, 80, 30, Box::new(output.clone()));
+    let mut app = App::new();
+    app.run_scripted(&con, screen, keys("/^beta$\n")).unwrap();
+    assert_eq!(app.nb_states(), 2);
+    let written = String::from_utf8(output.0.borrow().clone()).unwrap();
+    // only the state of beta tells about its profile
+    let (before, after) = written.split_at(written.find("(profile: betas)").unwrap());
+    assert!(!before.contains("(profile:"));
+    // with the dates, the year of the files is shown
+    let year = chrono::Local::now().format("%Y").to_string();
+    assert!(after.contains(&year));
+}
+
+// a terminal which goes away after some bytes were written,
+//  counting the writes tried after that
+struct VanishingTerminal {
+    remaining: usize,
+    writes_after_loss: Rc<RefCell<usize>>,
+}
+
+impl Write for VanishingTerminal {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.remaining == 0 {
+            *self.writes_after_loss.borrow_mut() += 1;
+            return Err(io::Error::from_raw_os_error(libc::EIO));
+        }
+        let n = buf.len().min(self.remaining);
+        self.remaining -= n;
+        Ok(n)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn lost_terminal_ends_the_run() {
+    let (_dir, root) = fixture();
+    let con = context(&root, "");
+    for &remaining in &[0, 1000, 4000, 12000] {
+        let skin = Skin::create(Palette::Dark, PaletteSource::Default, HashMap::new());
+        let writes_after_loss = Rc::new(RefCell::new(0));
+        let terminal = VanishingTerminal {
+            remaining,
+            writes_after_loss: Rc::clone(&writes_after_loss),
+        };
+        let screen = Screen::headless(skin, 80, 30, Box::new(terminal));
+        let mut app = App::new();
+        match app.run_scripted(&con, screen, keys("alp
+:help
+")) {
+            Err(ProgramError::TerminalLost) => {}
+            _ => panic!("the loss of the terminal isn't reported"),
+        }
+        // the first failed write is the last one
+        assert_eq!(*writes_after_loss.borrow(), 1);
+    }
+}
+
+// the text of the input, at the bottom of the screen, when the
+//  keys have been typed
+fn input_after(root: &Path, typed: &str) -> (App, String) {
+    let con = context(root, "");
+    let skin = Skin::create(Palette::Dark, PaletteSource::Default, HashMap::new());
+    let output = Output::default();
+    let screen = Screen::headless(skin, 80, 30, Box::new(output.clone()));
+    let mut app = App::new();
+    app.run_scripted(&con, screen, keys(typed)).unwrap();
+    let written = String::from_utf8(output.0.borrow().clone()).unwrap();
+    let input_row = written.rsplit("\x1b[30;1H").next().unwrap();
+    let input_row = input_row.split("\x1b[30;").next().unwrap();
+    let csi = regex::Regex::new(r"\x1b\[[0-9;?]*[A-Za-z]").unwrap();
+    let input = csi.replace_all(input_row, "").trim_end().to_string();
+    // the prompt of the pattern mode isn't part of the input
+    let input = input.chars().skip(2).collect();
+    (app, input)
+}
+
+#[test]
+fn back_restores_the_input() {
+    let (_dir, root) = fixture();
+    // from the root to alpha, then to the help. The inputs are
+    //  restored as typed, not rebuilt from the patterns
+    let typed = "/alp\nB.t:help\n:back\n";
+    let (app, input) = input_after(&root, typed);
+    assert_eq!(app.nb_states(), 2);
+    assert_eq!(input, "B.t");
+    assert_eq!(app.selected_path(), Some(root.join("alpha/b.txt")));
+    // back again to the root, where typing goes on with the old pattern
+    let (app, input) = input_after(&root, &format!("{}:back\nha", typed));
+    assert_eq!(app.nb_states(), 1);
+    assert_eq!(input, "/alpha");
+    assert_eq!(app.selected_path(), Some(root.join("alpha")));
+}
+
+// the events of pasting the text in a terminal in bracketed paste mode
+fn paste(text: &str) -> Vec<Event> {
+    let mut events = vec![Event::Unsupported(b"\x1b[200~".to_vec())];
+    events.extend(keys(text));
+    events.push(Event::Unsupported(b"\x1b[201~".to_vec()));
+    events
+}
+
+#[test]
+fn pasted_text_is_literal_input() {
+    let (_dir, root) = fixture();
+    // the newline doesn't open the file
+    let (app, launchables) = run_events(&root, "", paste("dpmd\n"));
+    assert_eq!(app.selected_path(), Some(root.join("beta/nested/deep.md")));
+    assert!(launchables.is_empty());
+    // a '?' doesn't open the help, and it's kept in the input
+    let mut events = paste("?");
+    events.extend(keys("\n"));
+    let (app, launchables) = run_events(&root, "", events);
+    assert_eq!(app.nb_states(), 1);
+    assert!(launchables.is_empty());
+    // a space or a ':' doesn't start a verb: "my" alone would select my.txt
+    fs::write(root.join("alpha/my.txt"), "").unwrap();
+    fs::write(root.join("beta/my notes:draft.txt"), "").unwrap();
+    let (app, _) = run_events(&root, "", paste("my notes:dr"));
+    assert_eq!(app.selected_path(), Some(root.join("beta/my notes:draft.txt")));
+    let (app, _) = run(&root, "", "my notes:dr");
+    assert_eq!(app.selected_path(), Some(root.join("alpha/my.txt")));
+}
+
+#[test]
+fn refresh_requests_are_coalesced() {
+    let (_dir, root) = fixture();
+    let con = context(&root, "");
+    let skin = Skin::create(Palette::Dark, PaletteSource::Default, HashMap::new());
+    let mut screen = Screen::headless(skin, 80, 30, Box::new(Vec::new()));
+    let options = con.launch_args.tree_options.clone();
+    let unlimited = TaskLifetime::unlimited();
+    let start = Instant::now();
+    let mut state = BrowserState::new(root.clone(), options, &screen, &unlimited)
+        .unwrap()
+        .unwrap();
+    // the requests of :refresh and the ones of the application, eg
+    //  after a verb, make one rebuild at most per period of 200ms
+    while start.elapsed() < Duration::from_millis(650) {
+        state.request_refresh();
+        state.refresh(&screen, &con);
+        state.do_pending_task(&mut screen, &unlimited);
+    }
+    let max = start.elapsed().as_millis() as usize / 200;
+    assert!(state.nb_refreshes >= 2, "{} refreshes", state.nb_refreshes);
+    assert!(state.nb_refreshes <= max, "{} refreshes", state.nb_refreshes);
+    // the last request is honored once the period is over
+    fs::write(root.join("new.txt"), "").unwrap();
+    state.request_refresh();
+    thread::sleep(Duration::from_millis(200));
+    assert!(state.has_pending_tasks());
+    // a rebuild interrupted by a new event is done again, once
+    let nb_refreshes = state.nb_refreshes;
+    let events = Arc::new(AtomicUsize::new(0));
+    let tl = TaskLifetime::new(&events);
+    events.fetch_add(1, Ordering::SeqCst);
+    state.do_pending_task(&mut screen, &tl);
+    assert_eq!(state.nb_refreshes, nb_refreshes);
+    assert!(state.has_pending_tasks());
+    while state.has_pending_tasks() {
+        state.do_pending_task(&mut screen, &unlimited);
+    }
+    assert_eq!(state.nb_refreshes, nb_refreshes + 1);
+    assert!(state.tree.lines.iter().any(|line| line.path == root.join("new.txt")));
+}
+
+// what broot writes in the --outcmd file when the keys are typed
+fn exported_after(root: &Path, conf: &str, typed: &str) -> String {
+    let out = tempdir().unwrap();
+    let out_path = out.path().join("outcmd");
+    fs::write(&out_path, "").unwrap();
+    let mut con = context(root, conf);
+    con.launch_args.cmd_export_path = Some(out_path.to_string_lossy().to_string());
+    let skin = Skin::create(Palette::Dark, PaletteSource::Default, HashMap::new());
+    let screen = Screen::headless(skin, 80, 30, Box::new(Vec::new()));
+    App::new().run_scripted(&con, screen, keys(typed)).unwrap();
+    fs::read_to_string(&out_path).unwrap()
+}
+
+#[test]
+fn cd_on_quit() {
+    let (_dir, root) = fixture();
+    let cd = |path: &Path| format!("cd {}\n", path.display());
+    // only when asked in conf
+    assert_eq!(exported_after(&root, "", ":quit\n"), "");
+    let conf = "cd_on_quit = true";
+    assert_eq!(exported_after(&root, conf, "nested\n:quit\n"), cd(&root.join("beta/nested")));
+    // <esc> pops the state, then removes the restored pattern, then quits
+    assert_eq!(exported_after(&root, conf, "alpha\n\x1b\x1b\x1b"), cd(&root));
+    // from the help, it's the root of the tree below
+    assert_eq!(exported_after(&root, conf, "alpha\n?:quit\n"), cd(&root.join("alpha")));
+    // a verb given to the shell defines what comes next
+    assert_eq!(exported_after(&root, conf, "nested:cd\n"), cd(&root.join("beta/nested")));
+}
+
+#[test]
+fn opening_a_file_doesnt_cd() {
+    let (_dir, root) = fixture();
+    let conf = "cd_on_quit = true";
+    // the system opener is launched once broot is gone, which
+    //  prints the path when it fails, and the shell doesn't cd
+    assert_eq!(exported_after(&root, conf, "gam\n"), "");
+    let (_, launchables) = run(&root, conf, "gam\n");
+    match launchables.as_slice() {
+        [Launchable::SystemOpen { path }] => assert_eq!(path, &root.join("gamma.txt")),
+        _ => panic!("the file wasn't opened: {:?}", launchables),
+    }
+}
+
+#[test]
+fn launch_warnings_are_told_in_the_status() {
+    let (_dir, root) = fixture();
+    let mut con = context(&root, "");
+    con.launch_args.picker = true;
+    con.launch_warnings = vec!["--out is ignored in picker mode".to_string()];
+    let skin = Skin::create(Palette::Dark, PaletteSource::Default, HashMap::new());
+    let output = Output::default();
+    let screen = Screen::headless(skin, 80, 30, Box::new(output.clone()));
+    App::new().run_scripted(&con, screen, Vec::new()).unwrap_err();
+    let written = String::from_utf8(output.0.borrow().clone()).unwrap();
+    assert!(written.contains("Warning: --out is ignored in picker mode"));
+    assert!(!written.contains("Hit <enter> to pick"));
+    // the warnings stay until the first key, even with tasks to do
+    con.launch_args.picker = false;
+    con.launch_args.tree_options.show_sizes = true;
+    let last_status = |typed: &str| {
+        let skin = Skin::create(Palette::Dark, PaletteSource::Default, HashMap::new());
+        let output = Output::default();
+        let screen = Screen::headless(skin, 80, 30, Box::new(output.clone()));
+        App::new().run_scripted(&con, screen, keys(typed)).unwrap();
+        let written = String::from_utf8(output.0.borrow().clone()).unwrap();
+        let status_row = regex::Regex::new(r"\x1b\[29;2H[^ ]*\x1b\[2K ([^\x1b]*)").unwrap();
+        status_row.captures_iter(&written).last().unwrap()[1].to_string()
+    };
+    assert!(last_status("").starts_with("Warning: "));
+    assert!(!last_status("gamma").starts_with("Warning: "));
+}
+
+// what a picker prints when the keys are typed, None when it quits
+//  without a pick
+fn picked_after(root: &Path, conf: &str, typed: &str) -> Option<String> {
+    let mut con = context(root, conf);
+    con.launch_args.picker = true;
+    let skin = Skin::create(Palette::Dark, PaletteSource::Default, HashMap::new());
+    let screen = Screen::headless(skin, 80, 30, Box::new(Vec::new()));
+    match App::new().run_scripted(&con, screen, keys(typed)) {
+        Ok(launchables) => match &launchables[..] {
+            [Launchable::Printer { to_print }] => Some(to_print.clone()),
+            other => panic!("unexpected launchables: {:?}", other),
+        },
+        Err(ProgramError::NothingPicked) => None,
+        Err(e) => panic!("unexpected error: {}", e),
+    }
+}
+
+#[test]
+fn picker_prints_the_selection() {
+    let (_dir, root) = fixture();
+    let picked = |path: &Path| Some(path.to_string_lossy().to_string());
+    // files and directories are picked alike
+    assert_eq!(picked_after(&root, "", "gam\n"), picked(&root.join("gamma.txt")));
+    assert_eq!(picked_after(&root, "", "nested\n"), picked(&root.join("beta/nested")));
+    assert_eq!(picked_after(&root, "", "\n"), picked(&root));
+    // other launches are refused, and <esc> quits without a pick
+    let conf = r#"
+        file_opener = "touch {file}.opened"
+    "#;
+    assert_eq!(picked_after(&root, conf, "gam:open_leave\n\x1b\x1b"), None);
+    assert!(!root.join("gamma.txt.opened").exists());
+    assert_eq!(picked_after(&root, "", "gam:print_path\n"), None);
+}
+
+#[test]
+fn tree_fills_the_screen_and_follows_its_height() {
+    let dir = tempdir().unwrap();
+    let root = dir.path().canonicalize().unwrap();
+    for i in 0..12 {
+        for j in 0..8 {
+            let sub = root.join(format!("d{:02}/s{}", i, j));
+            fs::create_dir_all(&sub).unwrap();
+            for k in 0..5 {
+                fs::write(sub.join(format!("f{}", k)), "").unwrap();
+            }
+        }
+    }
+    let skin = Skin::create(Palette::Dark, PaletteSource::Default, HashMap::new());
+    let mut screen = Screen::headless(skin, 80, 60, Box::new(Vec::new()));
+    let unlimited = TaskLifetime::unlimited();
+    let mut state = BrowserState::new(root.clone(), TreeOptions::new(), &screen, &unlimited)
+        .unwrap()
+        .unwrap();
+    // all the rows but the input, the status and the summary line
+    let nb_lines = |state: &BrowserState| state.displayed_tree().lines.len();
+    assert!(nb_lines(&state) >= 55, "{} lines", nb_lines(&state));
+    assert!(nb_lines(&state) <= 57, "{} lines", nb_lines(&state));
+    screen.h = 90;
+    state.resize(&screen);
+    assert!(state.is_refresh_requested());
+    while state.has_pending_tasks() {
+        state.do_pending_task(&mut screen, &unlimited);
+    }
+    assert!(nb_lines(&state) >= 85, "{} lines", nb_lines(&state));
+    screen.h = 20;
+    state.resize(&screen);
+    assert!(state.is_refresh_requested());
+    while state.has_pending_tasks() {
+        state.do_pending_task(&mut screen, &unlimited);
+    }
+    assert!(nb_lines(&state) <= 17, "{} lines", nb_lines(&state));
+}
+
+#[test]
+fn sizes_without_bars_keep_the_columns() {
+    let (_dir, root) = fixture();
+    // what's written, and the row of gamma.txt without its styles
+    let written = |size_bar: bool| {
+        let mut con = context(&root, "");
+        con.launch_args.tree_options.show_sizes = true;
+        con.launch_args.tree_options.size_bar = size_bar;
+        con.launch_args.tree_options.show_fs_info = false; // it has a bar too
+        let skin = Skin::create(Palette::Dark, PaletteSource::Default, HashMap::new());
+        let output = Output::default();
+        let screen = Screen::headless(skin, 80, 30, Box::new(output.clone()));
+        // (the key is only sent once the sizes are computed)
+        App::new().run_scripted(&con, screen, vec![Event::Key(Key::Down)]).unwrap();
+        let written = String::from_utf8(output.0.borrow().clone()).unwrap();
+        let goto = regex::Regex::new(r"\x1b\[\d+;\d+H").unwrap();
+        let csi = regex::Regex::new(r"\x1b\[[0-9;?]*[A-Za-z]").unwrap();
+        let row = goto
+            .split(&written)
+            .map(|row| csi.replace_all(row, "").to_string())
+            .filter(|row| row.contains("gamma.txt"))
+            .last()
+            .unwrap();
+        (written, row)
+    };
+    let skin = Skin::create(Palette::Dark, PaletteSource::Default, HashMap::new());
+    // the start of a bar, whose background is the size_bar_full one
+    let bar_start = format!("{}{}", skin.size_text.fg, skin.size_bar_full.bg);
+    let (with_bar, with_bar_row) = written(true);
+    let (without_bar, without_bar_row) = written(false);
+    assert!(with_bar_row.ends_with("5        gamma.txt"));
+    assert_eq!(with_bar_row, without_bar_row);
+    assert!(with_bar.contains(&bar_start));
+    assert!(!without_bar.contains(&bar_start));
+}
+
+// the last row written with the name, with its styles, once the sizes
+//  are computed
+fn sized_row(con: &AppContext, name: &str) -> String {
+    let skin = Skin::create(Palette::Dark, PaletteSource::Default, HashMap::new());
+    let output = Output::default();
+    let screen = Screen::headless(skin, 80, 30, Box::new(output.clone()));
+    App::new().run_scripted(con, screen, vec![Event::Key(Key::Down)]).unwrap();
+    let written = String::from_utf8(output.0.borrow().clone()).unwrap();
+    let goto = regex::Regex::new(r"\x1b\[\d+;\d+H").unwrap();
+    goto.split(&written)
+        .filter(|row| row.contains(name))
+        .last()
+        .unwrap()
+        .to_string()
+}
+
+#[test]
+fn sizes_as_percentages_of_the_parent() {
+    let (_dir, root) = fixture();
+    let mut con = context(&root, "");
+    con.launch_args.tree_options.show_sizes = true;
+    con.launch_args.tree_options.size_percent = true;
+    con.launch_args.tree_options.show_fs_info = false;
+    let csi = regex::Regex::new(r"\x1b\[[0-9;?]*[A-Za-z]").unwrap();
+    // a.txt and b.txt have one byte each
+    let row = csi.replace_all(&sized_row(&con, "b.txt"), "").to_string();
+    assert!(row.contains("50.0%"), "{:?}", row);
+    // the root line tells the total size
+    let root_name = root.file_name().unwrap().to_string_lossy().to_string();
+    let row = csi.replace_all(&sized_row(&con, &root_name), "").to_string();
+    assert!(row.starts_with(|c: char| c.is_ascii_digit()), "{:?}", row);
+    assert!(!row.contains('%'), "{:?}", row);
+}
+
+#[test]
+fn size_bars_compare_the_siblings() {
+    let (_dir, root) = fixture();
+    let skin = Skin::create(Palette::Dark, PaletteSource::Default, HashMap::new());
+    // the number of filled chars of the bar of b.txt
+    let filled = |base: SizeBarBase| {
+        let mut con = context(&root, "");
+        con.launch_args.tree_options.show_sizes = true;
+        con.launch_args.tree_options.size_bar_base = base;
+        con.launch_args.tree_options.show_fs_info = false;
+        let row = sized_row(&con, "b.txt");
+        let bar = row.split(&skin.size_bar_full.bg).nth(1).unwrap();
+        bar.split(&skin.size_bar_void.bg).next().unwrap().chars().count()
+    };
+    // b.txt is half of alpha, but a tiny part of the whole tree
+    let (parent, total) = (filled(SizeBarBase::Parent), filled(SizeBarBase::Total));
+    assert!(parent > total + 2, "{} vs {}", parent, total);
+}
+
+#[test]
+fn terminal_title_tells_the_root() {
+    let (_dir, root) = fixture();
+    let weird = root.join("we\x1bird");
+    fs::create_dir(&weird).unwrap();
+    let run_with = |conf: &str| {
+        let con = context(&root, conf);
+        let skin = Skin::create(Palette::Dark, PaletteSource::Default, HashMap::new());
+        let output = Output::default();
+        let mut screen = Screen::headless(skin, 80, 30, Box::new(output.clone()));
+        screen.terminal_title = con.terminal_title;
+        App::new().run_scripted(&con, screen, keys("/we.ird$\n\x1b")).unwrap();
+        let bytes = output.0.borrow().clone();
+        String::from_utf8(bytes).unwrap()
+    };
+    let written = run_with("terminal_title = true");
+    let title = |path: &Path| format!("\x1b]2;broot: {}\x07", path.to_string_lossy());
+    let root_title = written.find(&title(&root)).unwrap();
+    // the escape char of the name is removed
+    let weird_title = written.find(&title(&root.join("weird"))).unwrap();
+    assert!(root_title < weird_title);
+    // the root is shown again after the pop, then the title is restored
+    let back_title = written.rfind(&title(&root)).unwrap();
+    assert!(back_title > weird_title);
+    assert!(written.find("\x1b[22;0t").unwrap() < root_title);
+    assert!(written.rfind("\x1b[23;0t").unwrap() > back_title);
+    assert!(!run_with("").contains("\x1b]2;"));
+}
+
+#[test]
+fn unbound_keys_are_told_unless_an_error_is_shown() {
+    let (_dir, root) = fixture();
+    let con = context(&root, "");
+    let skin = Skin::create(Palette::Dark, PaletteSource::Default, HashMap::new());
+    let output = Output::default();
+    let screen = Screen::headless(skin, 80, 30, Box::new(output.clone()));
+    let mut events = vec![Event::Key(Key::F(7))];
+    events.extend(keys(":nope\n"));
+    events.push(Event::Key(Key::F(7)));
+    events.push(Event::Key(Key::Left));
+    App::new().run_scripted(&con, screen, events).unwrap();
+    let written = String::from_utf8(output.0.borrow().clone()).unwrap();
+    let told = written.find("key F7 is not bound — see :help").unwrap();
+    assert!(written.find("verb not found").unwrap() > told);
+    // the error stays the last status, the second F7 not being told
+    assert_eq!(written.matches("key F7 is not bound").count(), 1);
+    assert!(!written.contains("key left is not bound"));
+}
+
+#[test]
+fn selection_is_written_in_the_selection_file() {
+    let (_dir, root) = fixture();
+    let out_dir = tempdir().unwrap();
+    let run_with = |selection_file: PathBuf| {
+        let mut con = context(&root, "");
+        con.launch_args.selection_file = Some(selection_file);
+        let skin = Skin::create(Palette::Dark, PaletteSource::Default, HashMap::new());
+        let output = Output::default();
+        let screen = Screen::headless(skin, 80, 30, Box::new(output.clone()));
+        App::new().run_scripted(&con, screen, keys("gam\x1bdpmd")).unwrap();
+        let bytes = output.0.borrow().clone();
+        String::from_utf8(bytes).unwrap()
+    };
+    let selection_file = out_dir.path().join("selection");
+    run_with(selection_file.clone());
+    let written = fs::read_to_string(&selection_file).unwrap();
+    assert_eq!(written, format!("{}\n", root.join("beta/nested/deep.md").to_string_lossy()));
+    // only the file remains, the temporary one being renamed
+    assert_eq!(fs::read_dir(out_dir.path()).unwrap().count(), 1);
+    // an error is told once, then the selection isn't written anymore
+    let output = run_with(out_dir.path().join("missing/selection"));
+    assert_eq!(output.matches("the selection can't be written").count(), 1);
+}
+
+#[test]
+fn nested_broot_tells_it() {
+    let (_dir, root) = fixture();
+    let mut con = context(&root, "");
+    con.nesting_depth = 2;
+    let skin = Skin::create(Palette::Dark, PaletteSource::Default, HashMap::new());
+    let output = Output::default();
+    let screen = Screen::headless(skin, 120, 30, Box::new(output.clone()));
+    App::new().run_scripted(&con, screen, Vec::new()).unwrap();
+    let written = String::from_utf8(output.0.borrow().clone()).unwrap();
+    assert!(written.contains("You're already inside a broot-launched shell (2 broots running)"));
+}
+
+#[test]
+fn stack_breadcrumb_ends_the_root_line() {
+    let (_dir, root) = fixture();
+    let root_name = root.file_name().unwrap().to_string_lossy().to_string();
+    let run_with = |conf: &str| {
+        let con = context(&root, conf);
+        let skin = Skin::create(Palette::Dark, PaletteSource::Default, HashMap::new());
+        let output = Output::default();
+        let screen = Screen::headless(skin, 80, 30, Box::new(output.clone()));
+        App::new().run_scripted(&con, screen, keys("beta\nnested\n")).unwrap();
+        let bytes = output.0.borrow().clone();
+        String::from_utf8(bytes).unwrap()
+    };
+    let written = run_with("stack_breadcrumb = true");
+    let csi = regex::Regex::new(r"\x1b\[[0-9;?]*[A-Za-z]").unwrap();
+    let written = csi.replace_all(&written, "");
+    assert!(written.contains(&format!("{} ▸ beta (2)", root_name)));
+    assert!(written.contains(&format!("{} ▸ beta ▸ nested (3)", root_name)));
+    assert!(!run_with("").contains("▸"));
+}
+
+#[test]
+fn verbs_reading_a_fifo_are_refused() {
+    let (_dir, root) = fixture();
+    let made = std::process::Command::new("mkfifo")
+        .arg(root.join("pipe"))
+        .status()
+        .unwrap();
+    assert!(made.success());
+    let conf = r#"
+        [[verbs]]
+        invocation = "cat"
+        execution = "cat {file}"
+        leave_broot = false
+    "#;
+    let written_after = |typed: &str| {
+        let con = context(&root, conf);
+        let skin = Skin::create(Palette::Dark, PaletteSource::Default, HashMap::new());
+        let output = Output::default();
+        let screen = Screen::headless(skin, 80, 30, Box::new(output.clone()));
+        let launchables = App::new().run_scripted(&con, screen, keys(typed)).unwrap();
+        assert!(launchables.is_empty());
+        let written = String::from_utf8(output.0.borrow().clone()).unwrap();
+        written
+    };
+    // cat would wait for a writer, and the copy of a paste too
+    assert!(written_after("pipe:cat\n").contains("cat can't be applied to a fifo"));
+    assert!(written_after("pipe:cp\n").contains("cp can't be applied to a fifo"));
+    // but removing it doesn't open it
+    assert!(!written_after("pipe:rm\ny").contains("can't be applied"));
+    assert!(!root.join("pipe").exists());
+}
+
+#[test]
+fn duplicates_list_verbs_apply_to_the_selected_duplicate() {
+    let (_dir, root) = fixture();
+    fs::write(root.join("beta/gamma_copy.txt"), "gamma").unwrap();
+    let mode = |path: &str| fs::metadata(root.join(path)).unwrap().permissions().mode() & 0o777;
+    let original_mode = mode("gamma.txt");
+    let (app, _) = run(&root, "", ":dedup\n↓:chmod 600\n");
+    assert_eq!(app.nb_states(), 2);
+    let selected = app.selected_path().unwrap();
+    let other = if selected == root.join("gamma.txt") {
+        "beta/gamma_copy.txt"
+    } else {
+        "gamma.txt"
+    };
+    assert_eq!(fs::metadata(&selected).unwrap().permissions().mode() & 0o777, 0o600);
+    assert_eq!(mode(other), original_mode);
+    // the verbs needing the lines of a tree are refused
+    let con = context(&root, "");
+    let skin = Skin::create(Palette::Dark, PaletteSource::Default, HashMap::new());
+    let output = Output::default();
+    let screen = Screen::headless(skin, 80, 30, Box::new(output.clone()));
+    let mut app = App::new();
+    app.run_scripted(&con, screen, keys(":dedup\n:mkdir new\n")).unwrap();
+    let written = String::from_utf8(output.0.borrow().clone()).unwrap();
+    assert!(written.contains(":mkdir isn't available in the duplicates list"));
+    assert_eq!(app.nb_states(), 2);
+    assert!(!root.join("new").exists());
+}
+
+#[test]
+fn filesystems_list_refuses_the_tree_verbs() {
+    let (_dir, root) = fixture();
+    let con = context(&root, "");
+    let skin = Skin::create(Palette::Dark, PaletteSource::Default, HashMap::new());
+    let output = Output::default();
+    let screen = Screen::headless(skin, 80, 30, Box::new(output.clone()));
+    let mut app = App::new();
+    // the tree selection is alpha, which mustn't get the new entry
+    app.run_scripted(&con, screen, keys("alpha\x1b:filesystems\n:touch new\n")).unwrap();
+    let written = String::from_utf8(output.0.borrow().clone()).unwrap();
+    assert!(written.contains(":touch isn't available in the filesystems list"));
+    assert_eq!(app.nb_states(), 2);
+    assert!(!root.join("alpha/new").exists());
+    assert!(!root.join("new").exists());
+}
+
+#[test]
+fn filesystems_status_tells_the_bound_keys() {
+    let (_dir, root) = fixture();
+    let conf = r#"
+        [[verbs]]
+        invocation = "hid"
+        key = "alt-h"
+        execution = ":toggle_hidden"
+    "#;
+    let con = context(&root, conf);
+    let skin = Skin::create(Palette::Dark, PaletteSource::Default, HashMap::new());
+    let output = Output::default();
+    let screen = Screen::headless(skin, 120, 30, Box::new(output.clone()));
+    App::new().run_scripted(&con, screen, keys(":filesystems\n")).unwrap();
+    let written = String::from_utf8(output.0.borrow().clone()).unwrap();
+    assert!(written.contains("<alt-h> to show pseudo filesystems"));
+}
+
+#[test]
+fn hidden_entries_status_tells_the_bound_keys() {
+    let (_dir, root) = fixture();
+    fs::write(root.join("alpha/.secret"), "").unwrap();
+    let conf = r#"
+        [[verbs]]
+        invocation = "hid"
+        key = "alt-h"
+        execution = ":toggle_hidden"
+    "#;
+    let con = context(&root, conf);
+    let skin = Skin::create(Palette::Dark, PaletteSource::Default, HashMap::new());
+    let output = Output::default();
+    let screen = Screen::headless(skin, 120, 30, Box::new(output.clone()));
+    App::new().run_scripted(&con, screen, keys("↓↓")).unwrap();
+    let written = String::from_utf8(output.0.borrow().clone()).unwrap();
+    assert!(written.contains("1 hidden entry in this directory: hit <alt-h> to show them"));
+}
+
+#[test]
+fn path_arguments_are_completed() {
+    let (_dir, root) = fixture();
+    // the common start of the names is inserted
+    let (_, input) = input_after(&root, ":focus beta/ne\t");
+    assert_eq!(input, ":focus beta/nested/");
+    // several names are listed, then put in the input one after another
+    let (_, input) = input_after(&root, ":focus \t");
+    assert_eq!(input, ":focus");
+    let (_, input) = input_after(&root, ":focus \t\t\t");
+    assert_eq!(input, ":focus beta/");
+    // the completed path is focused
+    let (app, _) = input_after(&root, ":focus \t\t\t\n");
+    assert_eq!(app.nb_states(), 2);
+    assert_eq!(app.selected_path(), Some(root.join("beta")));
+    let (app, _) = input_after(&root, ":focus beta/ne\t\n");
+    assert_eq!(app.selected_path(), Some(root.join("beta/nested")));
+    // without argument, it's the selection which is focused
+    let (app, _) = input_after(&root, "alpha:focus\n");
+    assert_eq!(app.selected_path(), Some(root.join("alpha")));
+    assert_eq!(app.nb_states(), 2);
+    // goto is still the shortcut of focus
+    let (app, _) = input_after(&root, ":goto beta\n");
+    assert_eq!(app.selected_path(), Some(root.join("beta")));
+    assert_eq!(app.nb_states(), 2);
+}
+
+#[test]
+fn status_is_written_again_when_the_tasks_are_done() {
+    let (_dir, root) = fixture();
+    let con = context(&root, "");
+    let written = |typed: &str| {
+        let skin = Skin::create(Palette::Dark, PaletteSource::Default, HashMap::new());
+        let output = Output::default();
+        let screen = Screen::headless(skin, 80, 30, Box::new(output.clone()));
+        App::new().run_scripted(&con, screen, keys(typed)).unwrap();
+        let bytes = output.0.borrow().clone();
+        String::from_utf8(bytes).unwrap()
+    };
+    // the status (at the second column) follows the spinner's removal
+    let written_after_search = written("nested");
+    assert!(written_after_search.rfind("\x1b[29;2H") > written_after_search.rfind("\x1b[29;1H"));
+    // but a task telling its outcome isn't hidden
+    let status_row = regex::Regex::new(r"\x1b\[29;2H[^ ]*\x1b\[2K ([^\x1b]*)").unwrap();
+    let written_after_size = written(":size\n");
+    let last_status = status_row.captures_iter(&written_after_size).last().unwrap();
+    assert!(last_status[1].starts_with("Size of "), "{:?}", &last_status[1]);
+}
+
+#[test]
+fn enter_during_a_search_uses_its_best_match_so_far() {
+    let (_dir, root) = fixture();
+    let con = context(&root, "");
+    let skin = Skin::create(Palette::Dark, PaletteSource::Default, HashMap::new());
+    let output = Output::default();
+    let mut screen = Screen::headless(skin, 80, 30, Box::new(output.clone()));
+    let unlimited = TaskLifetime::unlimited();
+    let mut state = BrowserState::new(root.clone(), TreeOptions::new(), &screen, &unlimited)
+        .unwrap()
+        .unwrap();
+    let mut cmd = Command::new();
+    for c in "nested".chars() {
+        cmd.add_key(Key::Char(c), &con.chords);
+        state.apply(&mut cmd, &mut screen, &con).unwrap();
+    }
+    // the search is started, then interrupted by the next key
+    let events = Arc::new(AtomicUsize::new(0));
+    let tl = TaskLifetime::new(&events);
+    events.fetch_add(1, Ordering::SeqCst);
+    state.do_pending_task(&mut screen, &tl);
+    assert!(state.has_pending_tasks());
+    state.write_status(&mut screen, &cmd, &con).unwrap();
+    let written = String::from_utf8(output.0.borrow().clone()).unwrap();
+    let progress = regex::Regex::new(r"Searching… \d+ entries scanned, \d+ match(es)?").unwrap();
+    assert!(progress.is_match(&written));
+    cmd.add_key(Key::Char('\n'), &con.chords);
+    match state.apply(&mut cmd, &mut screen, &con).unwrap() {
+        AppStateCmdResult::NewState(focused, _) => {
+            assert_eq!(focused.selected_path(), Some(root.join("beta/nested")));
+        }
+        _ => panic!("the best match wasn't focused"),
+    }
+}
+
+#[test]
+fn arrow_keys_write_only_the_changed_rows() {
+    // a 200 rows terminal showing a directory of 250 files
+    let dir = tempdir().unwrap();
+    for i in 0..250 {
+        fs::write(dir.path().join(format!("file_{:03}.txt", i)), "").unwrap();
+    }
+    let con = context(dir.path(), "");
+    let skin = Skin::create(Palette::Dark, PaletteSource::Default, HashMap::new());
+    let output = Output::default();
+    let mut screen = Screen::headless(skin, 200, 200, Box::new(output.clone()));
+    let unlimited = TaskLifetime::unlimited();
+    let root = dir.path().to_path_buf();
+    let mut state = BrowserState::new(root, TreeOptions::new(), &screen, &unlimited)
+        .unwrap()
+        .unwrap();
+    state.display(&mut screen, &con).unwrap();
+    // the bytes of the tree area written for each <down>, either
+    //  as it's done, or repainting all rows
+    let mut written_per_key = |repaint: bool| {
+        let mut cmd = Command::new();
+        cmd.add_key(Key::Down, &con.chords);
+        state.apply(&mut cmd, &mut screen, &con).unwrap();
+        if repaint {
+            screen.stderr.invalidate();
+        }
+        let before = output.0.borrow().len();
+        state.display(&mut screen, &con).unwrap();
+        output.0.borrow().len() - before
+    };
+    let diffed: usize = (0..10).map(|_| written_per_key(false)).sum::<usize>() / 10;
+    let repainted: usize = (0..10).map(|_| written_per_key(true)).sum::<usize>() / 10;
+    assert!(diffed * 10 < repainted, "{} bytes per key, {} repainting", diffed, repainted);
+}
+
+#[test]
+fn created_entries_stay_single_and_below_the_directory() {
+    let (_dir, root) = fixture();
+    run(&root, "", ":touch one two\n");
+    assert!(!root.join("one").exists());
+    assert!(!root.join("two").exists());
+    run(&root, "", ":mkdir -p sub/../../out\n");
+    assert!(!root.parent().unwrap().join("out").exists());
+    assert!(!root.join("sub").exists());
+    let absolute = root.join("absolute");
+    run(&root, "", &format!(":mkdir -p {}\n", absolute.display()));
+    assert!(!absolute.exists());
+    run(&root, "", ":mkdir -p sub/dir\n");
+    assert!(root.join("sub/dir").is_dir());
+}
+
+#[test]
+fn enter_is_ignored_just_after_the_selection_moved() {
+    let (_dir, root) = fixture();
+    let con = context(&root, "");
+    let skin = Skin::create(Palette::Dark, PaletteSource::Default, HashMap::new());
+    let mut screen = Screen::headless(skin, 80, 30, Box::new(Vec::new()));
+    let unlimited = TaskLifetime::unlimited();
+    let mut state = BrowserState::new(root.clone(), TreeOptions::new(), &screen, &unlimited)
+        .unwrap()
+        .unwrap();
+    let mut cmd = Command::new();
+    while state.selected_path() != Some(root.join("alpha/b.txt")) {
+        cmd.add_key(Key::Down, &con.chords);
+        state.apply(&mut cmd, &mut screen, &con).unwrap();
+    }
+    // a new file pushes the selected line down, in a background refresh
+    fs::write(root.join("alpha/0.txt"), "0").unwrap();
+    state.request_refresh();
+    while state.has_pending_tasks() {
+        state.do_pending_task(&mut screen, &unlimited);
+    }
+    assert_eq!(state.selected_path(), Some(root.join("alpha/b.txt")));
+    cmd.add_key(Key::Char('\n'), &con.chords);
+    match state.apply(&mut cmd, &mut screen, &con).unwrap() {
+        AppStateCmdResult::DisplayMessage(message) => {
+            assert!(message.contains("hit <enter> again"), "{:?}", message);
+        }
+        _ => panic!("<enter> wasn't ignored"),
+    }
+    thread::sleep(Duration::from_millis(100));
+    cmd.add_key(Key::Char('\n'), &con.chords);
+    let result = state.apply(&mut cmd, &mut screen, &con).unwrap();
+    if let AppStateCmdResult::DisplayMessage(message) = result {
+        panic!("<enter> was ignored again: {:?}", message);
+    }
+}
+
+#[test]
+fn path_list_makes_the_tree() {
+    let (_dir, root) = fixture();
+    let lines: Vec<String> = vec!["beta/nested/deep.md", "gamma.txt", "missing.txt"]
+        .into_iter()
+        .map(String::from)
+        .collect();
+    let (list_root, path_list) = PathList::from_lines(&lines, &root).unwrap();
+    assert_eq!(list_root, root);
+    let mut con = context(&root, "");
+    con.launch_args.tree_options.path_list = Some(Arc::new(path_list));
+    let skin = Skin::create(Palette::Dark, PaletteSource::Default, HashMap::new());
+    let output = Output::default();
+    let screen = Screen::headless(skin, 80, 30, Box::new(output.clone()));
+    let mut app = App::new();
+    app.run_scripted(&con, screen, keys("deep")).unwrap();
+    let written = String::from_utf8(output.0.borrow().clone()).unwrap();
+    // only the listed paths and their ancestors are shown
+    assert!(written.contains("nested"));
+    assert!(written.contains("gamma.txt"));
+    assert!(!written.contains("alpha"));
+    assert!(written.contains("1 path read on stdin didn't exist"));
+    // and searched as usual
+    assert_eq!(app.selected_path(), Some(root.join("beta/nested/deep.md")));
+}
@@ -0,0 +1,124 @@
+//! The components of the root path, as drawn on the root line,
+//!  which can be highlighted to re-root the tree on an ancestor.
+//! The root line may also end with the breadcrumb of the stacked
+//!  states, telling the roots the back action goes through.
+
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+/// a component of the root path
+#[derive(Debug, Clone)]
+pub struct Crumb {
+    pub path: PathBuf,       // the ancestor ending with this component
+    pub range: Range<usize>, // the byte offsets of the component in the root line
+}
+
+#[derive(Debug, Clone)]
+pub struct Breadcrumbs {
+    pub crumbs: Vec<Crumb>, // from the filesystem root to the tree root
+}
+
+impl Breadcrumbs {
+    pub fn from(root: &Path) -> Breadcrumbs {
+        let displayed = root.to_string_lossy();
+        let mut crumbs = Vec::new();
+        if displayed.starts_with('/') {
+            crumbs.push(Crumb {
+                path: PathBuf::from("/"),
+                range: 0..1,
+            });
+        }
+        let mut start = 0;
+        for part in displayed.split('/') {
+            let end = start + part.len();
+            if !part.is_empty() {
+                crumbs.push(Crumb {
+                    path: PathBuf::from(&displayed[..end]),
+                    range: start..end,
+                });
+            }
+            start = end + 1; // skipping the separator
+        }
+        Breadcrumbs { crumbs }
+    }
+    pub fn len(&self) -> usize {
+        self.crumbs.len()
+    }
+    pub fn get(&self, idx: usize) -> Option<&Crumb> {
+        self.crumbs.get(idx)
+    }
+    /// the index of the component at the given byte offset of the
+    ///  root line, if any (the separators aren't part of a component)
+    #[cfg(test)]
+    pub fn index_at(&self, offset: usize) -> Option<usize> {
+        self.crumbs
+            .iter()
+            .position(|crumb| crumb.range.contains(&offset))
+    }
+}
+
+/// the breadcrumb of the roots of the stacked states, oldest first, eg
+///  `proj ▸ src ▸ widgets (3)`, cut in its start and its current part,
+///  drawn with different colors. The names before the current one are
+///  replaced with a '…', from the last ones, until it fits in width.
+///  None when even the shortest form doesn't fit
+pub fn fit_stack(names: &[String], width: usize) -> Option<(String, String)> {
+    let (current, previous) = names.split_last()?;
+    let current = format!("{} ({})", current, names.len());
+    let width = width.checked_sub(current.chars().count())?;
+    for kept in (0..=previous.len()).rev() {
+        let mut head: Vec<&str> = previous[..kept].iter().map(|name| name.as_str()).collect();
+        if kept < previous.len() {
+            head.push("…");
+        }
+        let head: String = head.iter().map(|name| format!("{} ▸ ", name)).collect();
+        if head.chars().count() <= width {
+            return Some((head, current));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod breadcrumbs_tests {
+    use super::*;
+
+    #[test]
+    fn components_and_offsets() {
+        let bc = Breadcrumbs::from(Path::new("/home/me/src"));
+        let paths: Vec<&Path> = bc.crumbs.iter().map(|c| c.path.as_path()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                Path::new("/"),
+                Path::new("/home"),
+                Path::new("/home/me"),
+                Path::new("/home/me/src"),
+            ]
+        );
+        assert_eq!(bc.crumbs[2].range, 6..8);
+        assert_eq!(bc.index_at(0), Some(0));
+        assert_eq!(bc.index_at(3), Some(1));
+        assert_eq!(bc.index_at(5), None); // a separator
+        assert_eq!(bc.index_at(11), Some(3));
+        assert_eq!(bc.index_at(12), None);
+    }
+
+    #[test]
+    fn fitted_stacks() {
+        let names: Vec<String> = ["proj", "src", "widgets"].iter().map(|s| s.to_string()).collect();
+        let fit = |width| fit_stack(&names, width).map(|(head, current)| head + &current);
+        assert_eq!(fit(80), Some("proj ▸ src ▸ widgets (3)".to_string()));
+        assert_eq!(fit(22), Some("proj ▸ … ▸ widgets (3)".to_string()));
+        assert_eq!(fit(15), Some("… ▸ widgets (3)".to_string()));
+        assert_eq!(fit(14), None);
+        assert_eq!(fit_stack(&[], 80), None);
+    }
+
+    #[test]
+    fn filesystem_root() {
+        let bc = Breadcrumbs::from(Path::new("/"));
+        assert_eq!(bc.len(), 1);
+        assert_eq!(bc.crumbs[0].path, PathBuf::from("/"));
+    }
+}
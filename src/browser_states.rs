@@ -1,34 +1,111 @@
 //! An application state dedicated to displaying a tree.
 //! It's the first and main screen of broot.
 
+use std::fs;
 use std::io::{self, Write};
-use std::path::PathBuf;
+use std::mem;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Component, Path, PathBuf};
 use std::result::Result;
-use std::time::Instant;
+use std::time::{Duration, Instant};
+
+use termion::event::Key;
 
 use crate::app::{AppState, AppStateCmdResult};
 use crate::app_context::AppContext;
+use crate::breadcrumbs::Breadcrumbs;
+use crate::collisions;
 use crate::commands::{Action, Command};
 use crate::errors::TreeBuildError;
-use crate::external::Launchable;
-use crate::flat_tree::{LineType, Tree};
+use crate::external;
+use crate::file_sizes::{SizeInfo, SizeJob};
+use crate::flat_tree::{LineType, Tree, TreeLine};
+use crate::git_repos;
 use crate::help_states::HelpState;
+use crate::hints;
+use crate::internals::Internal;
+use crate::mounts::{self, Mount};
+use crate::owners;
+use crate::path_completion::PathCompletion;
 use crate::patterns::Pattern;
+use crate::permissions::{ChmodArgs, ChmodJob};
 use crate::screens::Screen;
+use crate::special_paths;
 use crate::status::Status;
-use crate::task_sync::TaskLifetime;
+use crate::task_sync::{RefreshRequest, TaskLifetime};
 use crate::tree_build::TreeBuilder;
 use crate::tree_options::{OptionBool, TreeOptions};
-use crate::tree_views::TreeView;
+use crate::tree_views::{self, with_thousands_separator, TreeView};
+use crate::verb_invocation::VerbInvocation;
 use crate::verbs::{VerbExecutor};
 use crate::verb_store::{PrefixSearchResult};
+use crate::yank::{self, PasteJob, Yank, YankMode};
+
+/// the togglable flags proposed in the flags menu: the first
+///  char of the label is the key to type, then comes the toggle verb
+const FLAGS_MENU: &[(&str, &str)] = &[
+    ("hidden", "toggle_hidden"),
+    ("sizes", "toggle_sizes"),
+    ("perms", "toggle_perm"),
+    ("owner", "toggle_owner"),
+    ("dates", "toggle_dates"),
+    ("gitignore", "toggle_git_ignore"),
+    ("files", "toggle_files"),
+    ("trim root", "toggle_trim_root"),
+    ("numbers", "toggle_line_numbers"),
+];
+
+// a search done in steps, in the pending tasks
+struct SearchJob {
+    builder: TreeBuilder,
+    started_at: Instant,
+    published: bool, // whether a tree of this search is displayed
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BrowserMode {
+    Normal,
+    FlagsMenu, // the next key chooses a flag to toggle
+    Breadcrumb(usize), // a component of the root path is highlighted
+    Hints, // the visible lines are labeled, the next letters choose one
+}
 
 pub struct BrowserState {
     pub tree: Tree,
     pub filtered_tree: Option<Tree>,
     pending_pattern: Pattern, // a pattern (or not) which has not yet be applied
+    total_search: bool, // whether the pending pattern must be searched without limit
+    search: Option<SearchJob>, // the search of the pattern, being done
+    mode: BrowserMode,
+    pending_chmod: Option<ChmodJob>, // a recursive chmod being done
+    selection_after_refresh: Option<PathBuf>, // eg a newly created file
+    pending_paste: Option<PasteJob>, // a copy or move being done
+    selection_before_search: Option<PathBuf>, // restored when the pattern is removed
+    manual_selection: Option<PathBuf>, // chosen while searching, kept while it matches
+    fs_info: Option<Mount>, // the filesystem of the root, with its free space
+    pending_size: Option<SizeJob>, // the size of an entry, asked with :size
+    selection_moved_at: Option<Instant>, // when a background task last moved the selected line
+    changes_checked_at: Instant, // when the root was last checked for changes made outside broot
+    outdated: bool, // whether the root changed since the displayed tree was read
+    refresh: RefreshRequest, // whether the trees must be read again, as a pending task
+    completion: Option<PathCompletion>, // the last completion of a path argument, cycled on <tab>
+    #[cfg(test)]
+    pub nb_refreshes: usize, // how many times the trees were read again
 }
 
+// the width of the bar showing the used part of the filesystem
+const FS_BAR_WIDTH: usize = 5;
+
+// how long <enter> is ignored after a background task moved the selected line
+const MOVED_SELECTION_GUARD: Duration = Duration::from_millis(80);
+
+// the minimal delay between two checks of the root for external changes
+const CHANGES_CHECK_PERIOD: Duration = Duration::from_secs(3);
+
+// the duration of a step of a search, after which the best
+//  matches found so far are displayed
+const SEARCH_STEP: Duration = Duration::from_millis(150);
+
 impl BrowserState {
     pub fn new(
         path: PathBuf,
@@ -38,15 +115,45 @@ impl BrowserState {
     ) -> Result<Option<BrowserState>, TreeBuildError> {
         let pending_pattern = options.pattern;
         options.pattern = Pattern::None;
+        let fs_info = BrowserState::read_fs_info(&path, &options);
         let builder = TreeBuilder::from(path, options, BrowserState::page_height(screen) as usize)?;
-        Ok(match builder.build(tl) {
-            Some(tree) => Some(BrowserState {
-                tree,
-                filtered_tree: None,
-                pending_pattern,
-            }),
-            None => None, // interrupted
-        })
+        Ok(builder.build(tl).map(|tree| BrowserState::from_tree(tree, pending_pattern, fs_info)))
+    }
+    fn from_tree(tree: Tree, pending_pattern: Pattern, fs_info: Option<Mount>) -> BrowserState {
+        BrowserState {
+            tree,
+            filtered_tree: None,
+            pending_pattern,
+            total_search: false,
+            search: None,
+            mode: BrowserMode::Normal,
+            pending_chmod: None,
+            selection_after_refresh: None,
+            pending_paste: None,
+            selection_before_search: None,
+            manual_selection: None,
+            fs_info,
+            pending_size: None,
+            selection_moved_at: None,
+            changes_checked_at: Instant::now(),
+            outdated: false,
+            refresh: RefreshRequest::new(),
+            completion: None,
+            #[cfg(test)]
+            nb_refreshes: 0,
+        }
+    }
+    /// create a state for a new root, with the options of the
+    ///  special path of the configuration it matches, if any
+    pub fn new_root(
+        path: PathBuf,
+        mut options: TreeOptions,
+        con: &AppContext,
+        screen: &Screen,
+        tl: &TaskLifetime,
+    ) -> Result<Option<BrowserState>, TreeBuildError> {
+        special_paths::apply(&con.special_paths, &path, &mut options);
+        BrowserState::new(path, options, screen, tl)
     }
     pub fn with_new_options(
         &self,
@@ -66,11 +173,242 @@ impl BrowserState {
                 screen,
                 &TaskLifetime::unlimited(),
             ),
-            tree.options.pattern.to_command(),
+            Command::from_pattern(&tree.options.pattern),
         )
     }
+    /// a state showing other columns, whose trees aren't built again, the
+    ///  metadata they need being read by the pending task
+    pub fn with_new_columns(
+        &self,
+        screen: &Screen,
+        change_options: &dyn Fn(&mut TreeOptions),
+    ) -> AppStateCmdResult {
+        if self.search.is_some() || self.pending_pattern.is_some() {
+            // the search would be restarted anyway
+            return self.with_new_options(screen, change_options);
+        }
+        let with_columns = |tree: &Tree| {
+            let mut options = tree.options.clone();
+            change_options(&mut options);
+            tree.with_columns(options)
+        };
+        let mut state = BrowserState::from_tree(
+            with_columns(&self.tree),
+            Pattern::None,
+            self.fs_info.clone(),
+        );
+        state.filtered_tree = self.filtered_tree.as_ref().map(with_columns);
+        let pattern = &self.displayed_tree().options.pattern;
+        AppStateCmdResult::NewState(Box::new(state), Command::from_pattern(pattern))
+    }
+    // the filesystem containing the root, unless it's not wanted or
+    //  can't be known (then there's just no indicator)
+    fn read_fs_info(root: &Path, options: &TreeOptions) -> Option<Mount> {
+        if !options.show_fs_info {
+            return None;
+        }
+        match mounts::mount_of(root) {
+            Ok(mount) => Some(mount),
+            Err(e) => {
+                debug!("no filesystem info for {:?}: {:?}", root, e);
+                None
+            }
+        }
+    }
     fn page_height(screen: &Screen) -> i32 {
-        i32::from(screen.h) - 2
+        tree_views::page_height(screen)
+    }
+    // rebuild the tree with all the entries of the selected directory read,
+    //  even when there are more than max_children. When the selection is a
+    //  file or a pruning line, its parent directory is concerned
+    pub fn uncap_selected_dir(&self, screen: &Screen) -> AppStateCmdResult {
+        let line = self.displayed_tree().selected_line();
+        let dir = match line.line_type {
+            LineType::Dir => line.path.clone(),
+            _ => match line.path.parent() {
+                Some(parent) => parent.to_path_buf(),
+                None => line.path.clone(),
+            },
+        };
+        self.with_new_options(screen, &|o| o.uncapped_dirs.push(dir.clone()))
+    }
+    // remember what was selected before a search starts
+    fn start_search(&mut self) {
+        if self.filtered_tree.is_none() && self.selection_before_search.is_none() {
+            self.selection_before_search = Some(self.tree.selected_line().path.clone());
+        }
+    }
+    // remove the filtered tree, selecting again what was selected before
+    fn end_search(&mut self, page_height: i32) {
+        self.search = None;
+        self.filtered_tree = None;
+        self.manual_selection = None;
+        if let Some(path) = self.selection_before_search.take() {
+            if self.tree.try_select_path(&path) {
+                self.tree.make_selection_visible(page_height);
+            }
+        }
+    }
+    // start the search of the pending pattern, which is then done
+    //  in steps in the pending tasks
+    fn start_search_job(&mut self, page_height: i32) -> Result<(), TreeBuildError> {
+        let mut options = self.tree.options.clone();
+        options.pattern = self.pending_pattern.take();
+        let total_search = mem::replace(&mut self.total_search, false);
+        let root = self.tree.root().clone();
+        self.search = None;
+        let builder = TreeBuilder::from(root, options, page_height as usize)?;
+        self.search = Some(SearchJob {
+            builder: if total_search { builder.total_search() } else { builder },
+            started_at: Instant::now(),
+            published: total_search, // the quick result of the pattern is displayed
+        });
+        Ok(())
+    }
+    // do a step of the search, then display its tree: the final one when
+    //  it's done, else the one of the best matches found so far (unless
+    //  it's a total search, the quick result staying until the end)
+    fn advance_search(&mut self, screen: &mut Screen, tl: &TaskLifetime) {
+        let job = match &mut self.search {
+            Some(job) => job,
+            None => {
+                return;
+            }
+        };
+        let tree = match job.builder.advance(tl, SEARCH_STEP) {
+            Some(tree) => {
+                info!(
+                    "Tree search with pattern {} took {:?}",
+                    &tree.options.pattern,
+                    job.started_at.elapsed()
+                );
+                if job.builder.is_total_search() {
+                    let nb_matches = tree.lines[0].nb_matches;
+                    let _ = screen.write_status_text(&format!(
+                        "exhaustive: {} match{}",
+                        nb_matches,
+                        if nb_matches == 1 { "" } else { "es" },
+                    ));
+                }
+                self.search = None;
+                tree
+            }
+            // an interrupted search goes on once the key is handled
+            None if tl.is_expired() || job.builder.is_total_search() => {
+                return;
+            }
+            None => match job.builder.partial_tree(tl) {
+                Some(tree) => {
+                    job.published = true;
+                    tree
+                }
+                None => {
+                    return;
+                }
+            },
+        };
+        self.show_filtered_tree(tree, BrowserState::page_height(screen));
+    }
+    // display the tree of a search, selecting its best match, unless a
+    //  selection made while searching still matches
+    fn show_filtered_tree(&mut self, mut tree: Tree, page_height: i32) {
+        let kept = match &self.manual_selection {
+            Some(path) => tree.try_select_matching_path(path),
+            None => false,
+        };
+        if !kept {
+            self.manual_selection = None;
+            tree.try_select_best_match();
+        }
+        tree.make_selection_visible(page_height);
+        self.filtered_tree = Some(tree);
+    }
+    // before the selection is used, make sure it's in the tree of the
+    //  typed pattern, even if its search isn't done: the best match
+    //  found so far is then selected
+    fn catch_up_search(&mut self, screen: &mut Screen) {
+        if let Some(job) = &self.search {
+            if !job.published {
+                self.advance_search(screen, &TaskLifetime::unlimited());
+            }
+        }
+    }
+    // select the line of the path, if it's displayed
+    pub fn select_path(&mut self, path: &Path, screen: &Screen) {
+        let page_height = BrowserState::page_height(screen);
+        let tree = self.displayed_tree_mut();
+        if tree.try_select_path(path) {
+            tree.make_selection_visible(page_height);
+        }
+    }
+    // select the next (dir > 0) or previous line with the same depth
+    pub fn move_selection_same_depth(
+        &mut self,
+        dir: i32,
+        screen: &Screen,
+        con: &AppContext,
+    ) -> AppStateCmdResult {
+        let page_height = BrowserState::page_height(screen);
+        let tree = self.displayed_tree_mut();
+        if !tree.move_selection_same_depth(dir, page_height, con.same_depth_jump_to_parent) {
+            return AppStateCmdResult::DisplayError(
+                if dir > 0 { "no next line at this depth" } else { "no previous line at this depth" }
+                    .to_string(),
+            );
+        }
+        if let Some(ref tree) = self.filtered_tree {
+            self.manual_selection = Some(tree.selected_line().path.clone());
+        }
+        AppStateCmdResult::Keep
+    }
+    pub fn breadcrumbs(&self) -> Breadcrumbs {
+        Breadcrumbs::from(self.displayed_tree().root())
+    }
+    // open a new state whose root is the ancestor ending with the
+    //  highlighted component, selecting the current root
+    fn reroot_at_crumb(&self, idx: usize, screen: &Screen, con: &AppContext) -> AppStateCmdResult {
+        let crumb = match self.breadcrumbs().get(idx) {
+            Some(crumb) => crumb.clone(),
+            None => {
+                return AppStateCmdResult::Keep;
+            }
+        };
+        let tree = self.displayed_tree();
+        match BrowserState::new_root(
+            crumb.path,
+            tree.options.without_pattern(),
+            con,
+            screen,
+            &TaskLifetime::unlimited(),
+        ) {
+            Ok(Some(mut state)) => {
+                state.tree.try_select_path_or_ancestor(tree.root());
+                state.tree.make_selection_visible(BrowserState::page_height(screen));
+                AppStateCmdResult::NewState(Box::new(state), Command::new())
+            }
+            Ok(None) => AppStateCmdResult::Keep,
+            Err(e) => AppStateCmdResult::DisplayError(e.to_string()),
+        }
+    }
+    /// label the visible lines, the next letters typed choosing one
+    pub fn show_hints(&mut self) -> AppStateCmdResult {
+        self.mode = BrowserMode::Hints;
+        AppStateCmdResult::Keep
+    }
+    /// the labels of the visible selectable lines, shown in hint mode
+    fn hints(&self, screen: &Screen) -> Vec<(usize, String)> {
+        let tree = self.displayed_tree();
+        let nb_rows = BrowserState::page_height(screen) - 1; // the root's row excluded
+        let start = tree.scroll as usize + 1;
+        let end = (start + nb_rows.max(0) as usize).min(tree.lines.len());
+        let indexes: Vec<usize> = (start..end)
+            .filter(|&idx| tree.lines[idx].is_selectable())
+            .collect();
+        hints::labels(indexes.len())
+            .into_iter()
+            .zip(indexes)
+            .map(|(label, idx)| (idx, label))
+            .collect()
     }
     pub fn displayed_tree(&self) -> &Tree {
         match &self.filtered_tree {
@@ -78,6 +416,376 @@ impl BrowserState {
             None => &self.tree,
         }
     }
+    pub fn displayed_tree_mut(&mut self) -> &mut Tree {
+        match self.filtered_tree {
+            Some(ref mut tree) => tree,
+            None => &mut self.tree,
+        }
+    }
+    // create a directory or an empty file in the selected directory (or
+    //  the parent of the selected file), then select it after refresh.
+    // The name can contain path separators only for a directory
+    //  with the -p option, and must stay below the parent.
+    pub fn create_entry(&mut self, args: &Option<String>, dir: bool) -> AppStateCmdResult {
+        let args = match args {
+            Some(args) => args.as_str(),
+            None => "",
+        };
+        let mut parents = false;
+        let mut names = Vec::new();
+        for token in args.split_whitespace() {
+            match token {
+                "-p" if dir => parents = true,
+                _ => names.push(token),
+            }
+        }
+        let name = match names.as_slice() {
+            [name] => *name,
+            [_, _, ..] => {
+                return AppStateCmdResult::DisplayError(
+                    "only one entry can be created at a time".to_string(),
+                );
+            }
+            [] => {
+                return AppStateCmdResult::DisplayError(if dir {
+                    "usage: mkdir [-p] {subpath}".to_string()
+                } else {
+                    "usage: touch {name}".to_string()
+                });
+            }
+        };
+        if name.contains('/') && !parents {
+            return AppStateCmdResult::DisplayError(if dir {
+                format!("{:?} isn't a simple name (use `:mkdir -p {}` to create intermediate directories)", name, name)
+            } else {
+                format!("{:?} isn't a simple name", name)
+            });
+        }
+        let subpath = Path::new(name);
+        if subpath.is_absolute() || subpath.components().any(|c| c == Component::ParentDir) {
+            return AppStateCmdResult::DisplayError(format!(
+                "{:?} isn't below the selected directory",
+                name,
+            ));
+        }
+        let mut parent = self.displayed_tree().selected_line().target();
+        if !parent.is_dir() {
+            if let Some(p) = parent.parent() {
+                parent = p.to_path_buf();
+            }
+        }
+        let path = parent.join(name);
+        if path.exists() {
+            return AppStateCmdResult::DisplayError(format!("{:?} already exists", name));
+        }
+        let created = if dir {
+            if parents {
+                fs::create_dir_all(&path)
+            } else {
+                fs::create_dir(&path)
+            }
+        } else {
+            fs::OpenOptions::new().write(true).create_new(true).open(&path).map(|_| ())
+        };
+        match created {
+            Ok(()) => {
+                self.selection_after_refresh = Some(path);
+                AppStateCmdResult::RefreshState
+            }
+            Err(e) => AppStateCmdResult::DisplayError(format!("Unable to create {:?}: {}", name, e)),
+        }
+    }
+    fn select_next_match(&mut self, page_height: i32) {
+        if let Some(ref mut tree) = self.filtered_tree {
+            tree.try_select_next_match();
+            tree.make_selection_visible(page_height);
+            self.manual_selection = Some(tree.selected_line().path.clone());
+        }
+    }
+    // complete the path argument ending the input, from the directory of
+    //  the selection, or put the next candidate in the input when <tab>
+    //  is hit again. When it's not a path, <tab> goes to the next match
+    fn complete_path(
+        &mut self,
+        cmd: &mut Command,
+        page_height: i32,
+        con: &AppContext,
+    ) -> AppStateCmdResult {
+        let completion = match self.completion.take() {
+            Some(mut completion) if completion.raw == cmd.raw && completion.is_ambiguous() => {
+                completion.cycle();
+                Some(completion)
+            }
+            _ => match cmd.verb_invocation().map(|(_, inv)| con.verb_store.search(&inv.key)) {
+                Some(PrefixSearchResult::Match(verb)) => {
+                    let tree = self.displayed_tree();
+                    let mut dir = tree.selected_line().target();
+                    if !dir.is_dir() {
+                        if let Some(parent) = dir.parent() {
+                            dir = parent.to_path_buf();
+                        }
+                    }
+                    PathCompletion::new(cmd, verb, &dir, &tree.options)
+                }
+                _ => None,
+            },
+        };
+        match completion {
+            None => {
+                self.select_next_match(page_height);
+                AppStateCmdResult::Keep
+            }
+            Some(ref completion) if completion.is_empty() => {
+                AppStateCmdResult::DisplayError("no path completes the argument".to_string())
+            }
+            Some(completion) => {
+                cmd.set_raw(completion.raw.clone());
+                let result = if completion.is_ambiguous() {
+                    AppStateCmdResult::DisplayMessage(completion.status())
+                } else {
+                    AppStateCmdResult::Keep
+                };
+                self.completion = Some(completion);
+                result
+            }
+        }
+    }
+    // select the line whose number (as displayed with line numbers) is given
+    pub fn go_to_line(&mut self, args: &Option<String>, screen: &Screen) -> AppStateCmdResult {
+        let page_height = BrowserState::page_height(screen);
+        let arg = match args {
+            Some(arg) => arg.trim(),
+            None => "",
+        };
+        let idx = match arg.parse::<usize>() {
+            Ok(idx) => idx,
+            Err(_) => {
+                return AppStateCmdResult::DisplayError(format!("{:?} isn't a line number", arg));
+            }
+        };
+        let tree = self.displayed_tree_mut();
+        if idx >= tree.lines.len() || !tree.lines[idx].is_selectable() {
+            return AppStateCmdResult::DisplayError(format!("There's no line {}", idx));
+        }
+        tree.selection = idx;
+        tree.make_selection_visible(page_height);
+        AppStateCmdResult::Keep
+    }
+    // remember the selection, for a later :paste
+    pub fn yank(&self, mode: YankMode) -> AppStateCmdResult {
+        let path = self.displayed_tree().selected_line().path.clone();
+        yank::set(Yank {
+            mode,
+            paths: vec![path],
+        });
+        AppStateCmdResult::Keep
+    }
+    /// search the current pattern again, in the whole tree, without
+    ///  the limits of the search done while typing
+    pub fn total_search(&mut self) -> AppStateCmdResult {
+        match &self.filtered_tree {
+            Some(tree) if tree.options.pattern.is_some() => {
+                self.pending_pattern = tree.options.pattern.clone();
+                self.total_search = true;
+                AppStateCmdResult::Keep
+            }
+            _ => AppStateCmdResult::DisplayError("There's no pattern to search".to_string()),
+        }
+    }
+    // start computing the size of the selected entry, as a pending
+    //  task, or cancel the computation if there's already one
+    pub fn size(&mut self) -> AppStateCmdResult {
+        if let Some(job) = self.pending_size.take() {
+            return AppStateCmdResult::DisplayError(format!(
+                "Computation of the size of {} cancelled",
+                job.path.to_string_lossy(),
+            ));
+        }
+        let tree = self.displayed_tree();
+        let path = tree.selected_line().path.clone();
+        self.pending_size = Some(SizeJob::new(path, tree.root_dev()));
+        AppStateCmdResult::Keep
+    }
+    // copy or move the yanked paths into the selected directory (or
+    //  the parent of the selected file). The copies are done as a
+    //  pending task.
+    pub fn paste(&mut self, overwrite: bool, con: &AppContext) -> AppStateCmdResult {
+        let yank = match yank::get() {
+            Some(yank) => yank,
+            None => {
+                return AppStateCmdResult::DisplayError(
+                    "Nothing to paste: use :cp or :mv first".to_string(),
+                );
+            }
+        };
+        let mut dir = self.displayed_tree().selected_line().target();
+        if !dir.is_dir() {
+            if let Some(p) = dir.parent() {
+                dir = p.to_path_buf();
+            }
+        }
+        match PasteJob::new(&yank, &dir, overwrite, con.paste_conflict) {
+            Ok(job) => {
+                if job.is_move() {
+                    // the sources won't be there anymore
+                    yank::clear();
+                }
+                if job.is_done() {
+                    self.selection_after_refresh = job.targets.first().cloned();
+                    AppStateCmdResult::RefreshState
+                } else {
+                    self.pending_paste = Some(job);
+                    AppStateCmdResult::Keep
+                }
+            }
+            Err(e) => AppStateCmdResult::DisplayError(e),
+        }
+    }
+    /// ask for the trees to be read again. It's done as a pending
+    ///  task, the requests being coalesced
+    pub fn request_refresh(&mut self) {
+        self.refresh.request();
+    }
+    #[cfg(test)]
+    pub fn is_refresh_requested(&self) -> bool {
+        self.refresh.is_requested()
+    }
+    // read the trees again. Return whether the selected line of the
+    //  displayed tree moved on screen
+    fn refresh_trees(&mut self, page_height: usize) -> bool {
+        self.refresh_trees_within(page_height, &TaskLifetime::unlimited())
+            .unwrap() // an unlimited task isn't interrupted
+    }
+    // read the trees again, unless the task expires, in which case
+    //  None is returned and the refresh stays requested, to be started
+    //  again. A finished refresh fulfills the pending request
+    fn refresh_trees_within(&mut self, page_height: usize, tl: &TaskLifetime) -> Option<bool> {
+        let mut moved = match self.tree.refresh_within(page_height, tl) {
+            Ok(moved) => moved?,
+            Err(e) => {
+                warn!("refreshing base tree failed : {:?}", e);
+                false
+            }
+        };
+        // refresh the filtered tree, if any
+        if let Some(ref mut tree) = self.filtered_tree {
+            moved = match tree.refresh_within(page_height, tl) {
+                Ok(moved) => moved?,
+                Err(e) => {
+                    warn!("refreshing filtered tree failed : {:?}", e);
+                    false
+                }
+            };
+        }
+        self.refresh.done();
+        #[cfg(test)]
+        {
+            self.nb_refreshes += 1;
+        }
+        debug!("refresh of {:?}", self.tree.root());
+        self.outdated = false;
+        self.changes_checked_at = Instant::now();
+        // the free space may have changed too
+        self.fs_info = BrowserState::read_fs_info(self.tree.root(), &self.tree.options);
+        if let Some(path) = self.selection_after_refresh.take() {
+            let tree = self.displayed_tree_mut();
+            let row = tree.selection as i32 - tree.scroll;
+            tree.try_select_path_or_ancestor(&path);
+            tree.make_selection_visible(page_height as i32);
+            moved |= tree.selection as i32 - tree.scroll != row;
+        }
+        Some(moved)
+    }
+    // whether the selected line was moved by a background task so
+    //  recently that <enter> is probably meant for the line which was there
+    fn selection_just_moved(&self) -> bool {
+        self.selection_moved_at
+            .map_or(false, |moved_at| moved_at.elapsed() < MOVED_SELECTION_GUARD)
+    }
+    fn refresh_modes(&mut self) {
+        self.tree.refresh_modes();
+        if let Some(ref mut tree) = self.filtered_tree {
+            tree.refresh_modes();
+        }
+    }
+    // change the mode of the selected file. The args are the mode,
+    //  optionally preceded by -R to change a whole directory, which
+    //  is done as a pending task
+    pub fn chmod(&mut self, args: &Option<String>) -> AppStateCmdResult {
+        let ChmodArgs { recursive, spec } = match ChmodArgs::from(args) {
+            Ok(args) => args,
+            Err(e) => return AppStateCmdResult::DisplayError(e.to_string()),
+        };
+        let path = self.displayed_tree().selected_line().target();
+        if recursive && path.is_dir() {
+            self.pending_chmod = Some(ChmodJob::new(path, spec));
+            return AppStateCmdResult::Keep;
+        }
+        match spec.apply(&path) {
+            Ok(()) => {
+                self.refresh_modes();
+                AppStateCmdResult::Keep
+            }
+            Err(e) => AppStateCmdResult::DisplayError(format!("chmod failed: {}", e)),
+        }
+    }
+}
+
+// whether the action changes the pattern to search
+fn is_pattern_edit(action: &Action) -> bool {
+    match action {
+        Action::FuzzyPatternEdit(_) | Action::RegexEdit(_, _) | Action::WordsPatternEdit(_) => true,
+        _ => false,
+    }
+}
+
+// whether the action is applied to the selected line
+fn uses_selection(action: &Action) -> bool {
+    match action {
+        Action::OpenSelection | Action::AltOpenSelection | Action::Verb(_) | Action::HotKey(_) => {
+            true
+        }
+        _ => false,
+    }
+}
+
+// how far the search went, eg "12,400 entries scanned, 37 matches"
+fn search_progress(job: &SearchJob) -> String {
+    let progress = job.builder.progress();
+    format!(
+        "{} entries scanned, {} match{}",
+        with_thousands_separator(progress.nb_scanned),
+        with_thousands_separator(progress.nb_matches),
+        if progress.nb_matches == 1 { "" } else { "es" },
+    )
+}
+
+// what's checked out in the directory of the line, when it's the root
+//  of a git repository, read without running git
+fn git_head(line: &TreeLine) -> Option<String> {
+    if line.is_git_repo {
+        git_repos::head(&line.path).map(|head| head.to_string())
+    } else {
+        None
+    }
+}
+
+// the mode and the owner of the entry, which tell who may open it
+fn mode_and_owner(path: &Path, lookup_users: bool) -> String {
+    let md = match fs::metadata(path) {
+        Ok(md) => md,
+        Err(e) => {
+            return e.to_string();
+        }
+    };
+    let (user, group) = if lookup_users {
+        owners::look_for(md.uid(), md.gid());
+        let names = owners::known();
+        (names.user(md.uid()), names.group(md.gid()))
+    } else {
+        (md.uid().to_string(), md.gid().to_string())
+    };
+    format!("mode {:o}, owned by {}:{}", md.mode() & 0o7777, user, group)
 }
 
 impl AppState for BrowserState {
@@ -87,12 +795,120 @@ impl AppState for BrowserState {
         screen: &mut Screen,
         con: &AppContext,
     ) -> io::Result<AppStateCmdResult> {
+        // the search of the typed pattern goes on, unless the pattern
+        //  changes, but a total search is interrupted by any key
+        if self.pending_pattern.is_some() && !is_pattern_edit(&cmd.action) {
+            if let Err(e) = self.start_search_job(BrowserState::page_height(screen)) {
+                return Ok(AppStateCmdResult::DisplayError(e.to_string()));
+            }
+        }
+        if self.search.as_ref().map_or(false, |job| job.builder.is_total_search()) {
+            self.search = None;
+        }
+        if uses_selection(&cmd.action) {
+            self.catch_up_search(screen);
+        }
         self.pending_pattern = Pattern::None;
+        self.total_search = false;
         let page_height = BrowserState::page_height(screen);
-        Ok(match &cmd.action {
+        if let BrowserMode::Breadcrumb(idx) = self.mode {
+            self.mode = BrowserMode::Normal;
+            match &cmd.action {
+                Action::MoveBreadcrumb(dx) => {
+                    // moving past the last component ends the highlighting
+                    let idx = idx as i32 + dx;
+                    if idx >= 0 && (idx as usize) < self.breadcrumbs().len() {
+                        self.mode = BrowserMode::Breadcrumb(idx as usize);
+                    }
+                    return Ok(AppStateCmdResult::Keep);
+                }
+                Action::OpenSelection => {
+                    return Ok(self.reroot_at_crumb(idx, screen, con));
+                }
+                Action::Back => {
+                    return Ok(AppStateCmdResult::Keep);
+                }
+                _ => {} // the highlighting is ended and the action normally handled
+            }
+        }
+        if self.mode == BrowserMode::Hints {
+            self.mode = BrowserMode::Normal;
+            match &cmd.action {
+                Action::HintKeys(typed) => {
+                    // an uppercase label opens the line, like <enter>
+                    let open = typed.chars().any(char::is_uppercase);
+                    let typed = typed.to_lowercase();
+                    let hints = self.hints(screen);
+                    if let Some((idx, _)) = hints.iter().find(|(_, label)| *label == typed) {
+                        *cmd = cmd.pattern_input();
+                        let tree = self.displayed_tree_mut();
+                        tree.selection = *idx;
+                        tree.make_selection_visible(page_height);
+                        if let Some(ref tree) = self.filtered_tree {
+                            self.manual_selection = Some(tree.selected_line().path.clone());
+                        }
+                        if !open {
+                            return Ok(AppStateCmdResult::Keep);
+                        }
+                        cmd.action = Action::OpenSelection;
+                        return self.apply(cmd, screen, con);
+                    }
+                    if hints.iter().any(|(_, label)| label.starts_with(&typed)) {
+                        self.mode = BrowserMode::Hints;
+                        return Ok(AppStateCmdResult::Keep);
+                    }
+                    let error = format!("no line labeled {:?}", typed);
+                    *cmd = cmd.pattern_input();
+                    return Ok(AppStateCmdResult::DisplayError(error));
+                }
+                Action::Back => {
+                    *cmd = cmd.pattern_input();
+                    return Ok(AppStateCmdResult::Keep);
+                }
+                _ => {} // any other key ends the hint mode and is normally applied
+            }
+        }
+        if self.mode == BrowserMode::FlagsMenu {
+            self.mode = BrowserMode::Normal;
+            match cmd.action {
+                Action::Back => {
+                    return Ok(AppStateCmdResult::Keep);
+                }
+                Action::MenuKey(c) => {
+                    let toggle = FLAGS_MENU.iter().find(|(label, _)| label.starts_with(c));
+                    if let Some((_, verb_key)) = toggle {
+                        let verb = &con.verb_store.verbs[con.verb_store.index_of(verb_key)];
+                        let invocation = VerbInvocation::from(verb_key);
+                        return self.execute_verb(verb, &invocation, screen, con);
+                    }
+                    // a char which isn't in the menu closes it and is typed
+                    cmd.add_key(Key::Char(c), &con.chords);
+                }
+                _ => {} // any other key closes the menu and is normally applied
+            }
+        }
+        let result = match &cmd.action {
             Action::Back => {
-                if self.filtered_tree.is_some() {
-                    self.filtered_tree = None;
+                if let Some(mut job) = self.pending_paste.take() {
+                    job.abort();
+                    self.refresh_trees(page_height as usize);
+                    AppStateCmdResult::DisplayError(format!(
+                        "Paste interrupted after {} copied files",
+                        job.nb_copied,
+                    ))
+                } else if let Some(job) = self.pending_chmod.take() {
+                    self.refresh_modes();
+                    AppStateCmdResult::DisplayError(format!(
+                        "chmod interrupted after {} changes",
+                        job.nb_changed,
+                    ))
+                } else if let Some(job) = self.pending_size.take() {
+                    AppStateCmdResult::DisplayError(format!(
+                        "Computation of the size of {} cancelled",
+                        job.path.to_string_lossy(),
+                    ))
+                } else if self.filtered_tree.is_some() || self.search.is_some() {
+                    self.end_search(page_height);
                     cmd.raw.clear();
                     AppStateCmdResult::Keep
                 } else if self.tree.selection > 0 {
@@ -104,14 +920,38 @@ impl AppState for BrowserState {
                 }
             }
             Action::MoveSelection(dy) => {
-                match self.filtered_tree {
-                    Some(ref mut tree) => {
-                        tree.move_selection(*dy, page_height);
-                    }
-                    None => {
-                        self.tree.move_selection(*dy, page_height);
-                    }
-                };
+                let tree = self.displayed_tree_mut();
+                tree.move_selection(*dy, page_height);
+                // the view may have been scrolled away from the selection
+                tree.make_selection_visible(page_height);
+                if let Some(ref tree) = self.filtered_tree {
+                    self.manual_selection = Some(tree.selected_line().path.clone());
+                }
+                AppStateCmdResult::Keep
+            }
+            Action::MoveBreadcrumb(dx) => {
+                // we start from the end, the last component being the root itself
+                let len = self.breadcrumbs().len();
+                if *dx < 0 && len > 1 {
+                    self.mode = BrowserMode::Breadcrumb(len - 2);
+                } else if *dx > 0 && len > 1 {
+                    self.mode = BrowserMode::Breadcrumb(0);
+                }
+                AppStateCmdResult::Keep
+            }
+            Action::SelectFirst | Action::SelectLast => {
+                let dir = if let Action::SelectFirst = cmd.action { -1 } else { 1 };
+                self.displayed_tree_mut().select_end(dir, page_height);
+                if let Some(ref tree) = self.filtered_tree {
+                    self.manual_selection = Some(tree.selected_line().path.clone());
+                }
+                AppStateCmdResult::Keep
+            }
+            Action::MoveSameDepth(dir) => self.move_selection_same_depth(*dir, screen, con),
+            Action::Scroll(dy) => {
+                let tree = self.displayed_tree_mut();
+                // the root line stays at the top, only the lines below it scroll
+                tree.scroll = (tree.scroll + *dy).max(0).min(tree.max_scroll(page_height));
                 AppStateCmdResult::Keep
             }
             Action::ScrollPage(dp) => {
@@ -128,6 +968,19 @@ impl AppState for BrowserState {
                 }
                 AppStateCmdResult::Keep
             }
+            Action::OpenSelection | Action::AltOpenSelection if self.selection_just_moved() => {
+                AppStateCmdResult::DisplayMessage(
+                    "the selected line just moved, hit <enter> again if it's the right one"
+                        .to_string(),
+                )
+            }
+            Action::OpenSelection if con.launch_args.picker => {
+                // any line, the root included, is picked
+                external::pick(&self.displayed_tree().selected_line().path, con)
+            }
+            Action::AltOpenSelection if con.launch_args.picker => {
+                AppStateCmdResult::disabled_in_picker("alt-enter")
+            }
             Action::OpenSelection => {
                 let tree = match &self.filtered_tree {
                     Some(tree) => tree,
@@ -140,21 +993,35 @@ impl AppState for BrowserState {
                     let tl = TaskLifetime::unlimited();
                     match &line.line_type {
                         LineType::File => {
-                            AppStateCmdResult::Launch(Launchable::opener(line.path.clone()))
+                            external::open_file(line.path.clone(), con.enter_on_file, con)?
                         }
-                        LineType::Dir | LineType::SymLinkToDir(_) => {
-                            AppStateCmdResult::from_optional_state(
-                                BrowserState::new(
-                                    line.target(),
-                                    tree.options.without_pattern(),
-                                    screen,
-                                    &tl,
+                        LineType::Dir | LineType::SymLinkToDir(_) | LineType::Parent => {
+                            let target = line.target();
+                            // rather than failing to read it, we tell why
+                            match line.dir_denial() {
+                                Some(denial) => AppStateCmdResult::DisplayError(format!(
+                                    "{} can't be opened: {} ({})",
+                                    line.name,
+                                    denial,
+                                    mode_and_owner(&target, tree.options.lookup_users),
+                                )),
+                                None => AppStateCmdResult::from_optional_state(
+                                    BrowserState::new_root(
+                                        target,
+                                        tree.options.without_pattern(),
+                                        con,
+                                        screen,
+                                        &tl,
+                                    ),
+                                    Command::new(),
                                 ),
-                                Command::new(),
-                            )
+                            }
+                        }
+                        LineType::SymLinkToFile(_) => {
+                            external::open_file(line.target(), con.enter_on_file, con)?
                         }
-                        LineType::SymLinkToFile(target) => {
-                            AppStateCmdResult::Launch(Launchable::opener(PathBuf::from(target)))
+                        LineType::Special(kind) => {
+                            AppStateCmdResult::DisplayError(format!("a {} can't be opened", kind))
                         }
                         _ => {
                             unreachable!();
@@ -168,26 +1035,56 @@ impl AppState for BrowserState {
                     None => &self.tree,
                 };
                 let line = tree.selected_line();
-                let cd_idx = con.verb_store.index_of("cd");
-                con.verb_store.verbs[cd_idx].to_cmd_result(&line.target(), &None, screen, con)?
+                let verb = &con.verb_store.verbs[con.verb_store.index_of("cd")];
+                let invocation = VerbInvocation::from(&verb.invocation.key);
+                verb.to_cmd_result(&line.target(), tree.root(), &invocation, screen, con)?
             }
             Action::Verb(invocation) => match con.verb_store.search(&invocation.key) {
-                PrefixSearchResult::Match(verb) => self.execute_verb(verb, &invocation, screen, con)?,
+                PrefixSearchResult::Match(verb) => {
+                    let invocation = invocation.clone();
+                    let result = self.execute_or_confirm(verb, &invocation, cmd, screen, con)?;
+                    if let (AppStateCmdResult::Keep, Action::Verb(_)) = (&result, &cmd.action) {
+                        // the verb was executed in this state, we clear it from the input
+                        *cmd = Command::from_pattern(&self.displayed_tree().options.pattern);
+                    }
+                    result
+                }
                 _ => AppStateCmdResult::verb_not_found(&invocation.key),
             },
+            Action::ConfirmAnswer(_, true) => {
+                let result = self.apply_verb_action(cmd, screen, con)?;
+                if let AppStateCmdResult::Keep = result {
+                    // the verb was executed in this state, we clear it from the input
+                    *cmd = Command::from_pattern(&self.displayed_tree().options.pattern);
+                }
+                result
+            }
             Action::FuzzyPatternEdit(pat) => match pat.len() {
                 0 => {
-                    self.filtered_tree = None;
+                    self.end_search(page_height);
                     AppStateCmdResult::Keep
                 }
                 _ => {
+                    self.start_search();
                     self.pending_pattern = Pattern::fuzzy(pat);
                     AppStateCmdResult::Keep
                 }
             },
+            Action::WordsPatternEdit(pat) => match Pattern::words(pat) {
+                Pattern::None => {
+                    self.end_search(page_height);
+                    AppStateCmdResult::Keep
+                }
+                pattern => {
+                    self.start_search();
+                    self.pending_pattern = pattern;
+                    AppStateCmdResult::Keep
+                }
+            },
             Action::RegexEdit(pat, flags) => {
                 match Pattern::regex(pat, flags) {
                     Ok(regex_pattern) => {
+                        self.start_search();
                         self.pending_pattern = regex_pattern;
                         AppStateCmdResult::Keep
                     }
@@ -201,19 +1098,46 @@ impl AppState for BrowserState {
                 AppStateCmdResult::NewState(Box::new(HelpState::new(screen)), Command::new())
             }
             Action::Quit => AppStateCmdResult::Quit,
+            Action::FlagsMenu => {
+                self.mode = BrowserMode::FlagsMenu;
+                AppStateCmdResult::Keep
+            }
+            Action::HintKeys(_) => self.show_hints(),
+            Action::Complete => self.complete_path(cmd, page_height, con),
             Action::Next => {
-                if let Some(ref mut tree) = self.filtered_tree {
-                    tree.try_select_next_match();
-                    tree.make_selection_visible(page_height);
-                }
+                self.select_next_match(page_height);
                 AppStateCmdResult::Keep
             }
-            _ => AppStateCmdResult::Keep,
-        })
+            _ => self.apply_verb_action(cmd, screen, con)?,
+        };
+        // the hint mode may come from a verb, the next letters being the label
+        if self.mode == BrowserMode::Hints {
+            *cmd = cmd.pattern_input();
+            cmd.action = Action::HintKeys(String::new());
+        }
+        Ok(result)
     }
 
     fn has_pending_tasks(&self) -> bool {
-        if self.pending_pattern.is_some() {
+        if self.refresh.is_requested() {
+            return true;
+        }
+        if self.pending_pattern.is_some() || self.search.is_some() {
+            return true;
+        }
+        if self.pending_chmod.is_some() || self.pending_paste.is_some() {
+            return true;
+        }
+        if self.pending_size.is_some() {
+            return true;
+        }
+        if self.displayed_tree().has_unchecked_access() {
+            return true;
+        }
+        if self.displayed_tree().has_missing_metadata() {
+            return true;
+        }
+        if owners::has_unknown(self.displayed_tree()) {
             return true;
         }
         if self.displayed_tree().has_dir_missing_size() {
@@ -225,132 +1149,460 @@ impl AppState for BrowserState {
     /// do some work, totally or partially, if there's some to do.
     /// Stop as soon as the lifetime is expired.
     fn do_pending_task(&mut self, screen: &mut Screen, tl: &TaskLifetime) {
+        if self.refresh.is_requested() {
+            // the other tasks would be done again after the refresh
+            if !self.refresh.is_due() {
+                self.refresh.wait();
+                return;
+            }
+            // an interrupted refresh is done again from the start later
+            let page_height = BrowserState::page_height(screen) as usize;
+            if let Some(true) = self.refresh_trees_within(page_height, tl) {
+                self.selection_moved_at = Some(Instant::now());
+            }
+            return;
+        }
         if self.pending_pattern.is_some() {
-            let start = Instant::now();
-            let mut options = self.tree.options.clone();
-            options.pattern = self.pending_pattern.take();
-            let root = self.tree.root().clone();
-            let len = self.tree.lines.len() as u16;
-            let mut filtered_tree = match TreeBuilder::from(root, options, len as usize) {
-                Ok(builder) => builder.build(tl),
-                Err(e) => {
-                    let _ = screen.write_status_err(&e.to_string());
-                    warn!("Error while building tree: {:?}", e);
-                    return;
+            if let Err(e) = self.start_search_job(BrowserState::page_height(screen)) {
+                let _ = screen.write_status_err(&e.to_string());
+                warn!("Error while building tree: {:?}", e);
+                return;
+            }
+        }
+        if self.search.is_some() {
+            self.advance_search(screen, tl);
+            return;
+        }
+        if let Some(ref mut job) = self.pending_paste {
+            job.advance(tl);
+            if job.is_done() {
+                if job.nb_errors > 0 {
+                    let _ = screen.write_status_err(&format!(
+                        "{} files copied, {} errors",
+                        job.nb_copied, job.nb_errors,
+                    ));
                 }
-            };
-            if let Some(ref mut filtered_tree) = filtered_tree {
-                info!(
-                    "Tree search with pattern {} took {:?}",
-                    &filtered_tree.options.pattern,
-                    start.elapsed()
-                );
-                filtered_tree.try_select_best_match();
-                filtered_tree.make_selection_visible(BrowserState::page_height(screen));
-            } // if none: task was cancelled from elsewhere
-            self.filtered_tree = filtered_tree;
+                self.selection_after_refresh = job.targets.first().cloned();
+                self.pending_paste = None;
+                if self.refresh_trees(BrowserState::page_height(screen) as usize) {
+                    self.selection_moved_at = Some(Instant::now());
+                }
+            }
             return;
         }
-        if let Some(ref mut tree) = self.filtered_tree {
+        if let Some(ref mut job) = self.pending_chmod {
+            job.advance(tl);
+            if job.is_done() {
+                let _ = if job.nb_errors > 0 {
+                    screen.write_status_err(&format!(
+                        "{} modes changed, {} errors",
+                        job.nb_changed, job.nb_errors,
+                    ))
+                } else {
+                    screen.write_status_text(&format!("{} modes changed", job.nb_changed))
+                };
+                self.pending_chmod = None;
+                self.refresh_modes();
+            }
+            return;
+        }
+        if let Some(ref mut job) = self.pending_size {
+            job.advance(tl);
+            if job.is_done() {
+                let path = job.path.to_string_lossy();
+                let _ = match job.size {
+                    SizeInfo::Computed(size) => {
+                        let bytes: u64 = size.into();
+                        screen.write_status_text(&format!(
+                            "Size of {}: {} ({} bytes)",
+                            path,
+                            size.to_string(),
+                            bytes,
+                        ))
+                    }
+                    SizeInfo::Incomplete(size, nb_errors) => screen.write_status_err(&format!(
+                        "Size of {}: at least {}, {} director{} couldn't be read",
+                        path,
+                        size.to_string(),
+                        nb_errors,
+                        if nb_errors > 1 { "ies" } else { "y" },
+                    )),
+                    _ => Ok(()),
+                };
+                // only the line of the entry gets its size when they're shown
+                let (path, size) = (job.path.clone(), job.size);
+                for tree in Some(&mut self.tree).into_iter().chain(self.filtered_tree.as_mut()) {
+                    if tree.options.show_sizes {
+                        for line in tree.lines.iter_mut().filter(|line| line.path == path) {
+                            line.size = size;
+                        }
+                    }
+                }
+                self.pending_size = None;
+            }
+            return;
+        }
+        let page_height = BrowserState::page_height(screen);
+        let tree = self.displayed_tree_mut();
+        if tree.has_unchecked_access() {
+            tree.check_some_access(page_height, tl);
+        } else if owners::has_unknown(tree) && owners::ask_for_some(tree, page_height, tl) {
+            // the names of the owners are looked for before the metadata
+            //  is complete, as the visible lines have theirs
+        } else if tree.has_missing_metadata() {
+            tree.fetch_some_missing_metadata(page_height, tl);
+        } else if tree.has_dir_missing_size() {
             tree.fetch_some_missing_dir_size(tl);
         } else {
-            self.tree.fetch_some_missing_dir_size(tl);
+            // only the names being looked for remain
+            owners::wait_for_some();
         }
     }
 
     fn display(&mut self, screen: &mut Screen, _con: &AppContext) -> io::Result<()> {
-        screen.write_tree(&self.displayed_tree())
+        let root_highlight = match self.mode {
+            BrowserMode::Breadcrumb(idx) => self.breadcrumbs().get(idx).map(|c| c.range.clone()),
+            _ => None,
+        };
+        let hints = if self.mode == BrowserMode::Hints {
+            self.hints(screen)
+        } else {
+            Vec::new()
+        };
+        screen.write_tree(&self.displayed_tree(), root_highlight, &hints)
     }
 
     fn write_status(&self, screen: &mut Screen, cmd: &Command, con: &AppContext) -> io::Result<()> {
+        if self.mode == BrowserMode::FlagsMenu {
+            let labels: Vec<&str> = FLAGS_MENU.iter().map(|(label, _)| *label).collect();
+            return screen.write_status_menu(&labels);
+        }
+        let verbs = &con.verb_store;
+        if self.mode == BrowserMode::Hints {
+            return screen.write_status_text(&format!(
+                "Type the label of a line to select it, in uppercase to open it, or {} to cancel",
+                verbs.hint_for("back"),
+            ));
+        }
+        if let BrowserMode::Breadcrumb(idx) = self.mode {
+            if let Some(crumb) = self.breadcrumbs().get(idx) {
+                return screen.write_status_text(&format!(
+                    "Hit {} to go to {}, {} to cancel",
+                    verbs.hint_for("focus"),
+                    crumb.path.to_string_lossy(),
+                    verbs.hint_for("back"),
+                ));
+            }
+        }
+        if let Some(job) = &self.pending_paste {
+            return screen.write_status_text(&format!(
+                "Pasting: {} files copied, hit {} to cancel",
+                job.nb_copied,
+                verbs.hint_for("back"),
+            ));
+        }
+        if let Some(job) = &self.pending_size {
+            return screen.write_status_text(&match job.size {
+                SizeInfo::Partial(size) => format!(
+                    "Size of {}: {} so far…, hit {} to cancel",
+                    job.path.to_string_lossy(),
+                    size.to_string(),
+                    verbs.hint_for("back"),
+                ),
+                _ => format!(
+                    "Computing the size of {}, hit {} to cancel",
+                    job.path.to_string_lossy(),
+                    verbs.hint_for("back"),
+                ),
+            });
+        }
+        if let Some(job) = self.search.as_ref().filter(|job| job.builder.is_total_search()) {
+            return screen.write_status_text(&format!(
+                "Searching the whole tree… {}, hit a key to interrupt",
+                search_progress(job),
+            ));
+        }
         match &cmd.action {
-            Action::FuzzyPatternEdit(_) => {
-                screen.write_status_text("Hit <enter> to select, <esc> to remove the filter")
+            Action::Confirm(invocation) => {
+                let tree = self.displayed_tree();
+                screen.write_status_err(&verbs.confirmation_status(
+                    invocation,
+                    &tree.selected_line().target(),
+                    tree.root(),
+                    con.relative_base(),
+                ))
+            }
+            Action::FuzzyPatternEdit(_)
+            | Action::RegexEdit(_, _)
+            | Action::WordsPatternEdit(_) if self.search.is_some() => {
+                let job = self.search.as_ref().unwrap();
+                screen.write_status_text(&format!("Searching… {}", search_progress(job)))
             }
-            Action::RegexEdit(_, _) => {
-                screen.write_status_text("Hit <enter> to select, <esc> to remove the filter")
+            Action::FuzzyPatternEdit(_)
+            | Action::RegexEdit(_, _)
+            | Action::WordsPatternEdit(_) => {
+                let hint = format!(
+                    "Hit {} to select, {} to remove the filter",
+                    verbs.hint_for("focus"),
+                    verbs.hint_for("back"),
+                );
+                if self.displayed_tree().truncated_search {
+                    screen.write_status_text(&format!(
+                        "{}, {} to search everywhere",
+                        hint,
+                        verbs.hint_for("total_search"),
+                    ))
+                } else {
+                    screen.write_status_text(&hint)
+                }
             }
             Action::VerbEdit(invocation) => {
-                match con.verb_store.search(&invocation.key) {
-                    PrefixSearchResult::NoMatch => {
-                        screen.write_status_err("No matching verb (':?' for the list of verbs)")
-                    }
-                    PrefixSearchResult::Match(verb) => {
-                        if let Some(err) = verb.match_error(invocation) {
-                            screen.write_status_err(&err)
-                        } else {
-                            let line = match &self.filtered_tree {
-                                Some(tree) => tree.selected_line(),
-                                None => self.tree.selected_line(),
-                            };
-                            screen.write_status_text(
-                                &format!(
-                                    "Hit <enter> to {} : {}",
-                                    &verb.invocation.key,
-                                    verb.description_for(line.target(), &invocation.args)
-                                )
-                                .to_string(),
-                            )
-                        }
-                    }
-                    PrefixSearchResult::TooManyMatches => screen.write_status_text(
-                        // TODO show what verbs start with the currently edited verb key
-                        "Type a verb then <enter> to execute it (':?' for the list of verbs)",
-                    ),
+                let tree = self.displayed_tree();
+                let file = tree.selected_line().target();
+                match con.verb_store.edition_status(
+                    invocation,
+                    &file,
+                    tree.root(),
+                    con.relative_base(),
+                ) {
+                    Ok(status) => screen.write_status_text(&status),
+                    Err(status) => screen.write_status_err(&status),
                 }
             }
             _ => {
                 let tree = self.displayed_tree();
-                if tree.selection == 0 {
-                    screen.write_status_text(
-                        "Hit <enter> to quit, '?' for help, or a few letters to search",
-                    )
+                let nb_dropped = match &tree.options.path_list {
+                    Some(path_list) => path_list.nb_dropped,
+                    None => 0,
+                };
+                if tree.selection == 0 && nb_dropped > 0 {
+                    screen.write_status_err(&format!(
+                        "{} path{} read on stdin didn't exist",
+                        nb_dropped,
+                        if nb_dropped > 1 { "s" } else { "" },
+                    ))
+                } else if tree.selection == 0 {
+                    let hint = format!(
+                        "Hit {} to quit, {} for help, or a few letters to search",
+                        verbs.hint_for("focus"),
+                        verbs.hint_for("help"),
+                    );
+                    match git_head(&tree.lines[0]) {
+                        Some(head) => screen.write_status_text(&format!("{} - {}", head, hint)),
+                        None => screen.write_status_text(&hint),
+                    }
                 } else {
                     let line = &tree.lines[tree.selection];
-                    screen.write_status_text(if line.is_dir() {
-                        "Hit <enter> to focus, <alt><enter> to cd, or a space then a verb"
+                    if tree.options.show_sizes {
+                        if let SizeInfo::Incomplete(_, nb_errors) = line.size {
+                            return screen.write_status_err(&format!(
+                                "Size partial: {} director{} couldn't be read",
+                                nb_errors,
+                                if nb_errors > 1 { "ies" } else { "y" },
+                            ));
+                        }
+                    }
+                    let mut parts = Vec::new();
+                    if let Some(head) = git_head(line) {
+                        parts.push(head);
+                    }
+                    // a directory with entries hidden by the options tells
+                    //  which toggles would reveal them
+                    if line.filtered_out > 0 {
+                        let mut toggles = Vec::new();
+                        if line.filtered_out > line.gitignored_out {
+                            toggles.push(verbs.hint_for("toggle_hidden"));
+                        }
+                        if line.gitignored_out > 0 {
+                            toggles.push(verbs.hint_for("toggle_git_ignore"));
+                        }
+                        parts.push(format!(
+                            "{} hidden entr{} in this directory: hit {} to show them",
+                            line.filtered_out,
+                            if line.filtered_out > 1 { "ies" } else { "y" },
+                            toggles.join(" or "),
+                        ));
+                        return screen.write_status_text(&parts.join(" - "));
+                    }
+                    let hint = if line.is_dir() {
+                        format!(
+                            "Hit {} to focus, {} to cd, or a space then a verb",
+                            verbs.hint_for("focus"),
+                            verbs.hint_for("cd"),
+                        )
                     } else {
-                        "Hit <enter> to open the file, or type a space then a verb"
-                    })
+                        format!(
+                            "Hit {} to open the file{}, or type a space then a verb",
+                            verbs.hint_for("open"),
+                            match con.enter_on_file {
+                                Internal::OpenStay => "",
+                                Internal::OpenLater => " when broot quits",
+                                _ => " and quit",
+                            },
+                        )
+                    };
+                    if let Some(other) = &line.collides_with {
+                        parts.push(format!(
+                            "the name can't be told apart from {:?}: {}",
+                            other,
+                            collisions::difference(&line.name, other),
+                        ));
+                    }
+                    // the mark after the permissions is explained
+                    if tree.options.show_permissions {
+                        if let Some(description) = line.xattrs.description() {
+                            parts.push(description.to_string());
+                        }
+                    }
+                    parts.push(hint);
+                    screen.write_status_text(&parts.join(" - "))
                 }
             }
         }
     }
 
+    // the trees are built again for the new height, as they may now show
+    //  more or fewer lines, the selection staying visible until then
+    fn resize(&mut self, screen: &Screen) {
+        let page_height = BrowserState::page_height(screen);
+        self.displayed_tree_mut().make_selection_visible(page_height);
+        self.request_refresh();
+    }
+
     fn refresh(
         &mut self,
-        screen: &Screen,
+        _screen: &Screen,
         _con: &AppContext,
     ) -> Command {
-        let page_height = BrowserState::page_height(screen) as usize;
-        // refresh the base tree
-        if let Err(e) = self.tree.refresh(page_height) {
-            warn!("refreshing base tree failed : {:?}", e);
-        }
-        // refresh the filtered tree, if any
-        if let Some(ref mut tree) = self.filtered_tree {
-            if let Err(e) = tree.refresh(page_height) {
-                warn!("refreshing filtered tree failed : {:?}", e);
-            }
-            tree.options.pattern.to_command()
-        } else {
-            self.tree.options.pattern.to_command()
+        self.request_refresh();
+        Command::from_pattern(&self.displayed_tree().options.pattern)
+    }
+
+    fn selected_path(&self) -> Option<PathBuf> {
+        Some(self.displayed_tree().selected_line().path.clone())
+    }
+
+    fn tree_root(&self) -> Option<PathBuf> {
+        Some(self.tree.root().clone())
+    }
+
+    fn check_outdated(&mut self, con: &AppContext) -> bool {
+        if !con.watch_changes
+            || self.outdated
+            || self.changes_checked_at.elapsed() < CHANGES_CHECK_PERIOD
+        {
+            return false;
         }
+        self.changes_checked_at = Instant::now();
+        // only the direct entries of the root change its mtime, but
+        //  the check stays cheap
+        self.outdated = self.displayed_tree().is_outdated();
+        self.outdated
     }
 
-    fn write_flags(&self, screen: &mut Screen, _con: &AppContext) -> io::Result<()> {
+    fn write_flags(&self, screen: &mut Screen, con: &AppContext) -> io::Result<()> {
+        if screen.is_too_small() {
+            return Ok(());
+        }
         let tree = match &self.filtered_tree {
             Some(tree) => &tree,
             None => &self.tree,
         };
-        let total_char_size = 9;
+        // an arrow tells where the selection is when it has been scrolled out of view
+        let selection_mark = if tree.is_selection_visible(BrowserState::page_height(screen)) {
+            ' '
+        } else if tree.selection as i32 <= tree.scroll {
+            '↑'
+        } else {
+            '↓'
+        };
+        // the paths waiting for a :paste are recalled before the flags
+        let yank_label = match yank::description() {
+            Some(d) => format!("{}  ", d),
+            None => String::new(),
+        };
+        // without the users lookups, the owners are written as ids
+        let owner_flag = match (tree.options.show_owner, tree.options.lookup_users) {
+            (false, _) => "n",
+            (true, true) => "y",
+            (true, false) => "ids",
+        };
+        // the view doesn't refresh itself when the root changes outside broot
+        let outdated_label = if self.outdated {
+            match con.verb_store.binding_for("refresh") {
+                Some(key) => format!("view outdated — {} to refresh  ", key),
+                None => "view outdated — :refresh  ".to_string(),
+            }
+        } else {
+            String::new()
+        };
+        // the options of a special path of the conf were applied to the root
+        let special_path_label = match &tree.options.special_path {
+            Some(name) => format!("(profile: {})  ", name),
+            None => String::new(),
+        };
+        let mut total_char_size = 20
+            + owner_flag.len() as u16
+            + yank_label.chars().count() as u16
+            + outdated_label.chars().count() as u16
+            + special_path_label.chars().count() as u16;
+        // the free space of the filesystem comes first, when there's
+        //  enough room left for the input
+        let fs_label = match &self.fs_info {
+            Some(Mount {
+                device,
+                stats: Some(stats),
+                ..
+            }) => {
+                let label = format!(
+                    "{} free on {} ({}% used) ",
+                    stats.available.to_string(),
+                    device,
+                    stats.use_percent(),
+                );
+                let width = (label.chars().count() + FS_BAR_WIDTH + 2) as u16;
+                if total_char_size + width + 20 <= screen.w {
+                    total_char_size += width;
+                    Some((label, stats.use_percent()))
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        };
         write!(
             screen.stderr,
-            "{}{}{}{} h:{}{}{}{}{}  gi:{}{}{}",
-            termion::cursor::Goto(screen.w - total_char_size, screen.h),
+            "{}{}{}{}",
+            screen.goto(screen.w.saturating_sub(total_char_size).max(1), screen.h),
             screen.skin.flag_label.fg,
             screen.skin.flag_label.bg,
             termion::clear::UntilNewline,
+        )?;
+        write!(screen.stderr, "{}{}", outdated_label, special_path_label)?;
+        if let Some((label, use_percent)) = fs_label {
+            let full = ((use_percent as usize * FS_BAR_WIDTH + 50) / 100).min(FS_BAR_WIDTH);
+            write!(
+                screen.stderr,
+                "{}{}{}{}{}{}{}{}  ",
+                screen.skin.flag_value.fg,
+                screen.skin.flag_value.bg,
+                label,
+                screen.skin.size_bar_full.bg,
+                " ".repeat(full),
+                screen.skin.size_bar_void.bg,
+                " ".repeat(FS_BAR_WIDTH - full),
+                screen.skin.flag_label.bg,
+            )?;
+        }
+        write!(
+            screen.stderr,
+            "{}{}{}{}{}{} h:{}{}{}{}{}  gi:{}{}{}{}{}  p:{}{}{}{}{}  o:{}{}{}",
+            screen.skin.flag_value.fg,
+            screen.skin.flag_value.bg,
+            yank_label,
+            selection_mark,
+            screen.skin.flag_label.fg,
+            screen.skin.flag_label.bg,
             screen.skin.flag_value.fg,
             screen.skin.flag_value.bg,
             if tree.options.show_hidden { 'y' } else { 'n' },
@@ -363,6 +1615,16 @@ impl AppState for BrowserState {
                 OptionBool::Yes => 'y',
                 OptionBool::No => 'n',
             },
+            screen.skin.flag_label.fg,
+            screen.skin.flag_label.bg,
+            screen.skin.flag_value.fg,
+            screen.skin.flag_value.bg,
+            if tree.options.show_permissions { 'y' } else { 'n' },
+            screen.skin.flag_label.fg,
+            screen.skin.flag_label.bg,
+            screen.skin.flag_value.fg,
+            screen.skin.flag_value.bg,
+            owner_flag,
         )?;
         Ok(())
     }
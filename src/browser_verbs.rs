@@ -1,20 +1,27 @@
 use std::io;
+use std::path::{Path, PathBuf};
 
 use crate::app::AppStateCmdResult;
 use crate::app_context::AppContext;
+use crate::build_info;
+use crate::cli;
 use crate::browser_states::BrowserState;
 use crate::commands::Command;
-use crate::external::{self, Launchable};
+use crate::dedup_states::DedupState;
+use crate::external;
+use crate::filesystems_states::FilesystemsState;
 use crate::help_states::HelpState;
+use crate::internals::Internal;
 use crate::screens::Screen;
 use crate::task_sync::TaskLifetime;
 use crate::tree_options::{OptionBool, TreeOptions};
 use crate::verb_invocation::VerbInvocation;
-use crate::verbs::{Verb, VerbExecutor};
+use crate::verbs::{Verb, VerbExecution, VerbExecutor};
+use crate::yank::YankMode;
 
 impl VerbExecutor for BrowserState {
     fn execute_verb(
-        &self,
+        &mut self,
         verb: &Verb,
         invocation: &VerbInvocation,
         screen: &mut Screen,
@@ -28,45 +35,117 @@ impl VerbExecutor for BrowserState {
             None => &self.tree,
         };
         let line = &tree.selected_line();
-        Ok(match verb.execution.as_ref() {
-            ":back" => AppStateCmdResult::PopState,
-            ":focus" => {
-                let mut path = tree.selected_line().target();
+        let modifying = match &verb.execution {
+            VerbExecution::Internal(internal) => is_modifying(*internal),
+            VerbExecution::External(_) => true,
+        };
+        if line.is_parent() && modifying {
+            return Ok(AppStateCmdResult::DisplayError(format!(
+                "{} can't be applied to the parent line",
+                verb.invocation.key,
+            )));
+        }
+        // a program or a copy reading the file would block the
+        //  application or fail on a fifo or a device
+        if let Some(kind) = line.special_kind() {
+            let reading = match &verb.execution {
+                VerbExecution::Internal(internal) => reads_content(*internal),
+                VerbExecution::External(exec_pattern) => opens_file(exec_pattern),
+            };
+            if reading {
+                return Ok(AppStateCmdResult::DisplayError(format!(
+                    "{} can't be applied to a {}",
+                    verb.invocation.key, kind,
+                )));
+            }
+        }
+        let internal = match &verb.execution {
+            VerbExecution::Internal(internal) => *internal,
+            VerbExecution::External(_) => {
+                return verb.to_cmd_result(&line.target(), tree.root(), invocation, screen, con);
+            }
+        };
+        Ok(match internal {
+            Internal::All => self.uncap_selected_dir(screen),
+            Internal::Back => AppStateCmdResult::PopState,
+            Internal::Chmod => self.chmod(&invocation.args),
+            Internal::Cp => self.yank(YankMode::Copy),
+            Internal::Dedup => {
+                let mut path = line.target();
                 if !path.is_dir() {
                     path = path.parent().unwrap().to_path_buf();
                 }
+                AppStateCmdResult::NewState(
+                    Box::new(DedupState::new(path, tree.root_dev())),
+                    Command::new(),
+                )
+            }
+            Internal::EditConf => external::edit_conf()?,
+            Internal::Filesystems => FilesystemsState::new_state_cmd_result(),
+            Internal::Focus => {
+                let path = match focus_target(&line.target(), &invocation.args) {
+                    Ok(path) => path,
+                    Err(e) => return Ok(AppStateCmdResult::DisplayError(e)),
+                };
                 AppStateCmdResult::from_optional_state(
-                    BrowserState::new(
+                    BrowserState::new_root(
                         path,
                         tree.options.clone(),
+                        con,
                         screen,
                         &TaskLifetime::unlimited(),
                     ),
-                    tree.options.pattern.to_command(),
+                    Command::from_pattern(&tree.options.pattern),
                 )
             }
-            ":help" => {
+            Internal::Mkdir => self.create_entry(&invocation.args, true),
+            Internal::Touch => self.create_entry(&invocation.args, false),
+            Internal::GotoLine => self.go_to_line(&invocation.args, screen),
+            Internal::Hardlink => AppStateCmdResult::DisplayError(
+                "hardlink applies to the files listed by :dedup".to_string(),
+            ),
+            Internal::Help => {
                 AppStateCmdResult::NewState(Box::new(HelpState::new(screen)), Command::new())
             }
-            ":open" => AppStateCmdResult::Launch(Launchable::opener(line.target())),
-            ":parent" => match &line.target().parent() {
+            Internal::Hints => self.show_hints(),
+            Internal::Mv => self.yank(YankMode::Move),
+            Internal::NextSameDepth => self.move_selection_same_depth(1, screen, con),
+            Internal::Open | Internal::OpenLater | Internal::OpenLeave | Internal::OpenStay => {
+                external::open_file(line.target(), internal, con)?
+            }
+            Internal::PreviousSameDepth => self.move_selection_same_depth(-1, screen, con),
+            Internal::Parent => match &line.target().parent() {
                 Some(path) => AppStateCmdResult::from_optional_state(
-                    BrowserState::new(
+                    BrowserState::new_root(
                         path.to_path_buf(),
                         tree.options.clone(),
+                        con,
                         screen,
                         &TaskLifetime::unlimited(),
                     ),
-                    tree.options.pattern.to_command(),
+                    Command::from_pattern(&tree.options.pattern),
                 ),
                 None => AppStateCmdResult::DisplayError("no parent found".to_string()),
             },
-            ":print_path" => external::print_path(&line.target(), con)?,
-            ":toggle_files" => {
+            Internal::Paste => self.paste(false, con),
+            Internal::PasteOverwrite => self.paste(true, con),
+            Internal::PrintPath => {
+                external::print_path(&line.target(), con.relative_paths, con)?
+            }
+            Internal::PrintRelativePath => external::print_path(&line.target(), true, con)?,
+            Internal::Refresh => {
+                self.request_refresh();
+                AppStateCmdResult::Keep
+            }
+            Internal::Size => self.size(),
+            Internal::TotalSearch => self.total_search(),
+            Internal::ToggleDates => self.with_new_columns(screen, &|o| o.show_dates ^= true),
+            Internal::ToggleFiles => {
                 self.with_new_options(screen, &|o: &mut TreeOptions| o.only_folders ^= true)
             }
-            ":toggle_hidden" => self.with_new_options(screen, &|o| o.show_hidden ^= true),
-            ":toggle_git_ignore" => self.with_new_options(screen, &|options| {
+            Internal::ToggleFsInfo => self.with_new_options(screen, &|o| o.show_fs_info ^= true),
+            Internal::ToggleHidden => self.with_new_options(screen, &|o| o.show_hidden ^= true),
+            Internal::ToggleGitIgnore => self.with_new_options(screen, &|options| {
                 options.respect_git_ignore = match options.respect_git_ignore {
                     OptionBool::Auto => {
                         if tree.nb_gitignored > 0 {
@@ -79,11 +158,75 @@ impl VerbExecutor for BrowserState {
                     OptionBool::No => OptionBool::Yes,
                 };
             }),
-            ":toggle_perm" => self.with_new_options(screen, &|o| o.show_permissions ^= true),
-            ":toggle_sizes" => self.with_new_options(screen, &|o| o.show_sizes ^= true),
-            ":toggle_trim_root" => self.with_new_options(screen, &|o| o.trim_root ^= true),
-            ":quit" => AppStateCmdResult::Quit,
-            _ => verb.to_cmd_result(&line.target(), &invocation.args, screen, con)?,
+            Internal::ToggleLineNumbers => {
+                self.with_new_options(screen, &|o| o.show_line_numbers ^= true)
+            }
+            Internal::ToggleOwner => self.with_new_columns(screen, &|o| o.show_owner ^= true),
+            Internal::TogglePercent => self.with_new_columns(screen, &|o| {
+                // percentages are sizes, which must be shown
+                o.size_percent ^= true;
+                o.show_sizes |= o.size_percent;
+            }),
+            Internal::TogglePerm => self.with_new_columns(screen, &|o| o.show_permissions ^= true),
+            Internal::ToggleSizes => self.with_new_columns(screen, &|o| o.show_sizes ^= true),
+            Internal::ToggleTrimRoot => self.with_new_options(screen, &|o| o.trim_root ^= true),
+            Internal::Version => AppStateCmdResult::DisplayMessage(build_info::one_line()),
+            Internal::Quit => AppStateCmdResult::Quit,
+            Internal::ToggleScreen => AppStateCmdResult::ShowTerminal,
         })
     }
 }
+
+/// the directory a :focus goes to from the selected path: the path
+///  itself, or its parent for a file, joined with the optional argument
+pub fn focus_target(selected: &Path, arg: &Option<String>) -> Result<PathBuf, String> {
+    let mut path = selected.to_path_buf();
+    if !path.is_dir() {
+        path = path.parent().unwrap_or(selected).to_path_buf();
+    }
+    if let Some(arg) = arg {
+        // a relative path is relative to the selected directory
+        path = match cli::expand_path(arg).map(|arg| path.join(arg)) {
+            Ok(path) if path.is_dir() => path.canonicalize().map_err(|e| e.to_string())?,
+            Ok(_) => return Err(format!("{:?} isn't a directory", arg)),
+            Err(e) => return Err(e.to_string()),
+        };
+    }
+    Ok(path)
+}
+
+// tell whether the internal would read the content of the selected file
+fn reads_content(internal: Internal) -> bool {
+    match internal {
+        Internal::Cp
+        | Internal::Open
+        | Internal::OpenLater
+        | Internal::OpenLeave
+        | Internal::OpenStay => true,
+        _ => false,
+    }
+}
+
+// the programs which handle a file without opening it
+const NOT_OPENING_PROGRAMS: &[&str] = &[
+    "chgrp", "chmod", "chown", "ln", "ls", "mv", "rm", "stat", "touch",
+];
+
+// tell whether the external program would open the selected file,
+//  which is assumed unless it's one of the programs known not to
+fn opens_file(exec_pattern: &str) -> bool {
+    if !exec_pattern.contains("{file}") {
+        return false;
+    }
+    let program = exec_pattern.split_whitespace().next().unwrap_or("");
+    let program = program.rsplit('/').next().unwrap_or(program);
+    !NOT_OPENING_PROGRAMS.contains(&program)
+}
+
+// tell whether the internal would change the selected file
+fn is_modifying(internal: Internal) -> bool {
+    match internal {
+        Internal::Chmod | Internal::Cp | Internal::Mv => true,
+        _ => false,
+    }
+}
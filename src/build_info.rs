@@ -0,0 +1,24 @@
+//! what's known of the build of broot, embedded by the build script,
+//!  to be given in bug reports
+
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+pub const COMMIT: &str = env!("BROOT_COMMIT"); // "unknown" when built outside of git
+pub const BUILD_DATE: &str = env!("BROOT_BUILD_DATE"); // as yyyy-mm-dd
+pub const FEATURES: &str = env!("BROOT_FEATURES"); // comma separated, or "none"
+
+lazy_static! {
+    /// what `broot --version` prints after "broot ": one "key: value"
+    ///  line per information, to be easily parsed
+    pub static ref LONG_VERSION: String = format!(
+        "{}\ncommit: {}\nbuild date: {}\nfeatures: {}",
+        VERSION, COMMIT, BUILD_DATE, FEATURES,
+    );
+}
+
+/// all the build info on one line, eg for the status
+pub fn one_line() -> String {
+    format!(
+        "broot {} (commit {}, built {}, features: {})",
+        VERSION, COMMIT, BUILD_DATE, FEATURES,
+    )
+}
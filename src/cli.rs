@@ -1,28 +1,50 @@
+use crate::build_info;
 use crate::commands::Command;
 use crate::errors::{ProgramError, TreeBuildError};
-use crate::tree_options::TreeOptions;
+use crate::external;
+use crate::path_list::PathList;
+use crate::screens;
+use crate::skin::Palette;
+use crate::tree_options::{OptionBool, TreeOptions};
 /// this module manages reading and translating
 /// the arguments passed on launch of the application.
 use clap;
+use directories::UserDirs;
+use regex::{Captures, Regex};
 use std::env;
+use std::fs::OpenOptions;
 use std::io::{self, stdin};
-use std::path::PathBuf;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
 use std::result::Result;
+use std::sync::Arc;
 use termion::input::TermRead;
 
 pub struct AppLaunchArgs {
     pub root: PathBuf,                    // what should be the initial root
-    pub file_export_path: Option<String>, // where to write the produced path (if required with --out)
+    pub selection: Option<PathBuf>,       // the file given instead of a root, if any
+    pub file_export_path: Option<String>, // where to write the produced path (if required with --out or --out-trunc)
     pub cmd_export_path: Option<String>, // where to write the produced command (if required with --outcmd, or -oc)
     pub tree_options: TreeOptions,       // initial tree options
     pub commands: Vec<Command>,          // commands passed as cli argument
     pub install: bool,                   // installation is required
+    pub height: Option<u16>,             // an optional height to draw broot inline
+    pub profile: Option<usize>,          // number of tree builds to time, instead of the UI
+    pub skin_palette: Option<Palette>,   // the palette forced with --skin
+    pub completions: Option<clap::Shell>, // the shell whose completion script must be printed
+    pub picker: bool,                    // <enter> prints the selected path on stdout and quits
+    pub selection_file: Option<PathBuf>, // where the selected path is written each time it changes
 }
 
-// declare the possible CLI arguments, and gets the values
-fn get_cli_args<'a>() -> clap::ArgMatches<'a> {
+// the names of the programs whose arguments are completed: broot
+//  and the br shell function, which passes its arguments to broot
+const COMPLETED_PROGRAMS: &[&str] = &["broot", "br"];
+
+// declare the possible CLI arguments
+fn clap_app<'a, 'b>() -> clap::App<'a, 'b> {
     clap::App::new("broot")
-        .version(env!("CARGO_PKG_VERSION"))
+        .version(build_info::VERSION)
+        .long_version(build_info::LONG_VERSION.as_str())
         .author("dystroy <denys.seguret@gmail.com>")
         .about("Balanced tree view + fuzzy search + BFS + customizable launcher")
         .arg(clap::Arg::with_name("root").help("sets the root directory"))
@@ -39,6 +61,19 @@ fn get_cli_args<'a>() -> clap::ArgMatches<'a> {
                 .takes_value(true)
                 .help("commands to execute (space separated, experimental)"),
         )
+        .arg(
+            clap::Arg::with_name("completions")
+                .long("completions")
+                .takes_value(true)
+                .possible_values(&["bash", "zsh", "fish"])
+                .help("print the completion script for the given shell, then quit"),
+        )
+        .arg(
+            clap::Arg::with_name("dates")
+                .short("d")
+                .long("dates")
+                .help("show the date of last modification of files and directories"),
+        )
         .arg(
             clap::Arg::with_name("file_export_path")
                 .short("o")
@@ -46,6 +81,13 @@ fn get_cli_args<'a>() -> clap::ArgMatches<'a> {
                 .takes_value(true)
                 .help("where to write the produced path (if any)"),
         )
+        .arg(
+            clap::Arg::with_name("file_export_path_trunc")
+                .long("out-trunc")
+                .takes_value(true)
+                .conflicts_with("file_export_path")
+                .help("like --out, but the file is truncated on launch"),
+        )
         .arg(
             clap::Arg::with_name("gitignore")
                 .short("g")
@@ -53,6 +95,12 @@ fn get_cli_args<'a>() -> clap::ArgMatches<'a> {
                 .takes_value(true)
                 .help("respect .gitignore rules (yes, no, auto)"),
         )
+        .arg(
+            clap::Arg::with_name("height")
+                .long("height")
+                .takes_value(true)
+                .help("height (if you don't want to fill the screen)"),
+        )
         .arg(
             clap::Arg::with_name("hidden")
                 .short("h")
@@ -64,17 +112,56 @@ fn get_cli_args<'a>() -> clap::ArgMatches<'a> {
                 .long("install")
                 .help("install or reinstall the br shell function"),
         )
+        .arg(
+            clap::Arg::with_name("no-users")
+                .long("no-users")
+                .help("show the ids of the owners instead of looking for their names"),
+        )
+        .arg(
+            clap::Arg::with_name("one-filesystem")
+                .long("one-filesystem")
+                .help("don't read the directories of other filesystems"),
+        )
         .arg(
             clap::Arg::with_name("only-folders")
                 .short("f")
                 .long("only-folders")
                 .help("only show folders"),
         )
+        .arg(
+            clap::Arg::with_name("owner")
+                .long("owner")
+                .help("show the user and group owning the files"),
+        )
+        .arg(
+            clap::Arg::with_name("paths-from-stdin")
+                .long("paths-from-stdin")
+                .conflicts_with("root")
+                .help("only show the paths read on stdin (one per line)"),
+        )
         .arg(
             clap::Arg::with_name("permissions")
                 .short("p")
                 .long("permissions")
-                .help("show permissions, with owner and group"),
+                .help("show permissions"),
+        )
+        .arg(
+            clap::Arg::with_name("picker")
+                .long("picker")
+                .help("pick a path with <enter>, print it on stdout, then quit"),
+        )
+        .arg(
+            clap::Arg::with_name("profile")
+                .long("profile")
+                .takes_value(true)
+                .hidden(true)
+                .help("build the tree N times and print the timings, without UI"),
+        )
+        .arg(
+            clap::Arg::with_name("selection-file")
+                .long("selection-file")
+                .takes_value(true)
+                .help("write the selected path in this file each time it changes"),
         )
         .arg(
             clap::Arg::with_name("sizes")
@@ -82,46 +169,258 @@ fn get_cli_args<'a>() -> clap::ArgMatches<'a> {
                 .long("sizes")
                 .help("show the size of files and directories"),
         )
-        .get_matches()
+        .arg(
+            clap::Arg::with_name("skin")
+                .long("skin")
+                .takes_value(true)
+                .help("default colors for a dark or light background (dark, light)"),
+        )
 }
 
-// return the parsed launch arguments
-pub fn read_lauch_args() -> Result<AppLaunchArgs, ProgramError> {
-    let cli_args = get_cli_args();
-    let mut root = match cli_args.value_of("root") {
-        Some(path) => PathBuf::from(path),
-        None => env::current_dir()?,
+// gets the values of the CLI arguments
+fn get_cli_args<'a>() -> clap::ArgMatches<'a> {
+    clap_app().get_matches()
+}
+
+/// build the completion script of the given shell, generated from the
+///  declaration of the arguments, for broot and for br
+pub fn completion_script(shell: clap::Shell) -> String {
+    let mut script = Vec::new();
+    match shell {
+        clap::Shell::Zsh => {
+            // a zsh completion file starts with the list of the commands
+            //  it completes, and there can be only one per file
+            clap_app().gen_completions_to("broot", shell, &mut script);
+        }
+        _ => {
+            for program in COMPLETED_PROGRAMS {
+                clap_app().gen_completions_to(*program, shell, &mut script);
+            }
+        }
+    }
+    let script = String::from_utf8_lossy(&script).to_string();
+    match shell {
+        clap::Shell::Zsh => script.replacen(
+            "#compdef broot",
+            &format!("#compdef {}", COMPLETED_PROGRAMS.join(" ")),
+            1,
+        ),
+        _ => script,
+    }
+}
+
+// replace a leading ~ with the home directory of the user, and the
+//  $VAR or ${VAR} parts with the values of the environment variables,
+//  $$ being a literal $
+pub fn expand_path(raw: &str) -> Result<PathBuf, TreeBuildError> {
+    expand_path_with(raw, |name| env::var(name).ok())
+}
+
+// expand the path with the given variables
+fn expand_path_with<F>(raw: &str, var: F) -> Result<PathBuf, TreeBuildError>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    lazy_static! {
+        static ref ENV_VAR: Regex = Regex::new(r"\$(?:(\$)|\{(\w+)\}|(\w+))").unwrap();
+    }
+    let mut unknown = None;
+    let expanded = ENV_VAR.replace_all(raw, |c: &Captures<'_>| {
+        if c.get(1).is_some() {
+            return "$".to_string();
+        }
+        let name = c.get(2).or_else(|| c.get(3)).unwrap().as_str();
+        var(name).unwrap_or_else(|| {
+            unknown = Some(name.to_string());
+            String::new()
+        })
+    });
+    if let Some(name) = unknown {
+        return Err(TreeBuildError::UnknownEnvVar { name });
+    }
+    if expanded == "~" || expanded.starts_with("~/") {
+        let home = match UserDirs::new() {
+            Some(user_dirs) => user_dirs.home_dir().to_path_buf(),
+            None => {
+                return Err(TreeBuildError::NoHomeDir);
+            }
+        };
+        return Ok(home.join(expanded[1..].trim_start_matches('/')));
+    }
+    Ok(PathBuf::from(expanded.as_ref()))
+}
+
+// compute the initial root from the path given on launch, relative paths
+//  being relative to cwd. When this path is of a file, the root is its
+//  parent and the file is returned too, to be selected
+fn resolve_root(
+    raw: Option<&str>,
+    cwd: &Path,
+) -> Result<(PathBuf, Option<PathBuf>), TreeBuildError> {
+    let path = match raw {
+        Some(raw) => cwd.join(expand_path(raw)?),
+        None => cwd.to_path_buf(),
     };
-    if !root.exists() {
-        Err(TreeBuildError::FileNotFound {
-            path: format!("{:?}", &root),
-        })?;
-    }
-    if !root.is_dir() {
-        // we try to open the parent directory if the passed file isn't one
-        if let Some(parent) = root.parent() {
+    let path = match path.canonicalize() {
+        Ok(path) => path,
+        Err(_) => {
+            return Err(TreeBuildError::FileNotFound {
+                path: path.to_string_lossy().to_string(),
+            });
+        }
+    };
+    if path.is_dir() {
+        return Ok((path, None));
+    }
+    match path.parent() {
+        Some(parent) => {
             info!("Passed path isn't a directory => opening parent instead");
-            root = parent.to_path_buf();
-        } else {
-            // let's give up
-            Err(TreeBuildError::NotADirectory {
-                path: format!("{:?}", &root),
-            })?;
+            Ok((parent.to_path_buf(), Some(path)))
+        }
+        None => Err(TreeBuildError::NotADirectory {
+            path: path.to_string_lossy().to_string(),
+        }),
+    }
+}
+
+// check the file given with --out or --out-trunc can be written, creating
+//  it (or truncating it) right now so that there's no surprise after the
+//  selection, when broot is closed
+// the file given with --out (appended to) or --out-trunc (truncated),
+//  prepared only when it's used
+fn export_file(
+    out: Option<&str>,
+    out_trunc: Option<&str>,
+    used: bool,
+) -> Result<Option<String>, ProgramError> {
+    Ok(match (out, out_trunc) {
+        (Some(path), _) if !used => Some(path.to_string()),
+        (None, Some(path)) if !used => Some(path.to_string()),
+        (Some(path), _) => Some(prepare_export_file(path, false)?),
+        (None, Some(path)) => Some(prepare_export_file(path, true)?),
+        (None, None) => None,
+    })
+}
+
+fn prepare_export_file(raw: &str, truncate: bool) -> Result<String, ProgramError> {
+    let path = Path::new(raw);
+    if path.is_dir() {
+        return Err(ProgramError::ExportError {
+            path: raw.to_string(),
+            reason: "it's a directory".to_string(),
+        });
+    }
+    if let Some(parent) = path.parent() {
+        if parent != Path::new("") && !parent.is_dir() {
+            return Err(ProgramError::ExportError {
+                path: raw.to_string(),
+                reason: format!("{:?} isn't an existing directory", parent),
+            });
         }
     }
-    let root = root.canonicalize()?;
+    OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(!truncate)
+        .truncate(truncate)
+        .open(path)
+        .map_err(|e| external::export_error(raw, &e))?;
+    Ok(raw.to_string())
+}
+
+// make the terminal the new stdin, so that the keys can be read
+//  (and raw mode set) as when nothing was piped
+fn switch_stdin_to_tty() -> io::Result<()> {
+    let tty = termion::get_tty()?;
+    if unsafe { libc::dup2(tty.as_raw_fd(), libc::STDIN_FILENO) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+// read the paths, one per line, until the end of stdin, which is
+//  then switched to the terminal. Return the list and the deepest
+//  directory containing all the paths
+fn read_path_list(cwd: &Path) -> Result<(PathBuf, PathList), ProgramError> {
+    use std::io::BufRead; // (not in the module, as TermRead also has a read_line)
+    let lines = stdin().lock().lines().collect::<io::Result<Vec<String>>>()?;
+    switch_stdin_to_tty()?;
+    Ok(PathList::from_lines(&lines, cwd)?)
+}
+
+// return the parsed launch arguments
+pub fn read_lauch_args() -> Result<AppLaunchArgs, ProgramError> {
+    let cli_args = get_cli_args();
+    let cwd = env::current_dir()?;
     let mut tree_options = TreeOptions::new();
+    let (root, selection) = if cli_args.is_present("paths-from-stdin") {
+        // stdin must be completely read before the keys are listened to
+        let (root, path_list) = read_path_list(&cwd)?;
+        tree_options.path_list = Some(Arc::new(path_list));
+        (root, None)
+    } else {
+        resolve_root(cli_args.value_of("root"), &cwd)?
+    };
     tree_options.only_folders = cli_args.is_present("only-folders");
     tree_options.show_hidden = cli_args.is_present("hidden");
     tree_options.show_sizes = cli_args.is_present("sizes");
     tree_options.show_permissions = cli_args.is_present("permissions");
+    tree_options.show_owner = cli_args.is_present("owner");
+    tree_options.lookup_users = !cli_args.is_present("no-users");
+    tree_options.show_dates = cli_args.is_present("dates");
+    tree_options.one_filesystem = cli_args.is_present("one-filesystem");
     if let Some(respect_ignore) = cli_args.value_of("gitignore") {
         tree_options.respect_git_ignore = respect_ignore.parse()?;
     }
     let install = cli_args.is_present("install");
-    let file_export_path = cli_args
-        .value_of("file_export_path")
-        .and_then(|s| Some(s.to_owned()));
+    let picker = cli_args.is_present("picker");
+    let height = match cli_args.value_of("height") {
+        Some(height) => match height.parse() {
+            Ok(height) => Some(height),
+            Err(_) => {
+                return Err(ProgramError::ArgParse {
+                    bad: height.to_string(),
+                    valid: "a number of lines".to_string(),
+                });
+            }
+        },
+        None => None,
+    };
+    let profile = match cli_args.value_of("profile") {
+        Some(nb_runs) => match nb_runs.parse() {
+            Ok(nb_runs) if nb_runs > 0 => Some(nb_runs),
+            _ => {
+                return Err(ProgramError::ArgParse {
+                    bad: nb_runs.to_string(),
+                    valid: "a positive number of runs".to_string(),
+                });
+            }
+        },
+        None => None,
+    };
+    // when profiling or picking, --out is ignored, so the file isn't touched
+    let file_export_path = export_file(
+        cli_args.value_of("file_export_path"),
+        cli_args.value_of("file_export_path_trunc"),
+        profile.is_none() && !picker,
+    )?;
+    let skin_palette = match cli_args.value_of("skin") {
+        Some(palette) => Some(palette.parse()?),
+        None => None,
+    };
+    let completions = match cli_args.value_of("completions") {
+        Some(shell) => match shell.parse() {
+            Ok(shell) => Some(shell),
+            Err(_) => {
+                return Err(ProgramError::ArgParse {
+                    bad: shell.to_string(),
+                    valid: "bash, zsh, fish".to_string(),
+                });
+            }
+        },
+        None => None,
+    };
+    let selection_file = cli_args.value_of("selection-file").map(|path| cwd.join(path));
     let cmd_export_path = cli_args
         .value_of("cmd_export_path")
         .and_then(|s| Some(s.to_owned()));
@@ -134,14 +433,95 @@ pub fn read_lauch_args() -> Result<AppLaunchArgs, ProgramError> {
     };
     Ok(AppLaunchArgs {
         root,
+        selection,
         file_export_path,
         cmd_export_path,
         tree_options,
         commands,
         install,
+        height,
+        profile,
+        skin_palette,
+        completions,
+        picker,
+        selection_file,
     })
 }
 
+impl AppLaunchArgs {
+    /// check the combinations of arguments: the ones broot can't run
+    ///  with are errors, and the ones where an argument is ignored or
+    ///  can't have any effect are returned as warnings
+    pub fn check(&self) -> Result<Vec<String>, ProgramError> {
+        let mut warnings = Vec::new();
+        if let Some(height) = self.height {
+            if height < screens::MIN_HEIGHT {
+                return Err(ProgramError::ConflictingArgs {
+                    reason: format!("--height must be at least {}", screens::MIN_HEIGHT),
+                });
+            }
+        }
+        if let (Some(out), Some(outcmd)) = (&self.file_export_path, &self.cmd_export_path) {
+            // the shell would execute the paths written in the file
+            if Path::new(out) == Path::new(outcmd) {
+                return Err(ProgramError::ConflictingArgs {
+                    reason: "--out and --outcmd can't be the same file".to_string(),
+                });
+            }
+        }
+        if self.profile.is_some() {
+            // there's no interface when profiling
+            let ignored = [
+                ("--cmd", !self.commands.is_empty()),
+                ("--height", self.height.is_some()),
+                ("--install", self.install),
+                ("--out", self.file_export_path.is_some()),
+                ("--outcmd", self.cmd_export_path.is_some()),
+                ("--picker", self.picker),
+                ("--selection-file", self.selection_file.is_some()),
+                ("--skin", self.skin_palette.is_some()),
+            ];
+            for (arg, present) in ignored.iter() {
+                if *present {
+                    warnings.push(format!("{} is ignored when profiling", arg));
+                }
+            }
+        } else if self.picker {
+            // the picked path is printed on stdout, and nothing else is launched
+            let ignored = [
+                ("--install", self.install),
+                ("--out", self.file_export_path.is_some()),
+                ("--outcmd", self.cmd_export_path.is_some()),
+            ];
+            for (arg, present) in ignored.iter() {
+                if *present {
+                    warnings.push(format!("{} is ignored in picker mode", arg));
+                }
+            }
+        }
+        if let Some(path_list) = &self.tree_options.path_list {
+            // the listed paths are shown even when hidden or gitignored
+            if self.tree_options.show_hidden {
+                warnings.push("--hidden has no effect with --paths-from-stdin".to_string());
+            }
+            if self.tree_options.respect_git_ignore != OptionBool::Auto {
+                warnings.push("--gitignore has no effect with --paths-from-stdin".to_string());
+            }
+            if self.tree_options.only_folders {
+                let nb_files = path_list.nb_files();
+                if nb_files > 0 {
+                    warnings.push(format!(
+                        "--only-folders hides the {} file{} read on stdin",
+                        nb_files,
+                        if nb_files == 1 { "" } else { "s" },
+                    ));
+                }
+            }
+        }
+        Ok(warnings)
+    }
+}
+
 pub fn ask_authorization(question: &str) -> io::Result<bool> {
     println!("{}", question);
     let answer = stdin().lock().read_line()?;
@@ -153,3 +533,216 @@ pub fn ask_authorization(question: &str) -> io::Result<bool> {
         _ => true,
     })
 }
+
+#[cfg(test)]
+mod root_resolution_tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn directory_root() {
+        let dir = tempdir().unwrap();
+        let canonical = dir.path().canonicalize().unwrap();
+        let (root, selection) = resolve_root(dir.path().to_str(), Path::new("/")).unwrap();
+        assert_eq!(root, canonical);
+        assert_eq!(selection, None);
+        // no argument: the root is the current directory
+        let (root, selection) = resolve_root(None, dir.path()).unwrap();
+        assert_eq!(root, canonical);
+        assert_eq!(selection, None);
+    }
+
+    #[test]
+    fn file_root() {
+        let dir = tempdir().unwrap();
+        let file = dir.path().join("file.txt");
+        fs::write(&file, "content").unwrap();
+        let (root, selection) = resolve_root(file.to_str(), Path::new("/")).unwrap();
+        assert_eq!(root, dir.path().canonicalize().unwrap());
+        assert_eq!(selection, Some(file.canonicalize().unwrap()));
+    }
+
+    #[test]
+    fn relative_root() {
+        let dir = tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("a/b")).unwrap();
+        let (root, _) = resolve_root(Some("a/b/.."), dir.path()).unwrap();
+        assert_eq!(root, dir.path().join("a").canonicalize().unwrap());
+    }
+
+    #[test]
+    fn expanded_root() {
+        let home = UserDirs::new().unwrap().home_dir().canonicalize().unwrap();
+        let (root, _) = resolve_root(Some("~"), Path::new("/")).unwrap();
+        assert_eq!(root, home);
+        assert!(resolve_root(Some("$BROOT_TEST_UNSET_VAR/sub"), Path::new("/")).is_err());
+    }
+
+    #[test]
+    fn expanded_variables() {
+        // the variables are given, as the environment is shared by the tests
+        let var = |name: &str| match name {
+            "DIR" => Some("/some/dir".to_string()),
+            _ => None,
+        };
+        let expand = |raw| expand_path_with(raw, var).map(|path| path.to_string_lossy().to_string());
+        assert_eq!(expand("${DIR}/sub").unwrap(), "/some/dir/sub");
+        assert_eq!(expand("$DIR").unwrap(), "/some/dir");
+        assert!(expand("$UNSET/sub").is_err());
+        // $$ is a literal $, and a $ without a name is kept
+        assert_eq!(expand("/a/$$DIR").unwrap(), "/a/$DIR");
+        assert_eq!(expand("/a$/b $").unwrap(), "/a$/b $");
+    }
+
+    #[test]
+    fn missing_root() {
+        let dir = tempdir().unwrap();
+        let missing = dir.path().join("missing");
+        assert!(resolve_root(missing.to_str(), Path::new("/")).is_err());
+        assert!(resolve_root(Some("missing"), dir.path()).is_err());
+    }
+}
+
+#[cfg(test)]
+mod completions_tests {
+    use super::*;
+
+    #[test]
+    fn bash_script_has_all_long_flags() {
+        let mut help = Vec::new();
+        clap_app().write_long_help(&mut help).unwrap();
+        let help = String::from_utf8(help).unwrap();
+        let script = completion_script(clap::Shell::Bash);
+        let flag = Regex::new(r"\s(--[a-z][a-z-]*)").unwrap();
+        let flags: Vec<&str> = flag
+            .captures_iter(&help)
+            .map(|c| c.get(1).unwrap().as_str())
+            .collect();
+        assert!(flags.contains(&"--completions"));
+        for flag in flags {
+            assert!(script.contains(flag), "{} isn't completed", flag);
+        }
+        // the br function is completed as broot
+        assert!(script.contains("complete -F _br "));
+    }
+}
+
+#[cfg(test)]
+mod launch_checks_tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    // the arguments of a plain launch, which raise no warning
+    fn args() -> AppLaunchArgs {
+        AppLaunchArgs {
+            root: PathBuf::from("/"),
+            selection: None,
+            file_export_path: None,
+            cmd_export_path: None,
+            tree_options: TreeOptions::new(),
+            commands: Vec::new(),
+            install: false,
+            height: None,
+            profile: None,
+            skin_palette: None,
+            completions: None,
+            picker: false,
+            selection_file: None,
+        }
+    }
+
+    #[test]
+    fn plain_launch() {
+        assert!(args().check().unwrap().is_empty());
+    }
+
+    #[test]
+    fn too_small_height() {
+        let mut args = args();
+        args.height = Some(screens::MIN_HEIGHT - 1);
+        assert!(args.check().is_err());
+        args.height = Some(screens::MIN_HEIGHT);
+        assert!(args.check().unwrap().is_empty());
+    }
+
+    #[test]
+    fn same_out_and_outcmd() {
+        let mut args = args();
+        args.file_export_path = Some("/tmp/broot-out".to_string());
+        args.cmd_export_path = Some("/tmp/broot-out".to_string());
+        assert!(args.check().is_err());
+        args.cmd_export_path = Some("/tmp/broot-outcmd".to_string());
+        assert!(args.check().unwrap().is_empty());
+    }
+
+    #[test]
+    fn ignored_when_profiling() {
+        let mut args = args();
+        args.profile = Some(3);
+        assert!(args.check().unwrap().is_empty());
+        args.commands = vec![Command::from("src".to_string())];
+        args.height = Some(20);
+        args.install = true;
+        args.file_export_path = Some("/tmp/broot-out".to_string());
+        args.cmd_export_path = Some("/tmp/broot-outcmd".to_string());
+        args.picker = true;
+        args.skin_palette = Some(Palette::Light);
+        let warnings = args.check().unwrap();
+        assert_eq!(warnings.len(), 7);
+        assert!(warnings.iter().all(|w| w.ends_with("is ignored when profiling")));
+    }
+
+    #[test]
+    fn ignored_in_picker_mode() {
+        let mut args = args();
+        args.picker = true;
+        assert!(args.check().unwrap().is_empty());
+        args.install = true;
+        args.file_export_path = Some("/tmp/broot-out".to_string());
+        let warnings = args.check().unwrap();
+        assert_eq!(
+            warnings,
+            vec!["--install is ignored in picker mode", "--out is ignored in picker mode"]
+        );
+    }
+
+    #[test]
+    fn ignored_out_file_is_kept() {
+        let dir = tempdir().unwrap();
+        let out = dir.path().join("out");
+        fs::write(&out, "precious").unwrap();
+        let out = out.to_string_lossy().to_string();
+        let path = export_file(None, Some(&out), false).unwrap();
+        assert_eq!(path, Some(out.clone()));
+        assert_eq!(fs::read_to_string(&out).unwrap(), "precious");
+        // while a used --out-trunc file is emptied
+        export_file(None, Some(&out), true).unwrap();
+        assert_eq!(fs::read_to_string(&out).unwrap(), "");
+    }
+
+    #[test]
+    fn path_list_ignores_filters() {
+        let dir = tempdir().unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        fs::write(dir.path().join("sub/file"), "content").unwrap();
+        let lines = vec!["sub".to_string(), "sub/file".to_string()];
+        let (_, path_list) = PathList::from_lines(&lines, dir.path()).unwrap();
+        let mut args = args();
+        args.tree_options.path_list = Some(Arc::new(path_list));
+        assert!(args.check().unwrap().is_empty());
+        args.tree_options.show_hidden = true;
+        args.tree_options.respect_git_ignore = OptionBool::No;
+        args.tree_options.only_folders = true;
+        let warnings = args.check().unwrap();
+        assert_eq!(
+            warnings,
+            vec![
+                "--hidden has no effect with --paths-from-stdin",
+                "--gitignore has no effect with --paths-from-stdin",
+                "--only-folders hides the 1 file read on stdin",
+            ]
+        );
+    }
+}
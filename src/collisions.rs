@@ -0,0 +1,97 @@
+//! the detection of sibling names which a user can hardly tell apart,
+//!  like `Readme` and `README`, or `Résumé.txt` written with precomposed
+//!  letters (NFC) and with combining accents (NFD). Such names may
+//!  coexist on Linux, and collide when synced to a case insensitive or
+//!  normalizing filesystem.
+
+use caseless::Caseless;
+use unicode_normalization::UnicodeNormalization;
+
+// the name in the NFC form
+fn normalized(name: &str) -> String {
+    name.nfc().collect()
+}
+
+/// the form under which two names are the same for a user: their
+///  case folded as defined by Unicode, then normalized
+pub fn folded(name: &str) -> String {
+    name.nfd().default_case_fold().nfc().collect()
+}
+
+/// tell why two colliding names aren't equal
+pub fn difference(a: &str, b: &str) -> &'static str {
+    if caseless::default_case_fold_str(a) == caseless::default_case_fold_str(b) {
+        "they differ only by case"
+    } else if normalized(a) == normalized(b) {
+        "they're written with different Unicode normalizations"
+    } else {
+        "they differ by case and Unicode normalization"
+    }
+}
+
+/// find the names colliding with another one of the list, giving, for
+///  each name, the index of one of the names it collides with. The names
+///  are sorted by their folded form, so that the colliding ones follow
+pub fn collisions(names: &[&str]) -> Vec<Option<usize>> {
+    let mut keys: Vec<(String, usize)> = names
+        .iter()
+        .enumerate()
+        .map(|(idx, name)| (folded(name), idx))
+        .collect();
+    keys.sort();
+    let mut found = vec![None; names.len()];
+    let mut start = 0;
+    while start < keys.len() {
+        let end = start + keys[start..].iter().take_while(|(key, _)| *key == keys[start].0).count();
+        if end - start > 1 {
+            // each name is said to collide with the next one of its group
+            for i in start..end {
+                let other = if i + 1 < end { i + 1 } else { start };
+                found[keys[i].1] = Some(keys[other].1);
+            }
+        }
+        start = end;
+    }
+    found
+}
+
+#[cfg(test)]
+mod collisions_tests {
+    use super::*;
+
+    #[test]
+    fn colliding_names() {
+        let nfc = "R\u{e9}sum\u{e9}.txt";
+        let nfd = "Re\u{301}sume\u{301}.txt";
+        assert_ne!(nfc, nfd);
+        assert_eq!(folded(nfc), folded(nfd));
+        let names = [nfc, "Readme", "notes.txt", nfd, "README", "resume.txt"];
+        assert_eq!(
+            collisions(&names),
+            vec![Some(3), Some(4), None, Some(0), Some(1), None]
+        );
+        assert_eq!(difference("Readme", "README"), "they differ only by case");
+        assert_eq!(difference(nfc, nfd), "they're written with different Unicode normalizations");
+        assert_eq!(difference("r\u{e9}sum\u{e9}.txt", nfd), "they differ by case and Unicode normalization");
+        // three names colliding together
+        assert_eq!(collisions(&["a", "A", "b", "a"]).iter().filter(|c| c.is_some()).count(), 3);
+    }
+
+    #[test]
+    fn colliding_non_latin_names() {
+        // vietnamese letters with two marks, stacked in any order
+        let viet = ["Vi\u{1ec7}t", "Vie\u{323}\u{302}t", "Vie\u{302}\u{323}t", "VI\u{1ec6}T"];
+        assert!(collisions(&viet).iter().all(|c| c.is_some()));
+        assert_eq!(difference(viet[0], viet[2]), "they're written with different Unicode normalizations");
+        assert_eq!(difference(viet[0], viet[3]), "they differ only by case");
+        // greek, where the final sigma folds like the other ones
+        let upper = "\u{3a3}\u{38a}\u{3a3}\u{3a5}\u{3a6}\u{39f}\u{3a3}";
+        let lower = "\u{3c3}\u{3af}\u{3c3}\u{3c5}\u{3c6}\u{3bf}\u{3c2}";
+        assert_eq!(folded(upper), folded(lower));
+        assert_eq!(folded("\u{3c3}\u{3af}"), folded("\u{3c3}\u{3b9}\u{301}"));
+        // cyrillic
+        let cyr = ["\u{439}\u{43e}\u{434}", "\u{438}\u{306}\u{43e}\u{434}", "\u{419}\u{41e}\u{414}", "\u{438}\u{43e}\u{434}"];
+        assert_eq!(collisions(&cyr), vec![Some(1), Some(2), Some(0), None]);
+        assert_eq!(difference(cyr[1], cyr[2]), "they differ by case and Unicode normalization");
+    }
+}
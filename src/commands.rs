@@ -1,69 +1,275 @@
 //! A command is the parsed representation of what the user types
 //!  in the input. It's independant of the state of the application
 //!  (verbs arent checked at this point)
+//! What a key does to the input is told by parse_key, a pure function
+//!  of the previous input, the Command (owned by the App) keeping the
+//!  input and the states some keys depend on (chords, menus, hints,
+//!  confirmations).
 
 use regex::Regex;
-use termion::event::Key;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::{Duration, Instant};
+use termion::event::{Event, Key};
+use crate::patterns::Pattern;
 use crate::verb_invocation::VerbInvocation;
 
+/// how long broot waits for the second key of a chord before
+///  considering the leader was just typed as part of a pattern
+const CHORD_TIMEOUT: Duration = Duration::from_millis(1000);
+
+/// the two-key sequences defined in conf: when the input is empty,
+///  the leader key followed by another key executes a verb
+#[derive(Debug, Clone, Default)]
+pub struct Chords {
+    pub leader: Option<char>,
+    pub verbs: BTreeMap<char, String>, // the verb invocations, by second key
+}
+
+/// parse a hot key, as written in conf, eg "alt-g", "ctrl-t" or "F5".
+/// The keys already having a meaning in broot aren't accepted.
+pub fn parse_hot_key(s: &str) -> Option<Key> {
+    let lower = s.to_lowercase();
+    let key = if lower.starts_with("alt-") {
+        let mut chars = s[4..].chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) if !c.is_whitespace() => Key::Alt(c),
+            _ => {
+                return None;
+            }
+        }
+    } else if lower.starts_with("ctrl-") {
+        let mut chars = lower[5..].chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) if c.is_ascii_lowercase() => Key::Ctrl(c),
+            _ => {
+                return None;
+            }
+        }
+    } else if lower.starts_with('f') {
+        match lower[1..].parse() {
+            Ok(n) if n >= 1 && n <= 12 => Key::F(n),
+            _ => {
+                return None;
+            }
+        }
+    } else {
+        return None;
+    };
+    match key {
+        // ctrl-i, ctrl-j and ctrl-m are received as <tab> and <enter>
+        Key::Ctrl('f') | Key::Ctrl('q') | Key::Ctrl('u') | Key::Ctrl('d') | Key::Ctrl('z') => None,
+        Key::Ctrl('i') | Key::Ctrl('j') | Key::Ctrl('m') => None,
+        _ => Some(key),
+    }
+}
+
+/// a key, displayed the way the user would name it, eg "alt-enter",
+///  "F5" or "ctrl-x" (the hot keys being written as in conf)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KeyDescription {
+    Key(Key),
+    Sequence(&'static str), // a key termion doesn't parse, eg "alt-up"
+}
+
+impl fmt::Display for KeyDescription {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let key = match self {
+            KeyDescription::Key(key) => *key,
+            KeyDescription::Sequence(name) => return write!(f, "{}", name),
+        };
+        match key {
+            Key::Char('\n') | Key::Char('\r') => write!(f, "enter"),
+            Key::Char('\t') => write!(f, "tab"),
+            Key::Char(' ') => write!(f, "space"),
+            Key::Char(c) => write!(f, "{}", c),
+            Key::Alt('\n') | Key::Alt('\r') => write!(f, "alt-enter"),
+            Key::Alt(c) => write!(f, "alt-{}", c),
+            Key::Ctrl(c) => write!(f, "ctrl-{}", c),
+            Key::F(n) => write!(f, "F{}", n),
+            Key::Esc => write!(f, "esc"),
+            Key::Backspace => write!(f, "backspace"),
+            Key::Delete => write!(f, "delete"),
+            Key::Up => write!(f, "up"),
+            Key::Down => write!(f, "down"),
+            Key::Left => write!(f, "left"),
+            Key::Right => write!(f, "right"),
+            Key::Home => write!(f, "home"),
+            Key::End => write!(f, "end"),
+            Key::PageUp => write!(f, "page-up"),
+            Key::PageDown => write!(f, "page-down"),
+            key => write!(f, "{:?}", key),
+        }
+    }
+}
+
+impl KeyDescription {
+    /// the key as written in a status hint: "<alt-enter>", or
+    ///  "'?'" for a char typed in the input
+    pub fn hint(self) -> String {
+        match self {
+            KeyDescription::Key(Key::Char(c)) if !c.is_whitespace() => format!("'{}'", c),
+            _ => format!("<{}>", self),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Command {
     pub raw: String,     // what's visible in the input
     parts: CommandParts, // the parsed parts of the visible input
     pub action: Action, // what's required, based on the last key (which may be not visible, like esc)
+    chord_start: Option<(char, Instant)>, // the leader, when waiting for the second key of a chord
 }
 
 /// An intermediate parsed representation of the raw string
-#[derive(Debug, Clone)]
-struct CommandParts {
-    pattern: Option<String>,     // either a fuzzy pattern or the core of a regex
-    regex_flags: Option<String>, // may be Some("") if user asked for a regex but specified no flag
-    verb_invocation: Option<VerbInvocation>, // may be empty if user already typed the separator but no char after
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommandParts {
+    pub pattern: Option<String>,     // either a fuzzy pattern or the core of a regex
+    pub regex_flags: Option<String>, // may be Some("") if user asked for a regex but specified no flag
+    pub words: bool, // whether the pattern is made of words to find in any order (w/ prefix)
+    pub verb_invocation: Option<VerbInvocation>, // may be empty if user already typed the separator but no char after
 }
 
-#[derive(Debug, Clone)]
+/// what a key makes of the input: the new raw text, its parts,
+///  and the action required
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParsedKey {
+    pub raw: String,
+    pub parts: CommandParts,
+    pub action: Action,
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum Action {
     MoveSelection(i32),        // up (neg) or down (positive) in the list
+    MoveSameDepth(i32),        // to the previous (neg) or next line with the same parent
+    MoveBreadcrumb(i32),       // highlight the previous (neg) or next component of the root
     ScrollPage(i32),           // in number of pages, not lines
+    Scroll(i32),               // in number of lines, without moving the selection
+    SelectFirst,               // go to the top
+    SelectLast,                // go to the bottom
     OpenSelection,             // open the selected line
     AltOpenSelection,          // alternate open the selected line
     VerbEdit(VerbInvocation),          // verb invocation, unfinished
     Verb(VerbInvocation),              // verb invocation, after the user hit enter
     FuzzyPatternEdit(String),  // a pattern being edited
     RegexEdit(String, String), // a regex being edited (core & flags)
+    WordsPatternEdit(String),  // whitespace separated words being edited
     Back,                      // back to last app state, or clear pattern
     Next,                      // goes to the next matching entry
+    Complete,                  // complete the argument of the verb being typed
     Help,                      // goes to help state
+    FlagsMenu,                 // opens the menu of the togglable flags
+    MenuKey(char),             // a key typed while a menu is open
+    HintKeys(String),          // the letters typed since the hint labels are shown
+    Confirm(VerbInvocation),   // a verb waiting for the user to confirm it
+    ConfirmAnswer(VerbInvocation, bool), // the verb, and whether the user confirmed it
+    UnboundChord(char, char),  // a chord not defined in conf
+    HotKey(Key),               // a key with a modifier, or a function key, maybe bound to a verb
+    UnboundKey(String),        // a key meaning nothing, as the user would name it
     Quit,                      // quit broot
+    Suspend,                   // stop broot, giving the terminal back to the shell
     Unparsed,                  // or unparsable
 }
 
+// whether the char would end the pattern in the input, unless
+//  preceded by a backslash
+fn is_separator(c: char) -> bool {
+    c == ':' || c == '/' || c.is_whitespace()
+}
+
+// the text with a backslash before its spaces, '/' and ':', so
+//  that it's part of the pattern instead of starting a verb
+fn escaped(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if is_separator(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+// the pattern as written in the input, without the backslashes
+//  escaping its separators
+fn unescaped(pattern: &str) -> String {
+    let mut unescaped = String::with_capacity(pattern.len());
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            if let Some(&next) = chars.peek() {
+                if is_separator(next) {
+                    continue;
+                }
+            }
+        }
+        unescaped.push(c);
+    }
+    unescaped
+}
+
+// whether the pattern starts with a backslash followed by a char which,
+//  typed first, would be a key, like '{'. The backslash lets a pattern
+//  start with this char
+fn starts_with_escaped_key(pattern: &str) -> bool {
+    let mut chars = pattern.chars();
+    match (chars.next(), chars.next()) {
+        (Some('\\'), Some(c)) => "?'{}".contains(c),
+        _ => false,
+    }
+}
+
 impl CommandParts {
     fn new() -> CommandParts {
         CommandParts {
             pattern: None,
             regex_flags: None,
+            words: false,
             verb_invocation: None,
         }
     }
-    fn from(raw: &str) -> CommandParts {
+    pub fn from(raw: &str) -> CommandParts {
         let mut cp = CommandParts::new();
         lazy_static! {
             static ref RE: Regex = Regex::new(
                 r"(?x)
                 ^
                 (?P<slash_before>/)?
-                (?P<pattern>[^\s/:]+)?
+                (?P<pattern>(?:\\[\s/:]|[^\s/:])+)?
                 (?:/(?P<regex_flags>\w*))?
                 (?:[\s:]+(?P<verb_invocation>.*))?
                 $
                 "
             )
             .unwrap();
+            // after the w/ prefix, spaces separate the words, so
+            //  only a ':' introduces the verb
+            static ref WORDS_RE: Regex = Regex::new(
+                r"(?x)
+                ^
+                w/
+                (?P<pattern>(?:\\:|[^:])*)
+                (?::(?P<verb_invocation>.*))?
+                $
+                "
+            )
+            .unwrap();
         }
-        if let Some(c) = RE.captures(raw) {
+        if let Some(c) = WORDS_RE.captures(raw) {
+            cp.words = true;
+            cp.pattern = c.name("pattern").map(|pattern| unescaped(pattern.as_str()));
+            if let Some(verb) = c.name("verb_invocation") {
+                cp.verb_invocation = Some(VerbInvocation::from(verb.as_str()));
+            }
+        } else if let Some(c) = RE.captures(raw) {
             if let Some(pattern) = c.name("pattern") {
-                cp.pattern = Some(String::from(pattern.as_str()));
+                let mut pattern = unescaped(pattern.as_str());
+                if c.name("slash_before").is_none() && starts_with_escaped_key(&pattern) {
+                    pattern.remove(0);
+                }
+                cp.pattern = Some(pattern);
                 if let Some(rxf) = c.name("regex_flags") {
                     cp.regex_flags = Some(String::from(rxf.as_str()));
                 } else if c.name("slash_before").is_some() {
@@ -90,7 +296,9 @@ impl Action {
             Action::OpenSelection
         } else if let Some(pattern) = &cp.pattern {
             let pattern = String::from(pattern.as_str());
-            if let Some(regex_flags) = &cp.regex_flags {
+            if cp.words {
+                Action::WordsPatternEdit(pattern)
+            } else if let Some(regex_flags) = &cp.regex_flags {
                 Action::RegexEdit(pattern, String::from(regex_flags.as_str()))
             } else {
                 Action::FuzzyPatternEdit(String::from(pattern.as_str()))
@@ -107,6 +315,7 @@ impl Command {
             raw: String::new(),
             parts: CommandParts::new(),
             action: Action::Unparsed,
+            chord_start: None,
         }
     }
     // build a command from a string
@@ -121,57 +330,421 @@ impl Command {
     pub fn from(raw: String) -> Command {
         let parts = CommandParts::from(&raw);
         let action = Action::from(&parts, raw.contains(':'));
-        Command { raw, parts, action }
+        Command {
+            raw,
+            parts,
+            action,
+            chord_start: None,
+        }
     }
-    pub fn add_key(&mut self, key: Key) {
-        match key {
-            Key::Char('\t') => {
-                self.action = Action::Next;
-            }
-            Key::Char('\n') => {
-                self.action = Action::from(&self.parts, true);
+    /// the command whose input is the given pattern, as the user would
+    ///  have typed it
+    pub fn from_pattern(pattern: &Pattern) -> Command {
+        Command::from(match pattern {
+            Pattern::Fuzzy(fp) => escaped(&fp.to_string()),
+            Pattern::Regex(rp) => {
+                // the '/' before the flags isn't part of the regex
+                let rp = rp.to_string();
+                let (rex, flags) = rp.split_at(rp.rfind('/').unwrap_or(0));
+                format!("{}{}", escaped(rex), flags)
             }
-            Key::Alt('\r') | Key::Alt('\n') => {
-                self.action = Action::AltOpenSelection;
+            Pattern::Words(wp) => format!("w/{}", wp.to_string().replace(':', "\\:")),
+            Pattern::None => String::new(),
+        })
+    }
+    /// the command whose input is only the pattern part of this one, as
+    ///  it was before a verb was typed (eg "abc" for "abc:focus")
+    pub fn pattern_input(&self) -> Command {
+        Command::from(match self.pattern_end() {
+            Some(end) => self.raw[..end].to_string(),
+            None => self.raw.clone(),
+        })
+    }
+    // the index in raw of the separator ending the pattern part, if any
+    fn pattern_end(&self) -> Option<usize> {
+        // this follows the separations of CommandParts::from
+        let mut escaping = false;
+        for (idx, c) in self.raw.char_indices() {
+            if escaping {
+                escaping = false;
+            } else if c == '\\' {
+                escaping = true;
+            } else if c == ':' || (c.is_whitespace() && !self.parts.words) {
+                return Some(idx);
             }
-            Key::Ctrl('q') => {
-                self.action = Action::Quit;
+        }
+        None
+    }
+    /// the verb invocation of the visible input, when it's parsed as one,
+    ///  with the index in raw where it starts (its separator included)
+    pub fn verb_invocation(&self) -> Option<(usize, &VerbInvocation)> {
+        self.parts
+            .verb_invocation
+            .as_ref()
+            .map(|invocation| (self.pattern_end().unwrap_or(0), invocation))
+    }
+    /// the leader of the chord whose second key is awaited, if any
+    pub fn pending_chord(&self) -> Option<char> {
+        self.chord_start.map(|(leader, _)| leader)
+    }
+    /// when the second key of a chord didn't come in time, the leader
+    ///  is handled as a normal char. Return true when it's the case.
+    pub fn end_expired_chord(&mut self) -> bool {
+        match self.chord_start {
+            Some((leader, start)) if start.elapsed() > CHORD_TIMEOUT => {
+                self.chord_start = None;
+                self.add_char(leader);
+                true
             }
-            Key::Up => {
-                self.action = Action::MoveSelection(-1);
+            _ => false,
+        }
+    }
+    // when a verb waits for its confirmation, the event answers it,
+    //  the input staying as it was. Return whether it was the case
+    fn answer_confirmation(&mut self, confirmed: bool) -> bool {
+        if let Action::Confirm(invocation) = &self.action {
+            self.action = Action::ConfirmAnswer(invocation.clone(), confirmed);
+            true
+        } else {
+            false
+        }
+    }
+    pub fn add_event(&mut self, event: Event, chords: &Chords) {
+        if self.answer_confirmation(event == Event::Key(Key::Char('y'))) {
+            return;
+        }
+        match event {
+            Event::Key(key) => {
+                self.add_key(key, chords);
             }
-            Key::Down => {
-                self.action = Action::MoveSelection(1);
+            Event::Unsupported(seq) => {
+                self.action = parse_sequence(&seq)
+                    .unwrap_or_else(|| Action::UnboundKey(describe_sequence(&seq)));
             }
-            Key::PageUp | Key::Ctrl('u') => {
-                self.action = Action::ScrollPage(-1);
+            _ => {}
+        }
+    }
+    /// add a text pasted in the terminal, as if it was typed, but
+    ///  without the meaning some keys have on their own (eg '?' or a
+    ///  chord leader) and with the input parsed only once. A text
+    ///  pasted in the pattern stays in it, even with spaces or ':'
+    pub fn add_paste(&mut self, text: &str) {
+        if self.answer_confirmation(false) {
+            return;
+        }
+        let text = text.trim_end_matches(|c| c == '\n' || c == '\r');
+        let raw = if self.parts.verb_invocation.is_some() {
+            format!("{}{}", self.raw, text)
+        } else {
+            format!("{}{}", self.raw, escaped(text))
+        };
+        self.set_raw(raw);
+    }
+    /// replace the input, as if it was typed
+    pub fn set_raw(&mut self, raw: String) {
+        self.chord_start = None;
+        self.parts = CommandParts::from(&raw);
+        self.raw = raw;
+        self.action = Action::from(&self.parts, false);
+    }
+    fn add_char(&mut self, c: char) {
+        let raw = format!("{}{}", self.raw, c);
+        self.set_raw(raw);
+    }
+    pub fn add_key(&mut self, key: Key, chords: &Chords) {
+        if let Action::FlagsMenu = self.action {
+            // the key typed just after opening the menu isn't part of the input
+            if let Key::Char(c) = key {
+                self.action = Action::MenuKey(c);
+                return;
             }
-            Key::PageDown | Key::Ctrl('d') => {
-                self.action = Action::ScrollPage(1);
+        }
+        if let Action::HintKeys(typed) = &mut self.action {
+            // the letters of a hint label aren't part of the input either
+            if let Key::Char(c) = key {
+                if c != '\n' && c != '\t' {
+                    typed.push(c);
+                    return;
+                }
             }
-            Key::Char(c) => {
-                if c == '?' && self.raw.is_empty() {
-                    // as first character, a '?' is a request for help
-                    self.action = Action::Help;
-                } else {
-                    self.raw.push(c);
-                    self.parts = CommandParts::from(&self.raw);
-                    self.action = Action::from(&self.parts, false);
+        }
+        if let Some((leader, _)) = self.chord_start.take() {
+            match key {
+                Key::Esc => {
+                    self.action = Action::Unparsed;
+                    return;
                 }
+                Key::Char(c) if c != '\n' && c != '\t' => {
+                    self.action = match chords.verbs.get(&c) {
+                        Some(invocation) => Action::Verb(VerbInvocation::from(invocation.as_str())),
+                        None => Action::UnboundChord(leader, c),
+                    };
+                    return;
+                }
+                _ => {} // the chord is abandoned and the key normally handled
             }
-            Key::Esc => {
-                self.action = Action::Back;
+        }
+        let parsed = match parse_key(&self.raw, key) {
+            Some(parsed) => parsed,
+            None => {
+                self.action = Action::Unparsed;
+                return;
             }
-            Key::Backspace => {
-                if self.raw == "" {
-                    self.action = Action::Back;
-                } else {
-                    self.raw.pop();
-                    self.parts = CommandParts::from(&self.raw);
-                    self.action = Action::from(&self.parts, false);
-                }
+        };
+        if let Key::Char(c) = key {
+            // a leader which would start the input starts a chord instead
+            if self.raw.is_empty() && !parsed.raw.is_empty() && chords.leader == Some(c) {
+                self.chord_start = Some((c, Instant::now()));
+                self.action = Action::Unparsed;
+                return;
             }
-            _ => {}
         }
+        self.raw = parsed.raw;
+        self.parts = parsed.parts;
+        self.action = parsed.action;
+    }
+}
+
+/// the action of an escape sequence termion doesn't parse, like the
+///  arrow keys with a modifier
+pub fn parse_sequence(seq: &[u8]) -> Option<Action> {
+    match seq {
+        b"\x1b[1;5A" => Some(Action::Scroll(-1)),
+        b"\x1b[1;5B" => Some(Action::Scroll(1)),
+        b"\x1b[1;3A" => Some(Action::MoveSameDepth(-1)),
+        b"\x1b[1;3B" => Some(Action::MoveSameDepth(1)),
+        b"\x1b[1;3D" | b"\x1b[1;5D" => Some(Action::MoveBreadcrumb(-1)),
+        b"\x1b[1;3C" | b"\x1b[1;5C" => Some(Action::MoveBreadcrumb(1)),
+        _ => None,
+    }
+}
+
+// a sequence termion doesn't parse, as the user would name it, the
+//  escape char being written "^["
+fn describe_sequence(seq: &[u8]) -> String {
+    String::from_utf8_lossy(seq).replace('\x1b', "^[")
+}
+
+/// what the key does when typed after the raw input, whatever the state
+///  of the application. None when the key is deliberately ignored, the
+///  other keys meaning nothing giving an UnboundKey action.
+/// The keys whose meaning depends on what happened before (chords, menus,
+///  hint labels, confirmations) are handled by the Command
+pub fn parse_key(raw: &str, key: Key) -> Option<ParsedKey> {
+    let parts = CommandParts::from(raw);
+    let action = match key {
+        Key::Char('\t') if parts.verb_invocation.is_some() => Action::Complete,
+        Key::Char('\t') => Action::Next,
+        Key::Char('\n') => Action::from(&parts, true),
+        Key::Alt('\r') | Key::Alt('\n') => Action::AltOpenSelection,
+        Key::Ctrl('f') => Action::FlagsMenu,
+        Key::Ctrl('q') => Action::Quit,
+        Key::Ctrl('z') => Action::Suspend,
+        Key::Up => Action::MoveSelection(-1),
+        Key::Down => Action::MoveSelection(1),
+        Key::PageUp | Key::Ctrl('u') => Action::ScrollPage(-1),
+        Key::PageDown | Key::Ctrl('d') => Action::ScrollPage(1),
+        Key::Home => Action::SelectFirst,
+        Key::End => Action::SelectLast,
+        // the input isn't changed, so that the pattern stays
+        Key::Alt(_) | Key::Ctrl(_) | Key::F(_) => Action::HotKey(key),
+        // as first character, a '?' is a request for help
+        Key::Char('?') if raw.is_empty() => Action::Help,
+        // as first character, a quote shows the hint labels
+        Key::Char('\'') if raw.is_empty() => Action::HintKeys(String::new()),
+        Key::Char('{') if raw.is_empty() => Action::MoveSameDepth(-1),
+        Key::Char('}') if raw.is_empty() => Action::MoveSameDepth(1),
+        Key::Char(c) => {
+            return Some(ParsedKey::of(format!("{}{}", raw, c)));
+        }
+        Key::Esc => Action::Back,
+        Key::Backspace if raw.is_empty() => Action::Back,
+        Key::Backspace => {
+            let mut raw = raw.to_string();
+            raw.pop();
+            return Some(ParsedKey::of(raw));
+        }
+        // sent by some terminals on ctrl-space, which isn't worth a message
+        Key::Null => {
+            return None;
+        }
+        _ => Action::UnboundKey(KeyDescription::Key(key).to_string()),
+    };
+    Some(ParsedKey {
+        raw: raw.to_string(),
+        parts,
+        action,
+    })
+}
+
+impl ParsedKey {
+    // the parsing of an input being edited
+    fn of(raw: String) -> ParsedKey {
+        let parts = CommandParts::from(&raw);
+        let action = Action::from(&parts, false);
+        ParsedKey { raw, parts, action }
+    }
+}
+
+#[cfg(test)]
+mod commands_tests {
+    use super::*;
+
+    fn verb(invocation: &str) -> VerbInvocation {
+        VerbInvocation::from(invocation)
+    }
+
+    // the input and the action after each key typed in turn
+    fn typed(keys: &[Key]) -> Vec<(String, Action)> {
+        let mut cmd = Command::new();
+        keys.iter()
+            .map(|key| {
+                cmd.add_key(*key, &Chords::default());
+                (cmd.raw.clone(), cmd.action.clone())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn actions_of_keys() {
+        let fuzzy = |pat: &str| Action::FuzzyPatternEdit(pat.to_string());
+        let cases: Vec<(&str, Key, &str, Action)> = vec![
+            ("", Key::Char('a'), "a", fuzzy("a")),
+            ("ab", Key::Char('\n'), "ab", Action::OpenSelection),
+            ("", Key::Char('\n'), "", Action::OpenSelection),
+            ("ab:rm", Key::Char('\n'), "ab:rm", Action::Verb(verb("rm"))),
+            ("ab rm", Key::Char('\n'), "ab rm", Action::Verb(verb("rm"))),
+            (":cd", Key::Char('\n'), ":cd", Action::Verb(verb("cd"))),
+            ("ab", Key::Char(':'), "ab:", Action::VerbEdit(verb(""))),
+            ("", Key::Char(':'), ":", Action::VerbEdit(verb(""))),
+            ("ab:", Key::Backspace, "ab", fuzzy("ab")),
+            (":", Key::Backspace, "", fuzzy("")),
+            ("a", Key::Backspace, "", fuzzy("")),
+            ("", Key::Backspace, "", Action::Back),
+            ("ab", Key::Esc, "ab", Action::Back),
+            ("/a", Key::Char('b'), "/ab", Action::RegexEdit("ab".to_string(), "".to_string())),
+            ("a/", Key::Char('i'), "a/i", Action::RegexEdit("a".to_string(), "i".to_string())),
+            ("w/a", Key::Char(' '), "w/a ", Action::WordsPatternEdit("a ".to_string())),
+            ("w/a b:", Key::Char('e'), "w/a b:e", Action::VerbEdit(verb("e"))),
+            ("ab", Key::Up, "ab", Action::MoveSelection(-1)),
+            ("ab", Key::Down, "ab", Action::MoveSelection(1)),
+            ("", Key::PageUp, "", Action::ScrollPage(-1)),
+            ("", Key::Ctrl('d'), "", Action::ScrollPage(1)),
+            ("", Key::Home, "", Action::SelectFirst),
+            ("", Key::End, "", Action::SelectLast),
+            ("ab", Key::Char('\t'), "ab", Action::Next),
+            ("ab:cp sr", Key::Char('\t'), "ab:cp sr", Action::Complete),
+            ("ab", Key::Alt('\r'), "ab", Action::AltOpenSelection),
+            ("ab", Key::Alt('g'), "ab", Action::HotKey(Key::Alt('g'))),
+            ("", Key::F(5), "", Action::HotKey(Key::F(5))),
+            ("", Key::Ctrl('f'), "", Action::FlagsMenu),
+            ("ab", Key::Ctrl('q'), "ab", Action::Quit),
+            ("", Key::Char('?'), "", Action::Help),
+            ("a", Key::Char('?'), "a?", fuzzy("a?")),
+            ("", Key::Char('\''), "", Action::HintKeys(String::new())),
+            ("", Key::Char('{'), "", Action::MoveSameDepth(-1)),
+            ("a", Key::Char('}'), "a}", fuzzy("a}")),
+            ("\\", Key::Char('{'), "\\{", fuzzy("{")),
+            ("\\?", Key::Char('a'), "\\?a", fuzzy("?a")),
+            ("/\\", Key::Char('{'), "/\\{", Action::RegexEdit("\\{".to_string(), "".to_string())),
+            ("a\\ b", Key::Char('c'), "a\\ bc", fuzzy("a bc")),
+            ("a\\:b", Key::Char(' '), "a\\:b ", Action::VerbEdit(verb(""))),
+            ("\\/a\\/b", Key::Char('\n'), "\\/a\\/b", Action::OpenSelection),
+        ];
+        for (raw, key, new_raw, action) in cases {
+            let parsed = parse_key(raw, key).unwrap();
+            assert_eq!((raw, key, parsed.raw.as_str()), (raw, key, new_raw));
+            assert_eq!((raw, key, parsed.action), (raw, key, action));
+        }
+        assert!(parse_key("ab", Key::Null).is_none());
+        assert_eq!(
+            parse_key("ab", Key::Left).unwrap().action,
+            Action::UnboundKey("left".to_string())
+        );
+        assert_eq!(parse_sequence(b"\x1b[1;5A"), Some(Action::Scroll(-1)));
+        assert_eq!(parse_sequence(b"\x1b[1;3C"), Some(Action::MoveBreadcrumb(1)));
+        assert_eq!(parse_sequence(b"\x1b[42~"), None);
+    }
+
+    #[test]
+    fn erasing_back_into_the_pattern() {
+        let keys = [Key::Char('a'), Key::Char(':'), Key::Char('e'), Key::Backspace, Key::Backspace];
+        assert_eq!(
+            typed(&keys),
+            vec![
+                ("a".to_string(), Action::FuzzyPatternEdit("a".to_string())),
+                ("a:".to_string(), Action::VerbEdit(verb(""))),
+                ("a:e".to_string(), Action::VerbEdit(verb("e"))),
+                ("a:".to_string(), Action::VerbEdit(verb(""))),
+                ("a".to_string(), Action::FuzzyPatternEdit("a".to_string())),
+            ],
+        );
+    }
+
+    #[test]
+    fn keys_depending_on_the_state() {
+        let mut chords = Chords::default();
+        chords.leader = Some('g');
+        chords.verbs.insert('h', "focus ~".to_string());
+        let mut cmd = Command::new();
+        cmd.add_key(Key::Char('g'), &chords);
+        assert_eq!(cmd.pending_chord(), Some('g'));
+        cmd.add_key(Key::Char('h'), &chords);
+        assert_eq!(cmd.action, Action::Verb(verb("focus ~")));
+        assert_eq!(cmd.raw, "");
+        // the leader is a normal char once the input is started
+        cmd.add_key(Key::Char('a'), &chords);
+        cmd.add_key(Key::Char('g'), &chords);
+        assert_eq!(cmd.action, Action::FuzzyPatternEdit("ag".to_string()));
+        // the key after ctrl-f picks in the menu
+        cmd.add_key(Key::Ctrl('f'), &chords);
+        cmd.add_key(Key::Char('h'), &chords);
+        assert_eq!(cmd.action, Action::MenuKey('h'));
+        assert_eq!(cmd.raw, "ag");
+        // a verb waiting for its confirmation is answered
+        cmd.action = Action::Confirm(verb("rm"));
+        cmd.add_event(Event::Key(Key::Char('y')), &chords);
+        assert_eq!(cmd.action, Action::ConfirmAnswer(verb("rm"), true));
+        // an unbound key is told, an ignored one does nothing
+        cmd.add_key(Key::Insert, &chords);
+        assert_eq!(cmd.action, Action::UnboundKey("Insert".to_string()));
+        cmd.add_event(Event::Unsupported(b"\x1b[1;2P".to_vec()), &chords);
+        assert_eq!(cmd.action, Action::UnboundKey("^[[1;2P".to_string()));
+        cmd.add_key(Key::Null, &chords);
+        assert_eq!(cmd.action, Action::Unparsed);
+        assert_eq!(cmd.raw, "ag");
+    }
+    #[test]
+    fn chords_time_out() {
+        let mut verbs = BTreeMap::new();
+        verbs.insert('h', "focus ~".to_string());
+        let chords = Chords {
+            leader: Some('g'),
+            verbs,
+        };
+        let mut cmd = Command::new();
+        cmd.add_key(Key::Char('g'), &chords);
+        assert!(!cmd.end_expired_chord());
+        assert_eq!(cmd.pending_chord(), Some('g'));
+        // the leader is typed in the pattern when the second key doesn't come
+        cmd.chord_start = Some(('g', Instant::now() - CHORD_TIMEOUT - Duration::from_millis(1)));
+        assert!(cmd.end_expired_chord());
+        assert_eq!(cmd.pending_chord(), None);
+        assert_eq!(cmd.raw, "g");
+        assert_eq!(cmd.action, Action::FuzzyPatternEdit("g".to_string()));
+        assert!(!cmd.end_expired_chord());
+        // <esc> cancels a chord, another key which isn't a char abandons it
+        let mut cmd = Command::new();
+        cmd.add_key(Key::Char('g'), &chords);
+        cmd.add_key(Key::Esc, &chords);
+        assert_eq!((cmd.pending_chord(), cmd.raw.as_str()), (None, ""));
+        cmd.add_key(Key::Char('g'), &chords);
+        cmd.add_key(Key::Down, &chords);
+        assert_eq!(cmd.pending_chord(), None);
+        assert_eq!(cmd.action, Action::MoveSelection(1));
+        // an unknown second key is told
+        cmd.add_key(Key::Char('g'), &chords);
+        cmd.add_key(Key::Char('z'), &chords);
+        assert_eq!(cmd.action, Action::UnboundChord('g', 'z'));
     }
 }
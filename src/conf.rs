@@ -8,9 +8,20 @@ use std::io;
 use std::path::{Path, PathBuf};
 use std::result::Result;
 use toml::{self, Value};
+use termion::event::Key;
 
+use crate::cli;
+use crate::commands::{self, Chords};
+use crate::dates;
 use crate::errors::ConfError;
+use crate::internals::Internal;
+use crate::skin::{Palette, Skin};
 use crate::skin_conf;
+use crate::special_paths::SpecialPath;
+use crate::tree_options::{
+    Balance, DateFormat, SizeBarBase, SizeDisplay, Sort, DEFAULT_DATE_FORMAT, DEFAULT_MAX_CHILDREN, DEFAULT_SIZE_BAR_WIDTH,
+};
+use crate::yank::PasteConflict;
 
 /// what's needed to handle a verb
 #[derive(Debug)]
@@ -22,12 +33,47 @@ pub struct VerbConf {
     pub from_shell: Option<bool>,
     pub leave_broot: Option<bool>,
     pub confirm: Option<bool>,
+    pub working_dir: Option<String>,
+    pub env: Vec<(String, String)>,
+    pub key: Option<Key>,
 }
 
 #[derive(Debug)]
 pub struct Conf {
     pub verbs: Vec<VerbConf>,
     pub skin_entries: HashMap<String, String>,
+    pub skin_palette: Option<Palette>,
+    pub size_display: SizeDisplay,
+    pub size_bar_base: SizeBarBase,
+    pub size_bar: bool,
+    pub size_percent: bool,
+    pub date_format: DateFormat,
+    pub file_opener: Option<String>,
+    pub enter_on_file: Internal, // either OpenLeave or OpenStay
+    pub paste_conflict: PasteConflict,
+    pub show_parent_line: bool,
+    pub flatten_chains: bool,
+    pub show_fs_info: bool,
+    pub read_xattrs: bool,
+    pub detect_git_repos: bool,
+    pub detect_collisions: bool,
+    pub lookup_users: bool,
+    pub max_children: usize,
+    pub max_children_shown: Option<usize>,
+    pub balance: Balance,
+    pub max_visible_depth: Option<usize>,
+    pub special_paths: Vec<SpecialPath>,
+    pub sort: Sort,
+    pub dirs_first: bool,
+    pub chords: Chords,
+    pub same_depth_jump_to_parent: bool,
+    pub relative_paths: bool,
+    pub cd_on_quit: bool,
+    pub two_line_status: bool,
+    pub terminal_title: bool,
+    pub stack_breadcrumb: bool,
+    pub max_nesting_depth: Option<usize>,
+    pub watch_changes: bool,
 }
 
 fn string_field(value: &Value, field_name: &str) -> Option<String> {
@@ -40,6 +86,35 @@ fn string_field(value: &Value, field_name: &str) -> Option<String> {
     }
     None
 }
+// read a string made of exactly one char
+fn char_field(value: &Value, field_name: &str) -> Result<Option<char>, ConfError> {
+    match string_field(value, field_name) {
+        Some(s) => {
+            let mut chars = s.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => Ok(Some(c)),
+                _ => Err(ConfError::InvalidValue {
+                    key: field_name.to_string(),
+                    bad: s,
+                    valid: "a single char".to_string(),
+                }),
+            }
+        }
+        None => Ok(None),
+    }
+}
+// read a table whose values are strings, eg the environment of a verb.
+// None is returned when the field isn't such a table
+fn string_table_field(value: &Value, field_name: &str) -> Option<Vec<(String, String)>> {
+    match value.get(field_name) {
+        None => Some(Vec::new()),
+        Some(Value::Table(tbl)) => tbl
+            .iter()
+            .map(|(k, v)| v.as_str().map(|s| (k.to_string(), s.to_string())))
+            .collect(),
+        Some(_) => None,
+    }
+}
 fn bool_field(value: &Value, field_name: &str) -> Option<bool> {
     if let Value::Table(tbl) = value {
         if let Some(Value::Boolean(b)) = tbl.get(field_name) {
@@ -48,6 +123,87 @@ fn bool_field(value: &Value, field_name: &str) -> Option<bool> {
     }
     None
 }
+fn usize_field(value: &Value, field_name: &str) -> Option<usize> {
+    if let Value::Table(tbl) = value {
+        if let Some(Value::Integer(i)) = tbl.get(field_name) {
+            if *i > 0 {
+                return Some(*i as usize);
+            }
+        }
+    }
+    None
+}
+
+// read a [[special_paths]] entry. Contrary to the [[verbs]] ones, a bad
+//  entry isn't skipped, as the roots would be shown with unexpected options
+fn read_special_path(value: &Value) -> Result<SpecialPath, ConfError> {
+    let invalid = |reason: String| ConfError::InvalidSpecialPath { reason };
+    let tbl = match value {
+        Value::Table(tbl) => tbl,
+        _ => {
+            return Err(invalid("not a table".to_string()));
+        }
+    };
+    let bool_keys = [
+        "show_hidden",
+        "show_sizes",
+        "show_dates",
+        "show_permissions",
+        "show_owner",
+        "only_folders",
+        "dirs_first",
+    ];
+    for (key, v) in tbl.iter() {
+        let valid = match key.as_str() {
+            "path" | "name" | "gitignore" | "sort" => v.is_str(),
+            key if bool_keys.contains(&key) => v.is_bool(),
+            _ => {
+                return Err(invalid(format!("unknown key {:?}", key)));
+            }
+        };
+        if !valid {
+            return Err(invalid(format!("bad type of value for {:?}", key)));
+        }
+    }
+    let raw_path = string_field(value, "path").ok_or_else(|| invalid("missing path".to_string()))?;
+    // the path may start with ~ or hold env variables, as a launch argument
+    let path = cli::expand_path(&raw_path).map_err(|e| invalid(e.to_string()))?;
+    let glob = glob::Pattern::new(&path.to_string_lossy())
+        .map_err(|e| invalid(format!("bad path {:?}: {}", raw_path, e)))?;
+    let respect_git_ignore = match string_field(value, "gitignore") {
+        Some(s) => Some(s.parse().map_err(|_| ConfError::InvalidValue {
+            key: "gitignore".to_string(),
+            bad: s,
+            valid: "yes, no, auto".to_string(),
+        })?),
+        None => None,
+    };
+    let sort = match string_field(value, "sort") {
+        None => None,
+        Some(ref s) if s == "name" => Some(Sort::Name),
+        Some(ref s) if s == "none" => Some(Sort::None),
+        Some(s) => {
+            return Err(ConfError::InvalidValue {
+                key: "sort".to_string(),
+                bad: s,
+                valid: "name, none".to_string(),
+            });
+        }
+    };
+    Ok(SpecialPath {
+        name: string_field(value, "name").unwrap_or(raw_path),
+        glob,
+        show_hidden: bool_field(value, "show_hidden"),
+        show_sizes: bool_field(value, "show_sizes"),
+        show_dates: bool_field(value, "show_dates"),
+        show_permissions: bool_field(value, "show_permissions"),
+        show_owner: bool_field(value, "show_owner"),
+        only_folders: bool_field(value, "only_folders"),
+        respect_git_ignore,
+        sort,
+        dirs_first: bool_field(value, "dirs_first"),
+    })
+}
 
 // return the path to the config directory, based on XDG
 pub fn dir() -> PathBuf {
@@ -68,22 +224,32 @@ impl Conf {
         let conf_filepath = Conf::default_location();
         if !conf_filepath.exists() {
             Conf::write_sample(&conf_filepath)?;
-            println!(
+            // not on stdout, which may be redirected to receive a path
+            eprintln!(
                 "{}New Configuration file written in {:?}.{}",
                 termion::style::Bold,
                 &conf_filepath,
                 termion::style::Reset
             );
-            println!("You should have a look at it.");
+            eprintln!("You should have a look at it.");
         }
         Ok(Conf::from_file(&conf_filepath)?)
     }
     // assume the file doesn't yet exist
     pub fn write_sample(filepath: &Path) -> Result<(), io::Error> {
         fs::create_dir_all(filepath.parent().unwrap())?;
-        fs::write(filepath, DEFAULT_CONF_FILE)?;
+        fs::write(filepath, sample())?;
         Ok(())
     }
+    /// the path to the configuration file, which is written
+    ///  (with the sample content) if it doesn't exist yet
+    pub fn ensure_default_file() -> io::Result<PathBuf> {
+        let conf_filepath = Conf::default_location();
+        if !conf_filepath.exists() {
+            Conf::write_sample(&conf_filepath)?;
+        }
+        Ok(conf_filepath)
+    }
     // read the configuration from a given path. Assume it exists.
     // stderr is supposed to be a valid solution for displaying errors
     // (i.e. this function is called before or after the terminal alternation)
@@ -117,6 +283,25 @@ impl Conf {
                         eprintln!("You can't simultaneously have leave_broot=false and from_shell=true");
                         continue;
                 }
+                let env = match string_table_field(verb_value, "env") {
+                    Some(env) => env,
+                    None => {
+                        eprintln!("Invalid [[verbs]] entry in configuration");
+                        eprintln!("env must be a table of strings, eg env = {{ GIT_PAGER = \"cat\" }}");
+                        continue;
+                    }
+                };
+                let key = match string_field(verb_value, "key") {
+                    Some(s) => match commands::parse_hot_key(&s) {
+                        Some(key) => Some(key),
+                        None => {
+                            eprintln!("Invalid [[verbs]] entry in configuration");
+                            eprintln!("{:?} isn't a valid key: use eg \"alt-g\", \"ctrl-t\" or \"F5\"", s);
+                            continue;
+                        }
+                    },
+                    None => None,
+                };
                 verbs.push(VerbConf {
                     invocation,
                     execution,
@@ -125,10 +310,163 @@ impl Conf {
                     from_shell,
                     leave_broot,
                     confirm: bool_field(verb_value, "confirm"),
+                    working_dir: string_field(verb_value, "working_dir"),
+                    env,
+                    key,
+                });
+            }
+        }
+        // reading the display settings
+        let size_display = match string_field(&root, "size_display") {
+            None => SizeDisplay::Cell,
+            Some(ref s) if s == "cell" => SizeDisplay::Cell,
+            Some(ref s) if s == "bar" => SizeDisplay::Bar(
+                usize_field(&root, "size_bar_width").unwrap_or(DEFAULT_SIZE_BAR_WIDTH),
+            ),
+            Some(s) => {
+                return Err(ConfError::InvalidValue {
+                    key: "size_display".to_string(),
+                    bad: s,
+                    valid: "cell, bar".to_string(),
+                });
+            }
+        };
+        let size_bar_base = match string_field(&root, "size_bar_base") {
+            None => SizeBarBase::Parent,
+            Some(ref s) if s == "parent" => SizeBarBase::Parent,
+            Some(ref s) if s == "total" => SizeBarBase::Total,
+            Some(s) => {
+                return Err(ConfError::InvalidValue {
+                    key: "size_bar_base".to_string(),
+                    bad: s,
+                    valid: "parent, total".to_string(),
+                });
+            }
+        };
+        let size_bar = bool_field(&root, "size_bar").unwrap_or(true);
+        let size_percent = bool_field(&root, "size_percent").unwrap_or(false);
+        let date_format = match string_field(&root, "date_format") {
+            None => DateFormat::Strftime(DEFAULT_DATE_FORMAT.to_string()),
+            Some(ref s) if s == "relative" => DateFormat::Relative,
+            Some(s) => {
+                if !dates::is_valid_format(&s) {
+                    return Err(ConfError::InvalidValue {
+                        key: "date_format".to_string(),
+                        bad: s,
+                        valid: "relative, or a strftime format".to_string(),
+                    });
+                }
+                DateFormat::Strftime(s)
+            }
+        };
+        let file_opener = string_field(&root, "file_opener");
+        let enter_on_file = match string_field(&root, "enter_on_file") {
+            None => Internal::OpenLeave,
+            Some(ref s) if s == "open_leave" => Internal::OpenLeave,
+            Some(ref s) if s == "open_stay" => Internal::OpenStay,
+            Some(ref s) if s == "open_later" => Internal::OpenLater,
+            Some(s) => {
+                return Err(ConfError::InvalidValue {
+                    key: "enter_on_file".to_string(),
+                    bad: s,
+                    valid: "open_leave, open_stay, open_later".to_string(),
+                });
+            }
+        };
+        let paste_conflict = match string_field(&root, "paste_conflict") {
+            None => PasteConflict::Ask,
+            Some(ref s) if s == "ask" => PasteConflict::Ask,
+            Some(ref s) if s == "rename" => PasteConflict::Rename,
+            Some(s) => {
+                return Err(ConfError::InvalidValue {
+                    key: "paste_conflict".to_string(),
+                    bad: s,
+                    valid: "ask, rename".to_string(),
+                });
+            }
+        };
+        let show_parent_line = bool_field(&root, "show_parent_line").unwrap_or(true);
+        let flatten_chains = bool_field(&root, "flatten_chains").unwrap_or(false);
+        let show_fs_info = bool_field(&root, "show_fs_info").unwrap_or(true);
+        let read_xattrs = bool_field(&root, "read_extended_attributes").unwrap_or(true);
+        let detect_git_repos = bool_field(&root, "detect_git_repositories").unwrap_or(true);
+        let detect_collisions = bool_field(&root, "detect_collisions").unwrap_or(false);
+        let lookup_users = bool_field(&root, "lookup_users").unwrap_or(true);
+        let max_children = usize_field(&root, "max_children").unwrap_or(DEFAULT_MAX_CHILDREN);
+        let max_children_shown = usize_field(&root, "max_children_shown");
+        let balance = match string_field(&root, "balance") {
+            None => Balance::Even,
+            Some(ref s) if s == "even" => Balance::Even,
+            Some(ref s) if s == "proportional" => Balance::Proportional,
+            Some(s) => {
+                return Err(ConfError::InvalidValue {
+                    key: "balance".to_string(),
+                    bad: s,
+                    valid: "even, proportional".to_string(),
                 });
             }
+        };
+        let max_visible_depth = usize_field(&root, "max_visible_depth");
+        let same_depth_jump_to_parent =
+            bool_field(&root, "same_depth_jump_to_parent").unwrap_or(true);
+        let relative_paths = bool_field(&root, "relative_paths").unwrap_or(false);
+        let cd_on_quit = bool_field(&root, "cd_on_quit").unwrap_or(false);
+        let two_line_status = bool_field(&root, "two_line_status").unwrap_or(false);
+        let terminal_title = bool_field(&root, "terminal_title").unwrap_or(false);
+        let stack_breadcrumb = bool_field(&root, "stack_breadcrumb").unwrap_or(false);
+        let max_nesting_depth = usize_field(&root, "max_nesting_depth");
+        let watch_changes = bool_field(&root, "watch_changes").unwrap_or(true);
+        let sort = match string_field(&root, "sort") {
+            None => Sort::Name,
+            Some(ref s) if s == "name" => Sort::Name,
+            Some(ref s) if s == "none" => Sort::None,
+            Some(s) => {
+                return Err(ConfError::InvalidValue {
+                    key: "sort".to_string(),
+                    bad: s,
+                    valid: "name, none".to_string(),
+                });
+            }
+        };
+        let dirs_first = bool_field(&root, "dirs_first").unwrap_or(false);
+        let mut special_paths = Vec::new();
+        if let Some(Value::Array(special_paths_value)) = &root.get("special_paths") {
+            for special_path_value in special_paths_value.iter() {
+                special_paths.push(read_special_path(special_path_value)?);
+            }
+        }
+        // reading the chords
+        let mut chords = Chords::default();
+        chords.leader = char_field(&root, "leader_key")?;
+        if let Some(Value::Table(chords_tbl)) = &root.get("chords") {
+            for (k, v) in chords_tbl.iter() {
+                let mut chars = k.chars();
+                let c = match (chars.next(), chars.next(), v.as_str()) {
+                    (Some(c), None, Some(_)) => c,
+                    _ => {
+                        return Err(ConfError::InvalidValue {
+                            key: format!("chords.{}", k),
+                            bad: v.to_string(),
+                            valid: "a verb invocation, for a single char key".to_string(),
+                        });
+                    }
+                };
+                chords.verbs.insert(c, v.as_str().unwrap().to_string());
+            }
         }
         // reading the skin
+        let skin_palette = match string_field(&root, "skin_palette") {
+            None => None,
+            Some(ref s) if s == "dark" => Some(Palette::Dark),
+            Some(ref s) if s == "light" => Some(Palette::Light),
+            Some(s) => {
+                return Err(ConfError::InvalidValue {
+                    key: "skin_palette".to_string(),
+                    bad: s,
+                    valid: "dark, light".to_string(),
+                });
+            }
+        };
         let mut skin_entries = HashMap::new();
         if let Some(Value::Table(entries_tbl)) = &root.get("skin") {
             for (k, v) in entries_tbl.iter() {
@@ -144,6 +482,38 @@ impl Conf {
         Ok(Conf {
             verbs,
             skin_entries,
+            skin_palette,
+            size_display,
+            size_bar_base,
+            size_bar,
+            size_percent,
+            date_format,
+            file_opener,
+            enter_on_file,
+            paste_conflict,
+            show_parent_line,
+            flatten_chains,
+            show_fs_info,
+            read_xattrs,
+            detect_git_repos,
+            detect_collisions,
+            lookup_users,
+            max_children,
+            max_children_shown,
+            balance,
+            max_visible_depth,
+            special_paths,
+            sort,
+            dirs_first,
+            chords,
+            same_depth_jump_to_parent,
+            relative_paths,
+            cd_on_quit,
+            two_line_status,
+            terminal_title,
+            stack_breadcrumb,
+            max_nesting_depth,
+            watch_changes,
         })
     }
 }
@@ -156,6 +526,161 @@ const DEFAULT_CONF_FILE: &str = r#"
 # Configuration documentation is available at https://dystroy.org/broot
 #
 
+###############################
+# display settings
+# (they must be set before the [[verbs]] and [skin] sections)
+
+# sizes are displayed either in a small "cell" (default) or in
+# a wider "bar"
+#size_display = "bar"
+#size_bar_width = {size_bar_width}
+
+# the size bars show the part of the parent directory (default),
+# or the part of the whole tree
+#size_bar_base = "total"
+
+# without the bars, the sizes are only written, in the size_text color
+#size_bar = false
+
+# sizes may be written as percentages of the size of the parent
+# directory (:toggle_percent changes it)
+#size_percent = true
+
+# the dates of last modification (shown with :toggle_dates) are
+# formatted with a strftime format, or "relative", like "3h ago"
+#date_format = "%Y-%m-%d %H:%M"
+
+# a ".." line under the root lets you go up with <enter>
+#show_parent_line = false
+
+# a directory whose only child is a directory may be shown on the
+# same line, as in "src/main/java/com/example"
+#flatten_chains = true
+
+# the free space of the filesystem containing the root is shown
+# at the bottom right, unless you set (:toggle_fs_info changes it)
+#show_fs_info = false
+
+# only the first entries of a directory are read, the other
+# ones being listed with :all
+#max_children = {max_children}
+
+# when there's no pattern, the lines of the screen are shared evenly
+# between the directories of a level, unless you prefer them shared
+# in proportion of their numbers of entries ("proportional"). You may
+# also limit the number of entries shown in a directory
+#balance = "proportional"
+#max_children_shown = 40
+
+# the branches of deep entries take at most half the width of the
+# terminal, their first levels being collapsed into a '…'. You may
+# collapse them sooner by setting the number of levels drawn
+#max_visible_depth = 12
+
+# when permissions are shown, a '+', '.' or '@' after the mode tells
+# the file has an ACL, a SELinux context or other extended attributes.
+# Reading them may be slow on network filesystems, you may disable it
+#read_extended_attributes = false
+
+# the directories holding a .git entry are marked with a ±. It's one
+# more lookup per directory, you may disable it on slow network mounts
+#detect_git_repositories = false
+
+# sibling names which are the same once their case and their accents
+# are normalized (eg "Readme" and "README") may be marked with a '≈'.
+# It's disabled by default, as it costs some CPU in huge directories
+#detect_collisions = true
+
+# the names of the users and groups owning the files are read from the
+# system databases, which may hang with a broken NSS/LDAP setup. When
+# disabled, the owners are shown as numeric ids (like with --no-users)
+#lookup_users = false
+
+# files are opened according to OS settings, unless you
+# define the command opening them
+#file_opener = "$EDITOR {file}"
+
+# hitting <enter> on a file opens it then quits broot ("open_leave",
+# default), unless you prefer broot to stay ("open_stay"), waiting
+# for the end of the program when it's a terminal one, or to open
+# the files only when it quits ("open_later")
+#enter_on_file = "open_stay"
+
+# when a pasted file has the name of an existing one, broot
+# either asks you to use :paste! to overwrite it ("ask", default)
+# or adds a numeric suffix to the new name ("rename")
+#paste_conflict = "rename"
+
+# the default colors are chosen for a dark or light background
+# according to the COLORFGBG env variable. You may force them
+# ("dark" or "light"), the [skin] section overriding them
+#skin_palette = "light"
+
+# the entries of a directory are sorted by name, "img2" coming
+# before "img10", unless you prefer the order in which the system
+# lists them ("none"). Directories may be shown first
+#sort = "none"
+#dirs_first = true
+
+# when there's no next line at the same depth, :next_same_depth
+# (alt-down) goes to the next sibling of the parent, unless you set
+#same_depth_jump_to_parent = false
+
+# the paths printed by :print_path, or given to your shell (as by :cd),
+# may be written relative to the directory broot was launched in
+#relative_paths = true
+
+# when broot is launched with the br function, quitting it without
+# a verb (eg with :quit, or <esc> in the first screen) may change
+# your shell's directory to the last displayed root, like :cd does
+#cd_on_quit = true
+
+# the status line is cut with a '…' when it's too long. Long errors may
+# instead take two lines, the upper one hiding the last line of the tree
+#two_line_status = true
+
+# the title of the terminal may tell the root broot displays, eg
+# "broot: ~/work/proj", the previous title being restored on quit
+#terminal_title = true
+
+# when you focused several directories, the end of the root line may
+# recall their names, eg "proj ▸ src ▸ widgets (3)", :back going
+# through them in reverse order
+#stack_breadcrumb = true
+
+# a broot launched from a shell launched by broot tells it's nested.
+# You may also limit the number of broots running one in another
+#max_nesting_depth = 2
+
+# every few seconds, broot checks whether the root directory changed
+# since the tree was read, and then tells the view is outdated. You may
+# disable it on filesystems where the stat calls are expensive
+#watch_changes = false
+
+# two-key sequences, typed when the input is empty, may execute
+# verbs: uncomment the leader_key and the [chords] section below
+# to have for example "g" then "h" toggling the hidden files
+#leader_key = "g"
+
+###############################
+# chords (needing a leader_key)
+#[chords]
+#h = "toggle_hidden"
+#p = "parent"
+
+###############################
+# display options of some roots, applied when they're opened
+# ("**" matching the subdirectories too)
+#[[special_paths]]
+#path = "~/media"
+#name = "media"
+#show_sizes = true
+#
+#[[special_paths]]
+#path = "~/code/**"
+#gitignore = "yes"
+#show_sizes = false
+
 ###############################
 # shortcuts for built-in verbs:
 
@@ -163,6 +688,13 @@ const DEFAULT_CONF_FILE: &str = r#"
 invocation = "p"
 execution = ":parent"
 
+# a verb with a key ("alt-<char>", "ctrl-<letter>" or "F1" to "F12")
+# can be executed with this key, even while a pattern is typed
+#[[verbs]]
+#invocation = "hidden"
+#key = "alt-h"
+#execution = ":toggle_hidden"
+
 #####################
 # user defined verbs:
 
@@ -171,18 +703,139 @@ execution = ":parent"
 #  pattern.
 # Example:
 #  execution = "/usr/bin/nvim {file}"
+# The description is shown in the help and while the verb is typed.
 [[verbs]]
 invocation = "edit"
 shortcut = "e"
 execution = "$EDITOR {file}"
+description = "edit the file in $EDITOR"
 
 [[verbs]]
 invocation = "create {subpath}"
 execution = "$EDITOR {directory}/{subpath}"
+description = "create a file and edit it in $EDITOR"
 
 [[verbs]]
 name = "view"
 invocation = "view"
 execution = "less {file}"
+description = "view the file with less"
+
+# a verb with confirm = true must be confirmed with a 'y'
+#  before it's executed
+#[[verbs]]
+#invocation = "trash"
+#execution = "gio trash {file}"
+#leave_broot = false
+#confirm = true
 
 "#;
+
+/// the content of the configuration file written on first launch: the
+///  documented template, with the default values of the settings and
+///  of the skin entries
+pub fn sample() -> String {
+    let mut sample = DEFAULT_CONF_FILE
+        .replace("{size_bar_width}", &DEFAULT_SIZE_BAR_WIDTH.to_string())
+        .replace("{max_children}", &DEFAULT_MAX_CHILDREN.to_string());
+    sample.push_str(
+        "\n###############################\n\
+        # skin\n\
+        # Uncomment the [skin] line and the entries you want to change.\n\
+        # The values below are the default ones for a dark background.\n\
+        # Colors are names (eg \"yellow\"), grayscale levels from 0 to 23\n\
+        # (eg \"grayscale(12)\") or \"none\".\n\
+        #[skin]\n",
+    );
+    for (key, value) in Skin::default_conf_entries(Palette::Dark) {
+        if let Some(value) = value {
+            sample.push_str(&format!("#{} = \"{}\"\n", key, value));
+        }
+    }
+    sample
+}
+
+#[cfg(test)]
+mod sample_tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn defaults_are_written() {
+        let sample = sample();
+        assert!(!sample.contains("{size_bar_width}"));
+        assert!(sample.contains(&format!("#max_children = {}", DEFAULT_MAX_CHILDREN)));
+        assert!(sample.contains("#directory_fg = \"LightBlue\""));
+    }
+
+    #[test]
+    fn bad_special_paths() {
+        let dir = tempdir().unwrap();
+        let conf_path = dir.path().join("conf.toml");
+        let read = |entry: &str| {
+            fs::write(&conf_path, format!("[[special_paths]]\n{}", entry)).unwrap();
+            Conf::from_file(&conf_path)
+        };
+        let conf = read("path = \"~/media\"\nshow_sizes = true").unwrap();
+        assert_eq!(conf.special_paths[0].name, "~/media");
+        assert_eq!(conf.special_paths[0].show_sizes, Some(true));
+        assert!(read("show_sizes = true").is_err());
+        assert!(read("path = \"/tmp\"\nsort = \"date\"").is_err());
+        assert!(read("path = \"/tmp\"\nshow_sizes = \"yes\"").is_err());
+        assert!(read("path = \"/tmp\"\nsizes = true").is_err());
+        assert!(read("path = \"/tmp/[\"").is_err());
+    }
+
+    #[test]
+    fn verb_descriptions() {
+        let dir = tempdir().unwrap();
+        let conf_path = dir.path().join("conf.toml");
+        let conf = "[[verbs]]\ninvocation = \"tidy\"\nexecution = \"tidy {file}\"\n\
+            description = \"fix the indentation\"\n\n\
+            [[verbs]]\ninvocation = \"tig\"\nexecution = \"tig\"\n";
+        fs::write(&conf_path, conf).unwrap();
+        let conf = Conf::from_file(&conf_path).unwrap();
+        let descriptions: Vec<Option<&str>> =
+            conf.verbs.iter().map(|v| v.description.as_ref().map(|s| s.as_str())).collect();
+        assert_eq!(descriptions, vec![Some("fix the indentation"), None]);
+        // the sample describes its verbs
+        Conf::write_sample(&conf_path).unwrap();
+        let conf = Conf::from_file(&conf_path).unwrap();
+        let edit = conf.verbs.iter().find(|v| v.invocation == "edit").unwrap();
+        assert_eq!(edit.description.as_ref().unwrap(), "edit the file in $EDITOR");
+    }
+
+    #[test]
+    fn sample_is_valid() {
+        let dir = tempdir().unwrap();
+        let conf_path = dir.path().join("conf.toml");
+        Conf::write_sample(&conf_path).unwrap();
+        let conf = Conf::from_file(&conf_path).unwrap();
+        assert!(!conf.verbs.is_empty());
+        assert!(conf.skin_entries.is_empty());
+        // with the skin section uncommented, all entries are still valid
+        let sample = sample();
+        let uncommented: Vec<&str> = sample
+            .lines()
+            .map(|line| {
+                let is_skin_line = line == "#[skin]"
+                    || line.contains("_fg = \"")
+                    || line.contains("_bg = \"");
+                if is_skin_line {
+                    &line[1..]
+                } else {
+                    line
+                }
+            })
+            .collect();
+        fs::write(&conf_path, uncommented.join("\n")).unwrap();
+        let conf = Conf::from_file(&conf_path).unwrap();
+        assert_eq!(
+            conf.skin_entries.len(),
+            Skin::default_conf_entries(Palette::Dark)
+                .iter()
+                .filter(|(_, value)| value.is_some())
+                .count(),
+        );
+    }
+}
@@ -0,0 +1,82 @@
+//! the formatting of the dates of last modification, either with a
+//!  strftime format, or relative to the time the tree was built
+
+use chrono::format::{Item, StrftimeItems};
+use chrono::{Local, TimeZone};
+
+use crate::tree_options::DateFormat;
+
+const MINUTE: i64 = 60;
+const HOUR: i64 = 60 * MINUTE;
+const DAY: i64 = 24 * HOUR;
+const YEAR: i64 = 365 * DAY;
+
+/// the width of the widest relative date, like "364d ago", so that
+///  the column doesn't change when the tree is refreshed
+pub const RELATIVE_DATE_WIDTH: usize = 8;
+
+/// tell whether the strftime format can be used
+pub fn is_valid_format(format: &str) -> bool {
+    StrftimeItems::new(format).all(|item| item != Item::Error)
+}
+
+// the time elapsed between date and now, with a coarse precision.
+// Dates in the future (the clock of another computer may be ahead)
+//  are shown as now.
+fn relative(date: i64, now: i64) -> String {
+    let elapsed = now - date;
+    if elapsed < MINUTE {
+        "now".to_string()
+    } else if elapsed < HOUR {
+        format!("{}m ago", elapsed / MINUTE)
+    } else if elapsed < DAY {
+        format!("{}h ago", elapsed / HOUR)
+    } else if elapsed < YEAR {
+        format!("{}d ago", elapsed / DAY)
+    } else {
+        format!("{}y ago", elapsed / YEAR)
+    }
+}
+
+/// format the date (in seconds since the epoch), now being the time
+///  the relative dates refer to
+pub fn format_date(date: i64, now: i64, format: &DateFormat) -> String {
+    match format {
+        DateFormat::Strftime(format) => match Local.timestamp_opt(date, 0).single() {
+            Some(date) => date.format(format).to_string(),
+            None => "?".to_string(), // out of the range of chrono
+        },
+        DateFormat::Relative => relative(date, now),
+    }
+}
+
+#[cfg(test)]
+mod dates_tests {
+    use super::*;
+
+    #[test]
+    fn relative_dates() {
+        let now = 1_500_000_000;
+        assert_eq!(relative(now, now), "now");
+        assert_eq!(relative(now - 59, now), "now");
+        assert_eq!(relative(now - 60, now), "1m ago");
+        assert_eq!(relative(now - 3 * HOUR - 59, now), "3h ago");
+        assert_eq!(relative(now - DAY + 1, now), "23h ago");
+        assert_eq!(relative(now - DAY, now), "1d ago");
+        assert_eq!(relative(now - YEAR + 1, now), "364d ago");
+        assert_eq!(relative(now - 2 * YEAR, now), "2y ago");
+        // a clock skew mustn't give negative durations
+        assert_eq!(relative(now + 3 * HOUR, now), "now");
+        assert!(relative(now - YEAR + 1, now).len() <= RELATIVE_DATE_WIDTH);
+    }
+
+    #[test]
+    fn strftime_formats() {
+        assert!(is_valid_format("%Y-%m-%d %H:%M"));
+        assert!(!is_valid_format("%Y-%"));
+        let date = Local.ymd(2019, 12, 31).and_hms(23, 59, 0).timestamp();
+        let format = DateFormat::Strftime("%Y-%m-%d %H:%M".to_string());
+        assert_eq!(format_date(date, date, &format), "2019-12-31 23:59");
+        assert_eq!(format_date(i64::MAX, date, &format), "?");
+    }
+}
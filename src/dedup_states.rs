@@ -0,0 +1,351 @@
+//! an application state listing the groups of files having the
+//!  same content under a directory, from which the duplicates can be
+//!  removed or replaced by hard links
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::app::{AppState, AppStateCmdResult};
+use crate::app_context::AppContext;
+use crate::commands::{Action, Command};
+use crate::duplicates::{self, DedupJob, DupGroup};
+use crate::external;
+use crate::file_sizes::Size;
+use crate::help_states::HelpState;
+use crate::navigation;
+use crate::permissions::ChmodArgs;
+use crate::screens::Screen;
+use crate::status::Status;
+use crate::task_sync::{RefreshRequest, TaskLifetime};
+use crate::verb_store::PrefixSearchResult;
+use crate::verbs::VerbExecutor;
+use crate::yank::{self, Yank, YankMode};
+
+// the width of the column telling the number of files and their size
+const GROUP_WIDTH: usize = 12;
+
+pub struct DedupState {
+    root: PathBuf,
+    job: DedupJob,
+    selection: usize, // index among the files of all groups
+    scroll: usize,    // number of files hidden at the top
+    refresh: RefreshRequest, // whether the removed files must be looked for
+}
+
+impl DedupState {
+    pub fn new(root: PathBuf, dev: Option<u64>) -> DedupState {
+        DedupState {
+            job: DedupJob::new(root.clone(), dev),
+            root,
+            selection: 0,
+            scroll: 0,
+            refresh: RefreshRequest::new(),
+        }
+    }
+    // the number of files we can show, below the header
+    fn page_height(screen: &Screen) -> usize {
+        (screen.h as usize).saturating_sub(3).max(1)
+    }
+    // the listed files, as (index of the group, index in the group)
+    fn rows(&self) -> Vec<(usize, usize)> {
+        self.job
+            .groups
+            .iter()
+            .enumerate()
+            .flat_map(|(g, group)| (0..group.files.len()).map(move |f| (g, f)))
+            .collect()
+    }
+    fn selected_row(&self) -> Option<(usize, usize)> {
+        self.rows().get(self.selection).cloned()
+    }
+    pub fn selected_file(&self) -> Option<&Path> {
+        self.selected_row()
+            .map(|(g, f)| self.job.groups[g].files[f].path.as_path())
+    }
+    fn make_selection_visible(&mut self, page_height: usize) {
+        if self.selection < self.scroll {
+            self.scroll = self.selection;
+        } else if self.selection >= self.scroll + page_height {
+            self.scroll = self.selection + 1 - page_height;
+        }
+    }
+    // remove the files which don't exist anymore, and the groups
+    //  which aren't made of duplicates anymore
+    fn remove_missing(&mut self) {
+        for group in &mut self.job.groups {
+            group.files.retain(|file| fs::symlink_metadata(&file.path).is_ok());
+        }
+        self.job.groups.retain(|group| group.files.len() > 1);
+        let nb_rows = self.rows().len();
+        if self.selection >= nb_rows {
+            self.selection = nb_rows.saturating_sub(1);
+        }
+    }
+    /// replace the selected file by a hard link to another file of
+    ///  its group, so that their content is stored only once
+    pub fn hard_link_selection(&mut self) -> AppStateCmdResult {
+        if !self.job.is_done() {
+            return AppStateCmdResult::DisplayError(
+                "wait for the end of the search before linking".to_string(),
+            );
+        }
+        let (g, f) = match self.selected_row() {
+            Some(row) => row,
+            None => {
+                return AppStateCmdResult::DisplayError("no selected file".to_string());
+            }
+        };
+        let group = &self.job.groups[g];
+        let path = group.files[f].path.clone();
+        let target = group.files[if f == 0 { 1 } else { 0 }].path.clone();
+        if let Err(e) = duplicates::hard_link(&path, &target) {
+            return AppStateCmdResult::DisplayError(format!("hard link failed: {}", e));
+        }
+        let group = &mut self.job.groups[g];
+        group.files.remove(f);
+        if let Some(file) = group.files.iter_mut().find(|file| file.path == target) {
+            file.nb_links += 1;
+        }
+        // the group is removed if it's not made of duplicates anymore
+        self.remove_missing();
+        AppStateCmdResult::DisplayMessage(format!(
+            "{} is now a link to {}",
+            path.to_string_lossy(),
+            target.to_string_lossy(),
+        ))
+    }
+    /// change the mode of the selected file
+    pub fn chmod(&mut self, args: &Option<String>) -> AppStateCmdResult {
+        let path = match self.selected_file() {
+            Some(path) => path,
+            None => {
+                return AppStateCmdResult::DisplayError("no selected file".to_string());
+            }
+        };
+        // the listed entries are files, so -R changes nothing
+        let result = ChmodArgs::from(args)
+            .map_err(|e| e.to_string())
+            .and_then(|args| args.spec.apply(path).map_err(|e| format!("chmod failed: {}", e)));
+        match result {
+            Ok(()) => AppStateCmdResult::Keep,
+            Err(e) => AppStateCmdResult::DisplayError(e),
+        }
+    }
+    /// remember the selected file, for a :paste in a tree
+    pub fn yank(&self, mode: YankMode) -> AppStateCmdResult {
+        match self.selected_file() {
+            Some(path) => {
+                yank::set(Yank {
+                    mode,
+                    paths: vec![path.to_path_buf()],
+                });
+                AppStateCmdResult::Keep
+            }
+            None => AppStateCmdResult::DisplayError("no selected file".to_string()),
+        }
+    }
+    /// tell the size of the selected file
+    pub fn size(&self) -> AppStateCmdResult {
+        match self.selected_row() {
+            Some((g, f)) => AppStateCmdResult::DisplayMessage(format!(
+                "{} : {}",
+                self.job.groups[g].files[f].path.to_string_lossy(),
+                Size::from(self.job.groups[g].size).to_string(),
+            )),
+            None => AppStateCmdResult::DisplayError("no selected file".to_string()),
+        }
+    }
+    fn write_group_head(&self, screen: &mut Screen, group: &DupGroup, first: bool) -> io::Result<()> {
+        if first {
+            write!(
+                screen.stderr,
+                "{}{:>3}× {:>6}  ",
+                screen.skin.size_text.fg,
+                group.files.len(),
+                Size::from(group.size).to_string(),
+            )
+        } else {
+            write!(
+                screen.stderr,
+                "{}{:>width$}  ",
+                screen.skin.tree.fg,
+                "│",
+                width = GROUP_WIDTH - 2,
+            )
+        }
+    }
+}
+
+impl AppState for DedupState {
+    fn apply(
+        &mut self,
+        cmd: &mut Command,
+        screen: &mut Screen,
+        con: &AppContext,
+    ) -> io::Result<AppStateCmdResult> {
+        let page_height = DedupState::page_height(screen);
+        if let Some(selection) =
+            navigation::move_in_list(self.selection, self.rows().len(), page_height, &cmd.action)
+        {
+            self.selection = selection;
+            self.make_selection_visible(page_height);
+            return Ok(AppStateCmdResult::Keep);
+        }
+        Ok(match &cmd.action {
+            Action::Back => AppStateCmdResult::PopState,
+            Action::OpenSelection => match self.selected_file() {
+                Some(path) if con.launch_args.picker => external::pick(path, con),
+                Some(path) => external::open_file(path.to_path_buf(), con.enter_on_file, con)?,
+                None => AppStateCmdResult::DisplayError("no selected file".to_string()),
+            },
+            Action::Verb(invocation) => match con.verb_store.search(&invocation.key) {
+                PrefixSearchResult::Match(verb) => {
+                    let invocation = invocation.clone();
+                    self.execute_or_confirm(verb, &invocation, cmd, screen, con)?
+                }
+                _ => AppStateCmdResult::verb_not_found(&invocation.key),
+            },
+            Action::Help => {
+                AppStateCmdResult::NewState(Box::new(HelpState::new(screen)), Command::new())
+            }
+            Action::Quit => AppStateCmdResult::Quit,
+            _ => self.apply_verb_action(cmd, screen, con)?,
+        })
+    }
+
+    fn refresh(&mut self, _screen: &Screen, _con: &AppContext) -> Command {
+        // an external verb, like rm, may have removed files
+        self.refresh.request();
+        Command::new()
+    }
+
+    fn has_pending_tasks(&self) -> bool {
+        self.refresh.is_requested() || !self.job.is_done()
+    }
+
+    fn do_pending_task(&mut self, _screen: &mut Screen, tl: &TaskLifetime) {
+        if self.refresh.is_due() {
+            self.remove_missing();
+            self.refresh.done();
+        } else if !self.job.is_done() {
+            self.job.advance(tl);
+        } else {
+            self.refresh.wait();
+        }
+    }
+
+    fn display(&mut self, screen: &mut Screen, _con: &AppContext) -> io::Result<()> {
+        if screen.is_too_small() {
+            return screen.write_too_small();
+        }
+        let page_height = DedupState::page_height(screen);
+        self.make_selection_visible(page_height);
+        let wasted: u64 = self.job.groups.iter().map(DupGroup::wasted).sum();
+        write!(
+            screen.stderr,
+            "{}{}{}{} group{} of identical files under {}, {} could be freed{}",
+            screen.goto(1, 1),
+            screen.skin.tree_summary.fg,
+            screen.skin.tree_summary.bg,
+            self.job.groups.len(),
+            if self.job.groups.len() == 1 { "" } else { "s" },
+            self.root.to_string_lossy(),
+            Size::from(wasted).to_string(),
+            termion::clear::UntilNewline,
+        )?;
+        let rows = self.rows();
+        let path_width = (screen.w as usize).saturating_sub(GROUP_WIDTH);
+        for i in 0..page_height {
+            let y = i as u16 + 2;
+            write!(screen.stderr, "{}{}", screen.goto(1, y), screen.skin.reset.fgbg())?;
+            let (g, f) = match rows.get(self.scroll + i) {
+                Some(&row) => row,
+                None => {
+                    write!(screen.stderr, "{}", termion::clear::CurrentLine)?;
+                    continue;
+                }
+            };
+            if self.scroll + i == self.selection {
+                write!(screen.stderr, "{}", screen.skin.selected_line.bg)?;
+            }
+            let group = &self.job.groups[g];
+            self.write_group_head(screen, group, f == 0)?;
+            let file = &group.files[f];
+            let path = file.path.strip_prefix(&self.root).unwrap_or(&file.path);
+            let mut text = path.to_string_lossy().to_string();
+            if file.nb_links > 1 {
+                text.push_str(&format!(" (+{} links)", file.nb_links - 1));
+            }
+            let text: String = text.chars().take(path_width).collect();
+            write!(
+                screen.stderr,
+                "{}{}{}{}",
+                screen.skin.file.fg,
+                text,
+                termion::clear::UntilNewline,
+                screen.skin.reset.fgbg(),
+            )?;
+        }
+        screen.stderr.flush()
+    }
+
+    fn write_status(&self, screen: &mut Screen, cmd: &Command, con: &AppContext) -> io::Result<()> {
+        if let (Action::Confirm(invocation), Some(path)) = (&cmd.action, self.selected_file()) {
+            return screen.write_status_err(&con.verb_store.confirmation_status(
+                invocation,
+                path,
+                &self.root,
+                con.relative_base(),
+            ));
+        }
+        if let Action::VerbEdit(invocation) = &cmd.action {
+            return match self.selected_file() {
+                Some(path) => match con.verb_store.edition_status(
+                    invocation,
+                    path,
+                    &self.root,
+                    con.relative_base(),
+                ) {
+                    Ok(status) => screen.write_status_text(&status),
+                    Err(status) => screen.write_status_err(&status),
+                },
+                None => screen.write_status_err("No selected file"),
+            };
+        }
+        if self.job.is_walking() {
+            return screen.write_status_text(&format!(
+                "Looking for duplicates: {} files found, hit {} to cancel",
+                self.job.nb_files(),
+                con.verb_store.hint_for("back"),
+            ));
+        }
+        if !self.job.is_done() {
+            return screen.write_status_text(&format!(
+                "Comparing the files of the same size: {} of {} sizes, hit {} to cancel",
+                self.job.nb_checked,
+                self.job.nb_size_groups(),
+                con.verb_store.hint_for("back"),
+            ));
+        }
+        if self.job.groups.is_empty() {
+            return screen.write_status_text(&format!(
+                "No duplicate found, hit {} to go back",
+                con.verb_store.hint_for("back"),
+            ));
+        }
+        screen.write_status_text(&format!(
+            "Type {} to replace the file by a link to an identical one, or {} to remove it",
+            con.verb_store.hint_for("hardlink"),
+            con.verb_store.hint_for("rm"),
+        ))
+    }
+
+    fn write_flags(&self, _screen: &mut Screen, _con: &AppContext) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn selected_path(&self) -> Option<PathBuf> {
+        self.selected_file().map(Path::to_path_buf)
+    }
+}
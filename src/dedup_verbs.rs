@@ -0,0 +1,117 @@
+use std::io;
+
+use crate::app::AppStateCmdResult;
+use crate::app_context::AppContext;
+use crate::browser_states::BrowserState;
+use crate::browser_verbs;
+use crate::build_info;
+use crate::commands::Command;
+use crate::dedup_states::DedupState;
+use crate::external;
+use crate::filesystems_states::FilesystemsState;
+use crate::help_states::HelpState;
+use crate::internals::Internal;
+use crate::screens::Screen;
+use crate::task_sync::TaskLifetime;
+use crate::verb_invocation::VerbInvocation;
+use crate::verbs::{Verb, VerbExecution, VerbExecutor};
+use crate::yank::YankMode;
+
+impl VerbExecutor for DedupState {
+    fn execute_verb(
+        &mut self,
+        verb: &Verb,
+        invocation: &VerbInvocation,
+        screen: &mut Screen,
+        con: &AppContext,
+    ) -> io::Result<AppStateCmdResult> {
+        if let Some(err) = verb.match_error(invocation) {
+            return Ok(AppStateCmdResult::DisplayError(err));
+        }
+        let path = match self.selected_file() {
+            Some(path) => path.to_path_buf(),
+            None => {
+                return Ok(AppStateCmdResult::DisplayError("no selected file".to_string()));
+            }
+        };
+        let internal = match &verb.execution {
+            VerbExecution::Internal(internal) => *internal,
+            VerbExecution::External(_) => {
+                let dir = path.parent().unwrap_or(&path).to_path_buf();
+                return verb.to_cmd_result(&path, &dir, invocation, screen, con);
+            }
+        };
+        Ok(match internal {
+            Internal::Back => AppStateCmdResult::PopState,
+            Internal::Chmod => self.chmod(&invocation.args),
+            Internal::Cp => self.yank(YankMode::Copy),
+            Internal::Dedup => AppStateCmdResult::Keep,
+            Internal::EditConf => external::edit_conf()?,
+            Internal::Filesystems => FilesystemsState::new_state_cmd_result(),
+            Internal::Focus | Internal::Parent => {
+                // the directory of the file, or a path relative to it
+                let args = match internal {
+                    Internal::Focus => &invocation.args,
+                    _ => &None,
+                };
+                let dir = match browser_verbs::focus_target(&path, args) {
+                    Ok(dir) => dir,
+                    Err(e) => return Ok(AppStateCmdResult::DisplayError(e)),
+                };
+                AppStateCmdResult::from_optional_state(
+                    BrowserState::new_root(
+                        dir,
+                        con.launch_args.tree_options.without_pattern(),
+                        con,
+                        screen,
+                        &TaskLifetime::unlimited(),
+                    ),
+                    Command::new(),
+                )
+            }
+            Internal::Hardlink => self.hard_link_selection(),
+            Internal::Help => {
+                AppStateCmdResult::NewState(Box::new(HelpState::new(screen)), Command::new())
+            }
+            Internal::Mv => self.yank(YankMode::Move),
+            Internal::Open | Internal::OpenLater | Internal::OpenLeave | Internal::OpenStay => {
+                external::open_file(path, internal, con)?
+            }
+            Internal::PrintPath => {
+                external::print_path(&path, con.relative_paths, con)?
+            }
+            Internal::PrintRelativePath => external::print_path(&path, true, con)?,
+            Internal::Quit => AppStateCmdResult::Quit,
+            Internal::Refresh => AppStateCmdResult::RefreshState,
+            Internal::Size => self.size(),
+            Internal::ToggleScreen => AppStateCmdResult::ShowTerminal,
+            Internal::Version => AppStateCmdResult::DisplayMessage(build_info::one_line()),
+            // the verbs which only make sense on the lines of a tree
+            Internal::All
+            | Internal::GotoLine
+            | Internal::Hints
+            | Internal::Mkdir
+            | Internal::NextSameDepth
+            | Internal::Paste
+            | Internal::PasteOverwrite
+            | Internal::PreviousSameDepth
+            | Internal::Touch
+            | Internal::TotalSearch => AppStateCmdResult::DisplayError(format!(
+                ":{} isn't available in the duplicates list",
+                verb.invocation.key,
+            )),
+            // the display options are changed in the previous state
+            Internal::ToggleDates
+            | Internal::ToggleFiles
+            | Internal::ToggleFsInfo
+            | Internal::ToggleGitIgnore
+            | Internal::ToggleHidden
+            | Internal::ToggleLineNumbers
+            | Internal::ToggleOwner
+            | Internal::TogglePercent
+            | Internal::TogglePerm
+            | Internal::ToggleSizes
+            | Internal::ToggleTrimRoot => AppStateCmdResult::PopStateAndReapply,
+        })
+    }
+}
@@ -0,0 +1,372 @@
+//! the search of the files having the same content under a directory.
+//! The files are grouped by size, then by a hash of their content, and
+//!  the ones whose hashes are equal are finally compared byte to byte.
+//! Hard links to the same inode are found once, as they share their
+//!  content and don't take more space.
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{Duration, Instant};
+
+use crate::task_sync::TaskLifetime;
+
+// the maximal duration of a step of the search
+const DEDUP_STEP: Duration = Duration::from_millis(200);
+
+// the size of the chunks in which files are read
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// a file found with the same content than other ones
+#[derive(Debug, Clone)]
+pub struct DupFile {
+    pub path: PathBuf,
+    pub nb_links: usize, // number of paths found for this inode
+    dev: u64,
+    ino: u64,
+}
+
+/// files having the same content
+#[derive(Debug)]
+pub struct DupGroup {
+    pub size: u64, // the size of one of the files
+    pub files: Vec<DupFile>,
+}
+
+impl DupGroup {
+    /// the space which would be freed by keeping only one of the files
+    pub fn wasted(&self) -> u64 {
+        self.size * (self.files.len() as u64 - 1)
+    }
+}
+
+/// the search of the duplicates, done in steps
+pub struct DedupJob {
+    dirs: Vec<PathBuf>, // the directories which must still be read
+    dev: Option<u64>,   // when set, the directories of other devices are ignored
+    files: Vec<DupFile>,
+    sizes: Vec<u64>,                  // the sizes of the files, by index
+    inodes: HashMap<(u64, u64), usize>, // the index of the file of each inode
+    size_groups: Vec<Vec<usize>>,     // the files of the same size, biggest first
+    hashes: HashMap<usize, u64>,      // the hashes computed until now, by file index
+    pub nb_checked: usize,            // number of size groups already checked
+    pub nb_errors: usize,             // number of directories or files which couldn't be read
+    pub groups: Vec<DupGroup>,        // the duplicates found until now
+}
+
+impl DedupJob {
+    pub fn new(root: PathBuf, dev: Option<u64>) -> DedupJob {
+        DedupJob {
+            dirs: vec![root],
+            dev,
+            files: Vec::new(),
+            sizes: Vec::new(),
+            inodes: HashMap::new(),
+            size_groups: Vec::new(),
+            hashes: HashMap::new(),
+            nb_checked: 0,
+            nb_errors: 0,
+            groups: Vec::new(),
+        }
+    }
+    /// whether the directories are still being read
+    pub fn is_walking(&self) -> bool {
+        !self.dirs.is_empty()
+    }
+    pub fn is_done(&self) -> bool {
+        !self.is_walking() && self.nb_checked == self.size_groups.len()
+    }
+    /// number of files found until now
+    pub fn nb_files(&self) -> usize {
+        self.files.len()
+    }
+    /// number of groups of files having the same size, which must be compared
+    pub fn nb_size_groups(&self) -> usize {
+        self.size_groups.len()
+    }
+    /// advance the search by a step, which ends after some time or when
+    ///  the lifetime expires. The next call resumes it.
+    pub fn advance(&mut self, tl: &TaskLifetime) {
+        let start = Instant::now();
+        while let Some(dir) = self.dirs.pop() {
+            if tl.is_expired() || start.elapsed() > DEDUP_STEP {
+                self.dirs.push(dir);
+                return;
+            }
+            self.read_dir(&dir);
+            if self.dirs.is_empty() {
+                self.make_size_groups();
+            }
+        }
+        while self.nb_checked < self.size_groups.len() {
+            if tl.is_expired() || start.elapsed() > DEDUP_STEP {
+                return;
+            }
+            let size_group = self.size_groups[self.nb_checked].clone();
+            match self.check_size_group(&size_group, tl) {
+                Some(groups) => {
+                    self.groups.extend(groups);
+                    self.nb_checked += 1;
+                }
+                None => {
+                    return; // interrupted, the group will be checked again
+                }
+            }
+        }
+    }
+    fn read_dir(&mut self, dir: &Path) {
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(_) => {
+                self.nb_errors += 1;
+                return;
+            }
+        };
+        for e in entries.flatten() {
+            let md = match e.metadata() {
+                Ok(md) => md,
+                Err(_) => {
+                    continue;
+                }
+            };
+            if md.is_dir() {
+                if self.dev.map_or(true, |dev| md.dev() == dev) {
+                    self.dirs.push(e.path());
+                }
+            } else if md.is_file() && md.len() > 0 {
+                let key = (md.dev(), md.ino());
+                match self.inodes.get(&key) {
+                    Some(&idx) => {
+                        self.files[idx].nb_links += 1;
+                    }
+                    None => {
+                        self.inodes.insert(key, self.files.len());
+                        self.files.push(DupFile {
+                            path: e.path(),
+                            nb_links: 1,
+                            dev: key.0,
+                            ino: key.1,
+                        });
+                        self.sizes.push(md.len());
+                    }
+                }
+            }
+        }
+    }
+    fn make_size_groups(&mut self) {
+        let mut by_size: HashMap<u64, Vec<usize>> = HashMap::new();
+        for (idx, size) in self.sizes.iter().enumerate() {
+            by_size.entry(*size).or_insert_with(Vec::new).push(idx);
+        }
+        self.size_groups = by_size.into_iter().map(|(_, g)| g).filter(|g| g.len() > 1).collect();
+        let sizes = &self.sizes;
+        self.size_groups.sort_by(|a, b| sizes[b[0]].cmp(&sizes[a[0]]));
+    }
+    // the groups of identical files among files having the same size,
+    //  or None if the lifetime expired
+    fn check_size_group(&mut self, size_group: &[usize], tl: &TaskLifetime) -> Option<Vec<DupGroup>> {
+        let mut by_hash: HashMap<u64, Vec<usize>> = HashMap::new();
+        for &idx in size_group {
+            let hash = match self.hashes.get(&idx) {
+                Some(&hash) => hash,
+                None => match hash_file(&self.files[idx].path, tl) {
+                    Ok(Some(hash)) => {
+                        self.hashes.insert(idx, hash);
+                        hash
+                    }
+                    Ok(None) => {
+                        return None;
+                    }
+                    Err(_) => {
+                        self.nb_errors += 1;
+                        continue;
+                    }
+                },
+            };
+            by_hash.entry(hash).or_insert_with(Vec::new).push(idx);
+        }
+        let size = self.sizes[size_group[0]];
+        let mut groups = Vec::new();
+        for candidates in by_hash.values().filter(|c| c.len() > 1) {
+            // the hash only tells which files may be identical
+            let mut classes: Vec<Vec<usize>> = Vec::new();
+            for &idx in candidates {
+                let mut class_idx = None;
+                for (i, class) in classes.iter().enumerate() {
+                    let first = &self.files[class[0]].path;
+                    match same_content(first, &self.files[idx].path, tl) {
+                        Ok(Some(true)) => {
+                            class_idx = Some(i);
+                            break;
+                        }
+                        Ok(Some(false)) => {}
+                        Ok(None) => {
+                            return None;
+                        }
+                        Err(_) => {
+                            self.nb_errors += 1;
+                        }
+                    }
+                }
+                match class_idx {
+                    Some(i) => classes[i].push(idx),
+                    None => classes.push(vec![idx]),
+                }
+            }
+            for class in classes.into_iter().filter(|c| c.len() > 1) {
+                groups.push(DupGroup {
+                    size,
+                    files: class.into_iter().map(|idx| self.files[idx].clone()).collect(),
+                });
+            }
+        }
+        Some(groups)
+    }
+}
+
+impl DupFile {
+    /// whether the path is a link to the same inode than this file
+    pub fn is_same_inode(&self, path: &Path) -> bool {
+        fs::metadata(path).map_or(false, |md| md.dev() == self.dev && md.ino() == self.ino)
+    }
+}
+
+// FNV-1a, a fast non cryptographic hash, which is enough to find the
+//  files which may be identical. Return None when the lifetime expires.
+fn hash_file(path: &Path, tl: &TaskLifetime) -> io::Result<Option<u64>> {
+    let mut file = File::open(path)?;
+    let mut buf = vec![0; CHUNK_SIZE];
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    loop {
+        if tl.is_expired() {
+            return Ok(None);
+        }
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            return Ok(Some(hash));
+        }
+        for b in &buf[..n] {
+            hash ^= u64::from(*b);
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
+
+// read as many bytes as possible, until the buffer is full or the
+//  end of the file
+fn read_chunk(file: &mut File, buf: &mut [u8]) -> io::Result<usize> {
+    let mut n = 0;
+    while n < buf.len() {
+        match file.read(&mut buf[n..])? {
+            0 => break,
+            r => n += r,
+        }
+    }
+    Ok(n)
+}
+
+// compare two files byte to byte. Return None when the lifetime expires.
+fn same_content(a: &Path, b: &Path, tl: &TaskLifetime) -> io::Result<Option<bool>> {
+    let mut fa = File::open(a)?;
+    let mut fb = File::open(b)?;
+    let mut buf_a = vec![0; CHUNK_SIZE];
+    let mut buf_b = vec![0; CHUNK_SIZE];
+    loop {
+        if tl.is_expired() {
+            return Ok(None);
+        }
+        let na = read_chunk(&mut fa, &mut buf_a)?;
+        let nb = read_chunk(&mut fb, &mut buf_b)?;
+        if na != nb || buf_a[..na] != buf_b[..nb] {
+            return Ok(Some(false));
+        }
+        if na == 0 {
+            return Ok(Some(true));
+        }
+    }
+}
+
+// a free hidden name next to the given path, for the link which
+//  will replace it
+fn link_path(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map_or(String::new(), |n| n.to_string_lossy().to_string());
+    let mut i = 0;
+    loop {
+        let candidate = path.with_file_name(format!(".{}.broot-link-{}-{}", name, process::id(), i));
+        if fs::symlink_metadata(&candidate).is_err() {
+            return candidate;
+        }
+        i += 1;
+    }
+}
+
+/// replace the file at path with a hard link to target, after a last
+///  check that they're still identical. The link is made aside, then
+///  renamed, so that the file is never missing, even if the link can't
+///  be made
+pub fn hard_link(path: &Path, target: &Path) -> io::Result<()> {
+    if same_content(path, target, &TaskLifetime::unlimited())? != Some(true) {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            "the files aren't identical anymore",
+        ));
+    }
+    let tmp = link_path(path);
+    fs::hard_link(target, &tmp).and_then(|_| fs::rename(&tmp, path)).map_err(|e| {
+        let _ = fs::remove_file(&tmp);
+        e
+    })
+}
+
+#[cfg(test)]
+mod duplicates_tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn identical_files_are_grouped() {
+        let dir = tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        fs::create_dir(root.join("sub")).unwrap();
+        fs::write(root.join("a"), "same content").unwrap();
+        fs::write(root.join("sub/b"), "same content").unwrap();
+        fs::write(root.join("c"), "same size!!!").unwrap(); // but not the same content
+        fs::write(root.join("d"), "other").unwrap();
+        fs::hard_link(root.join("a"), root.join("a_link")).unwrap();
+        let mut job = DedupJob::new(root.clone(), None);
+        while !job.is_done() {
+            job.advance(&TaskLifetime::unlimited());
+        }
+        assert_eq!(job.groups.len(), 1);
+        let group = &job.groups[0];
+        assert_eq!(group.size, 12);
+        assert_eq!(group.files.len(), 2);
+        assert_eq!(group.wasted(), 12);
+        // the hard link isn't a duplicate, only another path to a file
+        let a = group.files.iter().find(|f| f.is_same_inode(&root.join("a"))).unwrap();
+        assert_eq!(a.nb_links, 2);
+        assert!(group.files.iter().any(|f| f.path == root.join("sub/b")));
+    }
+
+    #[test]
+    fn only_identical_files_are_linked() {
+        let dir = tempdir().unwrap();
+        let root = dir.path();
+        fs::write(root.join("a"), "same content").unwrap();
+        fs::write(root.join("b"), "same content").unwrap();
+        fs::write(root.join("c"), "changed since").unwrap();
+        hard_link(&root.join("b"), &root.join("a")).unwrap();
+        let ino = |name: &str| fs::metadata(root.join(name)).unwrap().ino();
+        assert_eq!(ino("a"), ino("b"));
+        assert!(hard_link(&root.join("c"), &root.join("a")).is_err());
+        assert_ne!(ino("a"), ino("c"));
+        assert_eq!(fs::read_to_string(root.join("c")).unwrap(), "changed since");
+        // no temporary file is left
+        assert_eq!(fs::read_dir(root).unwrap().count(), 3);
+    }
+}
@@ -1,28 +1,44 @@
-//! Definitions of custom errors used in broot
+//! Definitions of custom errors used in broot. The ones of
+//!  the core are defined in their modules, and the ones the binary
+//!  deals with are exported here.
 use custom_error::custom_error;
 use opener;
-use regex;
 use std::io;
+use std::process::ExitStatus;
 
-custom_error! {pub TreeBuildError
-    NotADirectory { path: String } = "Not a directory: {}",
-    FileNotFound { path: String } = "File not found: {}",
-}
+pub use broot::tree_build::TreeBuildError;
+pub use broot::tree_options::OptionBoolParseError;
 
 custom_error! {pub ProgramError
     Io {source: io::Error} = "IO Error : {:?}",
     Conf {source: ConfError} = "Bad configuration: {}",
     ArgParse {bad: String, valid: String} = "{:?} can't be parsed (valid values: {:?})",
+    OptionBoolParse {source: OptionBoolParseError} = "{source}",
     TreeBuild {source: TreeBuildError} = "{}",
-    OpenError {err: opener::OpenError} = "{}",
+    OpenError {err: opener::OpenError} = @{
+        match err {
+            opener::OpenError::Io(source) => format!("Unable to open: {}", source),
+            _ => err.to_string(),
+        }
+    },
     LaunchError {program: String, source: io::Error} = "Unable to launch {program}: {source}",
+    ProgramFailed {program: String, status: ExitStatus} = @{
+        match status.code() {
+            Some(code) => format!("{} exited with status {}", program, code),
+            None => format!("{} was killed by a signal", program),
+        }
+    },
+    ExportError {path: String, reason: String} = "could not write selected path to {path}: {reason}",
+    NotATerminal = "broot needs a terminal: its interface is written on stderr, which isn't one",
+    TerminalLost = "the terminal went away",
+    NothingPicked = "no path was picked",
+    TooDeeplyNested {max: usize} = "you're already in a shell launched by broot, and max_nesting_depth ({max}) forbids launching another one",
+    ConflictingArgs {reason: String} = "Conflicting launch arguments: {reason}",
 }
 
-custom_error! {pub RegexError
-    Parsing {source: regex::Error} = @{
-        format!("Invalid Regular Expression: {}", source.to_string().lines().last().unwrap_or(""))
-    },
-    UnknownFlag {bad: char} = "Unknown regular expression flag: {:?}",
+custom_error! {pub ModeSpecError
+    Invalid { spec: String } = "invalid mode: {:?} (expected eg 644, +x or go-w)",
+    Missing = "usage: chmod [-R|-d] {{mode}}",
 }
 
 custom_error! {pub InvalidSkinError
@@ -38,4 +54,8 @@ custom_error! {pub ConfError
     InvalidSkinEntry{
         key:String, source: InvalidSkinError}   = "Invalid skin configuration for {}: {}",
     InvalidVerbInvocation{invocation: String}   = "invalid verb invocation: {}",
+    UnknownInternal{key: String}                = "unknown internal: {:?}",
+    InvalidValue{
+        key: String, bad: String, valid: String} = "invalid value for {key}: {bad:?} (valid values: {valid})",
+    InvalidSpecialPath{reason: String}          = "invalid [[special_paths]] entry: {reason}",
 }
@@ -1,33 +1,76 @@
 use std::env;
 use regex::Regex;
-use std::fs::OpenOptions;
+use std::fs::{self, OpenOptions};
 use std::io::{self, Write};
-use std::path::{Path, PathBuf};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Component, Path, PathBuf};
 use std::process::Command;
 use opener;
 
 use crate::app::AppStateCmdResult;
 use crate::app_context::AppContext;
-use crate::errors::ProgramError;
+use crate::cli;
+use crate::conf::Conf;
+use crate::errors::{ProgramError, TreeBuildError};
+use crate::internals::Internal;
 
 /// description of a possible launch of an external program
-/// A launchable can only be executed on end of life of broot.
+/// A launchable is executed on end of life of broot, unless it's
+///  a program or an opener launched by `:open_stay`. Several ones
+///  may be queued (eg with `:open_later`), and are then executed in order.
 #[derive(Debug)]
 pub enum Launchable {
-    Printer { // just print something on stderr on end of broot
+    Printer { // just print something on stdout on end of broot
         to_print: String,
     },
     Program { // execute an external program
         exe: String,
         args: Vec<String>,
+        working_dir: Option<PathBuf>, // captured at verb execution, as the states are gone on launch
+        env: Vec<(String, String)>,   // variables added to the environment of the program
     },
     SystemOpen { // open a path
         path: PathBuf,
     }
 }
 
+/// the variable telling the programs launched by broot how many broots
+///  they run in, so that a broot launched from them knows it's nested
+pub const DEPTH_ENV_VAR: &str = "BROOT_DEPTH";
+
+/// the number of broots running one in another, this one included
+pub fn nesting_depth() -> usize {
+    nesting_depth_in(env::var(DEPTH_ENV_VAR).ok().as_ref().map(|s| s.as_str()))
+}
+
+// the nesting depth of a broot whose environment gives this depth, the
+//  highest one staying the highest instead of wrapping to 0
+fn nesting_depth_in(outer: Option<&str>) -> usize {
+    let outer: Option<usize> = outer.and_then(|depth| depth.parse().ok());
+    outer.unwrap_or(0).saturating_add(1)
+}
+
+/// check that a broot at this nesting depth may start. The outermost
+///  one always may, even with a max of 0
+pub fn check_nesting_depth(depth: usize, max: Option<usize>) -> Result<(), ProgramError> {
+    match max {
+        Some(max) if depth > max.max(1) => Err(ProgramError::TooDeeplyNested { max }),
+        _ => Ok(()),
+    }
+}
+
+// expand the exe of a program like the root given on launch: a leading
+//  ~ is the home directory, and the $VAR or ${VAR} parts are replaced
+fn expand_exe(exe: &str) -> Result<String, TreeBuildError> {
+    cli::expand_path(exe).map(|path| path.to_string_lossy().to_string())
+}
+
+fn is_executable(path: &Path) -> bool {
+    fs::metadata(path).map_or(false, |md| md.is_file() && md.mode() & 0o111 != 0)
+}
+
 /// If s starts by a '$', replace it by the environment variable of the same name
-fn resolve_env_variable(s: String) -> String {
+pub fn resolve_env_variable(s: String) -> String {
     if s.starts_with('$') {
         env::var(&s[1..]).unwrap_or(s)
     } else {
@@ -46,35 +89,120 @@ impl Launchable {
             to_print
         }
     }
-    pub fn program(mut parts: Vec<String>) -> io::Result<Launchable> {
-        let mut parts = parts.drain(0..).map(resolve_env_variable);
+    pub fn program(
+        mut parts: Vec<String>,
+        working_dir: Option<PathBuf>,
+        env: Vec<(String, String)>,
+    ) -> io::Result<Launchable> {
+        let mut parts = parts.drain(0..);
         match parts.next() {
             Some(exe) => Ok(Launchable::Program {
-                exe,
-                args: parts.collect(),
+                exe: expand_exe(&exe).unwrap_or(exe),
+                args: parts.map(resolve_env_variable).collect(),
+                working_dir,
+                env,
             }),
             None => Err(io::Error::new(io::ErrorKind::Other, "Empty launch string")),
         }
     }
+    /// find the executable which would be launched for the exe of a
+    ///  program, or tell why there's none. A name without slash is
+    ///  searched in the directories of PATH.
+    pub fn resolve_program(exe: &str) -> Result<PathBuf, String> {
+        let path = PathBuf::from(expand_exe(exe).map_err(|e| e.to_string())?);
+        if path.components().count() > 1 {
+            return if is_executable(&path) {
+                Ok(path)
+            } else {
+                Err(format!("'{}' not found", exe))
+            };
+        }
+        env::var_os("PATH")
+            .and_then(|dirs| {
+                env::split_paths(&dirs)
+                    .map(|dir| dir.join(&path))
+                    .find(|candidate| is_executable(candidate))
+            })
+            .ok_or_else(|| format!("'{}' not found in PATH", exe))
+    }
+    // build the launchable opening a file, either with the file_opener
+    // defined in conf or according to OS settings
+    pub fn file_opener(path: PathBuf, con: &AppContext) -> io::Result<Launchable> {
+        match &con.file_opener {
+            Some(verb) => {
+                let dir = path.parent().unwrap_or(&path);
+                verb.to_launchable(&path, dir, &None)
+            }
+            None => Ok(Launchable::opener(path)),
+        }
+    }
+
+    /// tell whether broot must leave the terminal to the launchable while
+    ///  it runs, as a program may be a terminal one (eg vi)
+    pub fn needs_terminal(&self) -> bool {
+        match self {
+            Launchable::Program { .. } => true,
+            _ => false,
+        }
+    }
+
+    /// execute the launchable while broot goes on: there's no fallback
+    ///  printing the path when the system has no opener, and a program
+    ///  is told it's launched from broot
+    pub fn execute_staying(&self) -> Result<(), ProgramError> {
+        match self {
+            Launchable::SystemOpen { path } => {
+                opener::open(&path).map_err(|err| ProgramError::OpenError { err })
+            }
+            _ => self.execute_in(true),
+        }
+    }
 
+    /// execute the launchable, broot being gone. A program which fails,
+    ///  ie which exits with a non zero status, gives a ProgramFailed error
     pub fn execute(&self) -> Result<(), ProgramError> {
+        self.execute_in(false)
+    }
+
+    fn execute_in(&self, staying: bool) -> Result<(), ProgramError> {
         match self {
             Launchable::Printer { to_print } => Ok(println!("{}", to_print)),
-            Launchable::Program { exe, args } => {
-                Command::new(&exe)
-                  .args(args.iter())
+            Launchable::Program { exe, args, working_dir, env } => {
+                let mut command = Command::new(&exe);
+                command.args(args.iter());
+                if staying {
+                    // so that a shell launched from broot can tell it
+                    command.env(DEPTH_ENV_VAR, nesting_depth().to_string());
+                }
+                command.envs(env.iter().map(|(k, v)| (k, v)));
+                if let Some(dir) = working_dir {
+                    command.current_dir(dir);
+                }
+                let status = command
                   .spawn()
                   .and_then(|mut p| p.wait())
                   .map_err(|source| ProgramError::LaunchError {
                       program: exe.clone(),
                       source,
                   })?;
-                Ok(())
+                if status.success() {
+                    Ok(())
+                } else {
+                    Err(ProgramError::ProgramFailed {
+                        program: exe.clone(),
+                        status,
+                    })
+                }
             }
             Launchable::SystemOpen { path } => {
                 match opener::open(&path) {
                     Ok(_) => Ok(()),
-                    Err(err) => Err(ProgramError::OpenError{err}),
+                    Err(err) => {
+                        // there's often no usable opener (eg on a headless server)
+                        // so we fall back to printing the path, like :print_path
+                        warn!("{}", ProgramError::OpenError{err});
+                        Launchable::printer(path.to_string_lossy().to_string()).execute()
+                    }
                 }
             }
         }
@@ -97,22 +225,267 @@ pub fn escape_for_shell(path: &Path) -> String {
     }
 }
 
-pub fn print_path(path: &Path, con: &AppContext) -> io::Result<AppStateCmdResult> {
+// build the error telling the path can't be written to the export file
+pub fn export_error(export_path: &str, err: &io::Error) -> ProgramError {
+    let reason = match err.kind() {
+        io::ErrorKind::PermissionDenied => "permission denied".to_string(),
+        io::ErrorKind::NotFound => "no such file or directory".to_string(),
+        _ => err.to_string(),
+    };
+    ProgramError::ExportError {
+        path: export_path.to_string(),
+        reason,
+    }
+}
+
+// append a line to the file given with --out (or --out-trunc)
+pub fn export_line(export_path: &str, line: &str) -> Result<(), ProgramError> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(export_path)
+        .and_then(|f| writeln!(&f, "{}", line))
+        .map_err(|e| export_error(export_path, &e))
+}
+
+/// write in the --outcmd file the command making the shell go to the
+///  directory, like :cd. Nothing is written when broot wasn't launched
+///  with this file (ie not from the br function)
+pub fn export_cd(dir: &Path, con: &AppContext) -> Result<(), ProgramError> {
+    match &con.launch_args.cmd_export_path {
+        Some(export_path) => {
+            let verb = &con.verb_store.verbs[con.verb_store.index_of("cd")];
+            export_line(export_path, &verb.shell_exec_string(dir, dir, &None, con.relative_base()))
+        }
+        None => Ok(()),
+    }
+}
+
+/// the path written relative to base, with '..' components when it's not
+///  inside it. The path is returned unchanged when there's no sane relative
+///  form: when one of them isn't absolute, when base contains '..', or when
+///  they don't share their root (like two drives on Windows)
+pub fn relative_path(path: &Path, base: &Path) -> PathBuf {
+    if !path.is_absolute() || !base.is_absolute() {
+        return path.to_path_buf();
+    }
+    let mut path_components = path.components().peekable();
+    let mut base_components = base.components().peekable();
+    while path_components.peek().is_some() && path_components.peek() == base_components.peek() {
+        path_components.next();
+        base_components.next();
+    }
+    let mut relative = PathBuf::new();
+    for c in base_components {
+        match c {
+            Component::Normal(_) => relative.push(".."),
+            _ => {
+                return path.to_path_buf();
+            }
+        }
+    }
+    for c in path_components {
+        match c {
+            Component::Prefix(_) | Component::RootDir => {
+                return path.to_path_buf();
+            }
+            _ => relative.push(c),
+        }
+    }
+    if relative.as_os_str().is_empty() {
+        relative.push(".");
+    }
+    relative
+}
+
+/// open the file, then quit or stay in broot according to the
+///  internal, `:open` doing what <enter> does on files. With
+///  `:open_later`, the file is opened when broot quits
+pub fn open_file(path: PathBuf, internal: Internal, con: &AppContext) -> io::Result<AppStateCmdResult> {
+    let launchable = Launchable::file_opener(path, con)?;
+    let internal = match internal {
+        Internal::Open => con.enter_on_file,
+        _ => internal,
+    };
+    Ok(match internal {
+        Internal::OpenStay => AppStateCmdResult::LaunchAndStay(launchable),
+        Internal::OpenLater => AppStateCmdResult::Launch {
+            launchable,
+            quit: false,
+        },
+        _ => AppStateCmdResult::Launch {
+            launchable,
+            quit: true,
+        },
+    })
+}
+
+/// print the path (relative to the directory broot was launched in when
+///  relative is true), or write it to the --out file, then quit
+pub fn print_path(path: &Path, relative: bool, con: &AppContext) -> io::Result<AppStateCmdResult> {
+    let path = if relative {
+        relative_path(path, &con.launch_dir)
+    } else {
+        path.to_path_buf()
+    };
     let path = path.to_string_lossy().to_string();
     Ok(
         if let Some(ref output_path) = con.launch_args.file_export_path {
             // an output path was provided, we write to it
-            let f = OpenOptions::new()
-                .create(true)
-                .append(true)
-                .open(output_path)?;
-            writeln!(&f, "{}", path)?;
-            AppStateCmdResult::Quit
+            match export_line(output_path, &path) {
+                Ok(()) => AppStateCmdResult::QuitWithExport,
+                Err(e) => AppStateCmdResult::DisplayError(e.to_string()),
+            }
         } else {
             // no output path provided. We write on stderr, but we must
             // do it after app closing to have the normal terminal
             let launchable = Launchable::printer(path);
-            AppStateCmdResult::Launch(launchable)
+            AppStateCmdResult::Launch {
+                launchable,
+                quit: true,
+            }
         },
     )
 }
+
+/// quit, printing the picked path on stdout (relative to the directory
+///  broot was launched in when it's asked in conf)
+pub fn pick(path: &Path, con: &AppContext) -> AppStateCmdResult {
+    let path = match con.relative_base() {
+        Some(base) => relative_path(path, base),
+        None => path.to_path_buf(),
+    };
+    AppStateCmdResult::Launch {
+        launchable: Launchable::printer(path.to_string_lossy().to_string()),
+        quit: true,
+    }
+}
+
+// open the configuration file (written first if it doesn't exist) in $EDITOR
+pub fn edit_conf() -> io::Result<AppStateCmdResult> {
+    let conf_path = Conf::ensure_default_file()?;
+    Ok(match env::var("EDITOR") {
+        Ok(editor) => {
+            let mut parts: Vec<String> = editor.split_whitespace().map(String::from).collect();
+            parts.push(conf_path.to_string_lossy().to_string());
+            AppStateCmdResult::Launch {
+                launchable: Launchable::program(parts, None, Vec::new())?,
+                quit: true,
+            }
+        }
+        Err(_) => AppStateCmdResult::DisplayError(
+            "$EDITOR isn't set, you may edit the configuration with :open in the help".to_string(),
+        ),
+    })
+}
+
+#[cfg(test)]
+mod launch_tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+    use tempfile::tempdir;
+
+    fn sh(script: &str, env: Vec<(String, String)>, dir: &Path) -> Result<(), ProgramError> {
+        sh_launchable(script, env, dir).execute()
+    }
+
+    fn sh_launchable(script: &str, env: Vec<(String, String)>, dir: &Path) -> Launchable {
+        let parts = vec!["sh".to_string(), "-c".to_string(), script.to_string()];
+        Launchable::program(parts, Some(dir.to_path_buf()), env).unwrap()
+    }
+
+    #[test]
+    fn nesting_limits() {
+        assert!(check_nesting_depth(5, None).is_ok());
+        assert!(check_nesting_depth(1, Some(1)).is_ok());
+        assert!(check_nesting_depth(2, Some(1)).is_err());
+        assert!(check_nesting_depth(1, Some(0)).is_ok());
+        assert!(check_nesting_depth(2, Some(0)).is_err());
+        assert!(check_nesting_depth(2, Some(2)).is_ok());
+        // a depth read in the environment can't overflow
+        assert_eq!(nesting_depth_in(None), 1);
+        assert_eq!(nesting_depth_in(Some("2")), 3);
+        assert_eq!(nesting_depth_in(Some("not a number")), 1);
+        let max = usize::MAX.to_string();
+        assert_eq!(nesting_depth_in(Some(&max)), usize::MAX);
+        assert!(check_nesting_depth(nesting_depth_in(Some(&max)), Some(3)).is_err());
+    }
+
+    #[test]
+    fn exit_status() {
+        let dir = tempdir().unwrap();
+        assert!(sh("exit 0", Vec::new(), dir.path()).is_ok());
+        // the program knows it's launched by broot, unless broot quit
+        let depth_check = format!("test \"${}\" = {}", DEPTH_ENV_VAR, nesting_depth());
+        assert!(sh_launchable(&depth_check, Vec::new(), dir.path()).execute_staying().is_ok());
+        let outer_depth = env::var(DEPTH_ENV_VAR).unwrap_or_else(|_| "none".to_string());
+        let depth_check = format!("test \"${{{}-none}}\" = {}", DEPTH_ENV_VAR, outer_depth);
+        assert!(sh(&depth_check, Vec::new(), dir.path()).is_ok());
+        match sh("exit 3", Vec::new(), dir.path()) {
+            Err(ProgramError::ProgramFailed { status, .. }) => assert_eq!(status.code(), Some(3)),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        match Launchable::program(vec!["/not/a/program".to_string()], None, Vec::new())
+            .unwrap()
+            .execute()
+        {
+            Err(ProgramError::LaunchError { .. }) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn program_resolution() {
+        let dir = tempdir().unwrap();
+        let script = dir.path().join("script.sh");
+        fs::write(&script, "#!/bin/sh\n").unwrap();
+        let not_exe = dir.path().join("not_exe.sh");
+        fs::write(&not_exe, "#!/bin/sh\n").unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+        assert!(Launchable::resolve_program("sh").is_ok());
+        assert!(Launchable::resolve_program(&script.to_string_lossy()).is_ok());
+        assert_eq!(
+            Launchable::resolve_program("surely-not-a-program"),
+            Err("'surely-not-a-program' not found in PATH".to_string()),
+        );
+        assert!(Launchable::resolve_program(&not_exe.to_string_lossy()).is_err());
+        assert!(Launchable::resolve_program("~/surely-not-a-program").is_err());
+        // the variables are expanded as for the root given on launch
+        assert!(Launchable::resolve_program("$BROOT_TEST_UNSET_VAR/script.sh").is_err());
+    }
+
+    #[test]
+    fn environment() {
+        let dir = tempdir().unwrap();
+        let env = vec![("BROOT_TEST_VAR".to_string(), "some value".to_string())];
+        sh("echo \"$BROOT_TEST_VAR\" > var.txt", env, dir.path()).unwrap();
+        let written = std::fs::read_to_string(dir.path().join("var.txt")).unwrap();
+        assert_eq!(written, "some value\n");
+    }
+}
+
+#[cfg(test)]
+mod relative_path_tests {
+    use super::*;
+
+    fn rel(path: &str, base: &str) -> String {
+        relative_path(Path::new(path), Path::new(base)).to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn relative_paths() {
+        assert_eq!(rel("/a/b", "/a/b"), ".");
+        assert_eq!(rel("/a/b/c/d", "/a/b"), "c/d");
+        assert_eq!(rel("/a", "/a/b/c"), "../..");
+        assert_eq!(rel("/a/x/y", "/a/b/c"), "../../x/y");
+        assert_eq!(rel("/", "/a/b"), "../..");
+        assert_eq!(rel("/a/b", "/"), "a/b");
+        // a common prefix of the names isn't a common ancestor
+        assert_eq!(rel("/a/bc", "/a/b"), "../bc");
+        assert_eq!(rel("/a/./b/", "/a"), "b");
+        // without a sane relative form, the path is kept
+        assert_eq!(rel("/a/b", "/a/../c"), "/a/b");
+        assert_eq!(rel("a/b", "/a"), "a/b");
+        assert_eq!(rel("/a/b", "a"), "/a/b");
+    }
+}
@@ -3,9 +3,11 @@
 ///  same directories again and again.
 /// Hard links are checked to avoid counting
 ///  twice an inode.
+use crate::flat_tree::SpecialKind;
 use crate::task_sync::TaskLifetime;
 use std::collections::{HashMap, HashSet};
 use std::fs;
+use std::mem;
 use std::ops::AddAssign;
 use std::os::unix::fs::MetadataExt;
 use std::path::{Path, PathBuf};
@@ -20,32 +22,119 @@ use std::time::Duration;
 
 const SIZE_NAMES: &[&str] = &["", "K", "M", "G", "T", "P", "E", "Z", "Y"]; // Y: for when your disk is bigger than 1024 ZB
 
+// the maximal duration of a step of a directory size computation
+const DIR_WALK_STEP: Duration = Duration::from_millis(200);
+
+/// a number of bytes. Sums saturate, so that they can't overflow
 #[derive(Debug, Copy, Clone)]
 pub struct Size(u64);
 
-impl Size {
-    pub fn from_file(path: &Path) -> Size {
-        Size(match fs::metadata(path) {
-            Ok(m) => m.len(),
-            Err(_) => 0,
-        })
+/// what we know about the size of a file or directory
+#[derive(Debug, Copy, Clone)]
+pub enum SizeInfo {
+    Unknown,            // not yet computed
+    Partial(Size),      // being computed, this is what was found until now
+    Computed(Size),
+    Incomplete(Size, usize), // the size of the readable parts, and the number of directories which couldn't be read
+}
+
+impl SizeInfo {
+    /// the size, when its computation is over, even if some
+    ///  directories couldn't be read
+    pub fn computed(self) -> Option<Size> {
+        match self {
+            SizeInfo::Computed(s) | SizeInfo::Incomplete(s, _) => Some(s),
+            _ => None,
+        }
+    }
+    /// the size found until now, whether its computation is over or not
+    pub(crate) fn found(self) -> Size {
+        match self {
+            SizeInfo::Unknown => Size::from(0),
+            SizeInfo::Partial(s) | SizeInfo::Computed(s) | SizeInfo::Incomplete(s, _) => s,
+        }
     }
+    pub(crate) fn is_pending(self) -> bool {
+        match self {
+            SizeInfo::Unknown | SizeInfo::Partial(_) => true,
+            _ => false,
+        }
+    }
+}
 
-    /// Return the size of the directory, either by computing it of by
-    ///  fetching it from cache.
-    /// If the lifetime expires before complete computation, None is returned.
-    pub fn from_dir(path: &Path, tl: &TaskLifetime) -> Option<Size> {
-        lazy_static! {
-            static ref SIZE_CACHE_MUTEX: Mutex<HashMap<PathBuf, Size>> = Mutex::new(HashMap::new());
+/// the computation, asked with the size verb, of the size of one
+///  file or directory whose size isn't otherwise computed
+pub struct SizeJob {
+    pub path: PathBuf,
+    dev: Option<u64>, // when set, the directories of other devices are ignored
+    pub size: SizeInfo,
+}
+
+impl SizeJob {
+    pub fn new(path: PathBuf, dev: Option<u64>) -> SizeJob {
+        SizeJob {
+            path,
+            dev,
+            size: SizeInfo::Unknown,
         }
-        let mut size_cache = SIZE_CACHE_MUTEX.lock().unwrap();
-        if let Some(s) = size_cache.get(path) {
-            return Some(*s);
+    }
+    pub fn is_done(&self) -> bool {
+        !self.size.is_pending()
+    }
+    /// advance the computation by a step. Directories go through the
+    ///  cache, so that their size isn't computed again when sizes are shown
+    pub fn advance(&mut self, tl: &TaskLifetime) {
+        self.size = match fs::symlink_metadata(&self.path) {
+            Ok(md) if md.is_dir() => Size::from_dir(&self.path, self.dev, tl),
+            Ok(md) => SizeInfo::Computed(Size::from(md.len())),
+            Err(_) => SizeInfo::Incomplete(Size::from(0), 1),
+        };
+    }
+}
+
+/// the state of a directory size computation, kept between steps
+struct DirWalk {
+    dirs: Vec<PathBuf>,   // the directories which must still be read
+    dev: Option<u64>,     // when set, the directories of other devices are ignored
+    size: u64,
+    inodes: HashSet<u64>, // to avoid counting twice an inode
+    nb_errors: usize,     // number of directories which couldn't be read
+    duration: Duration,   // time spent until now
+}
+
+impl DirWalk {
+    fn new(path: &Path, dev: Option<u64>) -> DirWalk {
+        let on_dev = match dev {
+            Some(dev) => fs::metadata(path).map_or(false, |md| md.dev() == dev),
+            None => true,
+        };
+        DirWalk {
+            dirs: if on_dev { vec![PathBuf::from(path)] } else { Vec::new() },
+            dev,
+            size: 0,
+            inodes: HashSet::new(),
+            nb_errors: 0,
+            duration: Duration::from_millis(0),
         }
+    }
 
+    // the size found until now, partial if some directories remain
+    fn size_info(&self) -> SizeInfo {
+        if !self.dirs.is_empty() {
+            SizeInfo::Partial(Size::from(self.size))
+        } else if self.nb_errors > 0 {
+            SizeInfo::Incomplete(Size::from(self.size), self.nb_errors)
+        } else {
+            SizeInfo::Computed(Size::from(self.size))
+        }
+    }
+    // read directories until there's none left, or the step is over, or
+    //  the lifetime expires
+    fn advance(self, tl: &TaskLifetime) -> DirWalk {
         let start = Instant::now();
-        let inodes = Arc::new(Mutex::new(HashSet::<u64>::new())); // to avoid counting twice an inode
-        let size = Arc::new(AtomicUsize::new(0));
+        let inodes = Arc::new(Mutex::new(self.inodes));
+        let size = Arc::new(Mutex::new(self.size));
+        let nb_errors = Arc::new(AtomicUsize::new(self.nb_errors));
 
         // this MPMC channel contains the directory paths which must be handled
         let (dirs_sender, dirs_receiver) = unbounded();
@@ -53,28 +142,43 @@ impl Size {
         // busy is the number of directories which are either being processed or queued
         // We use this count to determine when threads can stop waiting for tasks
         let busy = Arc::new(AtomicIsize::new(0));
-        busy.fetch_add(1, Ordering::Relaxed);
-        dirs_sender.send(Some(PathBuf::from(path))).unwrap();
+        for dir in self.dirs {
+            busy.fetch_add(1, Ordering::Relaxed);
+            dirs_sender.send(Some(dir)).unwrap();
+        }
 
         let wg = WaitGroup::new();
         let period = Duration::from_micros(50);
         for _ in 0..8 {
             let size = Arc::clone(&size);
+            let nb_errors = Arc::clone(&nb_errors);
             let busy = Arc::clone(&busy);
             let wg = wg.clone();
             let (dirs_sender, dirs_receiver) = (dirs_sender.clone(), dirs_receiver.clone());
             let tl = tl.clone();
             let inodes = inodes.clone();
+            let dev = self.dev;
             thread::spawn(move || {
+                let mut thread_size: u64 = 0; // added to size when the thread ends
                 loop {
+                    // a directory is either totally read or not at all, so that
+                    // the ones still in the channel are all we need to resume
+                    if tl.is_expired() || start.elapsed() > DIR_WALK_STEP {
+                        break;
+                    }
                     let o = dirs_receiver.recv_timeout(period);
                     if let Ok(Some(open_dir)) = o {
                         if let Ok(entries) = fs::read_dir(&open_dir) {
                             for e in entries.flatten() {
                                 if let Ok(md) = e.metadata() {
                                     if md.is_dir() {
+                                        if dev.map_or(false, |dev| md.dev() != dev) {
+                                            continue; // a mount point of another filesystem
+                                        }
                                         busy.fetch_add(1, Ordering::Relaxed);
                                         dirs_sender.send(Some(e.path())).unwrap();
+                                    } else if SpecialKind::from_file_type(&md.file_type()).is_some() {
+                                        continue; // sockets, fifos and devices take no space
                                     } else if md.nlink() > 1 {
                                         let mut inodes = inodes.lock().unwrap();
                                         if !inodes.insert(md.ino()) {
@@ -82,37 +186,90 @@ impl Size {
                                             continue; // let's not add the size
                                         }
                                     }
-                                    size.fetch_add(md.len() as usize, Ordering::Relaxed);
+                                    thread_size = thread_size.saturating_add(md.len());
                                 }
                             }
+                        } else {
+                            nb_errors.fetch_add(1, Ordering::Relaxed);
                         }
                         busy.fetch_sub(1, Ordering::Relaxed);
                         dirs_sender.send(None).unwrap();
                     } else if busy.load(Ordering::Relaxed) < 1 {
                         break;
                     }
-                    if tl.is_expired() {
-                        break;
-                    }
                 }
+                let mut size = size.lock().unwrap();
+                *size = size.saturating_add(thread_size);
                 drop(wg);
             });
         }
         wg.wait();
 
-        if tl.is_expired() {
-            return None;
+        let mut dirs = Vec::new();
+        while let Ok(o) = dirs_receiver.try_recv() {
+            if let Some(dir) = o {
+                dirs.push(dir);
+            }
+        }
+        let size = *size.lock().unwrap();
+        let nb_errors = nb_errors.load(Ordering::Relaxed);
+        let inodes = mem::replace(&mut *inodes.lock().unwrap(), HashSet::new());
+        DirWalk {
+            dirs,
+            dev: self.dev,
+            size,
+            inodes,
+            nb_errors,
+            duration: self.duration + start.elapsed(),
+        }
+    }
+}
+
+impl Size {
+    /// Advance the computation of the size of the directory, or fetch it
+    ///  from cache.
+    /// The computation is done in steps: when the step duration or the
+    ///  lifetime expires, the size found until now is returned as partial
+    ///  and the next call resumes the computation.
+    /// When a device is given, only the directories of this device are read.
+    pub(crate) fn from_dir(path: &Path, dev: Option<u64>, tl: &TaskLifetime) -> SizeInfo {
+        lazy_static! {
+            static ref SIZE_CACHE_MUTEX: Mutex<HashMap<(PathBuf, Option<u64>), SizeInfo>> = Mutex::new(HashMap::new());
+            static ref WALKS_MUTEX: Mutex<HashMap<(PathBuf, Option<u64>), DirWalk>> = Mutex::new(HashMap::new());
+        }
+        let key = (PathBuf::from(path), dev);
+        let mut size_cache = SIZE_CACHE_MUTEX.lock().unwrap();
+        if let Some(s) = size_cache.get(&key) {
+            return *s;
+        }
+        let mut walks = WALKS_MUTEX.lock().unwrap();
+        let walk = match walks.remove(&key) {
+            Some(walk) => walk,
+            None => DirWalk::new(path, dev),
+        };
+        let walk = walk.advance(tl);
+        let s = walk.size_info();
+        if walk.dirs.is_empty() {
+            debug!("size computation for {:?} took {:?}", path, walk.duration);
+            size_cache.insert(key, s);
+        } else {
+            walks.insert(key, walk);
         }
-        let size: usize = size.load(Ordering::Relaxed);
-        let size: u64 = size as u64;
-        let s = Size::from(size);
+        s
+    }
 
-        size_cache.insert(PathBuf::from(path), s);
-        debug!("size computation for {:?} took {:?}", path, start.elapsed());
-        Some(s)
+    /// Compute the size of the directory in one go, without using nor
+    ///  filling the cache (the result is partial if the lifetime expires)
+    pub fn compute_dir(path: &Path, tl: &TaskLifetime) -> SizeInfo {
+        let mut walk = DirWalk::new(path, None);
+        while !walk.dirs.is_empty() && !tl.is_expired() {
+            walk = walk.advance(tl);
+        }
+        walk.size_info()
     }
 
-    /// format a number of bytes as a string
+    /// format a number of bytes as a string, eg "0", "8999" or "12K", the
+    ///  value being truncated to the unit. u64 sizes go up to 15E
     pub fn to_string(self) -> String {
         let mut v = self.0;
         let mut i = 0;
@@ -122,11 +279,23 @@ impl Size {
         }
         format!("{}{}", v, &SIZE_NAMES[i])
     }
+    /// the part of total this size is, as a percentage
+    pub fn percent_of(self, total: Size) -> f64 {
+        if total.0 == 0 {
+            0.0
+        } else {
+            100.0 * self.0 as f64 / total.0 as f64
+        }
+    }
+    /// the length, in 0..=r, of the bar telling the part of max this size
+    ///  is. It's 0 when max is 0 (eg sizes not yet computed), and a size
+    ///  over max gives a full bar
     pub fn discrete_ratio(self, max: Size, r: u64) -> u64 {
         if max.0 == 0 || self.0 == 0 {
             0
         } else {
-            ((r as f64) * (self.0 as f64).cbrt() / (max.0 as f64).cbrt()).round() as u64
+            let ratio = ((r as f64) * (self.0 as f64).cbrt() / (max.0 as f64).cbrt()).round() as u64;
+            ratio.min(r)
         }
     }
 }
@@ -139,7 +308,7 @@ impl From<u64> for Size {
 
 impl AddAssign for Size {
     fn add_assign(&mut self, other: Size) {
-        *self = Size(self.0 + other.0);
+        *self = Size(self.0.saturating_add(other.0));
     }
 }
 
@@ -148,3 +317,87 @@ impl Into<u64> for Size {
         self.0
     }
 }
+
+#[cfg(test)]
+mod size_tests {
+    use super::*;
+    use quickcheck::{quickcheck, Arbitrary, Gen};
+
+    // a size whose magnitude, from bytes to exabytes, is random
+    #[derive(Debug, Clone, Copy)]
+    struct AnySize(u64);
+
+    impl Arbitrary for AnySize {
+        fn arbitrary<G: Gen>(g: &mut G) -> AnySize {
+            let bits = g.next_u32() % 65;
+            AnySize(g.next_u64().checked_shr(64 - bits).unwrap_or(0))
+        }
+    }
+
+    // read back a size written by to_string
+    fn parse(s: &str) -> (u64, u64) {
+        let digits = s.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+        let unit = SIZE_NAMES.iter().position(|name| *name == &s[digits.len()..]).unwrap();
+        let value: u64 = digits.parse().unwrap();
+        (value << (10 * unit), 1 << (10 * unit))
+    }
+
+    #[test]
+    fn extreme_sizes() {
+        assert_eq!(Size::from(0).to_string(), "0");
+        assert_eq!(Size::from(8999).to_string(), "8999");
+        assert_eq!(Size::from(9000).to_string(), "8K");
+        assert_eq!(Size::from(u64::MAX).to_string(), "15E");
+        let mut sum = Size::from(u64::MAX);
+        sum += Size::from(1);
+        assert_eq!(sum.to_string(), "15E");
+        assert_eq!(Size::from(0).discrete_ratio(Size::from(0), 8), 0);
+        assert_eq!(Size::from(5).discrete_ratio(Size::from(0), 8), 0);
+        assert_eq!(Size::from(5).discrete_ratio(Size::from(5), 8), 8);
+    }
+
+    #[test]
+    fn ratio_fits_in_the_width() {
+        fn fits(s: AnySize, max: AnySize, width: u8) -> bool {
+            let width = width as u64;
+            Size::from(s.0).discrete_ratio(Size::from(max.0), width) <= width
+        }
+        quickcheck(fits as fn(AnySize, AnySize, u8) -> bool);
+    }
+
+    #[test]
+    fn formatting_round_trips() {
+        // the parsed value is the size truncated to the unit
+        fn round_trips(s: AnySize) -> bool {
+            let (value, unit) = parse(&Size::from(s.0).to_string());
+            value <= s.0 && s.0 - value < unit
+        }
+        quickcheck(round_trips as fn(AnySize) -> bool);
+    }
+
+    #[test]
+    fn unreadable_directories_leave_the_size_incomplete() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("five"), "12345").unwrap();
+        let locked = dir.path().join("locked");
+        fs::create_dir(&locked).unwrap();
+        fs::write(locked.join("hidden"), "123").unwrap();
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o000)).unwrap();
+        let size = Size::compute_dir(dir.path(), &TaskLifetime::unlimited());
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+        // root reads everything
+        if unsafe { libc::geteuid() } == 0 {
+            assert!(size.computed().is_some());
+            return;
+        }
+        match size {
+            SizeInfo::Incomplete(s, nb_errors) => {
+                let bytes: u64 = s.into();
+                assert!(bytes >= 5, "{} bytes", bytes);
+                assert_eq!(nb_errors, 1);
+            }
+            _ => panic!("unexpected size {:?}", size),
+        }
+    }
+}
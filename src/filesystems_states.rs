@@ -0,0 +1,373 @@
+//! an application state listing the mounted filesystems, with
+//!  their usage, from which a filesystem can be explored
+
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use crate::app::{AppState, AppStateCmdResult};
+use crate::app_context::AppContext;
+use crate::browser_states::BrowserState;
+use crate::commands::{Action, Command};
+use crate::help_states::HelpState;
+use crate::mounts::{self, Mount};
+use crate::navigation;
+use crate::patterns::Pattern;
+use crate::screens::Screen;
+use crate::special_paths;
+use crate::status::Status;
+use crate::task_sync::{RefreshRequest, TaskLifetime};
+use crate::tree_views::TreeView;
+use crate::verb_store::PrefixSearchResult;
+use crate::verbs::VerbExecutor;
+
+// the width of the bar showing the used space
+const USED_BAR_WIDTH: usize = 10;
+
+pub struct FilesystemsState {
+    mounts: Vec<Mount>,
+    pub show_pseudo: bool, // whether proc, sysfs, cgroup, etc. are listed
+    pattern: Pattern,      // filters the mounts, on their mount point
+    displayed: Vec<usize>, // indexes of the listed mounts
+    selection: usize,      // index in displayed
+    scroll: usize,         // number of listed mounts hidden at the top
+    refresh: RefreshRequest, // whether the mounts must be read again
+}
+
+// cut or pad the string so that it takes exactly width chars
+fn fit(s: &str, width: usize) -> String {
+    let mut fitted: String = s.chars().take(width).collect();
+    for _ in fitted.chars().count()..width {
+        fitted.push(' ');
+    }
+    fitted
+}
+
+impl FilesystemsState {
+    pub fn new() -> io::Result<FilesystemsState> {
+        let mut state = FilesystemsState {
+            mounts: mounts::read_mounts()?,
+            show_pseudo: false,
+            pattern: Pattern::None,
+            displayed: Vec::new(),
+            selection: 0,
+            scroll: 0,
+            refresh: RefreshRequest::new(),
+        };
+        state.update_displayed();
+        Ok(state)
+    }
+    /// the command result pushing a new filesystems state, or
+    ///  telling why the filesystems can't be listed
+    pub fn new_state_cmd_result() -> AppStateCmdResult {
+        match FilesystemsState::new() {
+            Ok(state) => AppStateCmdResult::NewState(Box::new(state), Command::new()),
+            Err(e) => AppStateCmdResult::DisplayError(format!(
+                "unable to read the mounted filesystems: {}",
+                e
+            )),
+        }
+    }
+    // the number of mounts we can show, below the header
+    fn page_height(screen: &Screen) -> usize {
+        (screen.h as usize).saturating_sub(3).max(1)
+    }
+    // compute again the list of the mounts matching the settings,
+    //  trying to keep the selection
+    fn update_displayed(&mut self) {
+        let selected = self.selected_mount().map(|m| m.mount_point.clone());
+        let show_pseudo = self.show_pseudo;
+        let pattern = &self.pattern;
+        self.displayed = self
+            .mounts
+            .iter()
+            .enumerate()
+            .filter(|(_, m)| show_pseudo || !m.is_pseudo())
+            .filter(|(_, m)| {
+                !pattern.is_some() || pattern.find(&m.mount_point.to_string_lossy()).is_some()
+            })
+            .map(|(idx, _)| idx)
+            .collect();
+        self.selection = selected
+            .and_then(|path| {
+                self.displayed
+                    .iter()
+                    .position(|&idx| self.mounts[idx].mount_point == path)
+            })
+            .unwrap_or(0);
+    }
+    pub fn toggle_pseudo(&mut self) {
+        self.show_pseudo ^= true;
+        self.update_displayed();
+    }
+    pub fn selected_mount(&self) -> Option<&Mount> {
+        self.displayed
+            .get(self.selection)
+            .map(|&idx| &self.mounts[idx])
+    }
+    fn make_selection_visible(&mut self, page_height: usize) {
+        if self.selection < self.scroll {
+            self.scroll = self.selection;
+        } else if self.selection >= self.scroll + page_height {
+            self.scroll = self.selection + 1 - page_height;
+        }
+    }
+    /// open a browser state on the selected mount point, showing the sizes
+    ///  of what's on this filesystem only
+    pub fn explore_selection(&self, screen: &Screen, con: &AppContext) -> AppStateCmdResult {
+        let mount = match self.selected_mount() {
+            Some(mount) => mount,
+            None => {
+                return AppStateCmdResult::DisplayError("no filesystem selected".to_string());
+            }
+        };
+        let mut options = con.launch_args.tree_options.without_pattern();
+        special_paths::apply(&con.special_paths, &mount.mount_point, &mut options);
+        // the sizes are what's looked for in a filesystem
+        options.show_sizes = true;
+        options.one_filesystem = true;
+        AppStateCmdResult::from_optional_state(
+            BrowserState::new(
+                mount.mount_point.clone(),
+                options,
+                screen,
+                &TaskLifetime::unlimited(),
+            ),
+            Command::new(),
+        )
+    }
+    fn write_mount(
+        &self,
+        screen: &mut Screen,
+        mount: &Mount,
+        widths: (usize, usize),
+        selected: bool,
+    ) -> io::Result<()> {
+        let line_bg = if selected {
+            screen.skin.selected_line.bg.clone()
+        } else {
+            screen.skin.reset.bg.clone()
+        };
+        write!(
+            screen.stderr,
+            "{}{}{} {}{} ",
+            line_bg,
+            screen.skin.file.fg,
+            fit(&mount.device, widths.0),
+            screen.skin.permissions.fg,
+            fit(&mount.fs_type, widths.1),
+        )?;
+        match mount.stats {
+            Some(stats) => {
+                write!(
+                    screen.stderr,
+                    "{}{:>6} ",
+                    screen.skin.size_text.fg,
+                    stats.size.to_string(),
+                )?;
+                screen.write_size_bar(stats.used, stats.size, USED_BAR_WIDTH, false, &line_bg)?;
+                write!(
+                    screen.stderr,
+                    "{}{:>4} {:>6} ",
+                    screen.skin.size_text.fg,
+                    format!("{}%", stats.use_percent()),
+                    stats.available.to_string(),
+                )?;
+            }
+            None => {
+                write!(
+                    screen.stderr,
+                    "{}{:>6} {} {:>4} {:>6} ",
+                    screen.skin.tree.fg,
+                    "─",
+                    "─".repeat(USED_BAR_WIDTH),
+                    "─",
+                    "─",
+                )?;
+            }
+        }
+        write!(
+            screen.stderr,
+            "{}{}{}{}",
+            screen.skin.directory.fg,
+            mount.mount_point.to_string_lossy(),
+            termion::clear::UntilNewline,
+            screen.skin.reset.fgbg(),
+        )
+    }
+}
+
+impl AppState for FilesystemsState {
+    fn apply(
+        &mut self,
+        cmd: &mut Command,
+        screen: &mut Screen,
+        con: &AppContext,
+    ) -> io::Result<AppStateCmdResult> {
+        let page_height = FilesystemsState::page_height(screen);
+        if let Some(selection) = navigation::move_in_list(
+            self.selection,
+            self.displayed.len(),
+            page_height,
+            &cmd.action,
+        ) {
+            self.selection = selection;
+            self.make_selection_visible(page_height);
+            return Ok(AppStateCmdResult::Keep);
+        }
+        Ok(match &cmd.action {
+            Action::Back => {
+                if self.pattern.is_some() {
+                    self.pattern = Pattern::None;
+                    self.update_displayed();
+                    cmd.raw.clear();
+                    AppStateCmdResult::Keep
+                } else {
+                    AppStateCmdResult::PopState
+                }
+            }
+            Action::OpenSelection | Action::AltOpenSelection => self.explore_selection(screen, con),
+            Action::Verb(invocation) => match con.verb_store.search(&invocation.key) {
+                PrefixSearchResult::Match(verb) => {
+                    let invocation = invocation.clone();
+                    self.execute_or_confirm(verb, &invocation, cmd, screen, con)?
+                }
+                _ => AppStateCmdResult::verb_not_found(&invocation.key),
+            },
+            Action::FuzzyPatternEdit(pat) => {
+                self.pattern = match pat.len() {
+                    0 => Pattern::None,
+                    _ => Pattern::fuzzy(pat),
+                };
+                self.update_displayed();
+                self.make_selection_visible(page_height);
+                AppStateCmdResult::Keep
+            }
+            Action::WordsPatternEdit(pat) => {
+                self.pattern = Pattern::words(pat);
+                self.update_displayed();
+                self.make_selection_visible(page_height);
+                AppStateCmdResult::Keep
+            }
+            Action::RegexEdit(pat, flags) => match Pattern::regex(pat, flags) {
+                Ok(pattern) => {
+                    self.pattern = pattern;
+                    self.update_displayed();
+                    self.make_selection_visible(page_height);
+                    AppStateCmdResult::Keep
+                }
+                Err(e) => AppStateCmdResult::DisplayError(format!("{}", e)),
+            },
+            Action::Help => {
+                AppStateCmdResult::NewState(Box::new(HelpState::new(screen)), Command::new())
+            }
+            Action::Quit => AppStateCmdResult::Quit,
+            _ => self.apply_verb_action(cmd, screen, con)?,
+        })
+    }
+
+    fn refresh(&mut self, _screen: &Screen, _con: &AppContext) -> Command {
+        self.refresh.request();
+        Command::from_pattern(&self.pattern)
+    }
+
+    fn has_pending_tasks(&self) -> bool {
+        self.refresh.is_requested()
+    }
+
+    fn do_pending_task(&mut self, _screen: &mut Screen, _tl: &TaskLifetime) {
+        if !self.refresh.is_due() {
+            self.refresh.wait();
+            return;
+        }
+        if let Ok(mounts) = mounts::read_mounts() {
+            self.mounts = mounts;
+            self.update_displayed();
+        }
+        self.refresh.done();
+    }
+
+    fn display(&mut self, screen: &mut Screen, _con: &AppContext) -> io::Result<()> {
+        if screen.is_too_small() {
+            return screen.write_too_small();
+        }
+        let page_height = FilesystemsState::page_height(screen);
+        self.make_selection_visible(page_height);
+        let mounts: Vec<&Mount> = self.displayed.iter().map(|&idx| &self.mounts[idx]).collect();
+        let widths = (
+            mounts.iter().map(|m| m.device.chars().count()).max().unwrap_or(0).max(10).min(24),
+            mounts.iter().map(|m| m.fs_type.len()).max().unwrap_or(0).max(4).min(10),
+        );
+        write!(
+            screen.stderr,
+            "{}{}{}{} {} {:>6} {} {:>4} {:>6} {}{}",
+            screen.goto(1, 1),
+            screen.skin.tree_summary.fg,
+            screen.skin.tree_summary.bg,
+            fit("filesystem", widths.0),
+            fit("type", widths.1),
+            "size",
+            fit("used", USED_BAR_WIDTH),
+            "use%",
+            "avail",
+            "mount point",
+            termion::clear::UntilNewline,
+        )?;
+        for i in 0..page_height {
+            let y = i as u16 + 2;
+            write!(screen.stderr, "{}{}", screen.goto(1, y), screen.skin.reset.fgbg())?;
+            match mounts.get(self.scroll + i) {
+                Some(mount) => {
+                    let selected = self.scroll + i == self.selection;
+                    self.write_mount(screen, mount, widths, selected)?;
+                }
+                None => {
+                    write!(screen.stderr, "{}", termion::clear::CurrentLine)?;
+                }
+            }
+        }
+        screen.stderr.flush()
+    }
+
+    fn write_status(&self, screen: &mut Screen, cmd: &Command, con: &AppContext) -> io::Result<()> {
+        match &cmd.action {
+            Action::Confirm(invocation) => match self.selected_mount() {
+                Some(mount) => screen.write_status_err(&con.verb_store.confirmation_status(
+                    invocation,
+                    &mount.mount_point,
+                    &mount.mount_point,
+                    con.relative_base(),
+                )),
+                None => screen.write_status_err("No selected filesystem"),
+            },
+            Action::VerbEdit(invocation) => match self.selected_mount() {
+                Some(mount) => match con.verb_store.edition_status(
+                    invocation,
+                    &mount.mount_point,
+                    &mount.mount_point,
+                    con.relative_base(),
+                ) {
+                    Ok(status) => screen.write_status_text(&status),
+                    Err(status) => screen.write_status_err(&status),
+                },
+                None => screen.write_status_err("No selected filesystem"),
+            },
+            _ if self.displayed.is_empty() => {
+                screen.write_status_err("No matching filesystem")
+            }
+            _ => screen.write_status_text(&format!(
+                "Hit {} to explore the filesystem, {} to {} pseudo filesystems",
+                con.verb_store.hint_for("focus"),
+                con.verb_store.hint_for("toggle_hidden"),
+                if self.show_pseudo { "hide" } else { "show" },
+            )),
+        }
+    }
+
+    fn write_flags(&self, _screen: &mut Screen, _con: &AppContext) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn selected_path(&self) -> Option<PathBuf> {
+        self.selected_mount().map(|mount| mount.mount_point.clone())
+    }
+}
@@ -0,0 +1,107 @@
+use std::fs;
+use std::io;
+use std::os::unix::fs::MetadataExt;
+
+use crate::app::AppStateCmdResult;
+use crate::app_context::AppContext;
+use crate::build_info;
+use crate::commands::Command;
+use crate::dedup_states::DedupState;
+use crate::external;
+use crate::filesystems_states::FilesystemsState;
+use crate::help_states::HelpState;
+use crate::internals::Internal;
+use crate::screens::Screen;
+use crate::verb_invocation::VerbInvocation;
+use crate::verbs::{Verb, VerbExecution, VerbExecutor};
+
+impl VerbExecutor for FilesystemsState {
+    fn execute_verb(
+        &mut self,
+        verb: &Verb,
+        invocation: &VerbInvocation,
+        screen: &mut Screen,
+        con: &AppContext,
+    ) -> io::Result<AppStateCmdResult> {
+        if let Some(err) = verb.match_error(invocation) {
+            return Ok(AppStateCmdResult::DisplayError(err));
+        }
+        let mount_point = match self.selected_mount() {
+            Some(mount) => mount.mount_point.clone(),
+            None => {
+                return Ok(AppStateCmdResult::DisplayError(
+                    "no filesystem selected".to_string(),
+                ));
+            }
+        };
+        let internal = match &verb.execution {
+            VerbExecution::Internal(internal) => *internal,
+            VerbExecution::External(_) => {
+                return verb.to_cmd_result(&mount_point, &mount_point, invocation, screen, con);
+            }
+        };
+        Ok(match internal {
+            Internal::Back => AppStateCmdResult::PopState,
+            Internal::Dedup => {
+                // the search doesn't go into the other filesystems
+                let dev = fs::metadata(&mount_point).ok().map(|md| md.dev());
+                AppStateCmdResult::NewState(
+                    Box::new(DedupState::new(mount_point, dev)),
+                    Command::new(),
+                )
+            }
+            Internal::EditConf => external::edit_conf()?,
+            Internal::Filesystems => AppStateCmdResult::Keep,
+            Internal::Focus => self.explore_selection(screen, con),
+            Internal::Help => {
+                AppStateCmdResult::NewState(Box::new(HelpState::new(screen)), Command::new())
+            }
+            Internal::Open | Internal::OpenLater | Internal::OpenLeave | Internal::OpenStay => {
+                external::open_file(mount_point, internal, con)?
+            }
+            Internal::PrintPath => {
+                external::print_path(&mount_point, con.relative_paths, con)?
+            }
+            Internal::PrintRelativePath => external::print_path(&mount_point, true, con)?,
+            Internal::Quit => AppStateCmdResult::Quit,
+            Internal::Refresh => AppStateCmdResult::RefreshState,
+            Internal::ToggleScreen => AppStateCmdResult::ShowTerminal,
+            Internal::ToggleHidden => {
+                self.toggle_pseudo();
+                AppStateCmdResult::RefreshState
+            }
+            Internal::Version => AppStateCmdResult::DisplayMessage(build_info::one_line()),
+            // the verbs which only make sense on the lines of a tree
+            Internal::All
+            | Internal::Chmod
+            | Internal::Cp
+            | Internal::GotoLine
+            | Internal::Hardlink
+            | Internal::Hints
+            | Internal::Mkdir
+            | Internal::Mv
+            | Internal::NextSameDepth
+            | Internal::Parent
+            | Internal::Paste
+            | Internal::PasteOverwrite
+            | Internal::PreviousSameDepth
+            | Internal::Size
+            | Internal::Touch
+            | Internal::TotalSearch => AppStateCmdResult::DisplayError(format!(
+                ":{} isn't available in the filesystems list",
+                verb.invocation.key,
+            )),
+            // the display options are changed in the previous state
+            Internal::ToggleDates
+            | Internal::ToggleFiles
+            | Internal::ToggleFsInfo
+            | Internal::ToggleGitIgnore
+            | Internal::ToggleLineNumbers
+            | Internal::ToggleOwner
+            | Internal::TogglePercent
+            | Internal::TogglePerm
+            | Internal::ToggleSizes
+            | Internal::ToggleTrimRoot => AppStateCmdResult::PopStateAndReapply,
+        })
+    }
+}
@@ -1,15 +1,18 @@
 /// In the flat_tree structure, every "node" is just a line, there's
 ///  no link from a child to its parent or from a parent to its children.
-use std::cmp::{self, Ordering};
+use std::fmt;
 use std::fs;
 use std::mem;
+use std::os::unix::fs::{FileTypeExt, MetadataExt};
 use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
 
-use crate::errors;
-use crate::file_sizes::Size;
+use crate::access;
+use crate::file_sizes::{Size, SizeInfo};
 use crate::task_sync::TaskLifetime;
-use crate::tree_build::TreeBuilder;
+use crate::tree_build::{TreeBuildError, TreeBuilder};
 use crate::tree_options::TreeOptions;
+use crate::xattrs::ExtendedAttributes;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum LineType {
@@ -17,34 +20,108 @@ pub enum LineType {
     Dir,
     SymLinkToDir(String),  //
     SymLinkToFile(String), // (to file or to symlink)
+    Special(SpecialKind),  // a socket, a fifo or a device
     Pruning,               // a "xxx unlisted" line
+    Parent,                // the ".." line, under the root
+}
+
+/// the kind of a file which is neither a regular file, nor a
+///  directory, nor a link. Those files have no size and no content
+///  which could be read by a viewer or an editor.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SpecialKind {
+    Socket,
+    Fifo,
+    BlockDevice,
+    CharDevice,
+}
+
+impl SpecialKind {
+    pub fn from_file_type(file_type: &fs::FileType) -> Option<SpecialKind> {
+        if file_type.is_socket() {
+            Some(SpecialKind::Socket)
+        } else if file_type.is_fifo() {
+            Some(SpecialKind::Fifo)
+        } else if file_type.is_block_device() {
+            Some(SpecialKind::BlockDevice)
+        } else if file_type.is_char_device() {
+            Some(SpecialKind::CharDevice)
+        } else {
+            None
+        }
+    }
+    /// the char written after the name, as `ls -F` does (there's none
+    ///  for devices)
+    pub fn indicator(self) -> Option<char> {
+        match self {
+            SpecialKind::Socket => Some('='),
+            SpecialKind::Fifo => Some('|'),
+            SpecialKind::BlockDevice | SpecialKind::CharDevice => None,
+        }
+    }
+    /// the first char of the mode, as `ls -l` writes it
+    pub fn mode_char(self) -> char {
+        match self {
+            SpecialKind::Socket => 's',
+            SpecialKind::Fifo => 'p',
+            SpecialKind::BlockDevice => 'b',
+            SpecialKind::CharDevice => 'c',
+        }
+    }
+}
+
+impl fmt::Display for SpecialKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            SpecialKind::Socket => "socket",
+            SpecialKind::Fifo => "fifo",
+            SpecialKind::BlockDevice => "block device",
+            SpecialKind::CharDevice => "char device",
+        })
+    }
 }
 
 /// a line in the representation of the file hierarchy
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct TreeLine {
     pub left_branchs: Box<[bool]>, // a depth-sized array telling whether a branch pass
-    pub depth: u16,
+    pub depth: usize,
     pub name: String, // name of the first unlisted, in case of Pruning
     pub path: PathBuf,
     pub line_type: LineType,
     pub has_error: bool,
+    pub is_hidden: bool, // dotfiles are only shown with show_hidden
     pub nb_kept_children: usize,
     pub unlisted: usize, // number of not listed children (Dir) or brothers (Pruning)
+    pub filtered_out: usize, // number of children (Dir) or brothers (Pruning) hidden by the options
+    pub gitignored_out: usize, // number of the filtered out ones which are gitignored
+    pub is_capped: bool, // whether some of the unlisted weren't read, because of max_children
     pub score: i32,      // 0 if there's no pattern
-    pub size: Option<Size>, // None when not measured
+    pub size: SizeInfo,
     pub mode: u32,       // unix file mode
     pub uid: u32,        // unix user id
     pub gid: u32,        // unix group id
+    pub modified: i64,   // date of last modification, in seconds since the epoch
+    pub has_metadata: bool, // whether the mode, owner, date and file size were read
+    pub xattrs: ExtendedAttributes, // only read when permissions are shown
+    pub is_git_repo: bool, // a directory holding a .git entry
+    pub collides_with: Option<String>, // a sibling whose name is the same once case and accents are normalized
+    pub is_context: bool, // in a filtered tree, only kept because it contains matches
+    pub nb_matches: usize, // in a filtered tree, number of matching descendants found
+    pub can_open: Option<bool>, // whether the user may read the file or enter the directory, checked when visible
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Tree {
     pub lines: Box<[TreeLine]>,
     pub selection: usize, // there's always a selection (starts with root, which is 0)
     pub options: TreeOptions,
     pub scroll: i32, // the number of lines at the top hidden because of scrolling
     pub nb_gitignored: u32, // number of times a gitignore pattern excluded a file
+    pub nb_visited: usize,  // number of entries found by the builder, shown or not
+    pub truncated_search: bool, // whether the builder stopped looking for matches before the end
+    pub built_at: SystemTime, // when the lines started being read
+    pub owners_known: bool, // whether the names of the owners of all the lines were found, kept by the owners module
 }
 
 impl TreeLine {
@@ -58,18 +135,52 @@ impl TreeLine {
         match &self.line_type {
             LineType::Dir => true,
             LineType::SymLinkToDir(_) => true,
+            LineType::Parent => true,
             _ => false,
         }
     }
-    pub fn is_file(&self) -> bool {
-        match &self.line_type {
-            LineType::File => true,
-            _ => false,
+    pub fn is_parent(&self) -> bool {
+        self.line_type == LineType::Parent
+    }
+    pub fn special_kind(&self) -> Option<SpecialKind> {
+        match self.line_type {
+            LineType::Special(kind) => Some(kind),
+            _ => None,
         }
     }
     pub fn is_exe(&self) -> bool {
         (self.mode & 0o111) != 0
     }
+    // read the mode, owner and date of the entry and, for a simple file,
+    //  its size (the sizes of directories are computed in file_sizes)
+    pub fn read_metadata(&mut self, read_xattrs: bool) {
+        if read_xattrs {
+            self.xattrs = ExtendedAttributes::of(&self.path);
+        }
+        if let Ok(metadata) = fs::symlink_metadata(&self.path) {
+            self.mode = metadata.mode();
+            self.uid = metadata.uid();
+            self.gid = metadata.gid();
+            self.modified = metadata.mtime();
+            if metadata.is_file() {
+                self.size = SizeInfo::Computed(Size::from(metadata.len()));
+            }
+        }
+        self.has_metadata = true;
+    }
+    /// check whether the user may read the file or list and enter the
+    ///  directory. The other lines are always openable
+    pub fn check_access(&mut self) {
+        self.can_open = Some(match &self.line_type {
+            LineType::File | LineType::SymLinkToFile(_) => access::can_open(&self.path, false),
+            LineType::Dir | LineType::SymLinkToDir(_) => access::can_open(&self.path, true),
+            _ => true,
+        });
+    }
+    /// tell why the targeted directory can't be opened, if it can't
+    pub fn dir_denial(&self) -> Option<&'static str> {
+        access::dir_denial(&self.target())
+    }
     // build and return the absolute targeted path: either self.path or the
     //  solved canonicalized symlink
     // (the path may be invalid if the symlink is)
@@ -97,78 +208,74 @@ impl PartialEq for TreeLine {
 
 impl Eq for TreeLine {}
 
-impl Ord for TreeLine {
-    // paths are sorted in a complete ignore case way
-    // (A<a<B<b)
-    fn cmp(&self, other: &TreeLine) -> Ordering {
-        let mut sci = self.path.components();
-        let mut oci = other.path.components();
-        loop {
-            match sci.next() {
-                Some(sc) => {
-                    match oci.next() {
-                        Some(oc) => {
-                            let scs = sc.as_os_str().to_string_lossy();
-                            let ocs = oc.as_os_str().to_string_lossy();
-                            let lower_ordering = scs.to_lowercase().cmp(&ocs.to_lowercase());
-                            if lower_ordering != Ordering::Equal {
-                                return lower_ordering;
-                            }
-                            let ordering = scs.cmp(&ocs);
-                            if ordering != Ordering::Equal {
-                                return ordering;
-                            }
-                        }
-                        None => {
-                            return Ordering::Greater;
-                        }
-                    };
-                }
-                None => {
-                    if oci.next().is_some() {
-                        return Ordering::Less;
-                    } else {
-                        return Ordering::Equal;
-                    }
-                }
-            };
-        }
-    }
-}
-
-impl PartialOrd for TreeLine {
-    fn partial_cmp(&self, other: &TreeLine) -> Option<cmp::Ordering> {
-        Some(self.cmp(other))
-    }
-}
-
 impl Tree {
 
+    /// read the lines again, keeping the selection on the same path.
+    ///  Return whether the selected line moved on screen
     pub fn refresh(
         &mut self,
         page_height: usize,
-   ) -> Result<(), errors::TreeBuildError> {
+   ) -> Result<bool, TreeBuildError> {
+        let moved = self.refresh_within(page_height, &TaskLifetime::unlimited())?;
+        Ok(moved.unwrap()) // an unlimited task isn't interrupted
+    }
+
+    /// read the lines again, like refresh, unless the task expires
+    ///  first, in which case the tree is left untouched and None returned
+    pub fn refresh_within(
+        &mut self,
+        page_height: usize,
+        tl: &TaskLifetime,
+    ) -> Result<Option<bool>, TreeBuildError> {
         let builder = TreeBuilder::from(
             self.root().to_path_buf(),
             self.options.clone(),
             page_height,
         )?;
-        let mut tree = builder.build(&TaskLifetime::unlimited()).unwrap(); // should not fail
-        // we save the old selection to try restore it
+        let mut tree = match builder.build(tl) {
+            Some(tree) => tree,
+            None => {
+                return Ok(None);
+            }
+        };
+        Ok(Some(self.mutate_preserving_selection(page_height as i32, |old| {
+            mem::swap(&mut old.lines, &mut tree.lines);
+            old.nb_visited = tree.nb_visited;
+            old.truncated_search = tree.truncated_search;
+            old.built_at = tree.built_at;
+        })))
+    }
+
+    /// apply a change which may reorder or replace the lines, then select
+    ///  again the path which was selected (or its nearest ancestor) and
+    ///  restore the scroll if possible. All the changes of the lines done
+    ///  while the user is browsing must go through this function.
+    /// Return whether the selected line moved on screen.
+    pub fn mutate_preserving_selection<F>(&mut self, page_height: i32, mutate: F) -> bool
+    where
+        F: FnOnce(&mut Tree),
+    {
         let selected_path = self.selected_line().path.to_path_buf();
-        mem::swap(&mut self.lines, &mut tree.lines);
-        self.try_select_path(&selected_path);
-        self.make_selection_visible(page_height as i32);
-        Ok(())
+        let parent_selected = self.selected_line().is_parent();
+        let scroll = self.scroll;
+        let row = self.selection as i32 - scroll;
+        mutate(self);
+        self.selection = 0;
+        if !parent_selected {
+            self.try_select_path_or_ancestor(&selected_path);
+        }
+        self.scroll = scroll.min(self.max_scroll(page_height));
+        if !self.is_selection_visible(page_height) {
+            self.make_selection_visible(page_height);
+        }
+        self.selection as i32 - self.scroll != row
     }
 
     // do what must be done after line additions or removals:
-    // - sort the lines
     // - compute left branchs
-    pub fn after_lines_changed(&mut self) {
-        // we sort the lines
-        self.lines.sort();
-
+    // The lines must already be in the order of the tree (the tree builder
+    //  writes them so, according to the sort options).
+    pub(crate) fn after_lines_changed(&mut self) {
         for i in 1..self.lines.len() {
             for d in 0..self.lines[i].left_branchs.len() {
                 self.lines[i].left_branchs[d] = false;
@@ -178,33 +285,20 @@ impl Tree {
         // and we mark the last children as pruning, if they have unlisted brothers
         let mut last_parent_index: usize = self.lines.len() + 1;
         for end_index in (1..self.lines.len()).rev() {
-            let depth = (self.lines[end_index].depth - 1) as usize;
+            let depth = self.lines[end_index].depth - 1;
             let start_index = {
-                let parent_index = {
-                    let parent_path = &self.lines[end_index].path.parent();
-                    match parent_path {
-                        Some(parent_path) => {
-                            let mut index = end_index;
-                            loop {
-                                index -= 1;
-                                if self.lines[index].path == *parent_path {
-                                    break;
-                                }
-                                if index == 0 {
-                                    break;
-                                }
-                            }
-                            index
-                        }
-                        None => end_index, // Should not happen
-                    }
-                };
+                // (the parent is found by depth, as the line of a flattened
+                //  chain isn't the parent directory of its children's path)
+                let parent_index = self.parent_index(end_index).unwrap_or(0);
                 if parent_index != last_parent_index {
                     // the line at end_index is the last listed child of the line at parent_index
                     let unlisted = self.lines[parent_index].unlisted;
                     if unlisted > 0 && self.lines[end_index].nb_kept_children == 0 {
                         self.lines[end_index].line_type = LineType::Pruning;
                         self.lines[end_index].unlisted = unlisted + 1;
+                        self.lines[end_index].filtered_out = self.lines[parent_index].filtered_out;
+                        self.lines[end_index].gitignored_out = self.lines[parent_index].gitignored_out;
+                        self.lines[end_index].is_capped = self.lines[parent_index].is_capped;
                         self.lines[parent_index].unlisted = 0;
                     }
                     last_parent_index = parent_index;
@@ -216,12 +310,55 @@ impl Tree {
             }
         }
     }
+    // insert, just under the root, a line allowing to go up
+    pub(crate) fn insert_parent_line(&mut self) {
+        let path = match self.root().parent() {
+            Some(path) => path.to_path_buf(),
+            None => {
+                return;
+            }
+        };
+        let (mode, uid, gid, modified) = match fs::metadata(&path) {
+            Ok(md) => (md.mode(), md.uid(), md.gid(), md.mtime()),
+            Err(_) => (0, 0, 0, 0),
+        };
+        let line = TreeLine {
+            left_branchs: vec![true].into_boxed_slice(),
+            depth: 1,
+            name: "..".to_string(),
+            path,
+            line_type: LineType::Parent,
+            has_error: false,
+            is_hidden: false,
+            nb_kept_children: 0,
+            unlisted: 0,
+            filtered_out: 0,
+            gitignored_out: 0,
+            is_capped: false,
+            score: 0,
+            size: SizeInfo::Unknown,
+            mode,
+            uid,
+            gid,
+            modified,
+            has_metadata: true,
+            xattrs: ExtendedAttributes::None,
+            is_git_repo: false,
+            collides_with: None,
+            is_context: false,
+            nb_matches: 0,
+            can_open: Some(true),
+        };
+        let mut lines = mem::replace(&mut self.lines, Vec::new().into_boxed_slice()).into_vec();
+        lines.insert(1, line);
+        self.lines = lines.into_boxed_slice();
+    }
     pub fn has_branch(&self, line_index: usize, depth: usize) -> bool {
         if line_index >= self.lines.len() {
             return false;
         }
         let line = &self.lines[line_index];
-        depth < usize::from(line.depth) && line.left_branchs[depth]
+        depth < line.depth && line.left_branchs[depth]
     }
     pub fn move_selection(&mut self, dy: i32, page_height: i32) {
         // only work for +1 or -1
@@ -238,16 +375,78 @@ impl Tree {
         if dy < 0 && sel < self.scroll + 5 {
             self.scroll = (self.scroll + 2 * dy).max(0);
         } else if dy > 0 && l > page_height && sel > self.scroll + page_height - 5 {
-            self.scroll += 2 * dy;
+            self.scroll = (self.scroll + 2 * dy).min(self.max_scroll(page_height));
+        }
+    }
+    /// the scroll showing the last line at the bottom, when page_height
+    ///  lines can be shown (root included): only the lines below the
+    ///  root are scrolled
+    pub fn max_scroll(&self, page_height: i32) -> i32 {
+        (self.lines.len() as i32 - 1 - (page_height - 1).max(0)).max(0)
+    }
+    // index of the parent line, that is the first line above with a lower depth
+    pub fn parent_index(&self, idx: usize) -> Option<usize> {
+        let depth = self.lines[idx].depth;
+        (0..idx).rev().find(|&i| self.lines[i].depth < depth)
+    }
+    // index of the next (dir > 0) or previous selectable line having the
+    //  same parent, skipping the descendants of the lines in between
+    fn same_depth_sibling(&self, idx: usize, dir: i32) -> Option<usize> {
+        let depth = self.lines[idx].depth;
+        let mut i = idx;
+        loop {
+            if dir > 0 {
+                i += 1;
+                if i >= self.lines.len() {
+                    return None;
+                }
+            } else {
+                if i == 0 {
+                    return None;
+                }
+                i -= 1;
+            }
+            let line = &self.lines[i];
+            if line.depth < depth {
+                return None; // we left the parent
+            }
+            if line.depth == depth && line.is_selectable() {
+                return Some(i);
+            }
+        }
+    }
+    /// select the next (dir > 0) or previous line with the same depth and
+    ///  parent. When there's none, the move is done from the parent when
+    ///  jump_to_parent is true. Return false when the selection didn't move.
+    pub fn move_selection_same_depth(
+        &mut self,
+        dir: i32,
+        page_height: i32,
+        jump_to_parent: bool,
+    ) -> bool {
+        let mut idx = self.selection;
+        loop {
+            if let Some(sibling) = self.same_depth_sibling(idx, dir) {
+                self.selection = sibling;
+                self.make_selection_visible(page_height);
+                return true;
+            }
+            match self.parent_index(idx) {
+                Some(parent) if jump_to_parent => {
+                    idx = parent;
+                }
+                _ => {
+                    return false;
+                }
+            }
         }
     }
     pub fn try_scroll(&mut self, dy: i32, page_height: i32) {
-        self.scroll = (self.scroll + dy).max(0).min(self.lines.len() as i32 - 5);
+        self.scroll = (self.scroll + dy).max(0).min(self.max_scroll(page_height));
         self.select_visible_line(page_height);
     }
-    pub fn select_visible_line(&mut self, page_height: i32) {
-        let sel = self.selection as i32;
-        if sel < self.scroll || sel >= self.scroll + page_height {
+    pub(crate) fn select_visible_line(&mut self, page_height: i32) {
+        if !self.is_selection_visible(page_height) {
             self.selection = self.scroll as usize;
             let l = self.lines.len();
             loop {
@@ -258,15 +457,45 @@ impl Tree {
             }
         }
     }
+    /// select the root (dir < 0) or the last selectable line
+    pub fn select_end(&mut self, dir: i32, page_height: i32) {
+        self.selection = if dir < 0 {
+            0
+        } else {
+            (0..self.lines.len())
+                .rev()
+                .find(|&idx| self.lines[idx].is_selectable())
+                .unwrap_or(0)
+        };
+        self.make_selection_visible(page_height);
+    }
     pub fn make_selection_visible(&mut self, page_height: i32) {
         let sel = self.selection as i32;
         let l = self.lines.len() as i32;
-        if sel < self.scroll {
+        if sel <= self.scroll {
             self.scroll = (self.selection as i32 - 2).max(0);
         } else if l > page_height && sel >= self.scroll + page_height {
-            self.scroll = (self.selection as i32 - page_height + 2) as i32;
+            self.scroll = (self.selection as i32 - page_height + 2)
+                .min(self.max_scroll(page_height));
         }
     }
+    // number of lines showing an entry (the root, parent and pruning lines aren't counted)
+    pub fn nb_shown(&self) -> usize {
+        self.lines[1..]
+            .iter()
+            .filter(|line| line.is_selectable() && !line.is_parent())
+            .count()
+    }
+    // tell whether some entries found by the builder aren't displayed
+    pub fn is_pruned(&self) -> bool {
+        self.lines.iter().any(|line| line.unlisted > 0)
+    }
+    // the root is always visible, the other lines only when
+    // they're in the scrolled part of the page
+    pub fn is_selection_visible(&self, page_height: i32) -> bool {
+        let sel = self.selection as i32;
+        sel == 0 || (sel > self.scroll && sel < self.scroll + page_height)
+    }
     pub fn selected_line(&self) -> &TreeLine {
         &self.lines[self.selection]
     }
@@ -277,7 +506,7 @@ impl Tree {
     pub fn try_select_best_match(&mut self) {
         let mut best_score = 0;
         for (idx, line) in self.lines.iter().enumerate() {
-            if !line.is_selectable() {
+            if !line.is_selectable() || line.is_parent() {
                 continue;
             }
             if best_score > line.score {
@@ -293,13 +522,36 @@ impl Tree {
             self.selection = idx;
         }
     }
-    pub fn try_select_path(&mut self, path: &Path) {
+    // select the line of the path, if its name matches the pattern
+    pub fn try_select_matching_path(&mut self, path: &Path) -> bool {
         for (idx, line) in self.lines.iter().enumerate() {
-            if !line.is_selectable() {
+            if line.path == path && line.is_selectable() && !line.is_parent() {
+                if self.options.pattern.find(&line.name).is_some() {
+                    self.selection = idx;
+                    return true;
+                }
+                return false;
+            }
+        }
+        false
+    }
+    pub fn try_select_path(&mut self, path: &Path) -> bool {
+        for (idx, line) in self.lines.iter().enumerate() {
+            if !line.is_selectable() || line.is_parent() {
                 continue;
             }
             if path == line.path {
                 self.selection = idx;
+                return true;
+            }
+        }
+        false
+    }
+    // select the line of the path or, if it's not displayed,
+    // of its nearest displayed ancestor
+    pub fn try_select_path_or_ancestor(&mut self, path: &Path) {
+        for ancestor in path.ancestors() {
+            if self.try_select_path(ancestor) {
                 return;
             }
         }
@@ -308,7 +560,7 @@ impl Tree {
         for di in 0..self.lines.len() {
             let idx = (self.selection + di + 1) % self.lines.len();
             let line = &self.lines[idx];
-            if !line.is_selectable() {
+            if !line.is_selectable() || line.is_parent() {
                 continue;
             }
             if line.score > 0 {
@@ -318,34 +570,141 @@ impl Tree {
         }
         false
     }
+    // read again the modes of the lines, which may have been changed
+    pub fn refresh_modes(&mut self) {
+        for line in self.lines.iter_mut() {
+            if let Ok(metadata) = fs::symlink_metadata(&line.path) {
+                line.mode = metadata.mode();
+            }
+            line.can_open = None;
+        }
+    }
+    pub fn has_unchecked_access(&self) -> bool {
+        self.lines.iter().any(|line| line.can_open.is_none())
+    }
+    /// check the access of the lines which weren't checked, the visible
+    ///  ones first, as it's one more system call per line. It's left
+    ///  partial when the task expires.
+    pub fn check_some_access(&mut self, page_height: i32, tl: &TaskLifetime) {
+        let len = self.lines.len();
+        let first_visible = (self.scroll as usize + 1).min(len);
+        let last_visible = (first_visible + page_height.max(0) as usize).min(len);
+        for i in (first_visible..last_visible).chain(0..len) {
+            if tl.is_expired() {
+                return;
+            }
+            if self.lines[i].can_open.is_none() {
+                self.lines[i].check_access();
+            }
+        }
+    }
+    pub fn has_missing_metadata(&self) -> bool {
+        self.lines.iter().any(|line| !line.has_metadata)
+    }
+    /// read the metadata of the lines built without it, the visible ones
+    ///  first. It's left partial when the task expires.
+    pub fn fetch_some_missing_metadata(&mut self, page_height: i32, tl: &TaskLifetime) {
+        let read_xattrs = self.options.reads_xattrs();
+        let len = self.lines.len();
+        let first_visible = (self.scroll as usize + 1).min(len);
+        let last_visible = (first_visible + page_height.max(0) as usize).min(len);
+        for i in (first_visible..last_visible).chain(0..len) {
+            if tl.is_expired() {
+                return;
+            }
+            if !self.lines[i].has_metadata {
+                self.lines[i].read_metadata(read_xattrs);
+                self.owners_known = false;
+            }
+        }
+    }
+    /// a copy of the tree with other columns shown, whose lines keep what
+    ///  was read. What the new columns miss is left to the pending task
+    pub fn with_columns(&self, options: TreeOptions) -> Tree {
+        let mut tree = self.clone();
+        if options.reads_xattrs() && !self.options.reads_xattrs() {
+            for line in tree.lines.iter_mut() {
+                line.has_metadata = false;
+            }
+        }
+        tree.options = options;
+        tree
+    }
+    /// when the lines were read, in seconds since the epoch, which
+    ///  relative dates refer to
+    pub fn built_at_secs(&self) -> i64 {
+        self.built_at.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs() as i64)
+    }
+    /// whether the direct entries of the root changed since the lines
+    ///  were read. A removed root isn't told, the refresh will tell
+    pub fn is_outdated(&self) -> bool {
+        match fs::metadata(self.root()).and_then(|md| md.modified()) {
+            Ok(modified) => modified > self.built_at,
+            Err(_) => false,
+        }
+    }
+    // the device the tree is restricted to, if any
+    pub fn root_dev(&self) -> Option<u64> {
+        if self.options.one_filesystem {
+            fs::metadata(self.root()).ok().map(|md| md.dev())
+        } else {
+            None
+        }
+    }
+    // the first line whose size must be computed: the size of the root
+    //  is only needed for the percentages of its children
+    fn first_sized_line(&self) -> usize {
+        if self.options.size_percent {
+            0
+        } else {
+            1
+        }
+    }
     pub fn has_dir_missing_size(&self) -> bool {
         if !self.options.show_sizes {
             return false;
         }
-        for i in 1..self.lines.len() {
-            if self.lines[i].size.is_none() && self.lines[i].line_type == LineType::Dir {
+        for i in self.first_sized_line()..self.lines.len() {
+            if self.lines[i].size.is_pending() && self.lines[i].line_type == LineType::Dir {
                 return true;
             }
         }
         false
     }
-    pub fn fetch_file_sizes(&mut self) {
-        for i in 1..self.lines.len() {
-            if self.lines[i].is_file() {
-                self.lines[i].size = Some(Size::from_file(&self.lines[i].path));
+    // advance the computation of the size of the first directory
+    // whose size isn't known. It may be left partial.
+    pub fn fetch_some_missing_dir_size(&mut self, tl: &TaskLifetime) {
+        let dev = self.root_dev();
+        for i in self.first_sized_line()..self.lines.len() {
+            if self.lines[i].size.is_pending() && self.lines[i].line_type == LineType::Dir {
+                self.lines[i].size = Size::from_dir(&self.lines[i].path, dev, tl);
+                return;
             }
         }
     }
-    pub fn fetch_some_missing_dir_size(&mut self, tl: &TaskLifetime) {
-        for i in 1..self.lines.len() {
-            if self.lines[i].size.is_none() && self.lines[i].line_type == LineType::Dir {
-                self.lines[i].size = Size::from_dir(&self.lines[i].path, tl);
-                return;
+    // return the size of the parent of the line at idx or, if it's not
+    // yet known, the sum of the known sizes of the listed children
+    pub fn parent_size(&self, idx: usize) -> Size {
+        let depth = self.lines[idx].depth;
+        let parent_idx = self.parent_index(idx).unwrap_or(0);
+        if let Some(size) = self.lines[parent_idx].size.computed() {
+            return size;
+        }
+        let mut sum = Size::from(0);
+        for line in self.lines[parent_idx + 1..].iter() {
+            if line.depth < depth {
+                break;
+            }
+            if line.depth == depth {
+                if let Some(size) = line.size.computed() {
+                    sum += size;
+                }
             }
         }
+        sum
     }
     pub fn total_size(&self) -> Size {
-        if let Some(size) = self.lines[0].size {
+        if let Some(size) = self.lines[0].size.computed() {
             // if the real total size is computed, it's in the root line
             size
         } else {
@@ -354,7 +713,7 @@ impl Tree {
             let mut sum = Size::from(0);
             for i in 1..self.lines.len() {
                 if self.lines[i].depth == 1 {
-                    if let Some(size) = self.lines[i].size {
+                    if let Some(size) = self.lines[i].size.computed() {
                         sum += size;
                     }
                 }
@@ -363,3 +722,103 @@ impl Tree {
         }
     }
 }
+
+#[cfg(test)]
+mod selection_tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use crate::tree_build::build_tree;
+    use tempfile::tempdir;
+
+    #[test]
+    fn access_is_checked_until_the_task_expires() {
+        let dir = tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        for i in 0..20 {
+            fs::write(root.join(format!("f{:02}", i)), "").unwrap();
+        }
+        let mut tree = build_tree(root, TreeOptions::new(), 30).unwrap();
+        assert!(tree.has_unchecked_access());
+        let events = Arc::new(AtomicUsize::new(0));
+        let expired = TaskLifetime::new(&events);
+        events.fetch_add(1, Ordering::SeqCst);
+        tree.check_some_access(5, &expired);
+        assert!(tree.has_unchecked_access());
+        tree.check_some_access(5, &TaskLifetime::unlimited());
+        assert!(!tree.has_unchecked_access());
+        assert!(tree.lines.iter().all(|line| line.can_open == Some(true)));
+        // a change of modes makes them checked again
+        tree.refresh_modes();
+        assert!(tree.has_unchecked_access());
+    }
+
+    #[test]
+    fn selection_follows_the_reordered_path() {
+        let dir = tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        for name in &["a", "b", "c", "d"] {
+            fs::write(root.join(name), "").unwrap();
+        }
+        let mut options = TreeOptions::new();
+        options.show_parent_line = false;
+        let mut tree = build_tree(root.clone(), options, 10).unwrap();
+        assert!(tree.try_select_path(&root.join("b")));
+        // a change not touching the order doesn't move the selection
+        assert!(!tree.mutate_preserving_selection(10, |tree| tree.lines[1].score = 3));
+        assert_eq!(tree.selection, 2);
+        // a reorder, as when the lines are sorted again
+        assert!(tree.mutate_preserving_selection(10, |tree| tree.lines[1..].reverse()));
+        assert_eq!(tree.selected_line().path, root.join("b"));
+        assert_eq!(tree.selection, 3);
+        // a key press moving the selection between two reorders
+        tree.move_selection(1, 10);
+        assert_eq!(tree.selected_line().path, root.join("a"));
+        assert!(tree.mutate_preserving_selection(10, |tree| tree.lines[1..].reverse()));
+        assert_eq!(tree.selected_line().path, root.join("a"));
+        // when the selected path disappears, its nearest ancestor is selected
+        tree.mutate_preserving_selection(10, |tree| {
+            let mut lines = mem::replace(&mut tree.lines, Vec::new().into_boxed_slice()).into_vec();
+            lines.retain(|line| line.name != "a");
+            tree.lines = lines.into_boxed_slice();
+        });
+        assert_eq!(tree.selection, 0);
+    }
+
+    #[test]
+    fn moves_to_the_lines_of_same_depth() {
+        let dir = tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        for path in &["a/a1", "a/a2", "b/b1"] {
+            fs::create_dir_all(root.join(path)).unwrap();
+        }
+        fs::write(root.join("c"), "").unwrap();
+        let mut options = TreeOptions::new();
+        options.show_parent_line = false;
+        let mut tree = build_tree(root.clone(), options, 20).unwrap();
+        let selected = |tree: &Tree| tree.selected_line().path.strip_prefix(&root).unwrap().to_path_buf();
+        // the children of a are skipped
+        assert!(tree.try_select_path(&root.join("a")));
+        assert!(tree.move_selection_same_depth(1, 20, false));
+        assert_eq!(selected(&tree), Path::new("b"));
+        assert!(tree.move_selection_same_depth(1, 20, false));
+        assert_eq!(selected(&tree), Path::new("c"));
+        assert!(!tree.move_selection_same_depth(1, 20, true));
+        assert!(tree.move_selection_same_depth(-1, 20, false));
+        assert_eq!(selected(&tree), Path::new("b"));
+        // at the end of its parent, the move is done from the parent, if allowed
+        assert!(tree.try_select_path(&root.join("a/a2")));
+        assert!(!tree.move_selection_same_depth(1, 20, false));
+        assert_eq!(selected(&tree), Path::new("a/a2"));
+        assert!(tree.move_selection_same_depth(1, 20, true));
+        assert_eq!(selected(&tree), Path::new("b"));
+        // the scroll follows
+        assert!(tree.try_select_path(&root.join("a")));
+        tree.make_selection_visible(3);
+        assert!(tree.move_selection_same_depth(1, 3, false));
+        assert!(tree.move_selection_same_depth(1, 3, false));
+        assert_eq!(selected(&tree), Path::new("c"));
+        assert!(tree.is_selection_visible(3));
+        assert!(tree.scroll > 0);
+    }
+}
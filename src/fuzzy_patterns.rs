@@ -18,19 +18,33 @@ const BONUS_NB_HOLES: i32 = -30; // there's also a max on that number
 pub struct FuzzyPattern {
     lc_chars: Box<[char]>, // lowercase characters
     max_nb_holes: usize,
+    anchored_start: bool, // the match must start at the first char of the candidate
+    anchored_end: bool,   // the match must end at the last char of the candidate
 }
 
 impl fmt::Display for FuzzyPattern {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.anchored_start {
+            f.write_char('^')?;
+        }
         for &c in self.lc_chars.iter() {
             f.write_char(c)?
         }
+        if self.anchored_end {
+            f.write_char('$')?;
+        }
         Ok(())
     }
 }
 
 impl FuzzyPattern {
     pub fn from(pat: &str) -> FuzzyPattern {
+        FuzzyPattern::anchored(pat, false, false)
+    }
+    /// build a pattern whose matches must start at the start of the
+    ///  candidate and/or end at its end. The pattern doesn't contain
+    ///  the anchors, which are parsed in patterns.rs
+    pub fn anchored(pat: &str, anchored_start: bool, anchored_end: bool) -> FuzzyPattern {
         let lc_chars: Vec<char> = pat.chars().map(|c| c.to_ascii_lowercase()).collect();
         let lc_chars = lc_chars.into_boxed_slice();
         let max_nb_holes = match lc_chars.len() {
@@ -47,6 +61,8 @@ impl FuzzyPattern {
         FuzzyPattern {
             lc_chars,
             max_nb_holes,
+            anchored_start,
+            anchored_end,
         }
     }
     fn match_starting_at_index(
@@ -61,17 +77,28 @@ impl FuzzyPattern {
         pos.push(start_idx);
         let mut d = 1;
         let mut nb_holes = 0;
+        let last_pat_idx = self.lc_chars.len() - 1;
         for pat_idx in 1..self.lc_chars.len() {
             let hole_start = d;
-            loop {
-                let cand_idx = start_idx + d;
-                if cand_idx == cand_chars.len() {
+            if self.anchored_end && pat_idx == last_pat_idx {
+                // the last char can only match the last one of the candidate
+                let cand_idx = cand_chars.len() - 1;
+                if cand_idx < start_idx + d || cand_chars[cand_idx] != self.lc_chars[pat_idx] {
                     return None;
                 }
-                d += 1;
-                if cand_chars[cand_idx] == self.lc_chars[pat_idx] {
-                    pos.push(cand_idx);
-                    break;
+                d = cand_idx - start_idx + 1;
+                pos.push(cand_idx);
+            } else {
+                loop {
+                    let cand_idx = start_idx + d;
+                    if cand_idx == cand_chars.len() {
+                        return None;
+                    }
+                    d += 1;
+                    if cand_chars[cand_idx] == self.lc_chars[pat_idx] {
+                        pos.push(cand_idx);
+                        break;
+                    }
                 }
             }
             if hole_start + 1 != d {
@@ -83,6 +110,9 @@ impl FuzzyPattern {
                 nb_holes += 1;
             }
         }
+        if self.anchored_end && start_idx + d != cand_chars.len() {
+            return None;
+        }
         let mut score = BONUS_MATCH;
         score += BONUS_CANDIDATE_LENGTH * (cand_chars.len() as i32);
         score += BONUS_NB_HOLES * (nb_holes as i32);
@@ -111,7 +141,12 @@ impl FuzzyPattern {
         }
         let mut best_score = 0;
         let mut best_match: Option<Match> = None;
-        for start_idx in 0..=cand_chars.len() - self.lc_chars.len() {
+        let last_start_idx = if self.anchored_start {
+            0
+        } else {
+            cand_chars.len() - self.lc_chars.len()
+        };
+        for start_idx in 0..=last_start_idx {
             let sm = self.match_starting_at_index(&cand_chars, start_idx);
             if let Some(m) = sm {
                 if m.score > best_score {
@@ -124,7 +159,7 @@ impl FuzzyPattern {
     }
     // return the number of results we should find before starting to
     //  sort them (unless time is runing out).
-    pub fn optimal_result_number(&self, targeted_size: usize) -> usize {
+    pub(crate) fn optimal_result_number(&self, targeted_size: usize) -> usize {
         20 * targeted_size
     }
 }
@@ -0,0 +1,92 @@
+//! the git repositories, recognized by their `.git` entry, which is
+//!  a directory, or a file telling where the git directory is in the
+//!  case of worktrees and submodules. Git itself is never run.
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// what's checked out in a repository
+#[derive(Debug, PartialEq)]
+pub enum GitHead {
+    Branch(String),
+    Detached(String), // the short hash of the commit
+}
+
+impl fmt::Display for GitHead {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GitHead::Branch(name) => write!(f, "On branch {}", name),
+            GitHead::Detached(hash) => write!(f, "HEAD detached at {}", hash),
+        }
+    }
+}
+
+/// tell whether the directory is the root of a git repository (or
+///  of a worktree or submodule), with one metadata lookup
+pub fn is_repo(dir: &Path) -> bool {
+    fs::symlink_metadata(dir.join(".git")).is_ok()
+}
+
+// the directory holding the HEAD of the repository whose root is dir
+fn git_dir(dir: &Path) -> Option<PathBuf> {
+    let dot_git = dir.join(".git");
+    if dot_git.is_dir() {
+        return Some(dot_git);
+    }
+    // a file like "gitdir: ../.git/worktrees/name"
+    let content = fs::read_to_string(&dot_git).ok()?;
+    let content = content.trim();
+    if !content.starts_with("gitdir:") {
+        return None;
+    }
+    Some(dir.join(content["gitdir:".len()..].trim()))
+}
+
+/// read what's checked out in the repository whose root is dir
+pub fn head(dir: &Path) -> Option<GitHead> {
+    let head = fs::read_to_string(git_dir(dir)?.join("HEAD")).ok()?;
+    let head = head.trim();
+    if head.starts_with("ref: ") {
+        let reference = &head["ref: ".len()..];
+        let name = if reference.starts_with("refs/heads/") {
+            &reference["refs/heads/".len()..]
+        } else {
+            reference
+        };
+        Some(GitHead::Branch(name.to_string()))
+    } else if head.len() >= 7 && head.chars().all(|c| c.is_ascii_hexdigit()) {
+        Some(GitHead::Detached(head[..7].to_string()))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod git_repos_tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn heads() {
+        let dir = tempdir().unwrap();
+        let repo = dir.path().join("repo");
+        fs::create_dir_all(repo.join(".git/worktrees/wt")).unwrap();
+        fs::write(repo.join(".git/HEAD"), "ref: refs/heads/feature/x\n").unwrap();
+        assert!(is_repo(&repo));
+        assert!(!is_repo(dir.path()));
+        assert_eq!(head(&repo), Some(GitHead::Branch("feature/x".to_string())));
+        // a worktree, whose .git is a file
+        let worktree = dir.path().join("wt");
+        fs::create_dir(&worktree).unwrap();
+        fs::write(worktree.join(".git"), "gitdir: ../repo/.git/worktrees/wt\n").unwrap();
+        fs::write(
+            repo.join(".git/worktrees/wt/HEAD"),
+            "0123456789abcdef0123456789abcdef01234567\n",
+        )
+        .unwrap();
+        assert!(is_repo(&worktree));
+        assert_eq!(head(&worktree), Some(GitHead::Detached("0123456".to_string())));
+        assert_eq!(head(dir.path()), None);
+    }
+}
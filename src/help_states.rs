@@ -1,14 +1,17 @@
 //! an application state dedicated to help
 
 use std::io;
+use std::path::PathBuf;
 
 use crate::app::{AppState, AppStateCmdResult};
 use crate::app_context::AppContext;
-use crate::commands::{Action, Command};
-use crate::conf::Conf;
+use crate::build_info;
+use crate::commands::{Action, Command, KeyDescription};
+use crate::conf::{self, Conf};
+use crate::navigation;
 use crate::screen_text::{Text, TextTable};
 use crate::screens::{Screen, ScreenArea};
-use crate::status::Status;
+use crate::status::{self, Status};
 use crate::task_sync::TaskLifetime;
 use crate::verbs::{Verb, VerbExecutor};
 use crate::verb_store::{PrefixSearchResult};
@@ -17,6 +20,13 @@ pub struct HelpState {
     area: ScreenArea, // where the help is drawn
 }
 
+// a line of the table of verbs
+struct VerbRow<'v> {
+    verb: &'v Verb,
+    key: String, // the key doing the verb, if any
+    description: String, // cut to fit in the screen
+}
+
 impl HelpState {
     pub fn new(screen: &Screen) -> HelpState {
         let mut state = HelpState {
@@ -26,7 +36,7 @@ impl HelpState {
         state
     }
     fn resize_area(&mut self, screen: &Screen) {
-        self.area.bottom = screen.h - 2;
+        self.area.bottom = screen.h.saturating_sub(2);
         self.area.width = screen.w;
     }
 }
@@ -39,25 +49,27 @@ impl AppState for HelpState {
         con: &AppContext,
     ) -> io::Result<AppStateCmdResult> {
         self.resize_area(screen);
+        if navigation::scroll_text(&mut self.area, &cmd.action) {
+            return Ok(AppStateCmdResult::Keep);
+        }
         Ok(match &cmd.action {
             Action::Back => AppStateCmdResult::PopState,
             Action::Verb(invocation) => match con.verb_store.search(&invocation.key) {
-                PrefixSearchResult::Match(verb) => self.execute_verb(verb, &invocation, screen, con)?,
+                PrefixSearchResult::Match(verb) => {
+                    let invocation = invocation.clone();
+                    self.execute_or_confirm(verb, &invocation, cmd, screen, con)?
+                }
                 _ => AppStateCmdResult::verb_not_found(&invocation.key),
             },
-            Action::MoveSelection(dy) => {
-                self.area.try_scroll(*dy);
-                AppStateCmdResult::Keep
-            }
-            Action::ScrollPage(dp) => {
-                self.area.try_scroll(*dp * (self.area.height() - 1));
-                AppStateCmdResult::Keep
-            }
             Action::Quit => AppStateCmdResult::Quit,
-            _ => AppStateCmdResult::Keep,
+            _ => self.apply_verb_action(cmd, screen, con)?,
         })
     }
 
+    fn resize(&mut self, screen: &Screen) {
+        self.resize_area(screen);
+    }
+
     fn refresh(
         &mut self,
         _screen: &Screen,
@@ -80,45 +92,114 @@ impl AppState for HelpState {
         text.md(r#" **broot** lets you explore directory trees and launch commands."#);
         text.md(r#" site: https://github.com/Canop/broot."#);
         text.md(r#" broot is best used when launched as `br`."#);
+        text.md(&format!(" This is {}.", build_info::one_line()));
         text.md("");
-        text.md(r#" `<esc>` gets you back to the previous state."#);
+        if let Some(key) = con.verb_store.binding_for("back") {
+            text.md(&format!(" `<{}>` gets you back to the previous state.", key));
+        }
         text.md(r#" Typing some letters searches the tree and selects the most relevant file."#);
         text.md(r#" To use a regular expression, use a slash eg `/j(ava|s)$`."#);
         text.md("");
         text.md(r#" To execute a verb, type a space or `:` then start of its name or shortcut."#);
         text.md(" Verbs:");
-        let mut tbl: TextTable<Verb> = TextTable::new(&screen.skin);
-        tbl.add_col("name", &|verb| &verb.invocation.key);
-        tbl.add_col("shortcut", &|verb| {
-            if let Some(sk) = &verb.shortcut {
+        let mut rows: Vec<VerbRow> = con
+            .verb_store
+            .verbs
+            .iter()
+            .map(|verb| VerbRow {
+                verb,
+                key: con
+                    .verb_store
+                    .bindings_for(&verb.invocation.key)
+                    .iter()
+                    .map(|key| key.to_string())
+                    .collect::<Vec<String>>()
+                    .join(" "),
+                description: verb.description_or_execution().to_string(),
+            })
+            .collect();
+        let mut tbl: TextTable<VerbRow> = TextTable::new(&screen.skin);
+        tbl.add_col("name", &|row| &row.verb.invocation.key);
+        tbl.add_col("shortcut", &|row| {
+            if let Some(sk) = &row.verb.shortcut {
                 &sk
             } else {
                 ""
             }
         });
-        tbl.add_col("description", &|verb: &Verb| {
-            if let Some(s) = &verb.description {
-                &s
-            } else {
-                &verb.execution
+        tbl.add_col("key", &|row| &row.key);
+        tbl.add_col("description", &|row: &VerbRow| &row.description);
+        // the descriptions take the width left by the other columns
+        //  and the borders
+        let col_width = |title: &str, cell: &dyn Fn(&VerbRow) -> usize| {
+            rows.iter().map(cell).max().unwrap_or(0).max(title.len())
+        };
+        let others_width = col_width("name", &|row| row.verb.invocation.key.len())
+            + col_width("shortcut", &|row| row.verb.shortcut.as_ref().map_or(0, |sk| sk.len()))
+            + col_width("key", &|row| row.key.len())
+            + tbl.borders_width();
+        let description_width = (screen.w as usize).saturating_sub(others_width + 1).max(10);
+        for row in &mut rows {
+            row.description = status::fit(&row.description, description_width);
+        }
+        tbl.write(&rows, &mut text);
+        if let Some(leader) = con.chords.leader {
+            if !con.chords.verbs.is_empty() {
+                text.md("");
+                text.md(" Chords (typed when the input is empty):");
+                for (c, invocation) in &con.chords.verbs {
+                    text.md(&format!("  `{} {}` : {}", leader, c, invocation));
+                }
             }
-        });
-        tbl.write(&con.verb_store.verbs, &mut text);
+        }
+        let hot_verbs: Vec<&Verb> = con
+            .verb_store
+            .verbs
+            .iter()
+            .filter(|verb| verb.key.is_some())
+            .collect();
+        if !hot_verbs.is_empty() {
+            text.md("");
+            text.md(" Keys (working even while a pattern is typed):");
+            for verb in hot_verbs {
+                if let Some(key) = verb.key {
+                    text.md(&format!(
+                        "  `{}` : {}",
+                        KeyDescription::Key(key),
+                        verb.invocation.key
+                    ));
+                }
+            }
+        }
+        text.md("");
+        text.md(" Other keys:");
+        for (key, action) in con.verb_store.action_keys() {
+            text.md(&format!("  `{}` : {}", key, action));
+        }
         text.md("");
         text.md(&format!(
             " Verb can be configured in {:?}.",
             Conf::default_location()
         ));
+        text.md(&format!(
+            " The {} skin is used ({}).",
+            screen.skin.palette, screen.skin.palette_source,
+        ));
         text.md("");
         text.md(" Some options can be set on launch:");
         text.md("  `-h` or `--hidden` : show hidden files");
         text.md("  `-f` or `--only-folders` : only show folders");
         text.md("  `-s` or `--sizes` : display sizes");
+        text.md("  `-p` or `--permissions` : display permissions");
+        text.md("  `--owner` : display the user and group owning the files");
+        text.md("  `--skin dark` or `--skin light` : colors for the terminal background");
         text.md("  (for the complete list, run `broot --help`)");
         text.md("");
         text.md(" Flags are displayed at bottom right:");
         text.md("  `h:y` or `h:n` : whether hidden files are shown");
         text.md("  `gi:a`, `gi:y`, `gi:n` : gitignore on auto, yes or no");
+        text.md("  `p:y` or `p:n` : whether permissions are shown");
+        text.md("  `o:y` or `o:n` : whether owners are shown");
         text.md("  When gitignore is auto, .gitignore rules are respected if");
         text.md("   the displayed root is a git repository or in one.");
         self.area.content_length = text.height() as i32;
@@ -129,32 +210,33 @@ impl AppState for HelpState {
 
     fn write_status(&self, screen: &mut Screen, cmd: &Command, con: &AppContext) -> io::Result<()> {
         match &cmd.action {
-            Action::VerbEdit(invocation) => match con.verb_store.search(&invocation.key) {
-                PrefixSearchResult::NoMatch => screen.write_status_err("No matching verb)"),
-                PrefixSearchResult::Match(verb) => {
-                    if let Some(err) = verb.match_error(invocation) {
-                        screen.write_status_err(&err)
-                    } else {
-                        screen.write_status_text(
-                            &format!(
-                                "Hit <enter> to {} : {}",
-                                &verb.invocation.key,
-                                &verb.description_for(Conf::default_location(), &invocation.args)
-                            )
-                            .to_string()
-                        )
-                    }
-                }
-                PrefixSearchResult::TooManyMatches => {
-                    screen.write_status_text("Type a verb then <enter> to execute it")
-                }
+            Action::Confirm(invocation) => screen.write_status_err(&con.verb_store.confirmation_status(
+                invocation,
+                &Conf::default_location(),
+                &conf::dir(),
+                con.relative_base(),
+            )),
+            Action::VerbEdit(invocation) => match con.verb_store.edition_status(
+                invocation,
+                &Conf::default_location(),
+                &conf::dir(),
+                con.relative_base(),
+            ) {
+                Ok(status) => screen.write_status_text(&status),
+                Err(status) => screen.write_status_err(&status),
             },
-            _ => screen
-                .write_status_text("Hit <esc> to get back to the tree, or a space to start a verb"),
+            _ => screen.write_status_text(&format!(
+                "Hit {} to get back to the tree, or a space to start a verb",
+                con.verb_store.hint_for("back"),
+            )),
         }
     }
 
     fn write_flags(&self, _screen: &mut Screen, _con: &AppContext) -> io::Result<()> {
         Ok(())
     }
+
+    fn selected_path(&self) -> Option<PathBuf> {
+        None
+    }
 }
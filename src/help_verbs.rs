@@ -2,20 +2,23 @@ use std::io;
 
 use crate::app::AppStateCmdResult;
 use crate::app_context::AppContext;
+use crate::build_info;
 use crate::browser_states::BrowserState;
 use crate::commands::Command;
 use crate::conf::{self, Conf};
-use crate::external::{self, Launchable};
+use crate::external;
+use crate::filesystems_states::FilesystemsState;
 use crate::help_states::HelpState;
+use crate::internals::Internal;
 use crate::screens::Screen;
 use crate::task_sync::TaskLifetime;
 use crate::tree_options::TreeOptions;
 use crate::verb_invocation::VerbInvocation;
-use crate::verbs::{Verb, VerbExecutor};
+use crate::verbs::{Verb, VerbExecution, VerbExecutor};
 
 impl VerbExecutor for HelpState {
     fn execute_verb(
-        &self,
+        &mut self,
         verb: &Verb,
         invocation: &VerbInvocation,
         screen: &mut Screen,
@@ -24,30 +27,74 @@ impl VerbExecutor for HelpState {
         if let Some(err) = verb.match_error(invocation) {
             return Ok(AppStateCmdResult::DisplayError(err));
         }
-        Ok(match verb.execution.as_ref() {
-            ":back" => AppStateCmdResult::PopState,
-            ":focus" | ":parent" => AppStateCmdResult::from_optional_state(
-                BrowserState::new(
+        let internal = match &verb.execution {
+            VerbExecution::Internal(internal) => *internal,
+            VerbExecution::External(_) => {
+                let launchable = verb.to_launchable(
+                    &Conf::ensure_default_file()?,
+                    &conf::dir(),
+                    &invocation.args,
+                )?;
+                return Ok(AppStateCmdResult::Launch {
+                    launchable,
+                    quit: true,
+                });
+            }
+        };
+        Ok(match internal {
+            Internal::Back => AppStateCmdResult::PopState,
+            Internal::EditConf => external::edit_conf()?,
+            Internal::Filesystems => FilesystemsState::new_state_cmd_result(),
+            Internal::Focus | Internal::Parent => AppStateCmdResult::from_optional_state(
+                BrowserState::new_root(
                     conf::dir(),
                     TreeOptions::new(),
+                    con,
                     screen,
                     &TaskLifetime::unlimited(),
                 ),
                 Command::new(),
             ),
-            ":help" => AppStateCmdResult::Keep,
-            ":open" => AppStateCmdResult::Launch(Launchable::opener(Conf::default_location())),
-            ":print_path" => external::print_path(&Conf::default_location(), con)?,
-            ":quit" => AppStateCmdResult::Quit,
-            _ => {
-                if verb.execution.starts_with(":toggle") {
-                    AppStateCmdResult::PopStateAndReapply
-                } else {
-                    AppStateCmdResult::Launch(Launchable::program(
-                        verb.exec_token(&Conf::default_location(), &invocation.args),
-                    )?)
-                }
+            Internal::Help => AppStateCmdResult::Keep,
+            Internal::Open | Internal::OpenLater | Internal::OpenLeave | Internal::OpenStay => {
+                external::open_file(Conf::ensure_default_file()?, internal, con)?
+            }
+            Internal::PrintPath => {
+                external::print_path(&Conf::ensure_default_file()?, con.relative_paths, con)?
             }
+            Internal::PrintRelativePath => external::print_path(&Conf::ensure_default_file()?, true, con)?,
+            Internal::Quit => AppStateCmdResult::Quit,
+            Internal::Refresh => AppStateCmdResult::RefreshState,
+            Internal::ToggleScreen => AppStateCmdResult::ShowTerminal,
+            Internal::Version => AppStateCmdResult::DisplayMessage(build_info::one_line()),
+            // the verbs which need a tree are applied to the previous state
+            Internal::All
+            | Internal::Chmod
+            | Internal::Cp
+            | Internal::Dedup
+            | Internal::GotoLine
+            | Internal::Hardlink
+            | Internal::Hints
+            | Internal::Mkdir
+            | Internal::Mv
+            | Internal::NextSameDepth
+            | Internal::Paste
+            | Internal::PasteOverwrite
+            | Internal::PreviousSameDepth
+            | Internal::Size
+            | Internal::Touch
+            | Internal::ToggleDates
+            | Internal::ToggleFiles
+            | Internal::ToggleFsInfo
+            | Internal::ToggleGitIgnore
+            | Internal::ToggleHidden
+            | Internal::ToggleLineNumbers
+            | Internal::ToggleOwner
+            | Internal::TogglePercent
+            | Internal::TogglePerm
+            | Internal::ToggleSizes
+            | Internal::ToggleTrimRoot
+            | Internal::TotalSearch => AppStateCmdResult::PopStateAndReapply,
         })
     }
 }
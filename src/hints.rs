@@ -0,0 +1,53 @@
+//! the labels of the hint mode, shown over the visible lines so
+//!  that one of them is selected by typing its label
+
+// the letters of the labels, the ones of the home row first
+const LETTERS: &str = "asdfghjklqwertyuiopzxcvbnm";
+
+/// build the labels of count lines, with no label starting another
+///  one: the first letters are labels, the last ones start two-letter
+///  labels. There are at most 676 labels
+pub fn labels(count: usize) -> Vec<String> {
+    let letters: Vec<char> = LETTERS.chars().collect();
+    let n = letters.len();
+    // each letter starting two-letter labels gives n-1 more labels
+    let nb_prefixes = if count <= n {
+        0
+    } else {
+        ((count - n + n - 2) / (n - 1)).min(n)
+    };
+    let mut labels: Vec<String> = letters[..n - nb_prefixes]
+        .iter()
+        .map(|c| c.to_string())
+        .collect();
+    for prefix in &letters[n - nb_prefixes..] {
+        for c in &letters {
+            labels.push(format!("{}{}", prefix, c));
+        }
+    }
+    labels.truncate(count);
+    labels
+}
+
+#[cfg(test)]
+mod hints_tests {
+    use super::*;
+
+    #[test]
+    fn labels_are_short_and_unambiguous() {
+        assert_eq!(labels(3), vec!["a", "s", "d"]);
+        assert!(labels(26).iter().all(|label| label.len() == 1));
+        for &count in &[27, 50, 676] {
+            let labels = labels(count);
+            assert_eq!(labels.len(), count);
+            for (i, a) in labels.iter().enumerate() {
+                for (j, b) in labels.iter().enumerate() {
+                    assert!(i == j || !b.starts_with(a.as_str()), "{} starts {}", a, b);
+                }
+            }
+        }
+        // only one letter starts two-letter labels for 50 lines
+        assert_eq!(labels(50).iter().filter(|label| label.len() == 1).count(), 25);
+        assert_eq!(labels(1000).len(), 676);
+    }
+}
@@ -1,6 +1,7 @@
 /// displays the "input" at the bottom of the screen
 /// (reading is managed in the app module)
 use std::io::{self, Write};
+use std::ops::Range;
 
 use crate::commands::Command;
 use crate::screens::Screen;
@@ -9,16 +10,81 @@ pub trait Input {
     fn write_input(&mut self, cmd: &Command) -> io::Result<()>;
 }
 
+// the index in raw of the first char to show when only width chars
+//  are available: the start of a too long input is replaced with a '…',
+//  so that its end, where the cursor is, stays visible
+fn visible_start(raw: &str, width: usize) -> usize {
+    let len = raw.chars().count();
+    if len <= width {
+        0
+    } else {
+        let nb_hidden = len + 1 - width.max(1);
+        raw.char_indices().nth(nb_hidden).map_or(raw.len(), |(idx, _)| idx)
+    }
+}
+
+// the range of raw written with the input_verb color: the separator
+//  and the key of the verb invocation, when the input is parsed as one
+fn verb_range(cmd: &Command) -> Option<Range<usize>> {
+    cmd.verb_invocation().map(|(start, invocation)| {
+        let verb_part = &cmd.raw[start..];
+        let key_start = verb_part.len()
+            - verb_part
+                .trim_start_matches(|c: char| c == ':' || c.is_whitespace())
+                .len();
+        let forced_len = if invocation.forced { 1 } else { 0 };
+        let key_end = (key_start + invocation.key.len() + forced_len).min(verb_part.len());
+        start..start + key_end
+    })
+}
+
 impl Input for Screen {
+    // the input is prefixed with a '›' while it's a pattern. When it's
+    //  parsed as a verb invocation, the separator and the key are
+    //  written with the input_verb color
     fn write_input(&mut self, cmd: &Command) -> io::Result<()> {
+        if self.is_too_small() {
+            return Ok(());
+        }
+        // a pending chord is shown like "g-"
+        let chord = match cmd.pending_chord() {
+            Some(leader) => format!("{}-", leader),
+            None => String::new(),
+        };
         write!(
             self.stderr,
-            "{}{}{}{}{}{} {}",
-            termion::cursor::Goto(1, self.h),
+            "{}{}{}{}",
+            self.goto(1, self.h),
             self.skin.input.fg,
             self.skin.input.bg,
             termion::clear::CurrentLine,
-            cmd.raw,
+        )?;
+        let colored = match verb_range(cmd) {
+            Some(range) => {
+                write!(self.stderr, "  ")?;
+                range
+            }
+            None => {
+                write!(self.stderr, "{}›{} ", self.skin.input_prompt.fg, self.skin.input.fg)?;
+                0..0
+            }
+        };
+        // the prompt, the chord and the cursor take some of the width
+        let width = (self.w as usize).saturating_sub(3 + chord.chars().count());
+        let shown = visible_start(&cmd.raw, width);
+        if shown > 0 {
+            write!(self.stderr, "…")?;
+        }
+        let colored = colored.start.max(shown)..colored.end.max(shown);
+        write!(
+            self.stderr,
+            "{}{}{}{}{}{}{} {}",
+            &cmd.raw[shown..colored.start],
+            self.skin.input_verb.fg,
+            &cmd.raw[colored.clone()],
+            self.skin.input.fg,
+            &cmd.raw[colored.end..],
+            chord,
             termion::style::Invert,
             termion::style::NoInvert,
         )?;
@@ -26,3 +92,43 @@ impl Input for Screen {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod input_tests {
+    use super::*;
+
+    fn shown(raw: &str, width: usize) -> String {
+        let start = visible_start(raw, width);
+        if start > 0 {
+            format!("…{}", &raw[start..])
+        } else {
+            raw.to_string()
+        }
+    }
+
+    #[test]
+    fn long_inputs_are_cut_at_start() {
+        assert_eq!(shown("abc", 5), "abc");
+        assert_eq!(shown("abcde", 5), "abcde");
+        assert_eq!(shown("abcdef", 5), "…cdef");
+        assert_eq!(shown("a:mv some/long/path", 8), "…ng/path");
+        assert_eq!(shown("éàùçô", 3), "…çô");
+        assert_eq!(shown("abc", 1), "…");
+    }
+
+    #[test]
+    fn verbs_are_told_apart_from_patterns() {
+        let colored = |raw: &str| {
+            let cmd = Command::from(raw.to_string());
+            verb_range(&cmd).map(|range| cmd.raw[range].to_string())
+        };
+        assert_eq!(colored(""), None);
+        assert_eq!(colored("abc"), None);
+        assert_eq!(colored("w/two words"), None);
+        assert_eq!(colored(":"), Some(":".to_string()));
+        assert_eq!(colored(":rm"), Some(":rm".to_string()));
+        assert_eq!(colored("abc:mv! dest"), Some(":mv!".to_string()));
+        assert_eq!(colored("abc cp dest"), Some(" cp".to_string()));
+        assert_eq!(colored("w/two words:focus"), Some(":focus".to_string()));
+    }
+}
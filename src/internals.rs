@@ -0,0 +1,137 @@
+//! The internal behaviors a verb can have, instead of the
+//!  execution of an external program (eg `:focus` or `:toggle_hidden`).
+//! They're parsed when the verbs are loaded so that the executors
+//!  can match on them exhaustively.
+
+use std::str::FromStr;
+
+use crate::errors::ConfError;
+
+macro_rules! Internals {
+    (
+        $($name:ident: $key:literal,)*
+    ) => {
+        #[derive(Debug, Clone, Copy, PartialEq)]
+        pub enum Internal {
+            $($name,)*
+        }
+        impl Internal {
+            /// all the internals, in the order of their declaration
+            #[cfg(test)]
+            pub const ALL: &'static [Internal] = &[$(Internal::$name,)*];
+            /// the key of the internal, eg "toggle_hidden"
+            pub fn key(self) -> &'static str {
+                match self {
+                    $(Internal::$name => $key,)*
+                }
+            }
+            /// the execution pattern calling the internal, eg ":toggle_hidden"
+            pub fn execution(self) -> &'static str {
+                match self {
+                    $(Internal::$name => concat!(":", $key),)*
+                }
+            }
+        }
+        impl FromStr for Internal {
+            type Err = ConfError;
+            fn from_str(key: &str) -> Result<Internal, ConfError> {
+                match key {
+                    $($key => Ok(Internal::$name),)*
+                    _ => Err(ConfError::UnknownInternal {
+                        key: key.to_string(),
+                    }),
+                }
+            }
+        }
+    }
+}
+
+Internals! {
+    All: "all",
+    Back: "back",
+    Chmod: "chmod",
+    Cp: "cp",
+    Dedup: "dedup",
+    EditConf: "edit_conf",
+    Filesystems: "filesystems",
+    Focus: "focus",
+    GotoLine: "goto_line",
+    Hardlink: "hardlink",
+    Help: "help",
+    Hints: "hints",
+    Mkdir: "mkdir",
+    Mv: "mv",
+    NextSameDepth: "next_same_depth",
+    Open: "open",
+    OpenLater: "open_later",
+    OpenLeave: "open_leave",
+    OpenStay: "open_stay",
+    Parent: "parent",
+    Paste: "paste",
+    PasteOverwrite: "paste!",
+    PreviousSameDepth: "previous_same_depth",
+    PrintPath: "print_path",
+    PrintRelativePath: "print_relative_path",
+    Quit: "quit",
+    Refresh: "refresh",
+    Size: "size",
+    Touch: "touch",
+    ToggleDates: "toggle_dates",
+    ToggleFiles: "toggle_files",
+    ToggleFsInfo: "toggle_fs_info",
+    ToggleGitIgnore: "toggle_git_ignore",
+    ToggleHidden: "toggle_hidden",
+    ToggleLineNumbers: "toggle_line_numbers",
+    ToggleOwner: "toggle_owner",
+    TogglePercent: "toggle_percent",
+    TogglePerm: "toggle_perm",
+    ToggleScreen: "toggle_screen",
+    ToggleSizes: "toggle_sizes",
+    ToggleTrimRoot: "toggle_trim_root",
+    TotalSearch: "total_search",
+    Version: "version",
+}
+
+#[cfg(test)]
+mod internal_parsing_tests {
+    use super::*;
+    use crate::conf::Conf;
+    use crate::verb_store::VerbStore;
+    use crate::verbs::VerbExecution;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn keys_round_trip() {
+        for internal in Internal::ALL {
+            assert_eq!(internal.key().parse::<Internal>().unwrap(), *internal);
+            assert_eq!(internal.execution(), format!(":{}", internal.key()));
+        }
+    }
+
+    #[test]
+    fn builtins_are_parsed() {
+        // a conf without user defined verbs
+        let dir = tempdir().unwrap();
+        let conf_path = dir.path().join("conf.toml");
+        fs::write(&conf_path, "").unwrap();
+        let mut verb_store = VerbStore::new();
+        verb_store.init(&Conf::from_file(&conf_path).unwrap());
+        let mut nb_internals = 0;
+        for verb in &verb_store.verbs {
+            if let VerbExecution::Internal(internal) = verb.execution {
+                assert_eq!(internal.key(), verb.invocation.key);
+                nb_internals += 1;
+            }
+        }
+        // every internal is reachable with a built-in verb
+        assert_eq!(nb_internals, Internal::ALL.len());
+    }
+
+    #[test]
+    fn unknown_key() {
+        assert!("toggle_everything".parse::<Internal>().is_err());
+        assert!(VerbExecution::from(":toggle_everything").is_err());
+        assert!(VerbExecution::from("less {file}").is_ok());
+    }
+}
@@ -0,0 +1,42 @@
+//! job control: when broot is stopped, with ctrl-z or by a SIGTSTP
+//!  sent by another process, the terminal is given back to the shell
+//!  as it was, and it's taken again when broot is continued (eg by `fg`).
+//!
+//! The SIGTSTP signal is caught so that the main loop may restore
+//!  the terminal before really stopping.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static STOP_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn on_tstp(_: libc::c_int) {
+    STOP_REQUESTED.store(true, Ordering::SeqCst);
+}
+
+fn tstp_handler() -> libc::sighandler_t {
+    on_tstp as extern "C" fn(libc::c_int) as libc::sighandler_t
+}
+
+/// catch the SIGTSTP signals instead of being stopped with
+///  the terminal still in raw mode
+pub fn catch_stop_signal() {
+    unsafe {
+        libc::signal(libc::SIGTSTP, tstp_handler());
+    }
+}
+
+/// tell whether a SIGTSTP was received since the last call
+pub fn take_stop_request() -> bool {
+    STOP_REQUESTED.swap(false, Ordering::SeqCst)
+}
+
+/// stop the process, as the default handling of SIGTSTP would,
+///  and return once it's continued
+pub fn stop() {
+    unsafe {
+        libc::signal(libc::SIGTSTP, libc::SIG_DFL);
+        libc::raise(libc::SIGTSTP);
+        // we're here after a SIGCONT
+        libc::signal(libc::SIGTSTP, tstp_handler());
+    }
+}
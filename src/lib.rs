@@ -0,0 +1,56 @@
+//! broot's core: the building of a tree of files, filtered and
+//!  scored by a pattern or a list of paths, as the broot application
+//!  shows it, and the computation of the sizes of directories.
+//!
+//! Nothing here depends on a terminal, so that other tools may build
+//!  and display the trees their own way:
+//!
+//! ```
+//! use std::fs;
+//! use broot::patterns::Pattern;
+//! use broot::tree_build::build_tree;
+//! use broot::tree_options::TreeOptions;
+//!
+//! let dir = tempfile::tempdir().unwrap();
+//! fs::create_dir(dir.path().join("src")).unwrap();
+//! fs::write(dir.path().join("src/main.rs"), "fn main() {}").unwrap();
+//! fs::write(dir.path().join("README.md"), "# readme").unwrap();
+//!
+//! let mut options = TreeOptions::new();
+//! options.show_parent_line = false;
+//! options.pattern = Pattern::fuzzy("main");
+//! let tree = build_tree(dir.path().to_path_buf(), options, 20).unwrap();
+//! // the first line is the root, then come the matching entries
+//! //  and their ancestors, in the order of display
+//! let lines: Vec<(usize, &str)> = tree.lines[1..]
+//!     .iter()
+//!     .map(|line| (line.depth, line.name.as_str()))
+//!     .collect();
+//! assert_eq!(lines, vec![(1, "src"), (2, "main.rs")]);
+//! ```
+//!
+//! The rendering and the interactive application are in the broot
+//!  binary, which uses this crate.
+
+#[macro_use]
+extern crate lazy_static;
+#[macro_use]
+extern crate log;
+
+mod access;
+pub mod collisions;
+pub mod duplicates;
+pub mod file_sizes;
+pub mod flat_tree;
+pub mod fuzzy_patterns;
+mod git_ignore;
+pub mod git_repos;
+mod natural_sort;
+pub mod path_list;
+pub mod patterns;
+pub mod regex_patterns;
+pub mod task_sync;
+pub mod tree_build;
+pub mod tree_options;
+pub mod words_patterns;
+pub mod xattrs;
@@ -8,43 +8,64 @@ extern crate log;
 
 mod app;
 mod app_context;
+mod breadcrumbs;
 mod browser_states;
 mod browser_verbs;
+mod build_info;
 mod cli;
 mod commands;
 mod conf;
+mod dates;
+mod dedup_states;
+mod dedup_verbs;
 mod errors;
 mod external;
-mod file_sizes;
-mod flat_tree;
-mod fuzzy_patterns;
-mod git_ignore;
+mod filesystems_states;
+mod filesystems_verbs;
 mod help_states;
 mod help_verbs;
+mod hints;
 mod input;
-mod patterns;
-mod regex_patterns;
+mod internals;
+mod job_control;
+mod mounts;
+mod navigation;
+mod owners;
+mod path_completion;
+mod permissions;
+mod profile;
 mod screen_text;
 mod screens;
+mod selection_export;
 mod shell_bash;
 mod shell_fish;
 mod shell_install;
 mod skin;
 mod skin_conf;
+mod special_paths;
 mod spinner;
 mod status;
-mod task_sync;
-mod tree_build;
-mod tree_options;
+mod term_events;
 mod tree_views;
 mod verbs;
 mod verb_invocation;
 mod verb_store;
+mod yank;
+
+#[cfg(test)]
+mod app_tests;
+
+// the core modules, in the broot library
+use broot::{
+    collisions, duplicates, file_sizes, flat_tree, git_repos, path_list, patterns,
+    task_sync, tree_build, tree_options,
+};
 
 use log::LevelFilter;
 use simplelog;
 use std::env;
 use std::fs::File;
+use std::io;
 use std::result::Result;
 use std::str::FromStr;
 
@@ -53,7 +74,9 @@ use crate::app_context::AppContext;
 use crate::conf::Conf;
 use crate::errors::ProgramError;
 use crate::external::Launchable;
+use crate::skin::Palette;
 use crate::verb_store::VerbStore;
+use crate::verbs::Verb;
 
 // There's no log unless the BROOT_LOG environment variable is set to
 //  a valid log level (trace, debug, info, warn, error, off)
@@ -80,23 +103,99 @@ fn configure_log() {
     }
 }
 
-// run the application, and maybe return a launchable
-// which must be run after broot
-fn run() -> Result<Option<Launchable>, ProgramError> {
+// run the application, and return the launchables which
+// must be run after broot, in order
+fn run() -> Result<Vec<Launchable>, ProgramError> {
     configure_log();
-    let launch_args = cli::read_lauch_args()?;
-    let should_quit = shell_install::init(&launch_args)?;
+    let mut launch_args = cli::read_lauch_args()?;
+    if let Some(shell) = launch_args.completions {
+        print!("{}", cli::completion_script(shell));
+        return Ok(Vec::new());
+    }
+    // the warnings are told in the status until the first key
+    let launch_warnings = launch_args.check()?;
+    for warning in &launch_warnings {
+        warn!("launch args: {}", warning);
+    }
+    // the interface is drawn on stderr, while stdout may be redirected
+    //  to receive the output of :print_path
+    if launch_args.profile.is_none() && !termion::is_tty(&io::stderr()) {
+        return Err(ProgramError::NotATerminal);
+    }
+    // there's no need to install the br function when just profiling, and
+    //  its questions mustn't be written on the stdout of a picker
+    let should_quit = launch_args.profile.is_none()
+        && !launch_args.picker
+        && shell_install::init(&launch_args)?;
     if should_quit {
-        return Ok(None);
+        return Ok(Vec::new());
     }
     let mut verb_store = VerbStore::new();
     let config = Conf::from_default_location()?;
     verb_store.init(&config);
+    launch_args.tree_options.size_display = config.size_display;
+    launch_args.tree_options.size_bar_base = config.size_bar_base;
+    launch_args.tree_options.size_bar = config.size_bar;
+    launch_args.tree_options.size_percent = config.size_percent;
+    launch_args.tree_options.date_format = config.date_format;
+    launch_args.tree_options.show_parent_line = config.show_parent_line;
+    launch_args.tree_options.flatten_chains = config.flatten_chains;
+    launch_args.tree_options.show_fs_info = config.show_fs_info;
+    launch_args.tree_options.read_xattrs = config.read_xattrs;
+    launch_args.tree_options.detect_git_repos = config.detect_git_repos;
+    launch_args.tree_options.detect_collisions = config.detect_collisions;
+    if !config.lookup_users {
+        launch_args.tree_options.lookup_users = false;
+    }
+    launch_args.tree_options.max_children = config.max_children;
+    launch_args.tree_options.max_children_shown = config.max_children_shown;
+    launch_args.tree_options.balance = config.balance;
+    launch_args.tree_options.sort = config.sort;
+    launch_args.tree_options.dirs_first = config.dirs_first;
+    launch_args.tree_options.max_visible_depth = config.max_visible_depth;
+    if let Some(nb_runs) = launch_args.profile {
+        for warning in &launch_warnings {
+            eprintln!("Warning: {}", warning);
+        }
+        profile::run(&launch_args, nb_runs)?;
+        return Ok(Vec::new());
+    }
+    let nesting_depth = external::nesting_depth();
+    external::check_nesting_depth(nesting_depth, config.max_nesting_depth)?;
+    let file_opener = match config.file_opener {
+        Some(execution) => {
+            match Verb::create_external("open", None, execution, None, false, true, false) {
+                Ok(verb) => Some(verb),
+                Err(e) => {
+                    eprintln!("Invalid file_opener: {:?}", e);
+                    None
+                }
+            }
+        }
+        None => None,
+    };
     let context = AppContext {
         launch_args,
+        launch_warnings,
         verb_store,
+        file_opener,
+        enter_on_file: config.enter_on_file,
+        paste_conflict: config.paste_conflict,
+        chords: config.chords,
+        same_depth_jump_to_parent: config.same_depth_jump_to_parent,
+        launch_dir: env::current_dir()?,
+        relative_paths: config.relative_paths,
+        cd_on_quit: config.cd_on_quit,
+        two_line_status: config.two_line_status,
+        terminal_title: config.terminal_title,
+        stack_breadcrumb: config.stack_breadcrumb,
+        nesting_depth,
+        watch_changes: config.watch_changes,
+        special_paths: config.special_paths,
     };
-    let skin = skin::Skin::create(config.skin_entries);
+    let (palette, palette_source) =
+        Palette::choose(context.launch_args.skin_palette, config.skin_palette);
+    let skin = skin::Skin::create(palette, palette_source, config.skin_entries);
     App::new().run(&context, skin)
 }
 
@@ -104,19 +203,43 @@ fn main() {
     let res = match run() {
         Ok(res) => res,
         Err(e) => {
-            // this usually happens when the passed path isn't of a directory
+            // this usually happens when the passed path can't be used as root
             warn!("Error: {}", e);
-            eprintln!("{}", e);
-            return;
+            match e {
+                // there's nowhere to write the error
+                ProgramError::TerminalLost => {}
+                // a cancelled pick only shows in the exit code
+                ProgramError::NothingPicked => {}
+                _ => eprintln!("{}", e),
+            }
+            std::process::exit(1);
         }
     };
-    if let Some(launchable) = res {
+    // the queued launchables are all executed, even when one fails, and
+    //  broot's exit code tells the shell about the first failure
+    let mut exit_code = None;
+    for launchable in res {
         info!("launching {:?}", &launchable);
         if let Err(e) = launchable.execute() {
             warn!("Failed to launch {:?}", &launchable);
             warn!("Error: {:?}", e);
-            eprintln!("{}", e);
+            let code = match &e {
+                ProgramError::ProgramFailed { status, .. } => {
+                    if termion::is_tty(&io::stderr()) {
+                        eprintln!("{}", e);
+                    }
+                    status.code().unwrap_or(1)
+                }
+                _ => {
+                    eprintln!("{}", e);
+                    1
+                }
+            };
+            exit_code = exit_code.or(Some(code));
         }
     }
+    if let Some(code) = exit_code {
+        std::process::exit(code);
+    }
     info!("bye");
 }
@@ -0,0 +1,253 @@
+//! Reading the mounted filesystems (from /proc/mounts) and
+//!  their usage (with statvfs)
+
+use std::ffi::CString;
+use std::fs;
+use std::io;
+use std::mem;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+use crate::file_sizes::Size;
+
+const MOUNTS_FILE: &str = "/proc/mounts";
+
+// the types of the filesystems which don't store files
+const PSEUDO_FS_TYPES: &[&str] = &[
+    "autofs",
+    "binfmt_misc",
+    "bpf",
+    "cgroup",
+    "cgroup2",
+    "configfs",
+    "debugfs",
+    "devpts",
+    "efivarfs",
+    "fusectl",
+    "hugetlbfs",
+    "mqueue",
+    "nsfs",
+    "proc",
+    "pstore",
+    "rpc_pipefs",
+    "securityfs",
+    "selinuxfs",
+    "sysfs",
+    "tracefs",
+];
+
+/// the space of a filesystem, as seen by a normal user
+#[derive(Debug, Clone, Copy)]
+pub struct MountStats {
+    pub size: Size,
+    pub used: Size,
+    pub available: Size, // what's left for unprivileged users
+}
+
+/// a mounted filesystem
+#[derive(Debug, Clone)]
+pub struct Mount {
+    pub device: String,
+    pub mount_point: PathBuf,
+    pub fs_type: String,
+    pub stats: Option<MountStats>, // None when statvfs failed
+}
+
+impl MountStats {
+    pub fn of(mount_point: &Path) -> io::Result<MountStats> {
+        let c_path = CString::new(mount_point.as_os_str().as_bytes())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let mut vfs: libc::statvfs = unsafe { mem::zeroed() };
+        if unsafe { libc::statvfs(c_path.as_ptr(), &mut vfs) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let bsize = vfs.f_frsize as u64;
+        let size = (vfs.f_blocks as u64).saturating_mul(bsize);
+        let free = (vfs.f_bfree as u64).saturating_mul(bsize);
+        Ok(MountStats {
+            size: Size::from(size),
+            used: Size::from(size - free.min(size)),
+            available: Size::from((vfs.f_bavail as u64).saturating_mul(bsize)),
+        })
+    }
+    /// the used part, as a percentage of what's usable (like df does)
+    pub fn use_percent(self) -> u64 {
+        // computed on u128, where the sums can't overflow
+        let used = Into::<u64>::into(self.used) as u128;
+        let available = Into::<u64>::into(self.available) as u128;
+        if used + available == 0 {
+            0
+        } else {
+            ((100 * used + used + available - 1) / (used + available)) as u64
+        }
+    }
+}
+
+impl Mount {
+    pub fn is_pseudo(&self) -> bool {
+        PSEUDO_FS_TYPES.contains(&self.fs_type.as_str())
+    }
+}
+
+// the fields of /proc/mounts have their spaces, tabs, newlines and
+//  backslashes written as octal escapes, eg "\040"
+fn unescape(field: &str) -> String {
+    let bytes = field.as_bytes();
+    let mut unescaped = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'\\' && i + 3 < bytes.len() {
+            let code = &bytes[i + 1..i + 4];
+            if code.iter().all(|b| (b'0'..=b'7').contains(b)) {
+                unescaped.push(code.iter().fold(0u32, |v, b| v * 8 + u32::from(b - b'0')) as u8);
+                i += 4;
+                continue;
+            }
+        }
+        unescaped.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&unescaped).to_string()
+}
+
+/// parse the content of /proc/mounts (without the stats).
+/// A filesystem mounted several times on the same point is only kept once.
+pub fn parse_mounts(content: &str) -> Vec<Mount> {
+    let mut mounts: Vec<Mount> = Vec::new();
+    for line in content.lines() {
+        let mut fields = line.split_whitespace();
+        let (device, mount_point, fs_type) = match (fields.next(), fields.next(), fields.next()) {
+            (Some(device), Some(mount_point), Some(fs_type)) => (device, mount_point, fs_type),
+            _ => {
+                continue; // not a valid line
+            }
+        };
+        let mount = Mount {
+            device: unescape(device),
+            mount_point: PathBuf::from(unescape(mount_point)),
+            fs_type: fs_type.to_string(),
+            stats: None,
+        };
+        // a later mount on the same point hides the previous one
+        mounts.retain(|m| m.mount_point != mount.mount_point);
+        mounts.push(mount);
+    }
+    mounts
+}
+
+/// read the mounted filesystems, with their stats, sorted by mount point
+pub fn read_mounts() -> io::Result<Vec<Mount>> {
+    let mut mounts = parse_mounts(&fs::read_to_string(MOUNTS_FILE)?);
+    for mount in &mut mounts {
+        if !mount.is_pseudo() {
+            mount.stats = MountStats::of(&mount.mount_point).ok();
+        }
+    }
+    mounts.sort_by(|a, b| a.mount_point.cmp(&b.mount_point));
+    Ok(mounts)
+}
+
+/// the mount containing the given path, that is the one whose mount
+///  point is the longest ancestor of the (canonical) path
+pub fn find_mount<'m>(mounts: &'m [Mount], path: &Path) -> Option<&'m Mount> {
+    mounts
+        .iter()
+        .filter(|m| path.starts_with(&m.mount_point))
+        .max_by_key(|m| m.mount_point.components().count())
+}
+
+/// the filesystem containing the given path, with its stats
+pub fn mount_of(path: &Path) -> io::Result<Mount> {
+    let path = fs::canonicalize(path)?;
+    let mounts = parse_mounts(&fs::read_to_string(MOUNTS_FILE)?);
+    let mut mount = match find_mount(&mounts, &path) {
+        Some(mount) => mount.clone(),
+        None => {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "no mount point found"));
+        }
+    };
+    mount.stats = Some(MountStats::of(&path)?);
+    Ok(mount)
+}
+
+#[cfg(test)]
+mod mounts_parsing_tests {
+    use super::*;
+
+    const PROC_MOUNTS: &str = "\
+sysfs /sys sysfs rw,nosuid,nodev,noexec,relatime 0 0
+proc /proc proc rw,nosuid,nodev,noexec,relatime 0 0
+/dev/nvme0n1p2 / ext4 rw,relatime,errors=remount-ro 0 0
+cgroup2 /sys/fs/cgroup cgroup2 rw,nosuid,nodev,noexec,relatime 0 0
+tmpfs /run/user/1000 tmpfs rw,nosuid,nodev,relatime,size=1620540k,mode=700 0 0
+/dev/sdb1 /media/dys/My\\040Book ext4 rw,nosuid,nodev,relatime 0 0
+incomplete line
+/dev/nvme0n1p1 /boot/efi vfat rw,relatime,fmask=0077 0 0
+/dev/sdc1 /boot/efi ext4 rw,relatime 0 0
+";
+
+    #[test]
+    fn fields_are_read() {
+        let mounts = parse_mounts(PROC_MOUNTS);
+        assert_eq!(mounts.len(), 7);
+        let root = &mounts[2];
+        assert_eq!(root.device, "/dev/nvme0n1p2");
+        assert_eq!(root.mount_point, PathBuf::from("/"));
+        assert_eq!(root.fs_type, "ext4");
+        assert!(root.stats.is_none());
+    }
+
+    #[test]
+    fn escaped_spaces() {
+        let mounts = parse_mounts(PROC_MOUNTS);
+        assert!(mounts
+            .iter()
+            .any(|m| m.mount_point == PathBuf::from("/media/dys/My Book")));
+        assert_eq!(unescape(r"a\\b\011c\134"), "a\\\\b\tc\\");
+    }
+
+    #[test]
+    fn overmounts() {
+        // only the last filesystem mounted on /boot/efi is visible
+        let mounts = parse_mounts(PROC_MOUNTS);
+        let efi: Vec<&Mount> = mounts
+            .iter()
+            .filter(|m| m.mount_point == PathBuf::from("/boot/efi"))
+            .collect();
+        assert_eq!(efi.len(), 1);
+        assert_eq!(efi[0].device, "/dev/sdc1");
+    }
+
+    #[test]
+    fn pseudo_filesystems() {
+        let pseudo: Vec<String> = parse_mounts(PROC_MOUNTS)
+            .iter()
+            .filter(|m| m.is_pseudo())
+            .map(|m| m.device.clone())
+            .collect();
+        assert_eq!(pseudo, vec!["sysfs", "proc", "cgroup2"]);
+    }
+
+    #[test]
+    fn containing_mount() {
+        let mounts = parse_mounts(PROC_MOUNTS);
+        let device_of = |path: &str| find_mount(&mounts, Path::new(path)).map(|m| m.device.as_str());
+        assert_eq!(device_of("/home/dys/dev"), Some("/dev/nvme0n1p2"));
+        assert_eq!(device_of("/boot/efi/EFI"), Some("/dev/sdc1"));
+        assert_eq!(device_of("/boot/efi"), Some("/dev/sdc1"));
+        assert_eq!(device_of("/boot/efiles"), Some("/dev/nvme0n1p2"));
+        assert_eq!(device_of("/media/dys/My Book/photos"), Some("/dev/sdb1"));
+        assert_eq!(device_of("relative/path"), None);
+    }
+
+    #[test]
+    fn use_percent() {
+        let stats = MountStats {
+            size: Size::from(1000),
+            used: Size::from(500),
+            available: Size::from(400),
+        };
+        // as in df, rounded up and relative to what the user may use
+        assert_eq!(stats.use_percent(), 56);
+    }
+}
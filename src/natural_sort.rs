@@ -0,0 +1,185 @@
+//! The order of the entries of a directory: case insensitive, with the
+//!  numbers compared by value (`img2` before `img10`) and without taking
+//!  the leading dots into account (`.bashrc` is among the `b`s).
+//! It doesn't depend on the locale, so that a tree is always displayed
+//!  the same way, whatever the system or the filesystem.
+
+use std::cmp::Ordering;
+use std::iter::Peekable;
+use std::str::Chars;
+
+// reads a run of ascii digits, returning it without its leading zeros,
+//  and the number of zeros which were skipped
+fn read_number(chars: &mut Peekable<Chars<'_>>) -> (String, usize) {
+    let mut digits = String::new();
+    let mut nb_zeros = 0;
+    while let Some(&c) = chars.peek() {
+        if !c.is_ascii_digit() {
+            break;
+        }
+        if c == '0' && digits.is_empty() {
+            nb_zeros += 1;
+        } else {
+            digits.push(c);
+        }
+        chars.next();
+    }
+    (digits, nb_zeros)
+}
+
+// compares two numbers written without leading zeros, whatever their length
+fn compare_numbers(a: &str, b: &str) -> Ordering {
+    a.len().cmp(&b.len()).then_with(|| a.cmp(b))
+}
+
+// compares two chars ignoring their case
+fn compare_chars(a: char, b: char) -> Ordering {
+    a.to_lowercase().cmp(b.to_lowercase())
+}
+
+/// compare two file names in the natural order.
+/// Names which are only different by their case, their leading dots or
+///  the leading zeros of their numbers are still ordered (the one with the
+///  fewest dots first, then the fewest zeros, then uppercase first) so
+///  that the order is total and doesn't depend on the reading order.
+pub fn compare_names(a: &str, b: &str) -> Ordering {
+    let a_name = a.trim_start_matches('.');
+    let b_name = b.trim_start_matches('.');
+    let mut zeros_ordering = Ordering::Equal; // the first difference in leading zeros
+    let mut ai = a_name.chars().peekable();
+    let mut bi = b_name.chars().peekable();
+    loop {
+        match (ai.peek(), bi.peek()) {
+            (None, None) => break,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(ac), Some(bc)) if ac.is_ascii_digit() && bc.is_ascii_digit() => {
+                let (an, a_zeros) = read_number(&mut ai);
+                let (bn, b_zeros) = read_number(&mut bi);
+                let ordering = compare_numbers(&an, &bn);
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+                if zeros_ordering == Ordering::Equal {
+                    zeros_ordering = a_zeros.cmp(&b_zeros);
+                }
+            }
+            (Some(&ac), Some(&bc)) => {
+                let ordering = compare_chars(ac, bc);
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+                ai.next();
+                bi.next();
+            }
+        }
+    }
+    (a.len() - a_name.len())
+        .cmp(&(b.len() - b_name.len()))
+        .then(zeros_ordering)
+        .then_with(|| a.cmp(b))
+}
+
+#[cfg(test)]
+mod natural_sort_tests {
+    use super::*;
+
+    fn sorted(names: &[&str]) -> Vec<String> {
+        let mut names: Vec<String> = names.iter().map(|s| s.to_string()).collect();
+        names.sort_by(|a, b| compare_names(a, b));
+        names
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(
+            sorted(&["img10.png", "img2.png", "img1.png", "img20.png", "img3.png"]),
+            vec!["img1.png", "img2.png", "img3.png", "img10.png", "img20.png"],
+        );
+        assert_eq!(
+            sorted(&["v1.10.0", "v1.2.10", "v1.2.9", "v1.9"]),
+            vec!["v1.2.9", "v1.2.10", "v1.9", "v1.10.0"],
+        );
+        // digits come before letters
+        assert_eq!(sorted(&["b", "a", "9", "10"]), vec!["9", "10", "a", "b"]);
+    }
+
+    #[test]
+    fn big_numbers() {
+        // longer than what an u64 can hold
+        assert_eq!(
+            compare_names("f99999999999999999999999", "f100000000000000000000000"),
+            Ordering::Less,
+        );
+    }
+
+    #[test]
+    fn leading_zeros() {
+        assert_eq!(
+            sorted(&["f010", "f9", "f0010", "f10", "f1"]),
+            vec!["f1", "f9", "f10", "f010", "f0010"],
+        );
+        assert_eq!(compare_names("a0", "a00"), Ordering::Less);
+        assert_eq!(compare_names("a00", "a1"), Ordering::Less);
+    }
+
+    #[test]
+    fn mixed_case() {
+        assert_eq!(
+            sorted(&["cherry", "Banana", "apple", "Apple", "APPLE", "banana"]),
+            vec!["APPLE", "Apple", "apple", "Banana", "banana", "cherry"],
+        );
+        assert_eq!(compare_names("README", "readme.md"), Ordering::Less);
+        assert_eq!(compare_names("Makefile", "main.rs"), Ordering::Greater);
+    }
+
+    #[test]
+    fn leading_dots() {
+        assert_eq!(
+            sorted(&["c", ".bashrc", "a", "bashrc", "..b", ".a"]),
+            vec!["a", ".a", "..b", "bashrc", ".bashrc", "c"],
+        );
+        assert_eq!(compare_names(".", ".."), Ordering::Less);
+        // only the leading dots are ignored
+        assert_eq!(compare_names("a.b", "ab"), Ordering::Less);
+    }
+
+    #[test]
+    fn unicode() {
+        // the case of non ascii letters is ignored too
+        assert_eq!(compare_names("Été", "été"), Ordering::Less);
+        assert_eq!(compare_names("ÉTÉ.txt", "été.md"), Ordering::Greater);
+        assert_eq!(compare_names("Σίσυφος", "σκύλος"), Ordering::Less);
+        // but accents aren't removed: the order is the one of the chars
+        assert_eq!(
+            sorted(&["zèbre", "été", "eté", "ete", "f"]),
+            vec!["ete", "eté", "f", "zèbre", "été"],
+        );
+        assert_eq!(sorted(&["日本2", "日本10", "中国"]), vec!["中国", "日本2", "日本10"]);
+        // only ascii digits are read as numbers
+        assert_eq!(compare_names("x٣", "x10"), Ordering::Greater);
+    }
+
+    #[test]
+    fn total_order() {
+        let names = [
+            "a", "A", ".a", "a1", "a01", "a001", "A1", "a10", "a2", "b", ".B", "é", "É", "",
+        ];
+        for a in names.iter() {
+            assert_eq!(compare_names(a, a), Ordering::Equal);
+            for b in names.iter() {
+                assert_eq!(compare_names(a, b), compare_names(b, a).reverse());
+                if a != b {
+                    assert_ne!(compare_names(a, b), Ordering::Equal);
+                }
+                for c in names.iter() {
+                    if compare_names(a, b) == Ordering::Less
+                        && compare_names(b, c) == Ordering::Less
+                    {
+                        assert_eq!(compare_names(a, c), Ordering::Less, "{} {} {}", a, b, c);
+                    }
+                }
+            }
+        }
+    }
+}
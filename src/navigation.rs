@@ -0,0 +1,79 @@
+//! The navigation keys (arrows, page up and down, home and end) applied
+//!  the same way by the states which aren't trees: in a list, they move
+//!  the selection, in a text without selection, they scroll it.
+
+use crate::commands::Action;
+use crate::screens::ScreenArea;
+
+/// scroll the area of a text according to the action. Return false
+///  when the action isn't a navigation one.
+pub fn scroll_text(area: &mut ScreenArea, action: &Action) -> bool {
+    match action {
+        Action::MoveSelection(dy) | Action::Scroll(dy) => area.try_scroll(*dy),
+        Action::ScrollPage(dp) => area.try_scroll(*dp * (area.height() - 1).max(1)),
+        Action::SelectFirst => area.scroll = 0,
+        Action::SelectLast => area.scroll = area.max_scroll(),
+        _ => return false,
+    }
+    true
+}
+
+/// the index of the item of a list selected after the action, or None
+///  when the action isn't a navigation one.
+/// Like in trees, moving by one line past an end brings to the other one,
+///  while moving by pages stops at the ends.
+pub fn move_in_list(
+    selection: usize,
+    len: usize,
+    page_height: usize,
+    action: &Action,
+) -> Option<usize> {
+    let len = len.max(1) as i32;
+    let selection = selection as i32;
+    let page_height = page_height.max(1) as i32;
+    let selection = match action {
+        Action::MoveSelection(dy) => ((selection + dy) % len + len) % len,
+        Action::ScrollPage(dp) => (selection + dp * page_height).max(0).min(len - 1),
+        Action::SelectFirst => 0,
+        Action::SelectLast => len - 1,
+        _ => return None,
+    };
+    Some(selection as usize)
+}
+
+#[cfg(test)]
+mod navigation_tests {
+    use super::*;
+
+    #[test]
+    fn list() {
+        let nav = |selection, action| move_in_list(selection, 10, 4, &action);
+        assert_eq!(nav(0, Action::MoveSelection(1)), Some(1));
+        assert_eq!(nav(0, Action::MoveSelection(-1)), Some(9));
+        assert_eq!(nav(9, Action::MoveSelection(1)), Some(0));
+        assert_eq!(nav(3, Action::ScrollPage(1)), Some(7));
+        assert_eq!(nav(7, Action::ScrollPage(1)), Some(9));
+        assert_eq!(nav(2, Action::ScrollPage(-1)), Some(0));
+        assert_eq!(nav(5, Action::SelectFirst), Some(0));
+        assert_eq!(nav(5, Action::SelectLast), Some(9));
+        assert_eq!(nav(5, Action::Back), None);
+        // an empty list
+        assert_eq!(move_in_list(0, 0, 4, &Action::MoveSelection(-1)), Some(0));
+        assert_eq!(move_in_list(0, 0, 4, &Action::SelectLast), Some(0));
+    }
+
+    #[test]
+    fn text() {
+        let mut area = ScreenArea::new(1, 10, 80);
+        area.content_length = 25;
+        assert!(scroll_text(&mut area, &Action::ScrollPage(1)));
+        assert_eq!(area.scroll, 9);
+        assert!(scroll_text(&mut area, &Action::SelectLast));
+        assert_eq!(area.scroll, 15);
+        assert!(scroll_text(&mut area, &Action::MoveSelection(1)));
+        assert_eq!(area.scroll, 15);
+        assert!(scroll_text(&mut area, &Action::SelectFirst));
+        assert_eq!(area.scroll, 0);
+        assert!(!scroll_text(&mut area, &Action::Help));
+    }
+}
@@ -0,0 +1,200 @@
+//! the names of the users and groups owning the files. As looking for
+//!  them may be slow (eg over LDAP), it's done in a thread of its own,
+//!  the ids being shown until the names are known. The names are kept
+//!  for the life of broot.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::thread;
+use std::time::Duration;
+
+use crossbeam::channel::{unbounded, Sender};
+
+use crate::flat_tree::Tree;
+use crate::task_sync::TaskLifetime;
+
+// how long a pending task waits for the lookups before giving a hand
+//  back to the event loop
+const LOOKUP_WAIT: Duration = Duration::from_millis(20);
+
+/// the names already looked for, None meaning the id is unknown
+#[derive(Default)]
+pub struct OwnerNames {
+    users: HashMap<u32, Option<String>>,
+    groups: HashMap<u32, Option<String>>,
+    asked_users: HashSet<u32>, // sent to the lookup thread, not found yet
+    asked_groups: HashSet<u32>,
+}
+
+enum Lookup {
+    User(u32),
+    Group(u32),
+}
+
+lazy_static! {
+    static ref OWNER_NAMES: Mutex<OwnerNames> = Mutex::new(OwnerNames::default());
+    static ref FOUND: Condvar = Condvar::new();
+    static ref LOOKUPS: Sender<Lookup> = start_lookups();
+}
+
+// start the thread looking for the names, one at a time
+fn start_lookups() -> Sender<Lookup> {
+    let (sender, receiver) = unbounded();
+    thread::spawn(move || {
+        for lookup in receiver {
+            match lookup {
+                Lookup::User(uid) => {
+                    let name = user_name(uid);
+                    let mut names = known();
+                    names.users.insert(uid, name);
+                    names.asked_users.remove(&uid);
+                }
+                Lookup::Group(gid) => {
+                    let name = group_name(gid);
+                    let mut names = known();
+                    names.groups.insert(gid, name);
+                    names.asked_groups.remove(&gid);
+                }
+            }
+            FOUND.notify_all();
+        }
+    });
+    sender
+}
+
+fn user_name(uid: u32) -> Option<String> {
+    users::get_user_by_uid(uid).map(|u| u.name().to_string_lossy().to_string())
+}
+
+fn group_name(gid: u32) -> Option<String> {
+    users::get_group_by_gid(gid).map(|g| g.name().to_string_lossy().to_string())
+}
+
+/// the names found so far. A poisoned lock only means a previous
+///  lookup panicked: the names are still usable
+pub fn known() -> MutexGuard<'static, OwnerNames> {
+    OWNER_NAMES.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+impl OwnerNames {
+    /// the name of the user, or its id when it's not known (yet)
+    pub fn user(&self, uid: u32) -> String {
+        match self.users.get(&uid) {
+            Some(Some(name)) => name.clone(),
+            _ => uid.to_string(),
+        }
+    }
+    /// the name of the group, or its id when it's not known (yet)
+    pub fn group(&self, gid: u32) -> String {
+        match self.groups.get(&gid) {
+            Some(Some(name)) => name.clone(),
+            _ => gid.to_string(),
+        }
+    }
+    fn is_looked_for(&self, uid: u32, gid: u32) -> bool {
+        self.users.contains_key(&uid) && self.groups.contains_key(&gid)
+    }
+    // send the ids which were neither found nor asked to the lookup
+    //  thread. Return whether some were sent
+    fn ask(&mut self, uid: u32, gid: u32) -> bool {
+        let mut asked = false;
+        if !self.users.contains_key(&uid) && self.asked_users.insert(uid) {
+            asked = LOOKUPS.send(Lookup::User(uid)).is_ok();
+        }
+        if !self.groups.contains_key(&gid) && self.asked_groups.insert(gid) {
+            asked |= LOOKUPS.send(Lookup::Group(gid)).is_ok();
+        }
+        asked
+    }
+}
+
+// whether the names of the owners of the tree are shown
+fn are_shown(tree: &Tree) -> bool {
+    tree.options.show_owner && tree.options.lookup_users
+}
+
+/// whether some owners of the lines of the tree must be looked for
+pub fn has_unknown(tree: &Tree) -> bool {
+    are_shown(tree) && !tree.owners_known
+}
+
+/// ask the lookup thread for the owners of the lines, the visible ones
+///  first. Return whether some were asked, or the task expired before
+///  all lines were seen. When all the owners are known, it's kept in
+///  the tree, so that they're not checked again
+pub fn ask_for_some(tree: &mut Tree, page_height: i32, tl: &TaskLifetime) -> bool {
+    if !are_shown(tree) {
+        return false;
+    }
+    let len = tree.lines.len();
+    let first_visible = (tree.scroll as usize + 1).min(len);
+    let last_visible = (first_visible + page_height.max(0) as usize).min(len);
+    let mut names = known();
+    let mut asked = false;
+    let mut all_known = true;
+    for i in (first_visible..last_visible).chain(0..len) {
+        if tl.is_expired() {
+            return true;
+        }
+        let line = &tree.lines[i];
+        if !line.has_metadata || names.is_looked_for(line.uid, line.gid) {
+            continue;
+        }
+        all_known = false;
+        asked |= names.ask(line.uid, line.gid);
+    }
+    tree.owners_known = all_known;
+    asked
+}
+
+/// wait a little for the lookup thread to find some names
+pub fn wait_for_some() {
+    let names = known();
+    if !names.asked_users.is_empty() || !names.asked_groups.is_empty() {
+        let _ = FOUND.wait_timeout(names, LOOKUP_WAIT);
+    }
+}
+
+/// look for the names of the user and group, unless they're already known
+pub fn look_for(uid: u32, gid: u32) {
+    if !known().users.contains_key(&uid) {
+        let name = user_name(uid);
+        known().users.insert(uid, name);
+    }
+    if !known().groups.contains_key(&gid) {
+        let name = group_name(gid);
+        known().groups.insert(gid, name);
+    }
+}
+
+#[cfg(test)]
+mod owners_tests {
+    use super::*;
+    use std::fs;
+    use crate::tree_build::build_tree;
+    use crate::tree_options::TreeOptions;
+    use tempfile::tempdir;
+
+    #[test]
+    fn names_are_found_in_the_background() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("file"), "").unwrap();
+        let mut options = TreeOptions::new();
+        options.show_owner = true;
+        let mut tree = build_tree(dir.path().to_path_buf(), options, 10).unwrap();
+        let tl = TaskLifetime::unlimited();
+        tree.fetch_some_missing_metadata(10, &tl);
+        assert!(has_unknown(&tree));
+        while has_unknown(&tree) {
+            if !ask_for_some(&mut tree, 10, &tl) {
+                wait_for_some();
+            }
+        }
+        let uid = tree.lines[1].uid;
+        assert_eq!(known().user(uid), user_name(uid).unwrap_or_else(|| uid.to_string()));
+        // the lines getting their metadata may have other owners
+        tree.lines[1].has_metadata = false;
+        tree.fetch_some_missing_metadata(10, &tl);
+        assert!(has_unknown(&tree));
+    }
+}
@@ -0,0 +1,257 @@
+//! the completion, on <tab>, of the path argument being typed at the
+//!  end of a verb invocation. An argument is a path when its group in
+//!  the invocation of the verb is named like `{path}` or `{subpath}`.
+//! A relative path is completed from the directory of the selection.
+
+use directories::UserDirs;
+use regex::Regex;
+use std::path::Path;
+
+use crate::commands::Command;
+use crate::tree_build;
+use crate::tree_options::TreeOptions;
+use crate::verbs::{Verb, VerbExecution};
+
+/// how the arguments typed after a verb reach its execution, which
+///  tells where the argument being typed starts and how to escape it
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ArgsSyntax {
+    Words, // split on whitespace by a built-in, so names can't have spaces
+    Raw,   // inserted in the command, the last argument taking the rest of the input
+    Shell, // given to the shell, with the special chars escaped by a backslash
+}
+
+impl ArgsSyntax {
+    fn of(verb: &Verb) -> ArgsSyntax {
+        match verb.execution {
+            VerbExecution::Internal(_) => ArgsSyntax::Words,
+            VerbExecution::External(_) if verb.from_shell => ArgsSyntax::Shell,
+            VerbExecution::External(_) => ArgsSyntax::Raw,
+        }
+    }
+    // the name as it must be written in the input
+    fn escape(self, name: &str) -> String {
+        if self != ArgsSyntax::Shell {
+            return name.to_string();
+        }
+        let mut escaped = String::new();
+        for c in name.chars() {
+            if !(c.is_alphanumeric() || "_-.,+@%=:/".contains(c)) {
+                escaped.push('\\');
+            }
+            escaped.push(c);
+        }
+        escaped
+    }
+    // the argument as typed, its escapings removed
+    fn unescape(self, arg: &str) -> String {
+        if self != ArgsSyntax::Shell {
+            return arg.to_string();
+        }
+        let mut unescaped = String::new();
+        let mut chars = arg.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => unescaped.extend(chars.next()),
+                _ => unescaped.push(c),
+            }
+        }
+        unescaped
+    }
+    // the start, in args, of the argument being typed, with its index
+    //  in the invocation of a verb taking nb_args arguments
+    fn last_arg(self, args: &str, nb_args: usize) -> (usize, usize) {
+        let mut starts = Vec::new();
+        let mut in_arg = false;
+        let mut escaped = false;
+        for (i, c) in args.char_indices() {
+            if escaped {
+                escaped = false;
+            } else if c.is_whitespace() {
+                in_arg = false;
+            } else {
+                if !in_arg {
+                    starts.push(i);
+                    in_arg = true;
+                }
+                escaped = c == '\\' && self == ArgsSyntax::Shell;
+            }
+        }
+        if !in_arg {
+            starts.push(args.len()); // a new argument is started
+        }
+        let idx = (starts.len() - 1).min(nb_args.max(1) - 1);
+        if self == ArgsSyntax::Raw {
+            (starts[idx], idx)
+        } else {
+            (starts[starts.len() - 1], idx)
+        }
+    }
+}
+
+// tell whether the argument of the invocation is a path, from its
+//  group, eg "{newpath}"
+fn is_path_arg(spec: &str) -> bool {
+    lazy_static! {
+        static ref PATH_GROUP: Regex = Regex::new(r"^\{\w*path\}$").unwrap();
+    }
+    PATH_GROUP.is_match(spec)
+}
+
+/// the names which may complete the path argument ending the input,
+///  and the input they were inserted in
+#[derive(Debug, Clone)]
+pub struct PathCompletion {
+    head: String,       // the input before the name being completed
+    names: Vec<String>, // the matching names, as written in the input, with a '/' after directories
+    cycled: Option<usize>, // the index of the name in the input, when cycling
+    pub raw: String,    // the input, once completed
+}
+
+impl PathCompletion {
+    /// complete the argument at the end of the input, when the invocation
+    ///  of the verb tells it's a path. The names are the ones of the
+    ///  entries the tree would show with the options, relative paths
+    ///  being relative to dir
+    pub fn new(
+        cmd: &Command,
+        verb: &Verb,
+        dir: &Path,
+        options: &TreeOptions,
+    ) -> Option<PathCompletion> {
+        let (start, _) = cmd.verb_invocation()?;
+        let key_part = cmd.raw[start..].trim_start_matches(|c: char| c == ':' || c.is_whitespace());
+        // without a space after the key, it's the verb which is being typed
+        let key_end = key_part.find(char::is_whitespace)?;
+        let args = key_part[key_end..].trim_start();
+        let args_start = cmd.raw.len() - args.len();
+        let specs: Vec<&str> = match &verb.invocation.args {
+            Some(specs) => specs.split_whitespace().collect(),
+            None => Vec::new(),
+        };
+        let syntax = ArgsSyntax::of(verb);
+        let (arg_start, arg_idx) = syntax.last_arg(args, specs.len());
+        if !specs.get(arg_idx).map_or(false, |spec| is_path_arg(spec)) {
+            return None;
+        }
+        let arg = &args[arg_start..];
+        let name_start = arg.rfind('/').map_or(0, |idx| idx + 1);
+        let typed = syntax.unescape(arg);
+        let (typed_dir, prefix) = match typed.rfind('/') {
+            Some(idx) => (&typed[..=idx], &typed[idx + 1..]),
+            None => ("", typed.as_str()),
+        };
+        let searched_dir = if typed_dir.starts_with("~/") {
+            let home = UserDirs::new().map(|user_dirs| user_dirs.home_dir().to_path_buf())?;
+            home.join(&typed_dir[2..])
+        } else {
+            dir.join(typed_dir) // an absolute path replaces dir
+        };
+        let mut names = PathCompletion::matching_names(&searched_dir, prefix, options);
+        if syntax == ArgsSyntax::Words {
+            names.retain(|(name, _)| !name.contains(char::is_whitespace));
+        }
+        let names: Vec<String> = names
+            .into_iter()
+            .map(|(name, is_dir)| {
+                let mut name = syntax.escape(&name);
+                if is_dir {
+                    name.push('/');
+                }
+                name
+            })
+            .collect();
+        let head = cmd.raw[..args_start + arg_start + name_start].to_string();
+        let raw = format!("{}{}", head, common_prefix(&names));
+        Some(PathCompletion {
+            head,
+            names,
+            cycled: None,
+            raw,
+        })
+    }
+    // the entries of the directory whose name starts with the prefix,
+    //  sorted. The hidden ones are listed when the prefix starts with a dot
+    fn matching_names(dir: &Path, prefix: &str, options: &TreeOptions) -> Vec<(String, bool)> {
+        let mut options = options.clone();
+        options.show_hidden |= prefix.starts_with('.');
+        let mut names: Vec<(String, bool)> = match tree_build::list_dir(dir, &options) {
+            Ok(entries) => entries
+                .into_iter()
+                .filter(|(name, _)| name.starts_with(prefix))
+                .collect(),
+            Err(e) => {
+                debug!("no completion in {:?}: {}", dir, e);
+                Vec::new()
+            }
+        };
+        names.sort();
+        names
+    }
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+    /// whether several names complete the argument
+    pub fn is_ambiguous(&self) -> bool {
+        self.names.len() > 1
+    }
+    /// put the next name in the input, on a repeated <tab>
+    pub fn cycle(&mut self) {
+        if self.names.is_empty() {
+            return;
+        }
+        let idx = self.cycled.map_or(0, |idx| (idx + 1) % self.names.len());
+        self.cycled = Some(idx);
+        self.raw = format!("{}{}", self.head, self.names[idx]);
+    }
+    /// the status listing the names, the one in the input being
+    ///  bracketed when cycling
+    pub fn status(&self) -> String {
+        self.names
+            .iter()
+            .enumerate()
+            .map(|(idx, name)| match self.cycled {
+                Some(cycled) if cycled == idx => format!("[{}]", name),
+                _ => name.clone(),
+            })
+            .collect::<Vec<String>>()
+            .join("  ")
+    }
+}
+
+// the longest start shared by all the names
+fn common_prefix(names: &[String]) -> &str {
+    let first = match names.first() {
+        Some(first) => first,
+        None => {
+            return "";
+        }
+    };
+    let mut end = first.len();
+    for name in &names[1..] {
+        end = first
+            .char_indices()
+            .zip(name.chars())
+            .find(|((_, a), b)| a != b)
+            .map_or(end.min(name.len()), |((idx, _), _)| idx.min(end));
+    }
+    &first[..end]
+}
+
+#[cfg(test)]
+mod path_completion_tests {
+    use super::*;
+
+    #[test]
+    fn args_syntaxes() {
+        assert_eq!(ArgsSyntax::Shell.escape("my dir's"), r"my\ dir\'s");
+        assert_eq!(ArgsSyntax::Shell.unescape(r"my\ dir\'s"), "my dir's");
+        assert_eq!(ArgsSyntax::Raw.escape("my dir"), "my dir");
+        assert_eq!(ArgsSyntax::Words.last_arg("-p src/", 1), (3, 0));
+        assert_eq!(ArgsSyntax::Raw.last_arg("my dir/a", 1), (0, 0));
+        assert_eq!(ArgsSyntax::Shell.last_arg(r"my\ dir/a", 1), (0, 0));
+        assert_eq!(ArgsSyntax::Shell.last_arg("a ", 2), (2, 1));
+        assert_eq!(common_prefix(&["src/".to_string(), "static/".to_string()]), "s");
+        assert_eq!(common_prefix(&["src/".to_string()]), "src/");
+    }
+}
@@ -0,0 +1,96 @@
+//! The list of paths given on launch with --paths-from-stdin.
+//! When there's one, the tree only shows those paths and their ancestors.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use crate::tree_build::TreeBuildError;
+
+#[derive(Debug)]
+pub struct PathList {
+    accepted: HashSet<PathBuf>, // the listed paths and all their ancestors
+    listed: HashSet<PathBuf>,   // only the listed paths
+    pub nb_dropped: usize,      // number of listed paths which don't exist
+}
+
+// make the path absolute and remove its "." and ".." components,
+//  without resolving the symbolic links (the tree doesn't)
+fn normalize(path: &Path, cwd: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for c in cwd.join(path).components() {
+        match c {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            c => normalized.push(c.as_os_str()),
+        }
+    }
+    normalized
+}
+
+fn common_ancestor(a: &Path, b: &Path) -> PathBuf {
+    a.components()
+        .zip(b.components())
+        .take_while(|(ca, cb)| ca == cb)
+        .map(|(ca, _)| ca)
+        .collect()
+}
+
+impl PathList {
+    pub fn from_lines(lines: &[String], cwd: &Path) -> Result<(PathBuf, PathList), TreeBuildError> {
+        let mut accepted = HashSet::new();
+        let mut listed = HashSet::new();
+        let mut nb_dropped = 0;
+        let mut root: Option<PathBuf> = None;
+        for line in lines {
+            if line.is_empty() {
+                continue;
+            }
+            let path = normalize(Path::new(line), cwd);
+            if fs::symlink_metadata(&path).is_err() {
+                nb_dropped += 1;
+                continue;
+            }
+            root = Some(match root {
+                Some(root) => common_ancestor(&root, &path),
+                None => path.clone(),
+            });
+            accepted.extend(path.ancestors().map(Path::to_path_buf));
+            listed.insert(path);
+        }
+        let mut root = match root {
+            Some(root) => root,
+            None => {
+                return Err(TreeBuildError::EmptyPathList { nb_dropped });
+            }
+        };
+        if !root.is_dir() {
+            // a single file was given
+            if let Some(parent) = root.parent() {
+                root = parent.to_path_buf();
+            }
+        }
+        Ok((
+            root,
+            PathList {
+                accepted,
+                listed,
+                nb_dropped,
+            },
+        ))
+    }
+    /// tell whether the path was listed or is the ancestor of a listed one
+    pub fn contains(&self, path: &Path) -> bool {
+        self.accepted.contains(path)
+    }
+    /// number of listed paths which aren't directories
+    pub fn nb_files(&self) -> usize {
+        self.listed.iter().filter(|path| !path.is_dir()).count()
+    }
+    /// tell whether the path was listed (not just as an ancestor)
+    pub(crate) fn is_listed(&self, path: &Path) -> bool {
+        self.listed.contains(path)
+    }
+}
@@ -1,21 +1,21 @@
 //! a pattern for filtering and sorting filenames.
-//! It's backed either by a fuzzy pattern matcher or
-//!  by a regular expression (in which case there's no real
-//!  score)
+//! It's backed either by a fuzzy pattern matcher, by several
+//!  fuzzy patterns matched in any order, or by a regular
+//!  expression (in which case there's no real score)
 
 use core::result;
 use std::{fmt, mem};
 
-use crate::commands::Command;
-use crate::errors::RegexError;
 use crate::fuzzy_patterns::FuzzyPattern;
-use crate::regex_patterns::RegexPattern;
+use crate::regex_patterns::{RegexError, RegexPattern};
+use crate::words_patterns::WordsPattern;
 
 #[derive(Debug, Clone)]
 pub enum Pattern {
     None,
     Fuzzy(FuzzyPattern),
     Regex(RegexPattern),
+    Words(WordsPattern),
 }
 
 impl fmt::Display for Pattern {
@@ -23,15 +23,36 @@ impl fmt::Display for Pattern {
         match self {
             Pattern::Fuzzy(fp) => write!(f, "Fuzzy({})", fp),
             Pattern::Regex(rp) => write!(f, "Regex({})", rp),
+            Pattern::Words(wp) => write!(f, "Words({})", wp),
             Pattern::None => write!(f, "None"),
         }
     }
 }
 
 impl Pattern {
-    /// create a new fuzzy pattern
+    /// create a new fuzzy pattern. A leading '^' anchors the match
+    ///  at the start of the name, a trailing '$' at its end. A pattern
+    ///  made only of anchors is searched as is
     pub fn fuzzy(pat: &str) -> Pattern {
-        Pattern::Fuzzy(FuzzyPattern::from(pat))
+        let anchored_start = pat.starts_with('^');
+        let core = if anchored_start { &pat[1..] } else { pat };
+        let anchored_end = core.ends_with('$');
+        let core = if anchored_end { &core[..core.len() - 1] } else { core };
+        if core.is_empty() {
+            Pattern::Fuzzy(FuzzyPattern::from(pat))
+        } else {
+            Pattern::Fuzzy(FuzzyPattern::anchored(core, anchored_start, anchored_end))
+        }
+    }
+    /// create a pattern matching names containing all the whitespace
+    ///  separated words, in any order
+    pub fn words(pat: &str) -> Pattern {
+        let wp = WordsPattern::from(pat);
+        if wp.is_empty() {
+            Pattern::None
+        } else {
+            Pattern::Words(wp)
+        }
     }
     /// try to create a regex pattern
     pub fn regex(pat: &str, flags: &str) -> result::Result<Pattern, RegexError> {
@@ -41,6 +62,7 @@ impl Pattern {
         match self {
             Pattern::Fuzzy(fp) => fp.find(candidate),
             Pattern::Regex(rp) => rp.find(candidate),
+            Pattern::Words(wp) => wp.find(candidate),
             Pattern::None => Some(Match {
                 // this isn't really supposed to be used
                 score: 1,
@@ -61,30 +83,34 @@ impl Pattern {
     }
     // return the number of results we should find before starting to
     //  sort them (unless time is runing out).
-    pub fn optimal_result_number(&self, targeted_size: usize) -> usize {
+    pub(crate) fn optimal_result_number(&self, targeted_size: usize) -> usize {
         match self {
             Pattern::Fuzzy(fp) => fp.optimal_result_number(targeted_size),
             Pattern::Regex(rp) => rp.optimal_result_number(targeted_size),
+            Pattern::Words(wp) => wp.optimal_result_number(targeted_size),
             Pattern::None => targeted_size,
         }
     }
-    pub fn to_command(&self) -> Command {
-        Command::from(match self {
-            Pattern::Fuzzy(fp) => fp.to_string(),
-            Pattern::Regex(rp) => rp.to_string(),
-            Pattern::None => String::new(),
-        })
-    }
 }
 
 /// A Match is a positive result of pattern matching
 #[derive(Debug)]
 pub struct Match {
     pub score: i32, // score of the match, guaranteed strictly positive, bigger is better
-    pub pos: Vec<usize>, // positions of the matching chars
+    pub pos: Vec<usize>, // indexes of the matching chars (not bytes), sorted
 }
 
 impl Match {
+    /// the byte offsets, in the candidate (which should be at the origin of the
+    ///  match), of the matching chars, for the tools which don't count in chars
+    pub fn byte_offsets(&self, candidate: &str) -> Vec<usize> {
+        candidate
+            .char_indices()
+            .enumerate()
+            .filter(|(cand_idx, _)| self.pos.binary_search(cand_idx).is_ok())
+            .map(|(_, (offset, _))| offset)
+            .collect()
+    }
     // returns a new string made from candidate (which should be at the origin of the match)
     //  where the characters at positions pos (matching chars) are wrapped between
     //  prefix and postfix
@@ -104,3 +130,52 @@ impl Match {
         decorated
     }
 }
+
+#[cfg(test)]
+mod patterns_tests {
+
+    use super::*;
+
+    // "été" and "ñ" take two bytes per accented char
+    #[test]
+    fn positions_in_multibyte_names() {
+        let name = "été_ñu.rs";
+        let m = Pattern::fuzzy("tñu").find(name).unwrap();
+        assert_eq!(m.pos, vec![1, 4, 5]);
+        assert_eq!(m.byte_offsets(name), vec![2, 6, 8]);
+        let m = Pattern::regex("ñu", "").unwrap().find(name).unwrap();
+        assert_eq!(m.pos, vec![4, 5]);
+        assert_eq!(m.byte_offsets(name), vec![6, 8]);
+        assert_eq!(m.wrap_matching_chars(name, "<", ">"), "été_<ñ><u>.rs");
+        let m = Pattern::words("rs é").find(name).unwrap();
+        assert_eq!(m.byte_offsets(name), vec![0, 10, 11]);
+    }
+
+    #[test]
+    fn anchored_fuzzy_patterns() {
+        let starts = Pattern::fuzzy("^Test");
+        assert_eq!(starts.find("test_x.rs").unwrap().pos, vec![0, 1, 2, 3]);
+        assert_eq!(starts.find("TEST.md").unwrap().pos, vec![0, 1, 2, 3]);
+        assert!(starts.find("my_test.rs").is_none());
+        let ends = Pattern::fuzzy("rs$");
+        assert_eq!(ends.find("main.RS").unwrap().pos, vec![5, 6]);
+        assert!(ends.find("rs.toml").is_none());
+        // the last char is matched at the end, even when found before
+        assert_eq!(Pattern::fuzzy("ab$").find("abxb").unwrap().pos, vec![0, 3]);
+        let whole = Pattern::fuzzy("^mnrs$");
+        assert_eq!(whole.find("main.rs").unwrap().pos, vec![0, 3, 5, 6]);
+        assert!(whole.find("main.rs.bak").is_none());
+        assert!(whole.find("domain.rs").is_none());
+        // positions are in chars, anchors aren't highlighted
+        let name = "été_ñu";
+        let m = Pattern::fuzzy("^ét").find(name).unwrap();
+        assert_eq!(m.wrap_matching_chars(name, "<", ">"), "<é><t>é_ñu");
+        let m = Pattern::fuzzy("ñu$").find(name).unwrap();
+        assert_eq!(m.pos, vec![4, 5]);
+        assert_eq!(m.byte_offsets(name), vec![6, 8]);
+        assert!(Pattern::fuzzy("^t").find(name).is_none());
+        // lone anchors are searched as is
+        assert!(Pattern::fuzzy("$").find("a$b").is_some());
+        assert!(Pattern::fuzzy("^$").find("ab").is_none());
+    }
+}
@@ -0,0 +1,220 @@
+//! Parsing of the modes given to the :chmod verb, and their
+//!  application to files, possibly recursively
+
+use regex::Regex;
+use std::fs;
+use std::io;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+use crate::errors::ModeSpecError;
+use crate::task_sync::TaskLifetime;
+
+/// a clause of a symbolic mode, eg "go-w"
+#[derive(Debug, Clone)]
+pub struct SymbolicClause {
+    who: u32,  // mask of the concerned bits, eg 0o070 for "g"
+    op: char,  // '+', '-' or '='
+    what: u32, // rwx bits, eg 0o7 for "rwx"
+}
+
+/// a mode, as typed by the user: either octal ("644") or
+///  symbolic ("u+x,go-w")
+#[derive(Debug, Clone)]
+pub enum ModeSpec {
+    Octal(u32),
+    Symbolic(Vec<SymbolicClause>),
+}
+
+impl ModeSpec {
+    pub fn from(spec: &str) -> Result<ModeSpec, ModeSpecError> {
+        lazy_static! {
+            static ref OCTAL: Regex = Regex::new(r"^[0-7]{1,4}$").unwrap();
+            static ref CLAUSE: Regex = Regex::new(r"^([ugoa]*)([+=-])([rwx]*)$").unwrap();
+        }
+        if OCTAL.is_match(spec) {
+            return Ok(ModeSpec::Octal(u32::from_str_radix(spec, 8).unwrap()));
+        }
+        let mut clauses = Vec::new();
+        for clause in spec.split(',') {
+            let c = match CLAUSE.captures(clause) {
+                Some(c) => c,
+                None => {
+                    return Err(ModeSpecError::Invalid {
+                        spec: spec.to_string(),
+                    });
+                }
+            };
+            let mut who = 0;
+            for w in c[1].chars() {
+                who |= match w {
+                    'u' => 0o700,
+                    'g' => 0o070,
+                    'o' => 0o007,
+                    _ => 0o777,
+                };
+            }
+            if who == 0 {
+                who = 0o777;
+            }
+            let mut what = 0;
+            for w in c[3].chars() {
+                what |= match w {
+                    'r' => 0o4,
+                    'w' => 0o2,
+                    _ => 0o1,
+                };
+            }
+            clauses.push(SymbolicClause {
+                who,
+                op: c[2].chars().next().unwrap(),
+                what,
+            });
+        }
+        Ok(ModeSpec::Symbolic(clauses))
+    }
+
+    /// compute the new mode of a file whose current mode is given
+    pub fn applied_to(&self, mode: u32) -> u32 {
+        match self {
+            ModeSpec::Octal(m) => (mode & !0o7777) | m,
+            ModeSpec::Symbolic(clauses) => {
+                let mut mode = mode;
+                for clause in clauses {
+                    // the rwx bits repeated for user, group and others, then masked
+                    let bits = (clause.what * 0o111) & clause.who;
+                    mode = match clause.op {
+                        '+' => mode | bits,
+                        '-' => mode & !bits,
+                        _ => (mode & !clause.who) | bits,
+                    };
+                }
+                mode
+            }
+        }
+    }
+
+    /// change the mode of the file (following symlinks)
+    pub fn apply(&self, path: &Path) -> io::Result<()> {
+        let mode = fs::metadata(path)?.permissions().mode();
+        fs::set_permissions(path, fs::Permissions::from_mode(self.applied_to(mode)))
+    }
+}
+
+/// the arguments of :chmod: the mode, optionally preceded by
+///  -R (recursive) or -d (the directory only, which is the default)
+#[derive(Debug, Clone)]
+pub struct ChmodArgs {
+    pub recursive: bool,
+    pub spec: ModeSpec,
+}
+
+impl ChmodArgs {
+    pub fn from(args: &Option<String>) -> Result<ChmodArgs, ModeSpecError> {
+        let mut recursive = false;
+        let mut spec = None;
+        for token in args.as_ref().map_or("", |s| s.as_str()).split_whitespace() {
+            match token {
+                "-R" => recursive = true,
+                "-d" => recursive = false,
+                _ => spec = Some(token),
+            }
+        }
+        Ok(ChmodArgs {
+            recursive,
+            spec: ModeSpec::from(spec.ok_or(ModeSpecError::Missing)?)?,
+        })
+    }
+}
+
+/// a recursive change of mode, which can be done in several steps
+pub struct ChmodJob {
+    spec: ModeSpec,
+    dirs: Vec<PathBuf>, // the directories whose content must still be handled
+    pub nb_changed: usize,
+    pub nb_errors: usize,
+}
+
+impl ChmodJob {
+    pub fn new(root: PathBuf, spec: ModeSpec) -> ChmodJob {
+        ChmodJob {
+            spec,
+            dirs: vec![root],
+            nb_changed: 0,
+            nb_errors: 0,
+        }
+    }
+    pub fn is_done(&self) -> bool {
+        self.dirs.is_empty()
+    }
+    fn apply(&mut self, path: &Path) {
+        match self.spec.apply(path) {
+            Ok(()) => self.nb_changed += 1,
+            Err(e) => {
+                warn!("chmod of {:?} failed: {:?}", path, e);
+                self.nb_errors += 1;
+            }
+        }
+    }
+    /// change the modes until it's done or the lifetime expires.
+    /// Symbolic links aren't followed.
+    pub fn advance(&mut self, tl: &TaskLifetime) {
+        while let Some(dir) = self.dirs.pop() {
+            if tl.is_expired() {
+                self.dirs.push(dir);
+                return;
+            }
+            // the directory is changed before being read, so that
+            // a permission given by the mode can be used
+            self.apply(&dir);
+            let entries = match fs::read_dir(&dir) {
+                Ok(entries) => entries,
+                Err(_) => {
+                    self.nb_errors += 1;
+                    continue;
+                }
+            };
+            for e in entries.flatten() {
+                match e.file_type() {
+                    Ok(ft) if ft.is_dir() => self.dirs.push(e.path()),
+                    Ok(ft) if ft.is_symlink() => {}
+                    _ => self.apply(&e.path()),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod permissions_tests {
+    use super::*;
+
+    fn applied(spec: &str, mode: u32) -> u32 {
+        ModeSpec::from(spec).unwrap().applied_to(mode)
+    }
+
+    #[test]
+    fn octal_modes_replace_the_permissions() {
+        // the file type bits are kept
+        assert_eq!(applied("644", 0o100755), 0o100644);
+        assert_eq!(applied("0", 0o40755), 0o40000);
+        assert_eq!(applied("4755", 0o100644), 0o104755);
+    }
+
+    #[test]
+    fn symbolic_modes_change_the_given_bits() {
+        assert_eq!(applied("+x", 0o644), 0o755);
+        assert_eq!(applied("u+x", 0o644), 0o744);
+        assert_eq!(applied("go-w", 0o666), 0o644);
+        assert_eq!(applied("a=r", 0o751), 0o444);
+        assert_eq!(applied("o=", 0o757), 0o750);
+        assert_eq!(applied("u+w,g-r", 0o444), 0o604);
+    }
+
+    #[test]
+    fn invalid_modes_are_refused() {
+        for spec in &["", "8", "75555", "u+y", "x+r", "u+x,", "+x go-w"] {
+            assert!(ModeSpec::from(spec).is_err(), "{:?} was accepted", spec);
+        }
+    }
+}
@@ -0,0 +1,59 @@
+//! The hidden --profile mode: instead of starting the UI, the tree is
+//!  built several times and timings are printed, so that performance
+//!  changes can be measured
+
+use std::time::{Duration, Instant};
+
+use crate::cli::AppLaunchArgs;
+use crate::errors::ProgramError;
+use crate::file_sizes::Size;
+use crate::task_sync::TaskLifetime;
+use crate::tree_build::TreeBuilder;
+
+// the number of lines to fill when no height was given
+const DEFAULT_TARGETED_SIZE: usize = 40;
+
+fn percentile(sorted: &[Duration], p: usize) -> Duration {
+    sorted[(sorted.len() - 1) * p / 100]
+}
+
+/// build the tree (and compute the sizes of its directories when they're
+///  shown) nb_runs times, then print the timings on stdout
+pub fn run(launch_args: &AppLaunchArgs, nb_runs: usize) -> Result<(), ProgramError> {
+    let targeted_size = launch_args
+        .height
+        .map_or(DEFAULT_TARGETED_SIZE, |h| h as usize);
+    let options = &launch_args.tree_options;
+    let tl = TaskLifetime::unlimited();
+    let mut durations = Vec::with_capacity(nb_runs);
+    let mut nb_lines = 0;
+    for _ in 0..nb_runs {
+        let start = Instant::now();
+        let builder = TreeBuilder::from(launch_args.root.clone(), options.clone(), targeted_size)?;
+        if let Some(tree) = builder.build(&tl) {
+            if options.show_sizes {
+                for line in tree.lines.iter() {
+                    if line.is_dir() && !line.is_parent() {
+                        Size::compute_dir(&line.path, &tl);
+                    }
+                }
+            }
+            nb_lines = tree.lines.len();
+        }
+        durations.push(start.elapsed());
+    }
+    durations.sort();
+    println!(
+        "{} builds of a {} lines tree rooted at {:?}",
+        nb_runs, nb_lines, launch_args.root,
+    );
+    println!(
+        "min: {:?}  p50: {:?}  p90: {:?}  p99: {:?}  max: {:?}",
+        durations[0],
+        percentile(&durations, 50),
+        percentile(&durations, 90),
+        percentile(&durations, 99),
+        durations[nb_runs - 1],
+    );
+    Ok(())
+}
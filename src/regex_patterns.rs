@@ -4,9 +4,19 @@ use core::result;
 use regex;
 use std::fmt;
 
-use crate::errors::RegexError;
+use custom_error::custom_error;
+
 use crate::patterns;
 
+custom_error! {
+    /// why a regular expression can't be used as pattern
+    pub RegexError
+    Parsing {source: regex::Error} = @{
+        format!("Invalid Regular Expression: {}", source.to_string().lines().last().unwrap_or(""))
+    },
+    UnknownFlag {bad: char} = "Unknown regular expression flag: {:?}",
+}
+
 #[derive(Debug, Clone)]
 pub struct RegexPattern {
     rex: regex::Regex,
@@ -46,10 +56,10 @@ impl RegexPattern {
         //  find over is_match
         match self.rex.find(candidate) {
             Some(rm) => {
-                let mut pos = Vec::with_capacity(rm.end() - rm.start());
-                for i in rm.start()..rm.end() {
-                    pos.push(i);
-                }
+                // the positions are char indexes, as for the other patterns,
+                //  not the byte offsets of the regex match
+                let start = candidate[..rm.start()].chars().count();
+                let pos = (start..start + rm.as_str().chars().count()).collect();
                 Some(patterns::Match { score: 1, pos })
             }
             None => None,
@@ -59,7 +69,7 @@ impl RegexPattern {
     //  sort them (unless time is runing out).
     // In the case of regexes, there's no need to find more results, as
     //  their score is always 1
-    pub fn optimal_result_number(&self, targeted_size: usize) -> usize {
+    pub(crate) fn optimal_result_number(&self, targeted_size: usize) -> usize {
         targeted_size
     }
 }
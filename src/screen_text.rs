@@ -63,13 +63,16 @@ impl Text {
     // write the text in the area, taking into account the scrolled amount
     // and drawing a vertical scrollbar at the right if needed
     pub fn write(&self, screen: &mut Screen, area: &ScreenArea) -> io::Result<()> {
+        if screen.is_too_small() {
+            return screen.write_too_small();
+        }
         let scrollbar = area.scrollbar();
-        let mut i = area.scroll as usize;
+        let mut i = area.visible_range().start;
         for y in area.top..=area.bottom {
             write!(
                 screen.stderr,
                 "{}{}",
-                termion::cursor::Goto(1, y),
+                screen.goto(1, y),
                 termion::clear::CurrentLine,
             )?;
             if i < self.lines.len() {
@@ -78,7 +81,7 @@ impl Text {
             }
             if let Some((sctop, scbottom)) = scrollbar {
                 if sctop <= y && y <= scbottom {
-                    write!(screen.stderr, "{}▐", termion::cursor::Goto(screen.w, y),)?;
+                    write!(screen.stderr, "{}▐", screen.goto(screen.w, y),)?;
                 }
             }
         }
@@ -96,6 +99,9 @@ struct TextCol<'a, R> {
 
 impl<'a, R> TextCol<'a, R> {}
 
+/// the width on screen of the border written before each column
+pub const COL_BORDER_WIDTH: usize = 3; // " │ "
+
 /// A small utility to format some data in a tabular way on screen
 pub struct TextTable<'a, R> {
     cols: Vec<TextCol<'a, R>>,
@@ -119,6 +125,10 @@ impl<'a, R> TextTable<'a, R> {
             width,
         });
     }
+    /// the width on screen of the borders of the columns added until now
+    pub fn borders_width(&self) -> usize {
+        self.cols.len() * COL_BORDER_WIDTH
+    }
     fn compute_col_widths(&mut self, rows: &'a [R]) {
         for row in rows {
             for c in &mut self.cols {
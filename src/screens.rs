@@ -1,14 +1,50 @@
 use crate::skin::Skin;
-use std::io::{self, stderr, Write};
+use crate::term_events::{stdin_is_ready, StdinReader, DISABLE_BRACKETED_PASTE, ENABLE_BRACKETED_PASTE};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::io::{self, stderr, Read, Write};
+use std::mem;
+use std::ops::Range;
+use std::time::{Duration, Instant};
 use termion::color;
-use termion::raw::{IntoRawMode, RawTerminal};
+use termion::raw::IntoRawMode;
 use termion::screen::AlternateScreen;
 
+// under this size, we don't try to draw the application
+const MIN_WIDTH: u16 = 30;
+pub const MIN_HEIGHT: u16 = 8;
+
+// the xterm sequences saving the title of the terminal on a
+//  stack, and restoring it
+const PUSH_TITLE: &str = "\x1b[22;0t";
+const POP_TITLE: &str = "\x1b[23;0t";
+
+/// the writer of the screen. The rows written between `start_row` and
+/// `end_row` are sent to the terminal only when their content isn't
+/// the one which was last drawn at the same place.
+/// When the terminal goes away (eg the ssh connection dropped), nothing
+/// is written anymore and all writes fail, so that the application stops.
+pub struct ScreenWriter {
+    out: Box<dyn Write>,
+    row: Option<(u16, Vec<u8>)>,  // the row being recorded, with its y
+    drawn_rows: Vec<Option<u64>>, // the hash of the content of each drawn row
+    lost: bool,                   // whether the terminal went away
+}
+
 pub struct Screen {
     pub w: u16,
     pub h: u16,
-    pub stderr: AlternateScreen<RawTerminal<io::Stderr>>,
+    pub stderr: ScreenWriter,
     pub skin: Skin,
+    inline_height: Option<u16>, // when set, broot is drawn in the normal screen buffer
+    top: u16,                   // the first terminal row used by broot
+    headless: bool,             // when there's no terminal, the size never changes
+    pub two_line_status: bool,  // whether long errors may borrow the row above the status
+    pub terminal_title: bool,   // whether broot may change the title of the terminal
+    pub error_shown: bool,      // whether the status is an error, which a transient message doesn't hide
+    pub nb_statuses: usize,     // how many times the status was written, to know whether a task wrote one
+    pub stack_crumbs: Vec<String>, // the names of the roots of the stacked states, oldest first
+    title: Option<String>,      // the title broot gave to the terminal, if any
 }
 
 #[derive(Debug)]
@@ -20,25 +56,342 @@ pub struct ScreenArea {
     pub width: u16,
 }
 
+// how long the terminal is given to tell the position of the cursor
+const CURSOR_ROW_TIMEOUT: Duration = Duration::from_millis(500);
+
+// read a byte on stdin, unless none comes before the deadline
+fn read_byte_before(deadline: Instant) -> io::Result<u8> {
+    if !stdin_is_ready(deadline.saturating_duration_since(Instant::now())) {
+        return Err(io::Error::new(io::ErrorKind::TimedOut, "no answer of the terminal"));
+    }
+    let mut b = [0u8];
+    match StdinReader.read(&mut b)? {
+        1 => Ok(b[0]),
+        _ => Err(io::Error::from(io::ErrorKind::UnexpectedEof)),
+    }
+}
+
+// ask the terminal the row of the cursor, some terminals never answering.
+// This must be called before the thread reading the keys is started.
+fn cursor_row(stderr: &mut dyn Write) -> io::Result<u16> {
+    // the answer looks like `ESC [ row ; col R`
+    write!(stderr, "\x1B[6n")?;
+    stderr.flush()?;
+    let deadline = Instant::now() + CURSOR_ROW_TIMEOUT;
+    let mut answer = String::new();
+    loop {
+        match read_byte_before(deadline)? {
+            b'R' => break,
+            b => answer.push(b as char),
+        }
+    }
+    answer
+        .trim_start_matches(|c| c != '[')
+        .trim_start_matches('[')
+        .split(';')
+        .next()
+        .and_then(|row| row.parse().ok())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "unreadable cursor position"))
+}
+
+/// tell whether the error of a read or write means the terminal is gone
+pub fn is_terminal_loss(e: &io::Error) -> bool {
+    e.kind() == io::ErrorKind::BrokenPipe || e.raw_os_error() == Some(libc::EIO)
+}
+
+impl ScreenWriter {
+    pub fn new(out: Box<dyn Write>) -> ScreenWriter {
+        ScreenWriter {
+            out,
+            row: None,
+            drawn_rows: Vec::new(),
+            lost: false,
+        }
+    }
+    /// tell whether the terminal went away
+    pub fn is_lost(&self) -> bool {
+        self.lost
+    }
+    // check the result of a write to the terminal, stopping
+    //  all writes when the terminal is gone
+    fn check<T>(&mut self, result: io::Result<T>) -> io::Result<T> {
+        if let Err(e) = &result {
+            if is_terminal_loss(e) && !self.lost {
+                warn!("terminal lost: {}", e);
+                self.lost = true;
+                // the raw mode and the alternate screen can't be left: they
+                //  aren't dropped as their drop would fail writing to the terminal
+                mem::forget(mem::replace(&mut self.out, Box::new(io::sink())));
+            }
+        }
+        result
+    }
+    fn lost_error() -> io::Error {
+        io::Error::new(io::ErrorKind::BrokenPipe, "terminal lost")
+    }
+    /// start recording the content of a row instead of writing it
+    pub fn start_row(&mut self, y: u16) {
+        self.row = Some((y, Vec::new()));
+    }
+    /// write the recorded row, unless it's already on screen
+    pub fn end_row(&mut self) -> io::Result<()> {
+        let (y, content) = match self.row.take() {
+            Some(row) => row,
+            None => {
+                return Ok(());
+            }
+        };
+        let mut hasher = DefaultHasher::new();
+        content.hash(&mut hasher);
+        let hash = Some(hasher.finish());
+        let idx = y as usize;
+        if self.drawn_rows.len() <= idx {
+            self.drawn_rows.resize(idx + 1, None);
+        }
+        if self.drawn_rows[idx] != hash {
+            if self.lost {
+                return Err(ScreenWriter::lost_error());
+            }
+            let written = self.out.write_all(&content);
+            self.check(written)?;
+            self.drawn_rows[idx] = hash;
+        }
+        Ok(())
+    }
+    /// forget what was drawn on the row, so that it's written again
+    pub fn forget_row(&mut self, y: u16) {
+        if let Some(hash) = self.drawn_rows.get_mut(y as usize) {
+            *hash = None;
+        }
+    }
+    /// forget what was drawn, so that all rows are written again. This
+    /// must be called when the screen is changed by other writes
+    pub fn invalidate(&mut self) {
+        self.drawn_rows.clear();
+    }
+    /// write to another output, the previous one being dropped
+    pub fn set_out(&mut self, out: Box<dyn Write>) {
+        self.out = out;
+        self.invalidate();
+    }
+}
+
+impl Write for ScreenWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &mut self.row {
+            Some((_, content)) => {
+                content.extend_from_slice(buf);
+                Ok(buf.len())
+            }
+            None if self.lost => Err(ScreenWriter::lost_error()),
+            None => {
+                let written = self.out.write(buf);
+                self.check(written)
+            }
+        }
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        if self.lost {
+            return Err(ScreenWriter::lost_error());
+        }
+        let flushed = self.out.flush();
+        self.check(flushed)
+    }
+}
+
+// the size of the terminal, read on stderr (termion::terminal_size
+//  reads it on stdout, which may be redirected)
+fn terminal_size() -> io::Result<(u16, u16)> {
+    let mut size: libc::winsize = unsafe { mem::zeroed() };
+    if unsafe { libc::ioctl(libc::STDERR_FILENO, libc::TIOCGWINSZ, &mut size) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok((size.ws_col, size.ws_row))
+}
+
 impl Screen {
-    pub fn new(skin: Skin) -> io::Result<Screen> {
-        let stderr = AlternateScreen::from(stderr().into_raw_mode()?);
+    // create the screen, either on the alternate screen buffer or, when
+    // an inline height is given, in the normal buffer below the cursor
+    pub fn new(skin: Skin, inline_height: Option<u16>) -> io::Result<Screen> {
+        let mut raw = stderr().into_raw_mode()?;
+        let (stderr, top): (Box<dyn Write>, u16) = match inline_height {
+            Some(height) => {
+                // we make room for broot, which scrolls the terminal if needed
+                for _ in 1..height {
+                    writeln!(raw)?;
+                }
+                // without an answer, the cursor is assumed to be on the last row
+                let bottom = match cursor_row(&mut raw) {
+                    Ok(row) => row,
+                    Err(_) => terminal_size()?.1,
+                };
+                (Box::new(raw), (bottom + 1).saturating_sub(height).max(1))
+            }
+            None => (Box::new(AlternateScreen::from(raw)), 1),
+        };
         let mut screen = Screen {
             w: 0,
             h: 0,
-            stderr,
+            stderr: ScreenWriter::new(stderr),
             skin,
+            inline_height,
+            top,
+            headless: false,
+            two_line_status: false,
+            terminal_title: false,
+            error_shown: false,
+            nb_statuses: 0,
+            stack_crumbs: Vec::new(),
+            title: None,
         };
         screen.read_size()?;
-        write!(screen.stderr, "{}", termion::cursor::Hide)?;
+        write!(screen.stderr, "{}{}", termion::cursor::Hide, ENABLE_BRACKETED_PASTE)?;
         Ok(screen)
     }
-    pub fn read_size(&mut self) -> io::Result<()> {
-        let (w, h) = termion::terminal_size()?;
+    /// create a screen of the given size which isn't tied to
+    ///  a terminal, writing to out (eg to test broot)
+    #[cfg(test)]
+    pub fn headless(skin: Skin, w: u16, h: u16, out: Box<dyn Write>) -> Screen {
+        Screen {
+            w,
+            h,
+            stderr: ScreenWriter::new(out),
+            skin,
+            inline_height: None,
+            top: 1,
+            headless: true,
+            two_line_status: false,
+            terminal_title: false,
+            error_shown: false,
+            nb_statuses: 0,
+            stack_crumbs: Vec::new(),
+            title: None,
+        }
+    }
+    /// read the size of the terminal, returning whether it changed
+    pub fn read_size(&mut self) -> io::Result<bool> {
+        if self.headless {
+            return Ok(false);
+        }
+        let (w, h) = terminal_size()?;
+        let (old_w, old_h) = (self.w, self.h);
         self.w = w;
-        self.h = h;
+        self.h = match self.inline_height {
+            Some(inline_height) => inline_height.min(h),
+            None => h,
+        };
+        // the terminal may have been shrinked below our area
+        if self.top + self.h > h + 1 {
+            self.top = h + 1 - self.h;
+        }
+        let changed = self.w != old_w || self.h != old_h;
+        if changed {
+            self.stderr.invalidate();
+        }
+        Ok(changed)
+    }
+    // return the cursor move to a position given relatively
+    // to the area of broot (the top left corner being (1, 1))
+    pub fn goto(&self, x: u16, y: u16) -> termion::cursor::Goto {
+        termion::cursor::Goto(x, y + self.top - 1)
+    }
+    // clear all the lines used by broot
+    pub fn clear(&mut self) -> io::Result<()> {
+        self.stderr.invalidate();
+        for y in 1..=self.h {
+            write!(
+                self.stderr,
+                "{}{}",
+                self.goto(1, y),
+                termion::clear::CurrentLine
+            )?;
+        }
+        Ok(())
+    }
+    // tell whether the terminal is too small for a normal display.
+    // Writers should draw nothing in such a case.
+    pub fn is_too_small(&self) -> bool {
+        self.w < MIN_WIDTH || self.h < MIN_HEIGHT
+    }
+    // clear the screen and tell the user what size is needed
+    pub fn write_too_small(&mut self) -> io::Result<()> {
+        let mut text = format!(
+            "terminal too small (need at least {}x{})",
+            MIN_WIDTH, MIN_HEIGHT
+        );
+        text.truncate(self.w as usize);
+        let x = (self.w - text.len() as u16) / 2 + 1;
+        let y = self.h / 2 + 1;
+        self.reset_colors()?;
+        self.clear()?;
+        write!(self.stderr, "{}{}", self.goto(x, y), text)?;
+        self.stderr.flush()
+    }
+    /// give the terminal back as it was before broot, out of raw mode
+    ///  and showing the normal screen buffer, until resume is called
+    pub fn suspend(&mut self) -> io::Result<()> {
+        if self.headless {
+            return Ok(());
+        }
+        self.leave()?;
+        // dropping the raw terminal and the alternate screen restores them
+        self.stderr.set_out(Box::new(stderr()));
+        Ok(())
+    }
+    /// take the terminal again after a suspend. Everything
+    ///  must then be drawn again.
+    pub fn resume(&mut self) -> io::Result<()> {
+        if self.headless {
+            return Ok(());
+        }
+        let raw = stderr().into_raw_mode()?;
+        self.stderr.set_out(match self.inline_height {
+            Some(_) => Box::new(raw),
+            None => Box::new(AlternateScreen::from(raw)),
+        });
+        self.read_size()?;
+        write!(self.stderr, "{}{}", termion::cursor::Hide, ENABLE_BRACKETED_PASTE)
+    }
+    /// set the title of the terminal, when it's allowed. The title
+    ///  the terminal had before is saved, to be restored on leave
+    pub fn set_title(&mut self, title: &str) -> io::Result<()> {
+        if !self.terminal_title {
+            return Ok(());
+        }
+        // a weird name mustn't end the sequence or start another one
+        let title: String = title.chars().filter(|c| !c.is_control()).collect();
+        if self.title.as_ref() == Some(&title) {
+            return Ok(());
+        }
+        if self.title.is_none() {
+            write!(self.stderr, "{}", PUSH_TITLE)?;
+        }
+        write!(self.stderr, "\x1b]2;{}\x07", title)?;
+        self.title = Some(title);
+        Ok(())
+    }
+    /// give the terminal back the title it had before set_title
+    pub fn restore_title(&mut self) -> io::Result<()> {
+        if self.title.take().is_some() {
+            write!(self.stderr, "{}", POP_TITLE)?;
+        }
         Ok(())
     }
+    // clean what broot drew when it's not in the alternate screen,
+    //  and show the cursor
+    fn leave(&mut self) -> io::Result<()> {
+        self.restore_title()?;
+        if self.inline_height.is_some() {
+            // we leave no trace in the normal screen buffer
+            self.reset_colors()?;
+            self.clear()?;
+            write!(self.stderr, "{}", self.goto(1, 1))?;
+        }
+        write!(self.stderr, "{}{}", termion::cursor::Show, DISABLE_BRACKETED_PASTE)?;
+        // if we don't flush now, the standard screen may receive some
+        // unflushed data which was meant for the alternate screen.
+        self.stderr.flush()
+    }
     pub fn reset_colors(&mut self) -> io::Result<()> {
         write!(
             self.stderr,
@@ -51,10 +404,12 @@ impl Screen {
 
 impl Drop for Screen {
     fn drop(&mut self) {
-        write!(self.stderr, "{}", termion::cursor::Show).unwrap();
-        // if we don't flush now, the standard screen may receive some
-        // unflushed data which was meant for the alternate screen.
-        self.stderr.flush().unwrap();
+        if self.stderr.is_lost() {
+            return;
+        }
+        if let Err(e) = self.leave() {
+            warn!("error while leaving the screen: {}", e);
+        }
     }
 }
 
@@ -69,20 +424,125 @@ impl ScreenArea {
         }
     }
     pub fn try_scroll(&mut self, dy: i32) {
-        self.scroll = (self.scroll + dy)
-            .max(0)
-            .min(self.content_length - self.height() + 1);
+        self.scroll = (self.scroll + dy).max(0).min(self.max_scroll());
+    }
+    // the scroll showing the last line at the bottom of the area (when
+    // the content is shorter than the area, there's nothing to scroll)
+    pub fn max_scroll(&self) -> i32 {
+        (self.content_length - self.height()).max(0)
+    }
+    /// the indexes of the content lines drawn in the area, the first one
+    /// being on the top row. The scroll is clamped so that nothing is drawn
+    /// past the bottom and so that the last lines are shown at max scroll.
+    pub fn visible_range(&self) -> Range<usize> {
+        let scroll = self.scroll.max(0).min(self.max_scroll());
+        let end = (scroll + self.height()).min(self.content_length.max(0));
+        scroll as usize..end.max(scroll) as usize
     }
     pub fn scrollbar(&self) -> Option<(u16, u16)> {
         let h = self.height();
-        if self.content_length <= h {
+        if h <= 0 || self.content_length <= h {
             return None;
         }
-        let sbh = h * h / self.content_length;
-        let sc = i32::from(self.top) + self.scroll * h / self.content_length;
-        Some((sc as u16, (sc + sbh - 1).min(i32::from(self.bottom)) as u16))
+        let sbh = (h * h / self.content_length).max(1);
+        // the bar moves on h - sbh rows, from the top at scroll 0
+        //  to the bottom at max scroll
+        let scroll = self.visible_range().start as i32;
+        let max_scroll = self.max_scroll();
+        let sc = i32::from(self.top) + (scroll * (h - sbh) + max_scroll / 2) / max_scroll;
+        Some((sc as u16, (sc + sbh - 1) as u16))
     }
     pub fn height(&self) -> i32 {
-        i32::from(self.bottom - self.top) + 1
+        (i32::from(self.bottom) - i32::from(self.top) + 1).max(0)
+    }
+}
+
+#[cfg(test)]
+mod screen_area_tests {
+    use super::*;
+
+    #[test]
+    fn degenerate_dimensions() {
+        // bottom above top: no room at all
+        let mut area = ScreenArea::new(3, 1, 10);
+        area.content_length = 50;
+        assert_eq!(area.height(), 0);
+        assert_eq!(area.scrollbar(), None);
+        area.try_scroll(5);
+        assert_eq!(area.scroll, 5);
+        assert_eq!(area.visible_range(), 5..5);
+        // a single line
+        let mut area = ScreenArea::new(1, 1, 0);
+        area.content_length = 3;
+        assert_eq!(area.height(), 1);
+        assert_eq!(area.scrollbar(), Some((1, 1)));
+        // no content
+        let mut area = ScreenArea::new(1, 5, 10);
+        area.try_scroll(-3);
+        assert_eq!(area.scroll, 0);
+        area.try_scroll(3);
+        assert_eq!(area.scroll, 0);
+        assert_eq!(area.scrollbar(), None);
+    }
+
+    // a small deterministic generator, for tests over many sizes
+    //  which don't need a dependency
+    fn pseudo_random(seed: &mut u64, max: i32) -> i32 {
+        *seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        ((*seed >> 33) % (max as u64 + 1)) as i32
+    }
+
+    #[test]
+    fn nothing_drawn_past_bottom() {
+        let mut seed = 42;
+        for _ in 0..10_000 {
+            let top = 1 + pseudo_random(&mut seed, 5) as u16;
+            let bottom = pseudo_random(&mut seed, 40) as u16;
+            let mut area = ScreenArea::new(top, bottom, 80);
+            area.content_length = pseudo_random(&mut seed, 80);
+            area.scroll = pseudo_random(&mut seed, 100) - 10;
+            let range = area.visible_range();
+            assert!(range.end as i32 <= area.content_length);
+            assert!((range.end - range.start) as i32 <= area.height(), "{:?}", area);
+            if let Some((sctop, scbottom)) = area.scrollbar() {
+                assert!(top <= sctop && sctop <= scbottom && scbottom <= bottom, "{:?}", area);
+            }
+        }
+    }
+
+    #[test]
+    fn every_line_reachable() {
+        let mut seed = 7;
+        for _ in 0..2_000 {
+            let top = 1 + pseudo_random(&mut seed, 3) as u16;
+            let bottom = top + pseudo_random(&mut seed, 30) as u16;
+            let mut area = ScreenArea::new(top, bottom, 80);
+            area.content_length = pseudo_random(&mut seed, 60);
+            let mut seen = vec![false; area.content_length as usize];
+            let mut previous_scroll = -1;
+            // scrolling line by line, from the top to the end
+            while area.scroll != previous_scroll {
+                for i in area.visible_range() {
+                    seen[i] = true;
+                }
+                previous_scroll = area.scroll;
+                area.try_scroll(1);
+            }
+            assert!(seen.iter().all(|&s| s), "{:?}", area);
+            // at max scroll, the area is full or shows everything
+            let range = area.visible_range();
+            assert_eq!(range.end as i32, area.content_length);
+            assert_eq!(
+                (range.end - range.start) as i32,
+                area.height().min(area.content_length)
+            );
+            // and the scrollbar touches the bottom
+            if let Some((_, scbottom)) = area.scrollbar() {
+                assert_eq!(scbottom, bottom);
+            }
+            // scrolling a lot doesn't go farther
+            area.try_scroll(1000);
+            assert_eq!(area.visible_range(), range);
+        }
     }
 }
@@ -0,0 +1,89 @@
+//! the writing of the selected path in a file, as soon as it changes,
+//!  for external tools like a previewer watching this file.
+//! The file is replaced, never partially written, and the writing waits
+//!  for the selection to be stable a little, so that a fast move doesn't
+//!  make the watchers preview every line passed over.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{Duration, Instant};
+
+/// how long the selection must stay the same before being written
+const DEBOUNCE_DELAY: Duration = Duration::from_millis(50);
+
+pub struct SelectionExport {
+    path: PathBuf,                          // the file where the selection is written
+    written: Option<PathBuf>,               // the last selection written
+    pending: Option<(PathBuf, Instant)>,    // the selection to write, and when it was seen
+    failed: bool,                           // an error stopped the export
+}
+
+impl SelectionExport {
+    pub fn new(path: PathBuf) -> SelectionExport {
+        SelectionExport {
+            path,
+            written: None,
+            pending: None,
+            failed: false,
+        }
+    }
+    /// take the current selection into account, None meaning
+    ///  the state doesn't have one and the file is left as it is
+    pub fn note(&mut self, selection: Option<PathBuf>) {
+        let selection = match selection {
+            Some(selection) => selection,
+            None => {
+                return;
+            }
+        };
+        if self.pending.as_ref().map(|(path, _)| path) == Some(&selection) {
+            return;
+        }
+        if self.written.as_ref() == Some(&selection) {
+            self.pending = None;
+        } else {
+            self.pending = Some((selection, Instant::now()));
+        }
+    }
+    /// write the pending selection when it's stable enough, or, with
+    ///  force, right now. An error disables the export and is returned
+    ///  only once, so that it's told once
+    pub fn write(&mut self, force: bool) -> io::Result<()> {
+        if self.failed {
+            return Ok(());
+        }
+        let due = match &self.pending {
+            Some((_, seen)) => force || seen.elapsed() >= DEBOUNCE_DELAY,
+            None => false,
+        };
+        if !due {
+            return Ok(());
+        }
+        let (selection, _) = self.pending.take().unwrap();
+        if let Err(e) = replace_file(&self.path, &selection) {
+            self.failed = true;
+            return Err(e);
+        }
+        self.written = Some(selection);
+        Ok(())
+    }
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+// write the selection in a temporary file next to the destination,
+//  then rename it, so that a reader never sees a partial path
+fn replace_file(path: &Path, selection: &Path) -> io::Result<()> {
+    let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(format!(".{}.tmp", process::id()));
+    let tmp_path = path.with_file_name(tmp_name);
+    let content = format!("{}\n", selection.to_string_lossy());
+    if let Err(e) = fs::write(&tmp_path, content).and_then(|_| fs::rename(&tmp_path, path)) {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(e);
+    }
+    Ok(())
+}
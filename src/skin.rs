@@ -1,11 +1,100 @@
-/// Defines the Skin structure with its defautl value.
+/// Defines the Skin structure with its defautl values,
+/// for a dark or a light terminal background.
 ///
 /// A skin is a collection of skin entries and the
 /// store of all color changing TTY codes used by
 /// the application. It can be changed by configuration.
 use std::collections::HashMap;
+use std::env;
+use std::fmt;
+use std::str::FromStr;
 use termion::color::{self, *};
 
+use crate::errors::ProgramError;
+
+/// the set of default colors, which should be chosen
+/// according to the background of the terminal
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Palette {
+    Dark,
+    Light,
+}
+
+/// why a palette was chosen
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PaletteSource {
+    LaunchArgument, // --skin
+    Configuration,  // skin_palette in conf.toml
+    Detected,       // from the COLORFGBG env variable
+    Default,        // nothing was found
+}
+
+impl FromStr for Palette {
+    type Err = ProgramError;
+    fn from_str(s: &str) -> Result<Palette, ProgramError> {
+        match s {
+            "dark" => Ok(Palette::Dark),
+            "light" => Ok(Palette::Light),
+            _ => Err(ProgramError::ArgParse {
+                bad: s.to_string(),
+                valid: "dark, light".to_string(),
+            }),
+        }
+    }
+}
+
+impl fmt::Display for Palette {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Palette::Dark => "dark",
+            Palette::Light => "light",
+        })
+    }
+}
+
+impl Palette {
+    /// read the palette from the value of COLORFGBG, which is like "15;0"
+    /// or "0;default;15", the last number being the background color
+    pub fn from_colorfgbg(value: &str) -> Option<Palette> {
+        match value.rsplit(';').next()?.parse::<u8>().ok()? {
+            0..=6 | 8 => Some(Palette::Dark),
+            7 | 9..=15 => Some(Palette::Light),
+            _ => None,
+        }
+    }
+    /// choose the palette, the launch argument having precedence
+    /// over the configuration, which has precedence over the detection
+    pub fn choose(
+        launch_arg: Option<Palette>,
+        conf: Option<Palette>,
+    ) -> (Palette, PaletteSource) {
+        if let Some(palette) = launch_arg {
+            return (palette, PaletteSource::LaunchArgument);
+        }
+        if let Some(palette) = conf {
+            return (palette, PaletteSource::Configuration);
+        }
+        let detected = env::var("COLORFGBG")
+            .ok()
+            .and_then(|value| Palette::from_colorfgbg(&value));
+        match detected {
+            Some(palette) => (palette, PaletteSource::Detected),
+            None => (Palette::Dark, PaletteSource::Default),
+        }
+    }
+}
+
+impl fmt::Display for PaletteSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            PaletteSource::LaunchArgument => "set with --skin",
+            PaletteSource::Configuration => "set with skin_palette in the configuration",
+            PaletteSource::Detected => "detected from COLORFGBG",
+            PaletteSource::Default => "default, COLORFGBG giving no background",
+        })
+    }
+}
+
 /// a Skin entry is a couple of strings, one for the foreground
 /// and one for the background, each one made of TTY escape
 /// codes defining color changes.
@@ -22,53 +111,137 @@ impl SkinEntry {
 
 macro_rules! Skin {
     (
-        $($name:ident: $fg:expr, $bg:expr)*
+        $($name:ident: $dark_fg:expr, $dark_bg:expr; $light_fg:expr, $light_bg:expr)*
     ) => {
         pub struct Skin {
             $(pub $name: SkinEntry,)*
             pub reset: SkinEntry,
+            pub palette: Palette,
+            pub palette_source: PaletteSource,
         }
         impl Skin {
-            pub fn create(mut skin_conf: HashMap<String, String>) -> Skin {
+            /// build the skin from the default colors of the palette,
+            /// overridden by the entries of the configuration
+            pub fn create(
+                palette: Palette,
+                palette_source: PaletteSource,
+                mut skin_conf: HashMap<String, String>,
+            ) -> Skin {
                 Skin {
                     $($name: SkinEntry {
                         fg: skin_conf.remove(&format!("{}_fg", stringify!($name))).unwrap_or(
-                            format!("{}", color::Fg($fg)).to_string()
+                            match palette {
+                                Palette::Dark => format!("{}", color::Fg($dark_fg)),
+                                Palette::Light => format!("{}", color::Fg($light_fg)),
+                            }
                         ),
                         bg: skin_conf.remove(&format!("{}_bg", stringify!($name))).unwrap_or(
-                            format!("{}", color::Bg($bg)).to_string()
+                            match palette {
+                                Palette::Dark => format!("{}", color::Bg($dark_bg)),
+                                Palette::Light => format!("{}", color::Bg($light_bg)),
+                            }
                         ),
                     },)*
                     reset: SkinEntry {
                         fg: format!("{}", color::Fg(color::Reset)).to_string(),
                         bg: format!("{}", color::Bg(color::Reset)).to_string(),
                     },
+                    palette,
+                    palette_source,
                 }
             }
+            /// the default colors of the palette, as they would be written
+            /// in the [skin] section of the configuration (None when the
+            /// color can't be written there)
+            pub fn default_conf_entries(palette: Palette) -> Vec<(String, Option<String>)> {
+                let mut entries = Vec::new();
+                $({
+                    let (fg, bg) = match palette {
+                        Palette::Dark => (stringify!($dark_fg), stringify!($dark_bg)),
+                        Palette::Light => (stringify!($light_fg), stringify!($light_bg)),
+                    };
+                    entries.push((format!("{}_fg", stringify!($name)), conf_color(fg)));
+                    entries.push((format!("{}_bg", stringify!($name)), conf_color(bg)));
+                })*
+                entries
+            }
         }
     }
 }
 
+// translate the source of a default color (eg "AnsiValue::grayscale(2)")
+//  into the syntax of the configuration (eg "grayscale(2)")
+fn conf_color(source: &str) -> Option<String> {
+    let source = source.replace(' ', "");
+    if source == "Reset" {
+        Some("none".to_string())
+    } else if source.starts_with("AnsiValue::grayscale(") {
+        Some(source["AnsiValue::".len()..].to_string())
+    } else if source.chars().all(|c| c.is_ascii_alphabetic()) {
+        Some(source)
+    } else {
+        None // rgb colors can't be configured
+    }
+}
+
+// for each entry: the fg and bg on a dark background, then on a light one
 Skin! {
-    char_match: Green, Reset
-    code: Reset, AnsiValue::grayscale(2)
-    directory: LightBlue, Reset
-    exe: LightCyan, Reset
-    file: White, Reset
-    file_error: Red, Reset
-    flag_label: AnsiValue::grayscale(12), AnsiValue::grayscale(1)
-    flag_value: AnsiValue::grayscale(16), AnsiValue::grayscale(1)
-    input: White, Reset
-    link: LightMagenta, Reset
-    permissions: AnsiValue::grayscale(15), Reset
-    selected_line: Reset, AnsiValue::grayscale(3)
-    size_bar_full: Reset, Magenta
-    size_bar_void: Reset, AnsiValue::grayscale(2)
-    size_text: AnsiValue::grayscale(15), Reset
-    spinner: AnsiValue::grayscale(10), AnsiValue::grayscale(2)
-    status_error: Red, AnsiValue::grayscale(2)
-    status_normal: White, AnsiValue::grayscale(2)
-    table_border: AnsiValue::grayscale(8), Reset
-    tree: AnsiValue::grayscale(5), Reset
-    unlisted: AnsiValue::grayscale(13), Reset
+    char_match: Green, Reset; Green, Reset
+    char_match_selected: LightGreen, Reset; AnsiValue::rgb(0, 3, 0), Reset
+    code: Reset, AnsiValue::grayscale(2); Reset, AnsiValue::grayscale(21)
+    context: AnsiValue::grayscale(14), Reset; AnsiValue::grayscale(9), Reset
+    dates: AnsiValue::grayscale(15), Reset; AnsiValue::grayscale(8), Reset
+    device: LightYellow, Reset; Yellow, Reset
+    directory: LightBlue, Reset; Blue, Reset
+    exe: LightCyan, Reset; Cyan, Reset
+    fifo: Yellow, Reset; Yellow, Reset
+    file: White, Reset; Black, Reset
+    file_error: Red, Reset; Red, Reset
+    flag_label: AnsiValue::grayscale(12), AnsiValue::grayscale(1); AnsiValue::grayscale(9), AnsiValue::grayscale(22)
+    flag_value: AnsiValue::grayscale(16), AnsiValue::grayscale(1); AnsiValue::grayscale(4), AnsiValue::grayscale(22)
+    git_repo: LightGreen, Reset; Green, Reset
+    hidden: AnsiValue::grayscale(11), Reset; AnsiValue::grayscale(13), Reset
+    hint: Black, LightYellow; White, Red
+    input: White, Reset; Black, Reset
+    input_prompt: AnsiValue::grayscale(12), Reset; AnsiValue::grayscale(10), Reset
+    input_verb: LightCyan, Reset; Blue, Reset
+    line_number: AnsiValue::grayscale(9), Reset; AnsiValue::grayscale(14), Reset
+    link: LightMagenta, Reset; Magenta, Reset
+    lock: LightRed, Reset; Red, Reset
+    permissions: AnsiValue::grayscale(15), Reset; AnsiValue::grayscale(8), Reset
+    selected_line: Reset, AnsiValue::grayscale(3); Reset, AnsiValue::grayscale(20)
+    size_bar_full: Reset, Magenta; Reset, LightMagenta
+    size_bar_void: Reset, AnsiValue::grayscale(2); Reset, AnsiValue::grayscale(21)
+    size_text: AnsiValue::grayscale(15), Reset; AnsiValue::grayscale(8), Reset
+    socket: LightRed, Reset; Red, Reset
+    spinner: AnsiValue::grayscale(10), AnsiValue::grayscale(2); AnsiValue::grayscale(13), AnsiValue::grayscale(21)
+    status_error: Red, AnsiValue::grayscale(2); Red, AnsiValue::grayscale(21)
+    status_normal: White, AnsiValue::grayscale(2); Black, AnsiValue::grayscale(21)
+    table_border: AnsiValue::grayscale(8), Reset; AnsiValue::grayscale(15), Reset
+    tree: AnsiValue::grayscale(5), Reset; AnsiValue::grayscale(18), Reset
+    tree_summary: AnsiValue::grayscale(13), AnsiValue::grayscale(1); AnsiValue::grayscale(10), AnsiValue::grayscale(22)
+    unlisted: AnsiValue::grayscale(13), Reset; AnsiValue::grayscale(10), Reset
+}
+
+#[cfg(test)]
+mod palette_tests {
+    use super::*;
+
+    #[test]
+    fn colorfgbg() {
+        assert_eq!(Palette::from_colorfgbg("15;0"), Some(Palette::Dark));
+        assert_eq!(Palette::from_colorfgbg("0;15"), Some(Palette::Light));
+        assert_eq!(Palette::from_colorfgbg("0;default;15"), Some(Palette::Light));
+        assert_eq!(Palette::from_colorfgbg("12;8"), Some(Palette::Dark));
+        assert_eq!(Palette::from_colorfgbg("7;default"), None);
+        assert_eq!(Palette::from_colorfgbg(""), None);
+    }
+
+    #[test]
+    fn conf_colors() {
+        assert_eq!(conf_color("Reset"), Some("none".to_string()));
+        assert_eq!(conf_color("LightBlue"), Some("LightBlue".to_string()));
+        assert_eq!(conf_color("AnsiValue :: grayscale(12)"), Some("grayscale(12)".to_string()));
+        assert_eq!(conf_color("AnsiValue::rgb(0, 3, 0)"), None);
+    }
 }
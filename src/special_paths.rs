@@ -0,0 +1,169 @@
+//! the display options the configuration gives to the roots matching
+//!  some paths, eg sizes in ~/media and gitignore filtering in ~/code.
+//! They're applied when a root is opened (on launch, on :focus, etc.),
+//!  the options toggled afterwards winning for the state.
+
+use std::path::Path;
+
+use crate::tree_options::{OptionBool, Sort, TreeOptions};
+
+/// the options of the roots matching a glob, each one
+///  being None when it's not overriden
+#[derive(Debug)]
+pub struct SpecialPath {
+    pub name: String, // shown in the flags when the options are applied
+    pub glob: glob::Pattern,
+    pub show_hidden: Option<bool>,
+    pub show_sizes: Option<bool>,
+    pub show_dates: Option<bool>,
+    pub show_permissions: Option<bool>,
+    pub show_owner: Option<bool>,
+    pub only_folders: Option<bool>,
+    pub respect_git_ignore: Option<OptionBool>,
+    pub sort: Option<Sort>,
+    pub dirs_first: Option<bool>,
+}
+
+impl SpecialPath {
+    fn matches(&self, root: &Path) -> bool {
+        let options = glob::MatchOptions {
+            case_sensitive: true,
+            require_literal_separator: true, // so that ~/code/* isn't ~/code/a/b
+            require_literal_leading_dot: false,
+        };
+        self.glob.matches_path_with(root, &options)
+    }
+    // take back, from the options as they were before this special
+    //  path was applied, the ones it changes
+    fn take_back(&self, options: &mut TreeOptions, base: &TreeOptions) {
+        if self.show_hidden.is_some() {
+            options.show_hidden = base.show_hidden;
+        }
+        if self.show_sizes.is_some() {
+            options.show_sizes = base.show_sizes;
+        }
+        if self.show_dates.is_some() {
+            options.show_dates = base.show_dates;
+        }
+        if self.show_permissions.is_some() {
+            options.show_permissions = base.show_permissions;
+        }
+        if self.show_owner.is_some() {
+            options.show_owner = base.show_owner;
+        }
+        if self.only_folders.is_some() {
+            options.only_folders = base.only_folders;
+        }
+        if self.respect_git_ignore.is_some() {
+            options.respect_git_ignore = base.respect_git_ignore;
+        }
+        if self.sort.is_some() {
+            options.sort = base.sort;
+        }
+        if self.dirs_first.is_some() {
+            options.dirs_first = base.dirs_first;
+        }
+    }
+    fn apply_to(&self, options: &mut TreeOptions) {
+        if let Some(b) = self.show_hidden {
+            options.show_hidden = b;
+        }
+        if let Some(b) = self.show_sizes {
+            options.show_sizes = b;
+        }
+        if let Some(b) = self.show_dates {
+            options.show_dates = b;
+        }
+        if let Some(b) = self.show_permissions {
+            options.show_permissions = b;
+        }
+        if let Some(b) = self.show_owner {
+            options.show_owner = b;
+        }
+        if let Some(b) = self.only_folders {
+            options.only_folders = b;
+        }
+        if let Some(ob) = self.respect_git_ignore {
+            options.respect_git_ignore = ob;
+        }
+        if let Some(sort) = self.sort {
+            options.sort = sort;
+        }
+        if let Some(b) = self.dirs_first {
+            options.dirs_first = b;
+        }
+    }
+}
+
+/// change the options of a new root according to the first special
+///  path matching its canonical path. The options the special path of
+///  the previous root changed are taken back first
+pub fn apply(special_paths: &[SpecialPath], root: &Path, options: &mut TreeOptions) {
+    let root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+    if let Some(base) = options.before_special_path.take() {
+        let previous = options.special_path.as_ref()
+            .and_then(|name| special_paths.iter().find(|sp| &sp.name == name));
+        if let Some(previous) = previous {
+            previous.take_back(options, &base);
+        }
+    }
+    options.special_path = None;
+    if let Some(special_path) = special_paths.iter().find(|sp| sp.matches(&root)) {
+        debug!("options of special path {:?} applied", &special_path.name);
+        options.before_special_path = Some(Box::new(options.clone()));
+        special_path.apply_to(options);
+        options.special_path = Some(special_path.name.clone());
+    }
+}
+
+/// apply the special path of the root given on launch, the
+///  options set with launch arguments winning
+pub fn apply_at_launch(special_paths: &[SpecialPath], root: &Path, options: &mut TreeOptions) {
+    let launch_options = options.clone();
+    apply(special_paths, root, options);
+    // the flags of the arguments can only set the options
+    options.show_hidden |= launch_options.show_hidden;
+    options.show_sizes |= launch_options.show_sizes;
+    options.show_dates |= launch_options.show_dates;
+    options.show_permissions |= launch_options.show_permissions;
+    options.show_owner |= launch_options.show_owner;
+    options.only_folders |= launch_options.only_folders;
+    if launch_options.respect_git_ignore != OptionBool::Auto {
+        options.respect_git_ignore = launch_options.respect_git_ignore;
+    }
+}
+
+#[cfg(test)]
+mod special_paths_tests {
+    use super::*;
+
+    #[test]
+    fn options_taken_back_on_a_plain_root() {
+        let special_paths = vec![SpecialPath {
+            name: "media".to_string(),
+            glob: glob::Pattern::new("/media/*").unwrap(),
+            show_hidden: None,
+            show_sizes: Some(true),
+            show_dates: Some(false),
+            show_permissions: None,
+            show_owner: None,
+            only_folders: None,
+            respect_git_ignore: None,
+            sort: None,
+            dirs_first: None,
+        }];
+        let mut options = TreeOptions::new();
+        options.show_dates = true;
+        apply(&special_paths, Path::new("/media/films"), &mut options);
+        assert_eq!(options.special_path.as_deref(), Some("media"));
+        assert!(options.show_sizes);
+        assert!(!options.show_dates);
+        // the options toggled in the special root, and not set by it, are kept
+        options.show_hidden = true;
+        apply(&special_paths, Path::new("/home"), &mut options);
+        assert_eq!(options.special_path, None);
+        assert!(!options.show_sizes);
+        assert!(options.show_dates);
+        assert!(options.show_hidden);
+    }
+}
@@ -11,11 +11,14 @@ pub trait Spinner {
 
 impl Spinner for Screen {
     fn write_spinner(&mut self, spinning: bool) -> io::Result<()> {
-        let y = self.h - 1;
+        if self.is_too_small() {
+            return Ok(());
+        }
+        let y = self.h.saturating_sub(1);
         write!(
             self.stderr,
             "{}{}{}{}",
-            termion::cursor::Goto(1, y),
+            self.goto(1, y),
             self.skin.spinner.fg,
             self.skin.spinner.bg,
             if spinning { "⌛" } else { " " },
@@ -9,21 +9,82 @@ use crate::screens::Screen;
 pub trait Status {
     fn write_status_text(&mut self, text: &str) -> io::Result<()>;
     fn write_status_err(&mut self, text: &str) -> io::Result<()>;
+    fn write_status_transient(&mut self, text: &str) -> io::Result<()>;
+    fn write_status_menu(&mut self, labels: &[&str]) -> io::Result<()>;
+}
+
+// the text with its line breaks, tabs and other control chars replaced
+//  with spaces, as they would break the layout
+fn one_line(text: &str) -> String {
+    text.chars().map(|c| if c.is_control() { ' ' } else { c }).collect()
+}
+
+/// make the text fit in width chars, a too long one being cut with a '…'
+pub fn fit(text: &str, width: usize) -> String {
+    let text = one_line(text);
+    if text.chars().count() <= width {
+        text
+    } else {
+        let mut fitted: String = text.chars().take(width.saturating_sub(1)).collect();
+        fitted.push('…');
+        fitted
+    }
+}
+
+// cut the text in two lines of at most width chars, preferably after a
+//  space, the second one being cut with a '…' if it's still too long
+fn fit_in_two(text: &str, width: usize) -> (String, String) {
+    let text = one_line(text);
+    let chars: Vec<char> = text.chars().collect();
+    if chars.len() <= width {
+        return (text, String::new());
+    }
+    let cut = match chars[..=width].iter().rposition(|&c| c == ' ') {
+        Some(space) if space > width / 2 => space,
+        _ => width,
+    };
+    let first: String = chars[..cut].iter().collect();
+    let second: String = chars[cut..].iter().collect();
+    (first.trim_end().to_string(), fit(second.trim_start(), width))
 }
 
 impl Screen {
     fn write_status(&mut self, text: &str, error: bool) -> io::Result<()> {
+        if self.is_too_small() {
+            return Ok(());
+        }
+        self.error_shown = error;
+        self.nb_statuses += 1;
         let skin = if error {
             &self.skin.status_error
         } else {
             &self.skin.status_normal
         };
-        let mut text = String::from(text);
-        text.truncate(self.w as usize - 2);
+        let width = (self.w as usize).saturating_sub(2);
+        let y = self.h.saturating_sub(1);
+        let text = if error && self.two_line_status && text.chars().count() > width {
+            // the first line of the error borrows the last row of the
+            //  tree, which must be written again on next display
+            let (first, second) = fit_in_two(text, width);
+            write!(
+                self.stderr,
+                "{}{}{}{} {}{}",
+                self.goto(2, y - 1),
+                skin.fg,
+                skin.bg,
+                termion::clear::CurrentLine,
+                first,
+                self.skin.reset.bg,
+            )?;
+            self.stderr.forget_row(y - 1);
+            second
+        } else {
+            fit(text, width)
+        };
         write!(
             self.stderr,
             "{}{}{}{} {}{}",
-            termion::cursor::Goto(2, self.h - 1),
+            self.goto(2, y),
             skin.fg,
             skin.bg,
             termion::clear::CurrentLine,
@@ -43,4 +104,87 @@ impl Status for Screen {
     fn write_status_text(&mut self, text: &str) -> io::Result<()> {
         self.write_status(text, false)
     }
+
+    /// write an information only worth telling until the next event,
+    ///  which doesn't replace an error already shown
+    fn write_status_transient(&mut self, text: &str) -> io::Result<()> {
+        if self.error_shown {
+            return Ok(());
+        }
+        self.write_status(text, false)
+    }
+
+    /// write a list of entries whose first char is the key to type,
+    ///  eg "[h]idden [s]izes"
+    fn write_status_menu(&mut self, labels: &[&str]) -> io::Result<()> {
+        if self.is_too_small() {
+            return Ok(());
+        }
+        self.error_shown = false;
+        self.nb_statuses += 1;
+        let skin = &self.skin.status_normal;
+        let mut line = String::new();
+        let mut width = 1;
+        for label in labels {
+            let mut chars = label.chars();
+            let key = match chars.next() {
+                Some(c) => c,
+                None => continue,
+            };
+            let rest: String = chars.collect();
+            width += rest.chars().count() + 4;
+            if width > (self.w as usize).saturating_sub(2) {
+                break;
+            }
+            line.push_str(&format!(
+                " [{}{}{}]{}",
+                self.skin.char_match.fg, key, skin.fg, rest,
+            ));
+        }
+        write!(
+            self.stderr,
+            "{}{}{}{}{}{}",
+            self.goto(2, self.h.saturating_sub(1)),
+            skin.fg,
+            skin.bg,
+            termion::clear::CurrentLine,
+            line,
+            self.skin.reset.bg,
+        )?;
+        self.stderr.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod status_tests {
+    use super::*;
+
+    #[test]
+    fn fitting_texts() {
+        assert_eq!(fit("short", 10), "short");
+        assert_eq!(fit("exactly 10", 10), "exactly 10");
+        assert_eq!(fit("a bit too long", 10), "a bit too…");
+        assert_eq!(fit("line\nbreak", 20), "line break");
+        assert_eq!(fit("éèàùçôîû", 4), "éèà…");
+        assert_eq!(fit("abc", 0), "…");
+    }
+
+    #[test]
+    fn fitting_texts_in_two_lines() {
+        assert_eq!(fit_in_two("short", 10), ("short".to_string(), String::new()));
+        assert_eq!(
+            fit_in_two("not found: some_program", 12),
+            ("not found:".to_string(), "some_program".to_string()),
+        );
+        // without a space in the second half, the cut is done anywhere
+        assert_eq!(
+            fit_in_two("a averylongword", 10),
+            ("a averylon".to_string(), "gword".to_string()),
+        );
+        assert_eq!(
+            fit_in_two("one two three four five six", 8),
+            ("one two".to_string(), "three f…".to_string()),
+        );
+    }
 }
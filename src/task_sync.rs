@@ -1,5 +1,7 @@
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
 
 /// a TL initialized from an Arc<AtomicUsize> stays
 ///  alive as long as the passed arc doesn't change.
@@ -32,3 +34,57 @@ impl TaskLifetime {
         self.initial_value != self.external_value.load(Ordering::Relaxed)
     }
 }
+
+// the minimal delay between two refreshes, the requests made in
+//  between being coalesced
+const REFRESH_PERIOD: Duration = Duration::from_millis(200);
+
+// how long a pending refresh which isn't due waits at most, before
+//  letting the application check for events
+const REFRESH_WAIT_TICK: Duration = Duration::from_millis(10);
+
+/// the requests to read again what a state displays, coalesced so
+///  that a burst of requests doesn't make a refresh for each one
+pub struct RefreshRequest {
+    requested: bool,
+    done_at: Instant, // when the last refresh was done, or the state created
+}
+
+impl RefreshRequest {
+    pub fn new() -> RefreshRequest {
+        RefreshRequest {
+            requested: false,
+            done_at: Instant::now(),
+        }
+    }
+    pub fn request(&mut self) {
+        self.requested = true;
+    }
+    /// whether a refresh is requested, and thus pending, even when
+    ///  the last one is too recent for it to be done
+    pub fn is_requested(&self) -> bool {
+        self.requested
+    }
+    /// whether the requested refresh must now be done
+    pub fn is_due(&self) -> bool {
+        self.requested && self.done_at.elapsed() >= REFRESH_PERIOD
+    }
+    /// wait a little for the requested refresh to be due
+    pub fn wait(&self) {
+        let remaining = REFRESH_PERIOD
+            .checked_sub(self.done_at.elapsed())
+            .unwrap_or_default();
+        thread::sleep(remaining.min(REFRESH_WAIT_TICK));
+    }
+    /// tell the requests made until now are fulfilled
+    pub fn done(&mut self) {
+        self.requested = false;
+        self.done_at = Instant::now();
+    }
+}
+
+impl Default for RefreshRequest {
+    fn default() -> RefreshRequest {
+        RefreshRequest::new()
+    }
+}
@@ -0,0 +1,113 @@
+//! the events read in the terminal. The texts pasted while the
+//!  "bracketed paste" mode is on come between two marks, and they're
+//!  gathered into one event, so that the input is only parsed, and the
+//!  tree only filtered, once per paste.
+
+use std::io::{self, Read};
+use std::time::Duration;
+use termion::event::{Event, Key};
+
+/// what the terminal sends at the start and at the end of a paste
+const PASTE_START: &[u8] = b"\x1b[200~";
+const PASTE_END: &[u8] = b"\x1b[201~";
+
+/// what's given to the terminal to make it mark the pastes
+pub const ENABLE_BRACKETED_PASTE: &str = "\x1b[?2004h";
+pub const DISABLE_BRACKETED_PASTE: &str = "\x1b[?2004l";
+
+/// a reader of stdin without buffer, so that whether a key is waiting
+///  is known before it's read. Only the bytes of an escape sequence are
+///  read together, as termion needs them to tell it from the esc key
+pub struct StdinReader;
+
+impl Read for StdinReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let n = read_byte(&mut buf[0])?;
+        if n == 1 && buf[0] == b'\x1b' && buf.len() > 1 && stdin_is_ready(Duration::from_millis(0)) {
+            return Ok(1 + read_byte(&mut buf[1])?);
+        }
+        Ok(n)
+    }
+}
+
+fn read_byte(b: &mut u8) -> io::Result<usize> {
+    match unsafe { libc::read(libc::STDIN_FILENO, b as *mut u8 as *mut libc::c_void, 1) } {
+        n if n < 0 => Err(io::Error::last_os_error()),
+        n => Ok(n as usize),
+    }
+}
+
+/// wait at most timeout for something to read on stdin, the end of the
+///  terminal being something to read too
+pub fn stdin_is_ready(timeout: Duration) -> bool {
+    let mut pollfd = libc::pollfd {
+        fd: libc::STDIN_FILENO,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    unsafe { libc::poll(&mut pollfd, 1, timeout.as_millis() as libc::c_int) > 0 }
+}
+
+#[derive(Debug)]
+pub enum TermEvent {
+    Event(Event),
+    Paste(String), // the pasted chars, control ones excluded
+}
+
+/// an iterator over the events of termion, with the pastes gathered
+pub struct TermEvents<E> {
+    events: E,
+}
+
+impl<E> TermEvents<E>
+where
+    E: Iterator<Item = io::Result<Event>>,
+{
+    pub fn new(events: E) -> TermEvents<E> {
+        TermEvents { events }
+    }
+}
+
+impl<E> Iterator for TermEvents<E>
+where
+    E: Iterator<Item = io::Result<Event>>,
+{
+    type Item = io::Result<TermEvent>;
+    fn next(&mut self) -> Option<io::Result<TermEvent>> {
+        match self.events.next()? {
+            Ok(Event::Unsupported(ref seq)) if seq.as_slice() == PASTE_START => {}
+            Ok(event) => {
+                return Some(Ok(TermEvent::Event(event)));
+            }
+            Err(e) => {
+                return Some(Err(e));
+            }
+        }
+        let mut text = String::new();
+        loop {
+            match self.events.next() {
+                Some(Ok(Event::Key(Key::Char(c)))) => {
+                    // newlines and tabs in the paste mustn't submit the
+                    //  input or complete it
+                    if !c.is_control() {
+                        text.push(c);
+                    }
+                }
+                Some(Ok(Event::Unsupported(ref seq))) if seq.as_slice() == PASTE_END => {
+                    break;
+                }
+                Some(Ok(_)) => {} // not a char, so not a pasted one
+                Some(Err(e)) => {
+                    return Some(Err(e));
+                }
+                None => {
+                    break;
+                }
+            }
+        }
+        Some(Ok(TermEvent::Paste(text)))
+    }
+}
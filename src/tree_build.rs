@@ -1,32 +1,67 @@
 use std::cmp::{self, Ordering};
 use std::collections::{BinaryHeap, VecDeque};
 use std::fs;
+use std::io;
 use std::os::unix::fs::MetadataExt;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::result::Result;
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime};
 
-use crate::errors::TreeBuildError;
-use crate::flat_tree::{LineType, Tree, TreeLine};
+use custom_error::custom_error;
+
+use crate::collisions;
+use crate::file_sizes::{Size, SizeInfo};
+use crate::flat_tree::{LineType, SpecialKind, Tree, TreeLine};
 use crate::git_ignore::GitIgnoreFilter;
+use crate::git_repos;
+use crate::natural_sort;
 use crate::task_sync::TaskLifetime;
-use crate::tree_options::{OptionBool, TreeOptions};
+use crate::tree_options::{Balance, OptionBool, Sort, TreeOptions};
+use crate::xattrs::ExtendedAttributes;
+
+// the directories deeper than this aren't read, so that a pathological
+//  nesting can't make the tree arbitrarily deep
+const MAX_DEPTH: usize = 1000;
+
+custom_error! {
+    /// why a tree can't be built
+    pub TreeBuildError
+    NotADirectory { path: String } = "Not a directory: {}",
+    FileNotFound { path: String } = "File not found: {}",
+    UnknownEnvVar { name: String } = "Unknown environment variable: {}",
+    NoHomeDir = "Home directory not found",
+    EmptyPathList { nb_dropped: usize } = "No existing path was given on stdin ({} dropped)",
+}
+
+/// how far a search went: the number of directory entries read,
+///  and the number of direct matches among them
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SearchProgress {
+    pub nb_scanned: usize,
+    pub nb_matches: usize,
+}
 
 // like a tree line, but with the info needed during the build
 // This structure isn't usable independantly from the tree builder
 struct BLine {
     parent_idx: usize,
     path: PathBuf,
-    depth: u16,
+    depth: usize,
     name: String,
     file_type: fs::FileType,
     children: Option<Vec<usize>>, // sorted and filtered (indexes of the children in tree.blines)
     next_child_idx: usize,        // index for iteration, among the children
     has_error: bool,
-    has_match: bool,
+    has_match: bool, // a direct match, or an ancestor of one
+    is_match: bool,  // a direct match, when there's a pattern or a path list
     score: i32,
     ignore_filter: Option<GitIgnoreFilter>,
     nb_kept_children: i32, // used during the trimming step
+    nb_capped: usize,      // number of children not read because of max_children
+    nb_filtered_out: usize, // number of children hidden by the options (dotfiles, gitignored)
+    nb_gitignored_out: usize, // number of the filtered out children which are gitignored
+    quota: usize, // max number of children listed, the other ones being counted as unlisted
+    collides_with: Option<String>, // a sibling whose name is the same once normalized
 }
 
 // the result of trying to build a bline
@@ -36,6 +71,7 @@ enum BLineResult {
     FilteredOutByPattern,
     FilteredOutAsNonFolder,
     GitIgnored,
+    NotListed,
     Invalid,
 }
 
@@ -69,9 +105,15 @@ impl BLine {
                 file_type,
                 has_error: false,
                 has_match: true,
+                is_match: false,
                 score: 0,
                 ignore_filter,
                 nb_kept_children: 0,
+                nb_capped: 0,
+                nb_filtered_out: 0,
+                nb_gitignored_out: 0,
+                quota: usize::max_value(),
+                collides_with: None,
             })
         } else {
             Err(TreeBuildError::FileNotFound {
@@ -83,7 +125,7 @@ impl BLine {
     fn from(
         parent_idx: usize,
         e: fs::DirEntry,
-        depth: u16,
+        depth: usize,
         options: &TreeOptions,
         parent_ignore_filter: &Option<GitIgnoreFilter>,
     ) -> BLineResult {
@@ -94,11 +136,19 @@ impl BLine {
                 return BLineResult::Invalid;
             }
         };
-        if !options.show_hidden && name.starts_with('.') {
-            return BLineResult::FilteredOutAsHidden;
-        }
+        // the paths explicitly listed on launch are shown even when
+        //  they're hidden or gitignored
+        let listed = match &options.path_list {
+            Some(path_list) => {
+                if !path_list.contains(&e.path()) {
+                    return BLineResult::NotListed;
+                }
+                true
+            }
+            None => false,
+        };
         let mut has_match = true;
-        let mut score = 10000 - i32::from(depth); // we dope less deep entries
+        let mut score = 10000 - depth as i32; // we dope less deep entries
         if options.pattern.is_some() {
             if let Some(m) = options.pattern.find(&name) {
                 score += m.score;
@@ -112,7 +162,8 @@ impl BLine {
                 return BLineResult::Invalid;
             }
         };
-        if file_type.is_file() || file_type.is_symlink() {
+        if !file_type.is_dir() {
+            // files, links and special files
             if !has_match {
                 return BLineResult::FilteredOutByPattern;
             }
@@ -120,10 +171,21 @@ impl BLine {
                 return BLineResult::FilteredOutAsNonFolder;
             }
         }
+        // the hidden files are checked after the pattern, so that only
+        //  the ones which would be shown without the option are counted
+        if !listed && !options.show_hidden && name.starts_with('.') {
+            return BLineResult::FilteredOutAsHidden;
+        }
         let path = e.path();
+        // the ancestors of the listed paths aren't matches
+        let is_match = has_match
+            && match &options.path_list {
+                Some(path_list) => path_list.is_listed(&path),
+                None => options.pattern.is_some(),
+            };
         let mut ignore_filter = None;
         if let Some(gif) = parent_ignore_filter {
-            if !gif.accepts(&path, &name, file_type.is_dir()) {
+            if !listed && !gif.accepts(&path, &name, file_type.is_dir()) {
                 return BLineResult::GitIgnored;
             }
             if file_type.is_dir() {
@@ -140,21 +202,24 @@ impl BLine {
             next_child_idx: 0,
             has_error: false,
             has_match,
+            is_match,
             score,
             ignore_filter,
             nb_kept_children: 0,
+            nb_capped: 0,
+            nb_filtered_out: 0,
+            nb_gitignored_out: 0,
+            quota: usize::max_value(),
+            collides_with: None,
         })
     }
-    fn to_tree_line(&self) -> TreeLine {
-        let mut mode = 0;
-        let mut uid = 0;
-        let mut gid = 0;
+    fn to_tree_line(
+        &self,
+        options: &TreeOptions,
+        is_filtered: bool,
+        nb_matches: usize,
+    ) -> TreeLine {
         let mut has_error = self.has_error;
-        if let Ok(metadata) = fs::symlink_metadata(&self.path) {
-            mode = metadata.mode();
-            uid = metadata.uid();
-            gid = metadata.gid();
-        }
         let line_type = if self.file_type.is_dir() {
             LineType::Dir
         } else if self.file_type.is_symlink() {
@@ -178,6 +243,8 @@ impl BLine {
                 has_error = true;
                 LineType::SymLinkToFile(String::from("????"))
             }
+        } else if let Some(kind) = SpecialKind::from_file_type(&self.file_type) {
+            LineType::Special(kind)
         } else {
             LineType::File
         };
@@ -186,25 +253,46 @@ impl BLine {
             //    "{:?} children.len()={} nb_kept_children={}",
             //    &self.path, children.len(), self.nb_kept_children
             //);
-            children.len() - self.next_child_idx
+            children.len() - self.next_child_idx + self.nb_capped
         } else {
             0
         };
-        TreeLine {
-            left_branchs: vec![false; self.depth as usize].into_boxed_slice(),
+        let mut line = TreeLine {
+            left_branchs: vec![false; self.depth].into_boxed_slice(),
             depth: self.depth,
             name: self.name.to_string(),
             path: self.path.clone(),
             line_type,
             has_error,
+            is_hidden: self.name.starts_with('.'),
             nb_kept_children: self.nb_kept_children as usize,
             unlisted,
+            filtered_out: self.nb_filtered_out,
+            gitignored_out: self.nb_gitignored_out,
+            is_capped: self.nb_capped > 0,
             score: self.score,
-            mode,
-            uid,
-            gid,
-            size: None,
+            mode: 0,
+            uid: 0,
+            gid: 0,
+            modified: 0,
+            size: SizeInfo::Unknown,
+            has_metadata: false,
+            xattrs: ExtendedAttributes::None,
+            // only the lines kept in the tree are looked at
+            is_git_repo: options.detect_git_repos
+                && self.file_type.is_dir()
+                && git_repos::is_repo(&self.path),
+            collides_with: self.collides_with.clone(),
+            is_context: is_filtered && self.depth > 0 && !self.is_match,
+            nb_matches,
+            can_open: None,
+        };
+        // the stat of the entry, which may be slow on network filesystems,
+        //  is deferred when no column needs it (see Tree::fetch_some_missing_metadata)
+        if options.needs_metadata() {
+            line.read_metadata(options.reads_xattrs());
         }
+        line
     }
 }
 
@@ -238,16 +326,54 @@ impl PartialOrd for SortableBLineIdx {
     }
 }
 
+// the state of the exploration of the directories, kept between
+//  the steps of a build
+struct Gathering {
+    start: Instant,
+    out_blines: Vec<usize>, // the blines we want to display (indexes into blines)
+    nb_lines_ok: usize,     // in out_blines
+    open_dirs: VecDeque<usize>,
+    next_level_dirs: Vec<usize>,
+    nb_next_level_loaded: usize, // the next level dirs whose children are already loaded
+    budgeted: bool,
+    done: bool,
+}
+
+impl Gathering {
+    fn new(budgeted: bool) -> Gathering {
+        let mut open_dirs = VecDeque::new();
+        open_dirs.push_back(0);
+        Gathering {
+            start: Instant::now(),
+            out_blines: vec![0],
+            nb_lines_ok: 1,
+            open_dirs,
+            next_level_dirs: Vec::new(),
+            nb_next_level_loaded: 0,
+            budgeted,
+            done: false,
+        }
+    }
+}
+
 // The TreeBuilder builds a Tree according to options (including an optional search pattern)
 // Instead of the final TreeLine, the builder uses an internal structure: BLine.
 // All BLines used during build are stored in the blines vector and kept until the end.
 // Most operations and temporary data structures just deal with the indexes of lines in
 //  the blines vector.
+// The build may be done in steps (see advance), the work done being kept between them.
 pub struct TreeBuilder {
     blines: Vec<BLine>, // all blines, even the ones not yet "seen" by BFS
     options: TreeOptions,
     targeted_size: usize, // the number of lines we should fill (height of the screen)
     nb_gitignored: u32,   // number of times a gitignore pattern excluded a file
+    root_dev: Option<u64>, // the device of the root, when other ones aren't read
+    total_search: bool,    // whether all the matches must be looked for
+    truncated_search: bool, // whether the gathering stopped before all the matches were found
+    gathering: Option<Gathering>, // None until the children of the root are read
+    nb_scanned: usize,    // number of directory entries read
+    nb_matches: usize,    // number of direct matches found
+    started_at: SystemTime, // when the reading of the directories started
 }
 impl TreeBuilder {
     pub fn from(
@@ -255,28 +381,135 @@ impl TreeBuilder {
         options: TreeOptions,
         targeted_size: usize,
     ) -> Result<TreeBuilder, TreeBuildError> {
+        let root_dev = if options.one_filesystem {
+            fs::metadata(&path).ok().map(|md| md.dev())
+        } else {
+            None
+        };
         let mut blines = Vec::new();
         blines.push(BLine::from_root(path, options.respect_git_ignore)?);
+        // the parent line, if any, takes one of the lines
+        let targeted_size = if options.show_parent_line {
+            targeted_size.saturating_sub(1).max(1)
+        } else {
+            targeted_size
+        };
         Ok(TreeBuilder {
             blines,
             options,
             targeted_size,
             nb_gitignored: 0,
+            root_dev,
+            total_search: false,
+            truncated_search: false,
+            gathering: None,
+            nb_scanned: 0,
+            nb_matches: 0,
+            started_at: SystemTime::now(),
         })
     }
+    /// make the build look for all the matches of the pattern, instead of
+    ///  stopping when enough good ones were found or when it takes long
+    pub fn total_search(mut self) -> TreeBuilder {
+        self.total_search = true;
+        self
+    }
     // stores (move) the bline in the global vec. Returns its index
     fn store(&mut self, bline: BLine) -> usize {
         let idx = self.blines.len();
         self.blines.push(bline);
         idx
     }
-    // returns true when there are direct matches among children
-    fn load_children(&mut self, bline_idx: usize) -> bool {
+    /// whether all the matches are looked for
+    pub fn is_total_search(&self) -> bool {
+        self.total_search
+    }
+    /// how far the search went until now
+    pub fn progress(&self) -> SearchProgress {
+        SearchProgress {
+            nb_scanned: self.nb_scanned,
+            nb_matches: self.nb_matches,
+        }
+    }
+    // returns true when there are direct matches among children, or None
+    //  when the task expired. The children of a directory are read only
+    //  once, and an interrupted reading is undone, so that it can be done
+    //  again in a next step
+    fn load_children(&mut self, bline_idx: usize, task_lifetime: &TaskLifetime) -> Option<bool> {
+        if let Some(children) = &self.blines[bline_idx].children {
+            return Some(children.iter().any(|&idx| self.blines[idx].has_match));
+        }
+        let nb_blines = self.blines.len();
+        let counts = (self.nb_gitignored, self.nb_scanned, self.nb_matches);
+        let parent = &self.blines[bline_idx];
+        let parent_counts = (
+            parent.has_match,
+            parent.nb_capped,
+            parent.nb_filtered_out,
+            parent.nb_gitignored_out,
+        );
+        let has_child_match = self.read_children(bline_idx, task_lifetime);
+        if has_child_match.is_none() {
+            self.blines.truncate(nb_blines);
+            self.nb_gitignored = counts.0;
+            self.nb_scanned = counts.1;
+            self.nb_matches = counts.2;
+            let parent = &mut self.blines[bline_idx];
+            parent.has_match = parent_counts.0;
+            parent.nb_capped = parent_counts.1;
+            parent.nb_filtered_out = parent_counts.2;
+            parent.nb_gitignored_out = parent_counts.3;
+        }
+        has_child_match
+    }
+    // read the children of the directory. Only max_children entries are
+    //  read, the other ones being just counted, unless the directory was
+    //  explicitly uncapped or a pattern is searched, as the cap would
+    //  hide some matches
+    fn read_children(&mut self, bline_idx: usize, task_lifetime: &TaskLifetime) -> Option<bool> {
         let mut has_child_match = false;
+        let max_children = if self.options.pattern.is_some()
+            || self.options.uncapped_dirs.contains(&self.blines[bline_idx].path)
+        {
+            usize::max_value()
+        } else {
+            self.options.max_children
+        };
+        if self.blines[bline_idx].depth >= MAX_DEPTH {
+            self.blines[bline_idx].children = Some(Vec::new());
+            return Some(false);
+        }
+        if let Some(root_dev) = self.root_dev {
+            // the mount points of other filesystems are shown but not read
+            let dev = fs::metadata(&self.blines[bline_idx].path).map(|md| md.dev());
+            if dev.ok() != Some(root_dev) {
+                self.blines[bline_idx].children = Some(Vec::new());
+                return Some(false);
+            }
+        }
         match fs::read_dir(&self.blines[bline_idx].path) {
-            Ok(entries) => {
+            Ok(mut entries) => {
                 let mut children: Vec<usize> = Vec::new();
-                for e in entries {
+                let mut nb_read = 0;
+                while let Some(e) = entries.next() {
+                    if task_lifetime.is_expired() {
+                        info!("task expired (reading a directory)");
+                        return None;
+                    }
+                    self.nb_scanned += 1;
+                    if nb_read == max_children {
+                        let mut nb_capped = 1;
+                        for _ in &mut entries {
+                            if task_lifetime.is_expired() {
+                                return None;
+                            }
+                            nb_capped += 1;
+                        }
+                        self.nb_scanned += nb_capped - 1;
+                        self.blines[bline_idx].nb_capped = nb_capped;
+                        break;
+                    }
+                    nb_read += 1;
                     if let Ok(e) = e {
                         let bl = BLine::from(
                             bline_idx,
@@ -287,6 +520,9 @@ impl TreeBuilder {
                         );
                         match bl {
                             BLineResult::Some(bl) => {
+                                if bl.is_match {
+                                    self.nb_matches += 1;
+                                }
                                 if bl.has_match {
                                     // direct match
                                     self.blines[bline_idx].has_match = true;
@@ -294,8 +530,13 @@ impl TreeBuilder {
                                 }
                                 children.push(self.store(bl));
                             }
+                            BLineResult::FilteredOutAsHidden => {
+                                self.blines[bline_idx].nb_filtered_out += 1;
+                            }
                             BLineResult::GitIgnored => {
                                 self.nb_gitignored += 1;
+                                self.blines[bline_idx].nb_filtered_out += 1;
+                                self.blines[bline_idx].nb_gitignored_out += 1;
                             }
                             _ => {
                                 // other reason, we don't care
@@ -303,12 +544,21 @@ impl TreeBuilder {
                         }
                     }
                 }
-                children.sort_by(|&a, &b| {
-                    self.blines[a]
-                        .name
-                        .to_lowercase()
-                        .cmp(&self.blines[b].name.to_lowercase())
-                });
+                if self.options.sort == Sort::Name {
+                    let dirs_first = self.options.dirs_first;
+                    children.sort_by(|&a, &b| {
+                        let (a, b) = (&self.blines[a], &self.blines[b]);
+                        let dirs_ordering = if dirs_first {
+                            b.file_type.is_dir().cmp(&a.file_type.is_dir())
+                        } else {
+                            Ordering::Equal
+                        };
+                        dirs_ordering.then_with(|| natural_sort::compare_names(&a.name, &b.name))
+                    });
+                }
+                if self.options.detect_collisions {
+                    self.mark_collisions(&children);
+                }
                 self.blines[bline_idx].children = Some(children);
             }
             Err(_err) => {
@@ -320,7 +570,19 @@ impl TreeBuilder {
                 self.blines[bline_idx].children = Some(Vec::new());
             }
         }
-        has_child_match
+        Some(has_child_match)
+    }
+    // tell the children of a directory whose names can't be told apart
+    //  from the one of a sibling
+    fn mark_collisions(&mut self, children: &[usize]) {
+        let names: Vec<&str> = children.iter().map(|&idx| self.blines[idx].name.as_str()).collect();
+        let others: Vec<Option<String>> = collisions::collisions(&names)
+            .into_iter()
+            .map(|other| other.map(|other| names[other].to_string()))
+            .collect();
+        for (&idx, other) in children.iter().zip(others) {
+            self.blines[idx].collides_with = other;
+        }
     }
     // load_children must have been called before on bline_idx
     fn next_child(
@@ -329,7 +591,7 @@ impl TreeBuilder {
     ) -> Option<usize> {
         let bline = &mut self.blines[bline_idx];
         if let Some(children) = &bline.children {
-            if bline.next_child_idx < children.len() {
+            if bline.next_child_idx < children.len().min(bline.quota) {
                 let next_child = children[bline.next_child_idx];
                 bline.next_child_idx += 1;
                 Some(next_child)
@@ -341,61 +603,165 @@ impl TreeBuilder {
         }
     }
 
+    // whether the lines are shared between the directories according to
+    //  max_children_shown and balance. It's not the case when there's a
+    //  pattern or a path list, the lines being then chosen by their matches
+    fn is_budgeted(&self) -> bool {
+        !self.options.pattern.is_some() && self.options.path_list.is_none()
+    }
+    // the number of children of the directory which may be listed
+    fn max_shown(&self, bline_idx: usize) -> usize {
+        let bline = &self.blines[bline_idx];
+        let nb_children = bline.children.as_ref().map_or(0, |c| c.len());
+        if self.options.uncapped_dirs.contains(&bline.path) {
+            nb_children
+        } else {
+            nb_children.min(self.options.max_children_shown.unwrap_or(nb_children))
+        }
+    }
+    // set the quotas of the directories of a level, whose children are
+    //  loaded, before their children are listed. With an even balance, the
+    //  gathering taking the children of the directories in turn shares the
+    //  lines, while a proportional balance needs the lines allocated first.
+    // When sizes are shown, the lines are shared according to the sizes of
+    //  the directories (as far as they're computed in the task) instead of
+    //  their numbers of entries
+    fn allocate_lines(&mut self, dirs: &[usize], nb_lines: usize, task_lifetime: &TaskLifetime) {
+        let maxs: Vec<usize> = dirs.iter().map(|&idx| self.max_shown(idx)).collect();
+        let quotas = match self.options.balance {
+            Balance::Even => maxs,
+            Balance::Proportional => {
+                // each line goes to the directory having the greatest weight
+                //  per line already given (as in the D'Hondt method)
+                let weights: Vec<u64> = dirs
+                    .iter()
+                    .map(|&idx| {
+                        let bline = &self.blines[idx];
+                        if self.options.show_sizes {
+                            // empty directories still get their share
+                            let size: u64 =
+                                Size::from_dir(&bline.path, self.root_dev, task_lifetime).found().into();
+                            size.max(1)
+                        } else {
+                            bline.children.as_ref().map_or(0, |c| c.len()) as u64
+                        }
+                    })
+                    .collect();
+                let mut quotas = vec![0; dirs.len()];
+                for _ in 0..nb_lines {
+                    let mut best: Option<usize> = None;
+                    for i in 0..dirs.len() {
+                        if quotas[i] < maxs[i]
+                            && best.map_or(true, |b| {
+                                weights[i] as f64 / (quotas[i] + 1) as f64
+                                    > weights[b] as f64 / (quotas[b] + 1) as f64
+                            })
+                        {
+                            best = Some(i);
+                        }
+                    }
+                    match best {
+                        Some(i) => quotas[i] += 1,
+                        None => break,
+                    }
+                }
+                quotas
+            }
+        };
+        for (&idx, &quota) in dirs.iter().zip(quotas.iter()) {
+            self.blines[idx].quota = quota;
+        }
+    }
+
     // first step of the build: we explore the directories and gather lines.
     // If there's no search pattern we stop when we have enough lines to fill the screen.
     // If there's a pattern, we try to gather more lines that will be sorted afterwards.
-    fn gather_lines(&mut self, task_lifetime: &TaskLifetime) -> Option<Vec<usize>> {
-        let start = Instant::now();
-        let mut out_blines: Vec<usize> = Vec::new(); // the blines we want to display (indexes into blines)
+    // Return true when it's done, false when the deadline is passed or the task
+    //  expired, the gathering going on at the next call
+    fn gather_lines(&mut self, task_lifetime: &TaskLifetime, deadline: Option<Instant>) -> bool {
+        let mut g = match self.gathering.take() {
+            Some(g) => g,
+            None => {
+                if self.load_children(0, task_lifetime).is_none() {
+                    return false;
+                }
+                let budgeted = self.is_budgeted();
+                if budgeted {
+                    // the root is read as far as the screen goes, whatever the balance
+                    self.blines[0].quota = self.max_shown(0);
+                }
+                Gathering::new(budgeted)
+            }
+        };
+        if !g.done {
+            g.done = self.gather_more_lines(&mut g, task_lifetime, deadline);
+        }
+        let done = g.done;
+        self.gathering = Some(g);
+        done
+    }
+    fn gather_more_lines(
+        &mut self,
+        g: &mut Gathering,
+        task_lifetime: &TaskLifetime,
+        deadline: Option<Instant>,
+    ) -> bool {
         let not_long = Duration::from_millis(600);
         let optimal_size = self
             .options
             .pattern
             .optimal_result_number(self.targeted_size);
-        out_blines.push(0);
-        let mut nb_lines_ok = 1; // in out_blines
-        let mut open_dirs: VecDeque<usize> = VecDeque::new();
-        let mut next_level_dirs: Vec<usize> = Vec::new();
-        self.load_children(0);
-        open_dirs.push_back(0);
         loop {
-            if (nb_lines_ok > optimal_size)
-                || (nb_lines_ok >= self.targeted_size && start.elapsed() > not_long)
+            if !self.total_search
+                && ((g.nb_lines_ok > optimal_size)
+                    || (g.nb_lines_ok >= self.targeted_size && g.start.elapsed() > not_long))
             {
+                // (there may be nothing left to look at)
+                self.truncated_search = !g.open_dirs.is_empty() || !g.next_level_dirs.is_empty();
                 break;
             }
-            if let Some(open_dir_idx) = open_dirs.pop_front() {
+            if let Some(open_dir_idx) = g.open_dirs.pop_front() {
                 if let Some(child_idx) = self.next_child(open_dir_idx) {
-                    open_dirs.push_back(open_dir_idx);
-                    let child = &self.blines[child_idx];
-                    if child.has_match {
-                        nb_lines_ok += 1;
+                    g.open_dirs.push_back(open_dir_idx);
+                    if self.blines[child_idx].has_match && !self.is_chained(child_idx) {
+                        g.nb_lines_ok += 1;
                     }
+                    let child = &self.blines[child_idx];
                     if child.file_type.is_dir() {
-                        next_level_dirs.push(child_idx);
+                        g.next_level_dirs.push(child_idx);
                     }
-                    out_blines.push(child_idx);
+                    g.out_blines.push(child_idx);
                 }
             } else {
                 // this depth is finished, we must go deeper
-                if next_level_dirs.is_empty() {
+                if g.next_level_dirs.is_empty() {
                     // except there's nothing deeper
                     break;
                 }
-                for next_level_dir_idx in &next_level_dirs {
+                if g.budgeted && g.nb_lines_ok >= self.targeted_size {
+                    // no line is left to share
+                    break;
+                }
+                while g.nb_next_level_loaded < g.next_level_dirs.len() {
                     if task_lifetime.is_expired() {
                         info!("task expired (core build - inner loop)");
-                        return None;
+                        return false;
                     }
-                    let has_child_match = self.load_children(*next_level_dir_idx);
+                    let next_level_dir_idx = g.next_level_dirs[g.nb_next_level_loaded];
+                    let has_child_match = match self.load_children(next_level_dir_idx, task_lifetime) {
+                        Some(has_child_match) => has_child_match,
+                        None => {
+                            return false;
+                        }
+                    };
                     if has_child_match {
                         // we must ensure the ancestors are made Ok
-                        let mut idx = *next_level_dir_idx;
+                        let mut idx = next_level_dir_idx;
                         loop {
                             let mut bline = &mut self.blines[idx];
                             if !bline.has_match {
                                 bline.has_match = true;
-                                nb_lines_ok += 1;
+                                g.nb_lines_ok += 1;
                             }
                             idx = bline.parent_idx;
                             if idx == 0 {
@@ -403,19 +769,29 @@ impl TreeBuilder {
                             }
                         }
                     }
-                    open_dirs.push_back(*next_level_dir_idx);
+                    g.open_dirs.push_back(next_level_dir_idx);
+                    g.nb_next_level_loaded += 1;
+                    // a step loads at least a directory, so that the build goes on
+                    if deadline.map_or(false, |deadline| Instant::now() > deadline) {
+                        return false;
+                    }
+                }
+                if g.budgeted {
+                    let nb_lines = self.targeted_size.saturating_sub(g.nb_lines_ok);
+                    self.allocate_lines(&g.next_level_dirs, nb_lines, task_lifetime);
                 }
-                next_level_dirs.clear();
+                g.next_level_dirs.clear();
+                g.nb_next_level_loaded = 0;
             }
         }
         if self.options.show_sizes || !self.options.trim_root {
             // if the root directory isn't totally read, we finished it even
             // it it goes past the bottom of the screen
             while let Some(child_idx) = self.next_child(0) {
-                out_blines.push(child_idx);
+                g.out_blines.push(child_idx);
             }
         }
-        Some(out_blines)
+        true
     }
 
     // Post search trimming
@@ -428,7 +804,9 @@ impl TreeBuilder {
         let trim_root = self.options.trim_root && !self.options.show_sizes;
         for idx in out_blines[1..].iter() {
             if self.blines[*idx].has_match {
-                count += 1;
+                if !self.is_chained(*idx) {
+                    count += 1;
+                }
                 let parent_idx = self.blines[*idx].parent_idx;
                 self.blines[parent_idx].nb_kept_children += 1;
             }
@@ -472,17 +850,120 @@ impl TreeBuilder {
         }
     }
 
+    // whether the line at idx will probably be merged with its parent's
+    //  one, so that it doesn't take a line of the screen
+    fn is_chained(&self, idx: usize) -> bool {
+        let bline = &self.blines[idx];
+        if !self.options.flatten_chains || bline.depth < 2 || !bline.file_type.is_dir() {
+            return false;
+        }
+        // the directories not matching the pattern won't be shown
+        let parent = &self.blines[bline.parent_idx];
+        let nb_matching = parent.children.iter().flatten()
+            .filter(|&&child_idx| self.blines[child_idx].has_match)
+            .count();
+        parent.nb_capped == 0 && nb_matching == 1
+    }
+    // the directory the one at idx is merged with when chains are flattened:
+    //  its only kept child, when it's a directory. Hidden, gitignored or
+    //  not matching entries don't count, as they're not shown, but a chain
+    //  stops at the root of a git repository so that its mark isn't lost,
+    //  and at a directory with unlisted entries
+    fn chain_child(&self, idx: usize, kept: &[bool]) -> Option<usize> {
+        let bline = &self.blines[idx];
+        if !bline.file_type.is_dir() || bline.nb_capped > 0 {
+            return None;
+        }
+        let children = match &bline.children {
+            Some(children) if bline.next_child_idx >= children.len() => children,
+            _ => {
+                return None;
+            }
+        };
+        let mut kept_children = children.iter().filter(|&&child_idx| kept[child_idx]);
+        let child_idx = match (kept_children.next(), kept_children.next()) {
+            (Some(&child_idx), None) => child_idx,
+            _ => {
+                return None;
+            }
+        };
+        if !self.blines[child_idx].file_type.is_dir()
+            || (self.options.detect_git_repos && git_repos::is_repo(&bline.path))
+        {
+            return None;
+        }
+        Some(child_idx)
+    }
+
     // makes a tree from the builder's specific structure
-    fn take(&mut self, out_blines: &[usize]) -> Tree {
-        let mut lines: Vec<TreeLine> = Vec::new();
+    fn take(&mut self, out_blines: &[usize], task_lifetime: &TaskLifetime) -> Option<Tree> {
+        let mut kept = vec![false; self.blines.len()];
         for idx in out_blines.iter() {
             if self.blines[*idx].has_match {
                 // we need to count the children, so we load them
                 if self.blines[*idx].file_type.is_dir() && self.blines[*idx].children.is_none() {
-                    self.load_children(*idx);
+                    self.load_children(*idx, task_lifetime)?;
+                }
+                kept.resize(self.blines.len(), false);
+                kept[*idx] = true;
+            }
+        }
+        // in a filtered tree, the directories tell how many matches
+        //  were found inside them
+        let is_filtered = self.options.pattern.is_some() || self.options.path_list.is_some();
+        let mut nb_matches = vec![0; self.blines.len()];
+        if is_filtered {
+            for idx in 1..self.blines.len() {
+                if self.blines[idx].is_match {
+                    let mut ancestor_idx = self.blines[idx].parent_idx;
+                    while ancestor_idx > 0 {
+                        nb_matches[ancestor_idx] += 1;
+                        ancestor_idx = self.blines[ancestor_idx].parent_idx;
+                    }
+                    nb_matches[0] += 1;
+                }
+            }
+        }
+        // the lines are written in the order of the tree, each directory
+        //  being followed by its kept children, in the order of its children
+        // When chains are flattened, a chain of directories is written as
+        //  the line of its deepest one, and the lines below are raised by
+        //  the number of merged directories.
+        let mut lines: Vec<TreeLine> = Vec::new();
+        let mut stack = vec![(0, 0)]; // (bline index, depth removed by the chains above)
+        while let Some((idx, shift)) = stack.pop() {
+            let mut chain = vec![idx];
+            if self.options.flatten_chains && idx > 0 {
+                while let Some(child_idx) = self.chain_child(chain[chain.len() - 1], &kept) {
+                    chain.push(child_idx);
                 }
-                lines.push(self.blines[*idx].to_tree_line());
             }
+            let deepest = chain[chain.len() - 1];
+            let mut line =
+                self.blines[deepest].to_tree_line(&self.options, is_filtered, nb_matches[idx]);
+            let depth = self.blines[idx].depth - shift;
+            if line.depth != depth {
+                line.depth = depth;
+                line.left_branchs = vec![false; depth].into_boxed_slice();
+            }
+            if chain.len() > 1 {
+                let blines = chain.iter().map(|&i| &self.blines[i]);
+                line.name = blines.clone().map(|b| b.name.as_str()).collect::<Vec<_>>().join("/");
+                line.is_hidden = blines.clone().any(|b| b.name.starts_with('.'));
+                line.is_context = line.is_context && blines.clone().all(|b| !b.is_match);
+                line.score = blines.map(|b| b.score).max().unwrap_or(line.score);
+            }
+            if let Some(children) = &self.blines[deepest].children {
+                let shift = shift + chain.len() - 1;
+                stack.extend(
+                    children
+                        .iter()
+                        .rev()
+                        .filter(|&&child_idx| kept[child_idx])
+                        .map(|&child_idx| (child_idx, shift)),
+                );
+            }
+            lines.push(line);
         }
         let mut tree = Tree {
             lines: lines.into_boxed_slice(),
@@ -490,24 +971,462 @@ impl TreeBuilder {
             options: self.options.clone(),
             scroll: 0,
             nb_gitignored: self.nb_gitignored,
+            nb_visited: self.blines.len() - 1,
+            truncated_search: self.truncated_search && self.options.pattern.is_some(),
+            built_at: self.started_at,
+            owners_known: false,
         };
         tree.after_lines_changed();
+        if self.options.show_parent_line {
+            tree.insert_parent_line();
+        }
+        Some(tree)
+    }
 
-        if self.options.show_sizes {
-            tree.fetch_file_sizes(); // not the dirs, only simple files
+    // trim the gathered lines and make the tree. The blines are restored
+    //  afterwards when the gathering must go on, or when the task expired
+    fn make_tree(&mut self, keep_gathering: bool, task_lifetime: &TaskLifetime) -> Option<Tree> {
+        let out_blines = match &self.gathering {
+            Some(g) => g.out_blines.clone(),
+            None => {
+                return None;
+            }
+        };
+        let saved: Vec<(bool, i32, usize)> = self
+            .blines
+            .iter()
+            .map(|b| (b.has_match, b.nb_kept_children, b.next_child_idx))
+            .collect();
+        self.trim_excess(&out_blines);
+        let tree = self.take(&out_blines, task_lifetime);
+        if keep_gathering || tree.is_none() {
+            for (bline, &(has_match, nb_kept_children, next_child_idx)) in
+                self.blines.iter_mut().zip(saved.iter())
+            {
+                bline.has_match = has_match;
+                bline.nb_kept_children = nb_kept_children;
+                bline.next_child_idx = next_child_idx;
+            }
         }
         tree
     }
-
     // build a tree. Can be called only once per builder
     pub fn build(mut self, task_lifetime: &TaskLifetime) -> Option<Tree> {
         debug!("start building with pattern {}", self.options.pattern);
-        match self.gather_lines(task_lifetime) {
-            Some(out_blines) => {
-                self.trim_excess(&out_blines);
-                Some(self.take(&out_blines))
+        if self.gather_lines(task_lifetime, None) {
+            self.make_tree(false, task_lifetime)
+        } else {
+            None // interrupted
+        }
+    }
+    /// go on with the build for at most the duration of the step, or
+    ///  until the task expires. The tree is returned when it's done, the
+    ///  builder being then useless. Otherwise the work done until now is
+    ///  kept, and the build can be advanced again
+    pub fn advance(&mut self, task_lifetime: &TaskLifetime, step: Duration) -> Option<Tree> {
+        if self.gather_lines(task_lifetime, Some(Instant::now() + step)) {
+            self.make_tree(false, task_lifetime)
+        } else {
+            None
+        }
+    }
+    /// the tree of the lines gathered until now, with the best matches
+    ///  found so far, while the build isn't done
+    pub fn partial_tree(&mut self, task_lifetime: &TaskLifetime) -> Option<Tree> {
+        self.make_tree(true, task_lifetime)
+    }
+}
+
+/// build the tree of the root, as it would be displayed on targeted_size
+///  lines, with the options (the pattern or the path list filtering it).
+/// Contrary to a TreeBuilder, there's no way to interrupt it.
+pub fn build_tree(
+    root: PathBuf,
+    options: TreeOptions,
+    targeted_size: usize,
+) -> Result<Tree, TreeBuildError> {
+    let builder = TreeBuilder::from(root, options, targeted_size)?;
+    Ok(builder.build(&TaskLifetime::unlimited()).unwrap()) // an unlimited task isn't interrupted
+}
+
+/// the names of the entries of the directory, with whether they're
+///  directories (symlinks being followed), leaving out the hidden and
+///  gitignored ones as a tree built with the options would
+pub fn list_dir(dir: &Path, options: &TreeOptions) -> io::Result<Vec<(String, bool)>> {
+    let ignore_filter = match options.respect_git_ignore {
+        OptionBool::No => None,
+        respect => {
+            let gif = GitIgnoreFilter::applicable_to(dir);
+            if respect == OptionBool::Auto && gif.files.is_empty() {
+                None
+            } else {
+                Some(gif)
+            }
+        }
+    };
+    let mut entries = Vec::new();
+    for e in fs::read_dir(dir)? {
+        let e = e?;
+        let name = e.file_name().to_string_lossy().to_string();
+        if !options.show_hidden && name.starts_with('.') {
+            continue;
+        }
+        let path = e.path();
+        let is_dir = path.is_dir();
+        if let Some(gif) = &ignore_filter {
+            if !gif.accepts(&path, &name, is_dir) {
+                continue;
             }
-            None => None, // interrupted
         }
+        entries.push((name, is_dir));
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod context_tests {
+    use super::*;
+    use crate::file_sizes::Size;
+    use crate::tree_options::OptionBool;
+    use crate::patterns::Pattern;
+    use tempfile::tempdir;
+
+    #[test]
+    fn ancestors_of_matches() {
+        let dir = tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        fs::create_dir_all(root.join("src/deep")).unwrap();
+        fs::create_dir_all(root.join("docs")).unwrap();
+        for file in &["src/main.rs", "src/lib.rs", "src/deep/mod.rs", "docs/index.md"] {
+            fs::write(root.join(file), "").unwrap();
+        }
+        let mut options = TreeOptions::new();
+        options.show_parent_line = false;
+        options.pattern = Pattern::regex(r"\.rs$", "").unwrap();
+        let tree = TreeBuilder::from(root.clone(), options, 20)
+            .unwrap()
+            .build(&TaskLifetime::unlimited())
+            .unwrap();
+        let line = |path: &str| tree.lines.iter().find(|line| line.path == root.join(path));
+        let src = line("src").unwrap();
+        assert!(src.is_context);
+        assert_eq!(src.nb_matches, 3);
+        let deep = line("src/deep").unwrap();
+        assert!(deep.is_context);
+        assert_eq!(deep.nb_matches, 1);
+        assert!(!line("src/main.rs").unwrap().is_context);
+        assert!(line("docs").is_none());
+        assert!(!tree.lines[0].is_context);
+    }
+
+    #[test]
+    fn hidden_entries_counted_apart_from_pruned_ones() {
+        let dir = tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        for i in 0..20 {
+            fs::write(root.join(format!("file_{:02}.rs", i)), "").unwrap();
+        }
+        for file in &[".hidden.rs", ".hidden.md", ".config.rs"] {
+            fs::write(root.join(file), "").unwrap();
+        }
+        let mut options = TreeOptions::new();
+        options.show_parent_line = false;
+        let tree = TreeBuilder::from(root.clone(), options.clone(), 10)
+            .unwrap()
+            .build(&TaskLifetime::unlimited())
+            .unwrap();
+        let pruning = tree.lines.iter().find(|line| line.line_type == LineType::Pruning).unwrap();
+        assert_eq!(pruning.unlisted + tree.lines.len() - 2, 20);
+        assert_eq!(pruning.filtered_out, 3);
+        assert_eq!(tree.lines[0].filtered_out, 3);
+        // the hidden files which don't match the pattern aren't counted
+        options.pattern = Pattern::regex(r"\.rs$", "").unwrap();
+        let tree = TreeBuilder::from(root.clone(), options, 10)
+            .unwrap()
+            .build(&TaskLifetime::unlimited())
+            .unwrap();
+        assert_eq!(tree.lines[0].filtered_out, 2);
+    }
+
+    #[test]
+    fn gitignored_entries_counted_per_directory() {
+        let dir = tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        fs::create_dir(root.join(".git")).unwrap();
+        fs::write(root.join(".gitignore"), "*.log\n").unwrap();
+        for file in &["logs/a.log", "logs/b.log", "logs/ok.txt", "dots/.dot", "dots/ok.txt"] {
+            let path = root.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "").unwrap();
+        }
+        let mut options = TreeOptions::new();
+        options.show_parent_line = false;
+        options.respect_git_ignore = OptionBool::Yes;
+        let tree = TreeBuilder::from(root.clone(), options, 20)
+            .unwrap()
+            .build(&TaskLifetime::unlimited())
+            .unwrap();
+        let counts = |name: &str| {
+            let line = tree.lines.iter().find(|line| line.path == root.join(name)).unwrap();
+            (line.filtered_out, line.gitignored_out)
+        };
+        assert_eq!(counts("logs"), (2, 2));
+        assert_eq!(counts("dots"), (1, 0));
+    }
+
+    #[test]
+    fn big_directories_are_searched_whole() {
+        let dir = tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        for i in 0..30 {
+            fs::write(root.join(format!("file_{:02}", i)), "").unwrap();
+        }
+        let mut options = TreeOptions::new();
+        options.show_parent_line = false;
+        options.max_children = 10;
+        let tree = build_tree(root.clone(), options.clone(), 50).unwrap();
+        assert!(tree.lines[0].is_capped);
+        options.pattern = Pattern::regex("file_", "").unwrap();
+        let tree = build_tree(root, options, 50).unwrap();
+        assert!(!tree.lines[0].is_capped);
+        assert_eq!(tree.lines.len(), 31);
+    }
+
+    #[test]
+    fn total_search_finds_all_matches() {
+        let dir = tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        for i in 0..30 {
+            // the deeper matches aren't looked for, as there are
+            //  enough ones before
+            let sub = root.join(format!("d{:02}/deeper", i));
+            fs::create_dir_all(&sub).unwrap();
+            fs::write(sub.join("y.rs"), "").unwrap();
+            fs::write(sub.with_file_name("x.rs"), "").unwrap();
+        }
+        let mut options = TreeOptions::new();
+        options.show_parent_line = false;
+        options.pattern = Pattern::regex(r"\.rs$", "").unwrap();
+        let tree = TreeBuilder::from(root.clone(), options.clone(), 5)
+            .unwrap()
+            .build(&TaskLifetime::unlimited())
+            .unwrap();
+        assert!(tree.truncated_search);
+        assert!(tree.lines[0].nb_matches < 60);
+        let tree = TreeBuilder::from(root.clone(), options, 5)
+            .unwrap()
+            .total_search()
+            .build(&TaskLifetime::unlimited())
+            .unwrap();
+        assert!(!tree.truncated_search);
+        assert_eq!(tree.lines[0].nb_matches, 60);
+    }
+
+    #[test]
+    fn stepped_search_ends_as_a_direct_one() {
+        let dir = tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        for i in 0..8 {
+            let sub = root.join(format!("d{}/sub", i));
+            fs::create_dir_all(&sub).unwrap();
+            fs::write(sub.join(format!("match_{}.rs", i)), "").unwrap();
+            fs::write(sub.with_file_name("other.md"), "").unwrap();
+        }
+        let mut options = TreeOptions::new();
+        options.show_parent_line = false;
+        options.pattern = Pattern::regex(r"\.rs$", "").unwrap();
+        let paths = |tree: &Tree| tree.lines.iter().map(|line| line.path.clone()).collect::<Vec<PathBuf>>();
+        let direct = build_tree(root.clone(), options.clone(), 30).unwrap();
+        let tl = TaskLifetime::unlimited();
+        let mut builder = TreeBuilder::from(root.clone(), options, 30).unwrap();
+        let mut nb_steps = 0;
+        let stepped = loop {
+            // each step loads a directory, then is over
+            if let Some(tree) = builder.advance(&tl, Duration::from_millis(0)) {
+                break tree;
+            }
+            nb_steps += 1;
+            let partial = builder.partial_tree(&tl).unwrap();
+            assert!(partial.lines[0].nb_matches <= direct.lines[0].nb_matches);
+        };
+        assert!(nb_steps > 1);
+        assert_eq!(paths(&stepped), paths(&direct));
+        assert_eq!(builder.progress().nb_matches, 8);
+    }
+
+    #[test]
+    fn flattened_chains() {
+        let dir = tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        let deepest = root.join("src/main/java/com");
+        fs::create_dir_all(deepest.join("foo")).unwrap();
+        fs::create_dir_all(deepest.join("bar")).unwrap();
+        // a hidden entry, not shown, doesn't prevent the merge
+        fs::write(root.join("src/main/.hidden"), "").unwrap();
+        fs::write(root.join("README.md"), "").unwrap();
+        let mut options = TreeOptions::new();
+        options.show_parent_line = false;
+        options.flatten_chains = true;
+        let tree = build_tree(root.clone(), options, 20).unwrap();
+        let names: Vec<(&str, usize)> =
+            tree.lines[1..].iter().map(|line| (line.name.as_str(), line.depth)).collect();
+        assert_eq!(
+            names,
+            vec![("README.md", 1), ("src/main/java/com", 1), ("bar", 2), ("foo", 2)],
+        );
+        assert_eq!(tree.lines[2].path, deepest);
+        assert_eq!(tree.lines[3].path, deepest.join("bar"));
+    }
+
+    #[test]
+    fn chains_ignore_the_siblings_not_matching() {
+        let dir = tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        fs::create_dir_all(root.join("src/main/java")).unwrap();
+        fs::write(root.join("src/main/java/Found.java"), "").unwrap();
+        fs::create_dir_all(root.join("src/test")).unwrap();
+        fs::write(root.join("src/test/other.txt"), "").unwrap();
+        let mut options = TreeOptions::new();
+        options.show_parent_line = false;
+        options.flatten_chains = true;
+        options.pattern = Pattern::regex("Found", "").unwrap();
+        let tree = build_tree(root, options, 20).unwrap();
+        let names: Vec<&str> = tree.lines[1..].iter().map(|line| line.name.as_str()).collect();
+        assert_eq!(names, vec!["src/main/java", "Found.java"]);
+    }
+
+    #[test]
+    fn outdated_in_the_second_of_the_build() {
+        let dir = tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        let tree = build_tree(root.clone(), TreeOptions::new(), 10).unwrap();
+        assert!(!tree.is_outdated());
+        // the clock of the modification times may be coarser
+        std::thread::sleep(Duration::from_millis(20));
+        fs::write(root.join("new"), "").unwrap();
+        assert!(tree.is_outdated());
+    }
+
+    #[test]
+    fn lines_shared_between_directories() {
+        let dir = tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        for (name, nb_files) in &[("a", 30), ("b", 10), ("c", 5)] {
+            fs::create_dir(root.join(name)).unwrap();
+            for i in 0..*nb_files {
+                fs::write(root.join(name).join(format!("f{:02}", i)), "").unwrap();
+            }
+        }
+        // the number of lines of the children of a, b and c, on 13 lines (the
+        //  root, a, b, c and 9 lines to share), and the number of children they
+        //  don't show, the last line being a pruning one telling about them
+        let shown = |balance: Balance, max_children_shown: Option<usize>| {
+            let mut options = TreeOptions::new();
+            options.show_parent_line = false;
+            options.balance = balance;
+            options.max_children_shown = max_children_shown;
+            let tree = build_tree(root.clone(), options, 13).unwrap();
+            ["a", "b", "c"]
+                .iter()
+                .map(|name| {
+                    let path = root.join(name);
+                    let lines = tree.lines.iter().filter(|line| line.path.parent() == Some(&path));
+                    (lines.clone().count(), lines.map(|line| line.unlisted).sum())
+                })
+                .collect::<Vec<(usize, usize)>>()
+        };
+        assert_eq!(shown(Balance::Even, Some(3)), vec![(3, 28), (3, 8), (3, 3)]);
+        assert_eq!(shown(Balance::Proportional, None), vec![(6, 25), (2, 9), (1, 5)]);
+        // the lines a capped directory can't take go to the other ones
+        assert_eq!(shown(Balance::Proportional, Some(5)), vec![(5, 26), (3, 8), (1, 5)]);
+        // when sizes are shown, they share the lines instead of the counts
+        for i in 0..5 {
+            fs::write(root.join("c").join(format!("f{:02}", i)), "x".repeat(1000)).unwrap();
+        }
+        let mut options = TreeOptions::new();
+        options.show_parent_line = false;
+        options.show_sizes = true;
+        options.balance = Balance::Proportional;
+        let tree = build_tree(root.clone(), options, 13).unwrap();
+        let counts: Vec<usize> = ["a", "b", "c"]
+            .iter()
+            .map(|name| {
+                let path = root.join(name);
+                tree.lines.iter().filter(|line| line.path.parent() == Some(&path)).count()
+            })
+            .collect();
+        assert_eq!(counts, vec![2, 2, 5]);
+    }
+
+    #[test]
+    fn metadata_read_only_when_needed() {
+        let dir = tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        fs::write(root.join("file"), "12345").unwrap();
+        let mut options = TreeOptions::new();
+        options.show_parent_line = false;
+        let mut tree = build_tree(root.clone(), options.clone(), 10).unwrap();
+        assert!(tree.has_missing_metadata());
+        assert_eq!(tree.lines[1].modified, 0);
+        tree.fetch_some_missing_metadata(10, &TaskLifetime::unlimited());
+        assert!(!tree.has_missing_metadata());
+        assert!(tree.lines[1].modified > 0);
+        assert_eq!(tree.lines[1].size.computed().map(Size::into), Some(5u64));
+        // a column needing the metadata makes them read on build
+        options.show_dates = true;
+        let tree = build_tree(root, options, 10).unwrap();
+        assert!(!tree.has_missing_metadata());
+    }
+
+    #[test]
+    fn toggled_columns_leave_the_metadata_to_the_task() {
+        let dir = tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        for i in 0..20 {
+            fs::write(root.join(format!("file{}", i)), "").unwrap();
+        }
+        let mut options = TreeOptions::new();
+        options.show_parent_line = false;
+        let tree = build_tree(root, options.clone(), 30).unwrap();
+        options.show_dates = true;
+        let mut tree = tree.with_columns(options.clone());
+        assert!(tree.options.show_dates);
+        assert!(tree.lines.iter().all(|line| !line.has_metadata));
+        tree.fetch_some_missing_metadata(30, &TaskLifetime::unlimited());
+        assert!(tree.lines.iter().all(|line| line.modified > 0));
+        // what was read is kept when the columns change again
+        options.show_sizes = true;
+        let tree = tree.with_columns(options);
+        assert!(!tree.has_missing_metadata());
+    }
+
+    #[test]
+    fn collisions_marked_when_asked() {
+        let dir = tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        // the same name, in NFC and NFD forms
+        fs::write(root.join("R\u{e9}sum\u{e9}.txt"), "").unwrap();
+        fs::write(root.join("Re\u{301}sume\u{301}.txt"), "").unwrap();
+        fs::create_dir(root.join("Readme")).unwrap();
+        fs::write(root.join("README"), "").unwrap();
+        fs::write(root.join("notes.txt"), "").unwrap();
+        let mut options = TreeOptions::new();
+        options.show_parent_line = false;
+        let colliding = |options: &TreeOptions| {
+            let tree = build_tree(root.clone(), options.clone(), 10).unwrap();
+            tree.lines[1..]
+                .iter()
+                .filter(|line| line.collides_with.is_some())
+                .map(|line| line.name.clone())
+                .collect::<Vec<String>>()
+        };
+        assert!(colliding(&options).is_empty());
+        options.detect_collisions = true;
+        let mut names = colliding(&options);
+        names.sort();
+        assert_eq!(
+            names,
+            vec!["README", "Readme", "Re\u{301}sume\u{301}.txt", "R\u{e9}sum\u{e9}.txt"],
+        );
     }
 }
@@ -1,6 +1,16 @@
-use crate::errors::ProgramError;
-use crate::patterns::Pattern;
+use custom_error::custom_error;
+use std::path::PathBuf;
 use std::str::FromStr;
+use std::sync::Arc;
+
+use crate::path_list::PathList;
+use crate::patterns::Pattern;
+
+custom_error! {
+    /// the error of parsing an OptionBool
+    pub OptionBoolParseError
+    Invalid { bad: String } = "{bad:?} can't be parsed (valid values: \"auto, yes, no\")",
+}
 
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum OptionBool {
@@ -10,29 +20,94 @@ pub enum OptionBool {
 }
 
 impl FromStr for OptionBool {
-    type Err = ProgramError;
-    fn from_str(s: &str) -> Result<OptionBool, ProgramError> {
+    type Err = OptionBoolParseError;
+    fn from_str(s: &str) -> Result<OptionBool, OptionBoolParseError> {
         match s {
             "auto" => Ok(OptionBool::Auto),
             "yes" => Ok(OptionBool::Yes),
             "no" => Ok(OptionBool::No),
-            _ => Err(ProgramError::ArgParse {
-                bad: s.to_string(),
-                valid: "auto, yes, no".to_string(),
-            }),
+            _ => Err(OptionBoolParseError::Invalid { bad: s.to_string() }),
         }
     }
 }
 
+/// how sizes are displayed, when they are
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum SizeDisplay {
+    Cell,       // a small cell
+    Bar(usize), // a wider column
+}
+
+/// the size the bars of the size column are relative to
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum SizeBarBase {
+    Parent, // the size of the parent directory, so that siblings compare
+    Total,  // the size of the whole tree
+}
+
+/// how the dates of last modification are displayed, when they are
+#[derive(Debug, Clone, PartialEq)]
+pub enum DateFormat {
+    Strftime(String), // a format like "%Y-%m-%d %H:%M"
+    Relative,         // the time elapsed, like "3h ago"
+}
+
+pub const DEFAULT_DATE_FORMAT: &str = "%Y-%m-%d %H:%M";
+
+/// how the entries of a directory are ordered
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Sort {
+    Name, // natural order of the names (see natural_sort)
+    None, // the order in which the filesystem gives them
+}
+
+/// how the lines of the screen are shared between the directories
+///  of a level, when there's no pattern
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Balance {
+    Even,         // the directories get as many lines, the small ones leaving theirs
+    Proportional, // the directories get lines in proportion of their number of entries
+}
+
+pub const DEFAULT_SIZE_BAR_WIDTH: usize = 24;
+
+pub const DEFAULT_MAX_CHILDREN: usize = 3000;
+
 #[derive(Debug, Clone)]
 pub struct TreeOptions {
     pub show_hidden: bool, // whether files whose name starts with a dot should be shown
     pub only_folders: bool, // whether to hide normal files and links
     pub show_sizes: bool,  // whether to compute and show sizes of files and dirs
+    pub size_display: SizeDisplay, // how sizes are shown
+    pub size_bar_base: SizeBarBase, // what the size bars are relative to
+    pub size_bar: bool, // whether the sizes are written over a bar (else only as text)
+    pub size_percent: bool, // whether sizes are written as percentages of the parent's size
     pub trim_root: bool,   // whether to cut out direct children of root
     pub show_permissions: bool, // show classic rwx unix permissions
+    pub read_xattrs: bool,      // whether ACLs and extended attributes are signaled with the permissions
+    pub detect_git_repos: bool, // whether the directories holding a .git entry are marked
+    pub detect_collisions: bool, // whether the sibling names equal once case and accents are normalized are marked
+    pub show_owner: bool,       // show the names of the user and group owning the file
+    pub lookup_users: bool,     // whether the names of users and groups are looked for (else ids are shown)
+    pub show_dates: bool,       // show the date of last modification
+    pub date_format: DateFormat, // how the dates are shown
     pub respect_git_ignore: OptionBool, // hide files as requested by .gitignore ?
+    pub show_parent_line: bool, // whether there's a ".." line under the root
+    pub flatten_chains: bool, // whether the chains of single child directories are shown on one line
+    pub show_line_numbers: bool, // whether lines are prefixed with their index
+    pub show_fs_info: bool, // whether the free space of the filesystem is shown
     pub pattern: Pattern,  // an optional filtering/scoring pattern
+    pub path_list: Option<Arc<PathList>>, // when set, only those paths are shown
+    pub max_children: usize, // max number of entries read in a directory
+    pub uncapped_dirs: Vec<PathBuf>, // directories whose entries are all read
+    pub max_children_shown: Option<usize>, // max number of lines of the entries of a directory
+    pub balance: Balance,   // how the lines are shared between the directories
+    pub one_filesystem: bool, // whether to stay on the filesystem of the root
+    pub sort: Sort,         // how the entries of a directory are ordered
+    pub dirs_first: bool,   // whether directories come before the other entries
+    pub max_visible_depth: Option<usize>, // levels drawn before collapsing (None: according to the width)
+    pub special_path: Option<String>, // the name of the special path of the conf applied to the root
+    pub before_special_path: Option<Box<TreeOptions>>, // the options as they were before it was applied
 }
 
 impl TreeOptions {
@@ -41,10 +116,36 @@ impl TreeOptions {
             show_hidden: false,
             only_folders: false,
             show_sizes: false,
+            size_display: SizeDisplay::Cell,
+            size_bar_base: SizeBarBase::Parent,
+            size_bar: true,
+            size_percent: false,
             trim_root: true,
             show_permissions: false,
+            read_xattrs: true,
+            detect_git_repos: true,
+            detect_collisions: false,
+            show_owner: false,
+            lookup_users: true,
+            show_dates: false,
+            date_format: DateFormat::Strftime(DEFAULT_DATE_FORMAT.to_string()),
             respect_git_ignore: OptionBool::Auto,
+            show_parent_line: true,
+            flatten_chains: false,
+            show_line_numbers: false,
+            show_fs_info: true,
             pattern: Pattern::None,
+            path_list: None,
+            max_children: DEFAULT_MAX_CHILDREN,
+            uncapped_dirs: Vec::new(),
+            max_children_shown: None,
+            balance: Balance::Even,
+            one_filesystem: false,
+            sort: Sort::Name,
+            dirs_first: false,
+            max_visible_depth: None,
+            special_path: None,
+            before_special_path: None,
         }
     }
     pub fn without_pattern(&self) -> TreeOptions {
@@ -52,10 +153,45 @@ impl TreeOptions {
             show_hidden: self.show_hidden,
             only_folders: self.only_folders,
             show_sizes: self.show_sizes,
+            size_display: self.size_display,
+            size_bar_base: self.size_bar_base,
+            size_bar: self.size_bar,
+            size_percent: self.size_percent,
             trim_root: self.trim_root,
             show_permissions: self.show_permissions,
+            read_xattrs: self.read_xattrs,
+            detect_git_repos: self.detect_git_repos,
+            detect_collisions: self.detect_collisions,
+            show_owner: self.show_owner,
+            lookup_users: self.lookup_users,
+            show_dates: self.show_dates,
+            date_format: self.date_format.clone(),
             respect_git_ignore: self.respect_git_ignore,
+            show_parent_line: self.show_parent_line,
+            flatten_chains: self.flatten_chains,
+            show_line_numbers: self.show_line_numbers,
+            show_fs_info: self.show_fs_info,
             pattern: Pattern::None,
+            path_list: self.path_list.clone(),
+            max_children: self.max_children,
+            uncapped_dirs: self.uncapped_dirs.clone(),
+            max_children_shown: self.max_children_shown,
+            balance: self.balance,
+            one_filesystem: self.one_filesystem,
+            sort: self.sort,
+            dirs_first: self.dirs_first,
+            max_visible_depth: self.max_visible_depth,
+            special_path: self.special_path.clone(),
+            before_special_path: self.before_special_path.clone(),
         }
     }
+    /// whether a displayed column needs the metadata of the entries (mode,
+    ///  owner, date or size), which are otherwise read after the display
+    pub fn needs_metadata(&self) -> bool {
+        self.show_permissions || self.show_owner || self.show_dates || self.show_sizes
+    }
+    /// whether the extended attributes are read, with the metadata
+    pub fn reads_xattrs(&self) -> bool {
+        self.show_permissions && self.read_xattrs
+    }
 }
@@ -1,116 +1,348 @@
 use std::borrow::Cow;
 use std::io::{self, Write};
-use std::sync::Mutex;
+use std::ops::Range;
 use termion::style;
-use users::{Groups, Users, UsersCache};
 
-use crate::file_sizes::Size;
-use crate::flat_tree::{LineType, Tree, TreeLine};
+use crate::breadcrumbs;
+use crate::dates;
+use crate::file_sizes::{Size, SizeInfo};
+use crate::flat_tree::{LineType, SpecialKind, Tree, TreeLine};
+use crate::owners;
 use crate::patterns::Pattern;
 use crate::screens::{Screen, ScreenArea};
+use crate::tree_options::{DateFormat, SizeBarBase, SizeDisplay};
+
+/// what's needed to write the name of a line, besides the line itself:
+///  the pattern whose matching chars are highlighted, and the colors
+///  which depend on whether the line is selected
+pub struct NameStyle<'p> {
+    pattern: &'p Pattern,
+    line_bg: String,  // the background of the row, re-asserted after each change
+    match_fg: String, // the foreground of the matching chars
+    root_highlight: Option<Range<usize>>, // the highlighted part of the root path
+}
+
+impl<'p> NameStyle<'p> {
+    // wrap the chars matching the pattern, so that they're drawn with
+    //  match_fg before the name foreground is restored.
+    // The names of a flattened chain are matched one by one, as they
+    //  were when the tree was built
+    fn decorate<'a>(&self, name: &'a str, fg: &str) -> Cow<'a, str> {
+        if self.pattern.is_some() {
+            if name.contains('/') {
+                let names: Vec<Cow<'_, str>> =
+                    name.split('/').map(|name| self.decorate(name, fg)).collect();
+                return Cow::Owned(names.join("/"));
+            }
+            if let Some(m) = self.pattern.find(name) {
+                let prefix = format!("{}{}", self.match_fg, self.line_bg);
+                let postfix = format!("{}{}", fg, self.line_bg);
+                return Cow::Owned(m.wrap_matching_chars(name, &prefix, &postfix));
+            }
+        }
+        Cow::Borrowed(name)
+    }
+}
+
+/// the number of rows of the tree, the root's one included. The row
+///  below is kept for the summary of the entries which aren't shown
+pub fn page_height(screen: &Screen) -> i32 {
+    (i32::from(screen.h) - 3).max(1)
+}
 
 pub trait TreeView {
-    fn write_tree(&mut self, tree: &Tree) -> io::Result<()>;
-    fn write_line_size(&mut self, line: &TreeLine, total_size: Size) -> io::Result<()>;
-    fn write_mode(&mut self, mode: u32) -> io::Result<()>;
-    fn write_line_name(&mut self, line: &TreeLine, idx: usize, pattern: &Pattern)
+    fn write_tree(
+        &mut self,
+        tree: &Tree,
+        root_highlight: Option<Range<usize>>,
+        hints: &[(usize, String)],
+    ) -> io::Result<()>;
+    fn write_tree_summary(&mut self, tree: &Tree, y: u16) -> io::Result<()>;
+    fn write_line_size(
+        &mut self,
+        line: &TreeLine,
+        base_size: Size,
+        display: SizeDisplay,
+        bar: bool,
+        relative_to: Option<SizeInfo>,
+        line_bg: &str,
+    ) -> io::Result<()>;
+    fn write_size_bar(
+        &mut self,
+        s: Size,
+        base_size: Size,
+        width: usize,
+        right_aligned: bool,
+        line_bg: &str,
+    ) -> io::Result<()>;
+    fn write_size_bar_text(
+        &mut self,
+        s: Size,
+        base_size: Size,
+        text: &str,
+        width: usize,
+        right_aligned: bool,
+        line_bg: &str,
+    ) -> io::Result<()>;
+    fn write_pending_size(
+        &mut self,
+        partial: Option<Size>,
+        width: usize,
+        right_aligned: bool,
+        line_bg: &str,
+    ) -> io::Result<()>;
+    fn write_padded(&mut self, text: &str, width: usize, right_aligned: bool) -> io::Result<()>;
+    fn write_mode(&mut self, line: &TreeLine) -> io::Result<()>;
+    fn write_line_name(&mut self, line: &TreeLine, idx: usize, style: &NameStyle<'_>)
         -> io::Result<()>;
 }
 
 impl TreeView for Screen {
-    fn write_tree(&mut self, tree: &Tree) -> io::Result<()> {
-        lazy_static! {
-            static ref USERS_CACHE_MUTEX: Mutex<UsersCache> = Mutex::new(UsersCache::new());
+    fn write_tree(
+        &mut self,
+        tree: &Tree,
+        root_highlight: Option<Range<usize>>,
+        hints: &[(usize, String)],
+    ) -> io::Result<()> {
+        if self.is_too_small() {
+            return self.write_too_small();
         }
-        let users_cache = USERS_CACHE_MUTEX.lock().unwrap();
+        // names of the user and group owning the file, or their ids when
+        //  they're unknown, not looked for, or not found yet (the lookups
+        //  being done in pending tasks)
+        let owner_names = if tree.options.show_owner {
+            Some(owners::known())
+        } else {
+            None
+        };
+        let lookup_users = tree.options.lookup_users;
+        let user_name = |uid: u32| match &owner_names {
+            Some(names) if lookup_users => names.user(uid),
+            _ => uid.to_string(),
+        };
+        let group_name = |gid: u32| match &owner_names {
+            Some(names) if lookup_users => names.group(gid),
+            _ => gid.to_string(),
+        };
         let mut max_user_name_len = 0;
         let mut max_group_name_len = 0;
-        if tree.options.show_permissions {
-            // we compute the max size of user/group names to reserve width for the columns
-            for i in 1..tree.lines.len() {
-                let line = &tree.lines[i];
-                if let Some(user) = users_cache.get_user_by_uid(line.uid) {
-                    max_user_name_len = max_user_name_len.max(user.name().to_string_lossy().len());
-                }
-                if let Some(group) = users_cache.get_group_by_gid(line.uid) {
-                    max_group_name_len =
-                        max_group_name_len.max(group.name().to_string_lossy().len());
-                }
+        if tree.options.show_owner {
+            // the width of the columns grows as the names are found
+            for line in &tree.lines[1..] {
+                max_user_name_len = max_user_name_len.max(user_name(line.uid).len());
+                max_group_name_len = max_group_name_len.max(group_name(line.gid).len());
             }
         }
+        // relative dates take a fixed width, while the width of the
+        //  formatted ones depends on the format and the dates
+        let date_width = match &tree.options.date_format {
+            _ if !tree.options.show_dates => 0,
+            DateFormat::Relative => dates::RELATIVE_DATE_WIDTH,
+            format => tree.lines[1..]
+                .iter()
+                .map(|line| dates::format_date(line.modified, tree.built_at_secs(), format).chars().count())
+                .max()
+                .unwrap_or(0),
+        };
         let total_size = tree.total_size();
+        let bottom = page_height(self) as u16;
+        // the root is always on the first row, the other lines are
+        //  scrolled in the rows below
         let area = ScreenArea {
-            top: 1,
-            bottom: self.h - 1,
+            top: 2,
+            bottom,
             scroll: tree.scroll,
-            content_length: tree.lines.len() as i32,
+            content_length: tree.lines.len() as i32 - 1,
             width: self.w,
         };
+        let visible_range = area.visible_range();
         let scrollbar = area.scrollbar();
-        for y in 1..self.h - 1 {
-            write!(self.stderr, "{}", termion::cursor::Goto(1, y),)?;
-            let mut line_index = (y - 1) as usize;
-            if line_index > 0 {
-                line_index += tree.scroll as usize;
-            }
-            if line_index < tree.lines.len() {
+        // the width of the line numbers column adapts to the biggest one
+        let line_number_width = (tree.lines.len() - 1).to_string().len();
+        // the branches take at most half the width, 3 columns per level
+        let max_depth = (usize::from(self.w) / 6).max(2);
+        let max_depth = match tree.options.max_visible_depth {
+            Some(depth) => depth.max(2).min(max_depth),
+            None => max_depth,
+        };
+        // the rows are recorded, so that only the changed ones are written
+        for y in 1..=bottom {
+            self.stderr.start_row(y);
+            write!(self.stderr, "{}{}", self.goto(1, y), style::Reset)?;
+            let line_index = if y == 1 {
+                0
+            } else {
+                1 + visible_range.start + (y - 2) as usize
+            };
+            if line_index == 0 || line_index <= visible_range.end {
                 let line = &tree.lines[line_index];
+                let selected = line_index == tree.selection;
+                // the background of the whole row, which must be restored
+                //  after the cells having their own background
+                let line_bg = if selected {
+                    self.skin.selected_line.bg.clone()
+                } else {
+                    self.skin.tree.bg.clone()
+                };
+                if tree.options.show_line_numbers {
+                    if line.is_selectable() {
+                        write!(
+                            self.stderr,
+                            "{}{}{:>w$} ",
+                            self.skin.line_number.fg,
+                            line_bg,
+                            line_index,
+                            w = line_number_width,
+                        )?;
+                    } else {
+                        write!(
+                            self.stderr,
+                            "{}{:w$} ",
+                            line_bg,
+                            "",
+                            w = line_number_width
+                        )?;
+                    }
+                }
                 //self.apply_skin_entry(&self.skin.tree)?;
-                write!(self.stderr, "{}", self.skin.tree.fgbg())?;
-                for depth in 0..line.depth {
-                    write!(
-                        self.stderr,
-                        "{}",
-                        if line.left_branchs[depth as usize] {
-                            if tree.has_branch(line_index + 1, depth as usize) {
-                                if depth == line.depth - 1 {
-                                    "├──"
-                                } else {
-                                    "│  "
-                                }
+                write!(self.stderr, "{}{}", self.skin.tree.fg, line_bg)?;
+                // the first levels of the deepest lines are collapsed
+                //  into one column, the last ones being drawn
+                let mut branches = String::new();
+                let first_depth = if line.depth > max_depth {
+                    branches.push_str("…  ");
+                    line.depth + 1 - max_depth
+                } else {
+                    0
+                };
+                for depth in first_depth..line.depth {
+                    branches.push_str(if line.left_branchs[depth] {
+                        if tree.has_branch(line_index + 1, depth) {
+                            if depth == line.depth - 1 {
+                                "├──"
                             } else {
-                                "└──"
+                                "│  "
                             }
                         } else {
-                            "   "
-                        },
-                    )?;
+                            "└──"
+                        }
+                    } else {
+                        "   "
+                    });
                 }
-                if tree.options.show_sizes && line_index > 0 {
-                    self.write_line_size(line, total_size)?;
+                // in hint mode, the label of the line hides the start of its branches
+                match hints.iter().find(|(idx, _)| *idx == line_index) {
+                    Some((_, label)) => {
+                        let hidden = label.chars().count();
+                        write!(
+                            self.stderr,
+                            "{}{}{}{}{}{}",
+                            self.skin.hint.fg,
+                            self.skin.hint.bg,
+                            label,
+                            self.skin.tree.fg,
+                            line_bg,
+                            branches.chars().skip(hidden).collect::<String>(),
+                        )?;
+                    }
+                    None => write!(self.stderr, "{}", branches)?,
+                }
+                // with percentages, the root line shows the total size
+                if tree.options.show_sizes && (line_index > 0 || tree.options.size_percent) {
+                    let base_size = match tree.options.size_bar_base {
+                        SizeBarBase::Parent => tree.parent_size(line_index),
+                        SizeBarBase::Total => total_size,
+                    };
+                    let relative_to = if tree.options.size_percent {
+                        tree.parent_index(line_index).map(|idx| tree.lines[idx].size)
+                    } else {
+                        None
+                    };
+                    self.write_line_size(
+                        line,
+                        base_size,
+                        tree.options.size_display,
+                        tree.options.size_bar,
+                        relative_to,
+                        &line_bg,
+                    )?;
                 }
                 if tree.options.show_permissions && line_index > 0 {
-                    if line.is_selectable() {
-                        self.write_mode(line.mode)?;
-                        if let Some(user) = users_cache.get_user_by_uid(line.uid) {
-                            write!(
-                                self.stderr,
-                                " {:w$}",
-                                user.name().to_string_lossy(),
-                                w = max_user_name_len,
-                            )?;
-                        }
-                        if let Some(group) = users_cache.get_group_by_gid(line.uid) {
-                            write!(
-                                self.stderr,
-                                " {:w$} ",
-                                group.name().to_string_lossy(),
-                                w = max_group_name_len,
-                            )?;
+                    // the column has one more char when the extended attributes are read
+                    if !line.has_metadata {
+                        // not read yet
+                        write!(self.stderr, "{:w$} ", "", w = if tree.options.read_xattrs { 11 } else { 10 })?;
+                    } else if line.is_selectable() && !line.is_parent() {
+                        self.write_mode(line)?;
+                        if tree.options.read_xattrs {
+                            write!(self.stderr, "{}", line.xattrs.mark())?;
                         }
+                        write!(self.stderr, " ")?;
                     } else {
                         write!(
                             self.stderr,
-                            "{}──────────────{}",
-                            self.skin.tree.fg, self.skin.reset.fg,
+                            "{}──────────{}{} ",
+                            self.skin.tree.fg,
+                            if tree.options.read_xattrs { "─" } else { "" },
+                            self.skin.reset.fg,
                         )?;
                     }
                 }
-                let selected = line_index == tree.selection;
-                if selected {
-                    write!(self.stderr, "{}", self.skin.selected_line.bg)?;
+                if tree.options.show_owner && line_index > 0 {
+                    if !line.has_metadata {
+                        write!(self.stderr, "{:w$} ", "", w = max_user_name_len + 1 + max_group_name_len)?;
+                    } else if line.is_selectable() && !line.is_parent() {
+                        write!(
+                            self.stderr,
+                            "{}{:uw$} {:gw$} ",
+                            self.skin.permissions.fg,
+                            user_name(line.uid),
+                            group_name(line.gid),
+                            uw = max_user_name_len,
+                            gw = max_group_name_len,
+                        )?;
+                    } else {
+                        write!(
+                            self.stderr,
+                            "{}{}{} ",
+                            self.skin.tree.fg,
+                            "─".repeat(max_user_name_len + 1 + max_group_name_len),
+                            self.skin.reset.fg,
+                        )?;
+                    }
                 }
-                self.write_line_name(line, line_index, &tree.options.pattern)?;
+                if tree.options.show_dates && line_index > 0 {
+                    if !line.has_metadata {
+                        write!(self.stderr, "{:w$} ", "", w = date_width)?;
+                    } else if line.is_selectable() && !line.is_parent() {
+                        write!(
+                            self.stderr,
+                            "{}{:>w$} ",
+                            self.skin.dates.fg,
+                            dates::format_date(line.modified, tree.built_at_secs(), &tree.options.date_format),
+                            w = date_width,
+                        )?;
+                    } else {
+                        write!(
+                            self.stderr,
+                            "{}{}{} ",
+                            self.skin.tree.fg,
+                            "─".repeat(date_width),
+                            self.skin.reset.fg,
+                        )?;
+                    }
+                }
+                let name_style = NameStyle {
+                    pattern: &tree.options.pattern,
+                    match_fg: if selected {
+                        self.skin.char_match_selected.fg.clone()
+                    } else {
+                        self.skin.char_match.fg.clone()
+                    },
+                    line_bg,
+                    root_highlight: root_highlight.clone(),
+                };
+                self.write_line_name(line, line_index, &name_style)?;
             }
             write!(
                 self.stderr,
@@ -118,21 +350,66 @@ impl TreeView for Screen {
                 termion::clear::UntilNewline,
                 style::Reset,
             )?;
+            if y == 1 && self.stack_crumbs.len() > 1 {
+                // the breadcrumb of the stack takes at most half the row,
+                //  hiding the end of a long root path
+                let fitted = breadcrumbs::fit_stack(&self.stack_crumbs, self.w as usize / 2);
+                if let Some((head, current)) = fitted {
+                    let width = (head.chars().count() + current.chars().count()) as u16;
+                    write!(
+                        self.stderr,
+                        "{}{}{}{}{}{}{}",
+                        self.goto(self.w - width + 1, y),
+                        self.skin.flag_label.fg,
+                        self.skin.flag_label.bg,
+                        head,
+                        self.skin.flag_value.fg,
+                        current,
+                        style::Reset,
+                    )?;
+                }
+            }
             if let Some((sctop, scbottom)) = scrollbar {
                 if sctop <= y && y <= scbottom {
-                    write!(self.stderr, "{}▐", termion::cursor::Goto(self.w, y),)?;
+                    write!(self.stderr, "{}▐", self.goto(self.w, y),)?;
                 }
             }
+            self.stderr.end_row()?;
+        }
+        let y = bottom + 1;
+        self.stderr.start_row(y);
+        if tree.is_pruned() {
+            self.write_tree_summary(tree, y)?;
+        } else {
+            write!(self.stderr, "{}{}", self.goto(1, y), termion::clear::CurrentLine)?;
         }
+        self.stderr.end_row()?;
         self.stderr.flush()?;
         Ok(())
     }
 
-    fn write_mode(&mut self, mode: u32) -> io::Result<()> {
+    fn write_tree_summary(&mut self, tree: &Tree, y: u16) -> io::Result<()> {
+        write!(
+            self.stderr,
+            "{}{}{}{} showing {} of {} entries - type a few letters to filter{}{}",
+            self.goto(1, y),
+            self.skin.tree_summary.fg,
+            self.skin.tree_summary.bg,
+            style::Italic,
+            with_thousands_separator(tree.nb_shown()),
+            with_thousands_separator(tree.nb_visited),
+            termion::clear::UntilNewline,
+            style::Reset,
+        )
+    }
+
+    fn write_mode(&mut self, line: &TreeLine) -> io::Result<()> {
+        let mode = line.mode;
         write!(
             self.stderr,
-            "{} {}{}{}{}{}{}{}{}{}",
+            "{}{}{}{}{}{}{}{}{}{}{}",
             self.skin.permissions.fg,
+            line.special_kind().map_or(' ', SpecialKind::mode_char),
             if (mode & (1 << 8)) != 0 { 'r' } else { '-' },
             if (mode & (1 << 7)) != 0 { 'w' } else { '-' },
             if (mode & (1 << 6)) != 0 { 'x' } else { '-' },
@@ -145,29 +422,137 @@ impl TreeView for Screen {
         )
     }
 
-    fn write_line_size(&mut self, line: &TreeLine, total_size: Size) -> io::Result<()> {
-        if let Some(s) = line.size {
-            let dr: usize = s.discrete_ratio(total_size, 8) as usize;
-            let s: Vec<char> = s.to_string().chars().collect();
-            write!(
+    fn write_line_size(
+        &mut self,
+        line: &TreeLine,
+        base_size: Size,
+        display: SizeDisplay,
+        bar: bool, // whether the text is written over a bar (else just padded)
+        relative_to: Option<SizeInfo>, // the size of the parent, when percentages are shown
+        line_bg: &str,
+    ) -> io::Result<()> {
+        // in a cell the text is left aligned, in a bar it's right aligned
+        let (width, right_aligned) = match display {
+            SizeDisplay::Cell => (8, false),
+            SizeDisplay::Bar(width) => (width, true),
+        };
+        if !line.is_selectable() || line.is_parent() || line.special_kind().is_some() {
+            return write!(
                 self.stderr,
-                "{}{}",
-                self.skin.size_text.fg, self.skin.size_bar_full.bg,
-            )?;
-            for i in 0..dr {
-                write!(self.stderr, "{}", if i < s.len() { s[i] } else { ' ' })?;
+                "{}{}{} ",
+                self.skin.tree.fg,
+                "─".repeat(width),
+                self.skin.reset.fg,
+            );
+        }
+        let (s, complete) = match line.size {
+            SizeInfo::Computed(s) => (s, true),
+            SizeInfo::Incomplete(s, _) => (s, false),
+            SizeInfo::Unknown => {
+                return self.write_pending_size(None, width, right_aligned, line_bg);
             }
-            write!(self.stderr, "{}", self.skin.size_bar_void.bg)?;
-            for i in dr..8 {
-                write!(self.stderr, "{}", if i < s.len() { s[i] } else { ' ' })?;
+            SizeInfo::Partial(s) => {
+                return self.write_pending_size(Some(s), width, right_aligned, line_bg);
             }
-            write!(self.stderr, "{}{} ", self.skin.reset.fg, self.skin.reset.bg,)
+        };
+        // the percentage changes as long as the size of the parent
+        //  is being computed
+        let mut text = match relative_to.map(SizeInfo::computed) {
+            None => s.to_string(),
+            Some(Some(parent_size)) => format!("{:.1}%", s.percent_of(parent_size)),
+            Some(None) => "…%".to_string(),
+        };
+        if !complete {
+            // some directories couldn't be read: it's at least this size
+            text.push('+');
+        }
+        if bar {
+            self.write_size_bar_text(s, base_size, &text, width, right_aligned, line_bg)
         } else {
-            write!(
-                self.stderr,
-                "{}────────{} ",
-                self.skin.tree.fg, self.skin.reset.fg,
-            )
+            write!(self.stderr, "{}", self.skin.size_text.fg)?;
+            self.write_padded(&text, width, right_aligned)?;
+            write!(self.stderr, "{}{} ", self.skin.reset.fg, line_bg)
+        }
+    }
+
+    fn write_size_bar(
+        &mut self,
+        s: Size,
+        base_size: Size,
+        width: usize,
+        right_aligned: bool,
+        line_bg: &str,
+    ) -> io::Result<()> {
+        self.write_size_bar_text(s, base_size, &s.to_string(), width, right_aligned, line_bg)
+    }
+
+    // the text is written over the bar, whose filled part is
+    //  proportional to s
+    fn write_size_bar_text(
+        &mut self,
+        s: Size,
+        base_size: Size,
+        text: &str,
+        width: usize,
+        right_aligned: bool,
+        line_bg: &str,
+    ) -> io::Result<()> {
+        let dr: usize = (s.discrete_ratio(base_size, width as u64) as usize).min(width);
+        let s: Vec<char> = text.chars().collect();
+        let start = if right_aligned {
+            width.saturating_sub(s.len())
+        } else {
+            0
+        };
+        let char_at = |i: usize| {
+            if i >= start && i - start < s.len() {
+                s[i - start]
+            } else {
+                ' '
+            }
+        };
+        write!(
+            self.stderr,
+            "{}{}",
+            self.skin.size_text.fg, self.skin.size_bar_full.bg,
+        )?;
+        for i in 0..dr {
+            write!(self.stderr, "{}", char_at(i))?;
+        }
+        write!(self.stderr, "{}", self.skin.size_bar_void.bg)?;
+        for i in dr..width {
+            write!(self.stderr, "{}", char_at(i))?;
+        }
+        write!(self.stderr, "{}{} ", self.skin.reset.fg, line_bg)
+    }
+
+    // a size being computed: the part already found, if any, followed by an ellipsis
+    fn write_pending_size(
+        &mut self,
+        partial: Option<Size>,
+        width: usize,
+        right_aligned: bool,
+        line_bg: &str,
+    ) -> io::Result<()> {
+        let text = match partial {
+            Some(s) => format!("{}…", s.to_string()),
+            None => "…".to_string(),
+        };
+        write!(
+            self.stderr,
+            "{}{}",
+            self.skin.spinner.fg, self.skin.spinner.bg,
+        )?;
+        self.write_padded(&text, width, right_aligned)?;
+        write!(self.stderr, "{}{} ", self.skin.reset.fg, line_bg)
+    }
+
+    // the text, padded with spaces to the width of the size column
+    fn write_padded(&mut self, text: &str, width: usize, right_aligned: bool) -> io::Result<()> {
+        if right_aligned {
+            write!(self.stderr, "{:>w$}", text, w = width)
+        } else {
+            write!(self.stderr, "{:<w$}", text, w = width)
         }
     }
 
@@ -175,75 +560,111 @@ impl TreeView for Screen {
         &mut self,
         line: &TreeLine,
         idx: usize,
-        pattern: &Pattern,
+        name_style: &NameStyle<'_>,
     ) -> io::Result<()> {
         // TODO draw in red lines with has_error
+        if line.is_hidden && idx > 0 {
+            write!(self.stderr, "{}", style::Italic)?;
+        }
+        // the color of the name, before any decoration
+        let name_fg = |normal_fg: &str| -> String {
+            if line.is_hidden {
+                self.skin.hidden.fg.to_string()
+            } else {
+                normal_fg.to_string()
+            }
+        };
+        // a name which can't be told apart from a sibling one is
+        //  followed by a warning mark
+        let collision_mark = if line.collides_with.is_some() {
+            format!(" {}≈", &self.skin.file_error.fg)
+        } else {
+            String::new()
+        };
         match &line.line_type {
             LineType::Dir => {
+                // the roots of git repositories get a mark after their name,
+                //  which isn't part of the name matched by the pattern
+                let git_mark = if line.is_git_repo {
+                    format!("{} {}±", collision_mark, &self.skin.git_repo.fg)
+                } else {
+                    collision_mark
+                };
                 if idx == 0 {
+                    let path = line.path.to_string_lossy();
+                    match &name_style.root_highlight {
+                        Some(range) => write!(
+                            self.stderr,
+                            "{}{}{}{}{}{}{}",
+                            style::Bold,
+                            &self.skin.directory.fg,
+                            &path[..range.start],
+                            style::Invert,
+                            &path[range.clone()],
+                            style::NoInvert,
+                            &path[range.end..],
+                        )?,
+                        None => write!(
+                            self.stderr,
+                            "{}{}{}",
+                            style::Bold,
+                            &self.skin.directory.fg,
+                            &path,
+                        )?,
+                    }
+                    write!(self.stderr, "{}", git_mark)?;
+                } else if line.is_context {
+                    // a directory only shown for the matches it contains
+                    let fg = &self.skin.context.fg;
                     write!(
                         self.stderr,
-                        "{}{}{}",
-                        style::Bold,
-                        &self.skin.directory.fg,
-                        &line.path.to_string_lossy(),
+                        "{}{}{} {}({})",
+                        fg,
+                        name_style.decorate(&line.name, fg),
+                        git_mark,
+                        fg,
+                        line.nb_matches,
                     )?;
+                    if line.unlisted > 0 {
+                        write!(self.stderr, " …",)?;
+                    }
                 } else {
+                    let fg = name_fg(&self.skin.directory.fg);
                     write!(
                         self.stderr,
-                        "{}{}{}",
+                        "{}{}{}{}",
                         style::Bold,
-                        &self.skin.directory.fg,
-                        decorated_name(
-                            &line.name,
-                            pattern,
-                            &self.skin.char_match.fg,
-                            &self.skin.directory.fg
-                        ),
+                        &fg,
+                        name_style.decorate(&line.name, &fg),
+                        git_mark,
                     )?;
                     if line.unlisted > 0 {
-                        write!(self.stderr, " …",)?;
+                        write!(self.stderr, "{} …", &fg)?;
                     }
                 }
             }
             LineType::File => {
-                if line.is_exe() {
-                    write!(
-                        self.stderr,
-                        "{}{}",
-                        &self.skin.exe.fg,
-                        decorated_name(
-                            &line.name,
-                            pattern,
-                            &self.skin.char_match.fg,
-                            &self.skin.exe.fg
-                        ),
-                    )?;
+                let fg = if line.is_exe() {
+                    name_fg(&self.skin.exe.fg)
                 } else {
-                    write!(
-                        self.stderr,
-                        "{}{}",
-                        &self.skin.file.fg,
-                        decorated_name(
-                            &line.name,
-                            pattern,
-                            &self.skin.char_match.fg,
-                            &self.skin.file.fg
-                        ),
-                    )?;
-                }
+                    name_fg(&self.skin.file.fg)
+                };
+                write!(
+                    self.stderr,
+                    "{}{}{}",
+                    &fg,
+                    name_style.decorate(&line.name, &fg),
+                    collision_mark,
+                )?;
             }
             LineType::SymLinkToFile(target) => {
+                let fg = name_fg(&self.skin.link.fg);
                 write!(
                     self.stderr,
-                    "{}{} {}->{} {}",
-                    &self.skin.link.fg,
-                    decorated_name(
-                        &line.name,
-                        pattern,
-                        &self.skin.char_match.fg,
-                        &self.skin.link.fg
-                    ),
+                    "{}{}{} {}->{} {}",
+                    &fg,
+                    name_style.decorate(&line.name, &fg),
+                    collision_mark,
                     if line.has_error {
                         &self.skin.file_error.fg
                     } else {
@@ -254,16 +675,13 @@ impl TreeView for Screen {
                 )?;
             }
             LineType::SymLinkToDir(target) => {
+                let fg = name_fg(&self.skin.link.fg);
                 write!(
                     self.stderr,
-                    "{}{} {}->{}{} {}",
-                    &self.skin.link.fg,
-                    decorated_name(
-                        &line.name,
-                        pattern,
-                        &self.skin.char_match.fg,
-                        &self.skin.link.fg
-                    ),
+                    "{}{}{} {}->{}{} {}",
+                    &fg,
+                    name_style.decorate(&line.name, &fg),
+                    collision_mark,
                     if line.has_error {
                         &self.skin.file_error.fg
                     } else {
@@ -274,6 +692,35 @@ impl TreeView for Screen {
                     &target,
                 )?;
             }
+            LineType::Special(kind) => {
+                let fg = name_fg(match kind {
+                    SpecialKind::Socket => &self.skin.socket.fg,
+                    SpecialKind::Fifo => &self.skin.fifo.fg,
+                    SpecialKind::BlockDevice | SpecialKind::CharDevice => &self.skin.device.fg,
+                });
+                write!(
+                    self.stderr,
+                    "{}{}{}",
+                    &fg,
+                    name_style.decorate(&line.name, &fg),
+                    collision_mark,
+                )?;
+                if let Some(indicator) = kind.indicator() {
+                    write!(self.stderr, "{}", indicator)?;
+                }
+            }
+            LineType::Parent => {
+                write!(self.stderr, "{}{}..", style::Bold, &self.skin.directory.fg)?;
+            }
+            LineType::Pruning if line.is_capped => {
+                write!(
+                    self.stderr,
+                    "{}{}{} more entries (press :all to list)",
+                    self.skin.unlisted.fg,
+                    style::Italic,
+                    &line.unlisted,
+                )?;
+            }
             LineType::Pruning => {
                 write!(
                     self.stderr,
@@ -283,22 +730,42 @@ impl TreeView for Screen {
                     style::Italic,
                     &line.unlisted,
                 )?;
+                if line.filtered_out > 0 {
+                    write!(self.stderr, " (+{} hidden)", line.filtered_out)?;
+                }
             }
         }
+        // a lock marks the entries the user can't read or enter
+        if idx > 0 && line.can_open == Some(false) {
+            write!(self.stderr, " {}🔒", self.skin.lock.fg)?;
+        }
         Ok(())
     }
 }
 
-fn decorated_name<'a>(
-    name: &'a str,
-    pattern: &Pattern,
-    prefix: &str,
-    postfix: &str,
-) -> Cow<'a, str> {
-    if pattern.is_some() {
-        if let Some(m) = pattern.find(name) {
-            return Cow::Owned(m.wrap_matching_chars(name, prefix, postfix));
+/// format a count in a more readable way, eg "8,912"
+pub fn with_thousands_separator(n: usize) -> String {
+    let digits = n.to_string();
+    let mut s = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i) % 3 == 0 {
+            s.push(',');
         }
+        s.push(c);
+    }
+    s
+}
+
+#[cfg(test)]
+mod tree_views_tests {
+    use super::*;
+
+    #[test]
+    fn thousands_separators() {
+        assert_eq!(with_thousands_separator(0), "0");
+        assert_eq!(with_thousands_separator(999), "999");
+        assert_eq!(with_thousands_separator(1000), "1,000");
+        assert_eq!(with_thousands_separator(12_400), "12,400");
+        assert_eq!(with_thousands_separator(1_234_567), "1,234,567");
     }
-    Cow::Borrowed(name)
 }
@@ -1,9 +1,12 @@
 use regex::Regex;
 
-#[derive(Clone, Debug)]
+use crate::internals::Internal;
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct VerbInvocation {
     pub key: String,
     pub args: Option<String>,
+    pub forced: bool, // a '!' after the key forces the launch of a program not found
 }
 impl VerbInvocation {
     pub fn from(invocation: &str) -> VerbInvocation {
@@ -11,11 +14,17 @@ impl VerbInvocation {
             static ref PARTS: Regex = Regex::new(r"^(\S*)\s*(.+?)?\s*$").unwrap();
         }
         let caps = PARTS.captures(invocation).unwrap(); // this regex should always match
-        let key = caps.get(1).unwrap().as_str().to_string();
+        let mut key = caps.get(1).unwrap().as_str().to_string();
+        // the '!' of a built-in like "paste!" is part of its key
+        let forced = key.ends_with('!') && key.parse::<Internal>().is_err();
+        if forced {
+            key.pop();
+        }
         let args = caps.get(2).map(|c| c.as_str().to_string());
         VerbInvocation {
             key,
             args,
+            forced,
         }
     }
     pub fn is_empty(&self) -> bool {
@@ -1,5 +1,12 @@
+use std::path::Path;
+use termion::event::Key;
+
+use crate::commands::KeyDescription;
 use crate::conf::Conf;
-use crate::verbs::Verb;
+use crate::internals::Internal;
+use crate::status;
+use crate::verb_invocation::VerbInvocation;
+use crate::verbs::{Verb, VerbExecution};
 
 /// Provide access to the verbs:
 /// - the built-in ones
@@ -9,19 +16,25 @@ use crate::verbs::Verb;
 /// - if only one verb key starts with the input
 pub struct VerbStore {
     pub verbs: Vec<Verb>,
+    input_keys: Vec<(KeyDescription, &'static str)>, // the keys handled by the input, by verb or action name
 }
 
+// the length at which the descriptions of the verbs matching the
+//  typed key are cut, so that several ones fit in the status
+const MAX_CANDIDATE_DESCRIPTION_LEN: usize = 32;
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum PrefixSearchResult<T> {
     NoMatch,
     Match(T),
-    TooManyMatches,
+    TooManyMatches(Vec<T>), // the candidates
 }
 
 impl VerbStore {
     pub fn new() -> VerbStore {
         VerbStore {
             verbs: Vec::new(),
+            input_keys: Vec::new(),
         }
     }
     fn add_builtin(
@@ -36,79 +49,192 @@ impl VerbStore {
             description,
         ));
     }
+    /// add a built-in verb triggered by a key by default
+    fn add_builtin_with_key(
+        &mut self,
+        key: &str,
+        shortcut: Option<String>,
+        description: &str,
+        hotkey: Key,
+    ) {
+        let mut verb = Verb::create_builtin(key, shortcut, description);
+        verb.key = Some(hotkey);
+        self.verbs.push(verb);
+    }
     pub fn init(&mut self, conf: &Conf) {
+        // the keys which aren't hot keys of verbs but are handled by the
+        //  input. <enter> focuses a directory and opens a file. The keys
+        //  whose name isn't the one of a verb do actions without verb
+        let key = KeyDescription::Key;
+        self.input_keys = vec![
+            (key(Key::Char('\n')), "focus"),
+            (key(Key::Char('\n')), "open"),
+            (key(Key::Char('\n')), conf.enter_on_file.key()),
+            (key(Key::Alt('\r')), "cd"),
+            (key(Key::Esc), "back"),
+            (key(Key::Char('?')), "help"),
+            (key(Key::Char('\'')), "hints"),
+            (key(Key::Ctrl('q')), "quit"),
+            (KeyDescription::Sequence("alt-up"), "previous_same_depth"),
+            (key(Key::Char('{')), "previous_same_depth"),
+            (KeyDescription::Sequence("alt-down"), "next_same_depth"),
+            (key(Key::Char('}')), "next_same_depth"),
+            (key(Key::Ctrl('f')), "flags_menu"),
+            (key(Key::Ctrl('z')), "suspend"),
+            (key(Key::Home), "select_first"),
+            (key(Key::End), "select_last"),
+        ];
         // we first add the built-in verbs
+        self.add_builtin(
+            "all",
+            None,
+            "list all the entries of the selected directory",
+        );
         self.add_builtin(
             "back",
             None,
-            "revert to the previous state (mapped to `<esc>`)",
+            "revert to the previous state",
         );
         self.verbs.push(Verb::create_external(
             "cd",
             None, // no real need for a shortcut as it's mapped to alt-enter
             "cd {directory}".to_string(),
-            Some("change directory and quit (mapped to `<alt><enter>`)".to_string()),
+            Some("change directory and quit".to_string()),
             true, // needs to be launched from the parent shell
             true, // leaves broot
             false,
         ).unwrap());
-        self.verbs.push(Verb::create_external(
-            "cp {newpath}",
+        self.add_builtin(
+            "chmod {mode}",
+            None,
+            "change the mode of the selected file (eg `644`, `+x` or `go-w`, `-R 644` to recurse)",
+        );
+        self.add_builtin(
+            "cp",
             None,
-            "/bin/cp -r {file} {parent}/{newpath}".to_string(),
+            "yank the selection, to be copied with :paste",
+        );
+        self.add_builtin(
+            "dedup",
             None,
-            false,
-            false,
-            false,
-        ).unwrap());
+            "list the files having the same content in the directory",
+        );
         self.add_builtin(
-            "focus",
+            "edit_conf",
+            None,
+            "open the configuration file in $EDITOR",
+        );
+        self.add_builtin(
+            "filesystems",
+            Some("fs".to_string()),
+            "list the mounted filesystems",
+        );
+        self.add_builtin(
+            "focus {path}",
             Some("goto".to_string()),
-            "display the directory (mapped to `<enter>` in tree)",
+            "display the directory, or the selected one",
+        );
+        self.add_builtin(
+            "goto_line {line}",
+            None,
+            "select the line with this number",
+        );
+        self.add_builtin(
+            "hardlink",
+            None,
+            "replace a duplicate by a hard link to an identical file",
         );
         self.add_builtin(
             "help",
             Some("?".to_string()),
             "display broot's help",
         );
-        self.verbs.push(Verb::create_external(
+        self.add_builtin(
+            "hints",
+            None,
+            "label the visible lines, to select one by typing its label",
+        );
+        self.add_builtin(
             "mkdir {subpath}",
             Some("md".to_string()),
-            "/bin/mkdir -p {directory}/{subpath}".to_string(),
-            None,
-            false,
-            false, // doesn't leave broot
-            false,
-        ).unwrap());
-        self.verbs.push(Verb::create_external(
-            "mv {newpath}",
+            "create a directory (with `-p` for intermediate ones)",
+        );
+        self.add_builtin(
+            "mv",
             None,
-            "/bin/mv {file} {parent}/{newpath}".to_string(),
+            "yank the selection, to be moved with :paste",
+        );
+        self.add_builtin(
+            "next_same_depth",
             None,
-            false,
-            false, // doesn't leave broot
-            false,
-        ).unwrap());
+            "select the next line with the same parent",
+        );
+        // <enter> on a file does :open_leave, :open_stay or :open_later, and :open follows it
         self.add_builtin(
             "open",
             None,
-            "open file according to OS settings (mapped to `<enter>`)",
+            match conf.enter_on_file {
+                Internal::OpenStay => "open file according to OS settings, staying in broot, like `<enter>`",
+                Internal::OpenLater => "open file according to OS settings when broot quits, like `<enter>`",
+                _ => "open file according to OS settings, then quit, like `<enter>`",
+            },
+        );
+        self.add_builtin(
+            "open_later",
+            None,
+            "open file according to OS settings when broot quits",
+        );
+        self.add_builtin(
+            "open_leave",
+            None,
+            "open file according to OS settings, then quit",
+        );
+        self.add_builtin(
+            "open_stay",
+            None,
+            "open file according to OS settings, staying in broot",
         );
         self.add_builtin(
             "parent",
             None,
             "move to the parent directory",
         );
+        self.add_builtin(
+            "paste",
+            None,
+            "copy or move the yanked paths into the selected directory",
+        );
+        self.add_builtin(
+            "paste!",
+            None,
+            "paste, overwriting existing entries with the same name",
+        );
+        self.add_builtin(
+            "previous_same_depth",
+            None,
+            "select the previous line with the same parent",
+        );
         self.add_builtin(
             "print_path",
             Some("pp".to_string()),
             "print path and leaves broot",
         );
+        self.add_builtin(
+            "print_relative_path",
+            Some("prp".to_string()),
+            "print path, relative to the launch directory, and leaves broot",
+        );
         self.add_builtin(
             "quit",
             Some("q".to_string()),
             "quit the application",
         );
+        self.add_builtin_with_key(
+            "refresh",
+            None,
+            "read the directories again",
+            Key::F(5),
+        );
         self.verbs.push(Verb::create_external(
             "rm",
             None,
@@ -116,13 +242,33 @@ impl VerbStore {
             None,
             false,
             false, // doesn't leave broot
-            false,
+            true, // asks for a confirmation
         ).unwrap());
+        self.add_builtin(
+            "size",
+            Some("du".to_string()),
+            "compute the size of the selected file or directory",
+        );
+        self.add_builtin(
+            "touch {name}",
+            None,
+            "create an empty file",
+        );
+        self.add_builtin(
+            "toggle_dates",
+            Some("dates".to_string()),
+            "toggle showing the dates of last modification",
+        );
         self.add_builtin(
             "toggle_files",
             Some("files".to_string()),
             "toggle showing files (or just folders)",
         );
+        self.add_builtin(
+            "toggle_fs_info",
+            Some("fs_info".to_string()),
+            "toggle showing the free space of the filesystem",
+        );
         self.add_builtin(
             "toggle_git_ignore",
             Some("gi".to_string()),
@@ -133,11 +279,31 @@ impl VerbStore {
             Some("h".to_string()),
             "toggle showing hidden files",
         );
+        self.add_builtin(
+            "toggle_line_numbers",
+            Some("line_numbers".to_string()),
+            "toggle showing line numbers",
+        );
+        self.add_builtin(
+            "toggle_owner",
+            Some("owner".to_string()),
+            "toggle showing file owners",
+        );
+        self.add_builtin(
+            "toggle_percent",
+            None,
+            "toggle showing sizes as percentages of the parents",
+        );
         self.add_builtin(
             "toggle_perm",
             Some("perm".to_string()),
             "toggle showing file permissions",
         );
+        self.add_builtin(
+            "toggle_screen",
+            Some("screen".to_string()),
+            "show the terminal under broot until a key is hit",
+        );
         self.add_builtin(
             "toggle_sizes",
             Some("sizes".to_string()),
@@ -148,6 +314,16 @@ impl VerbStore {
             Some("t".to_string()),
             "toggle removing nodes at first level too (default)",
         );
+        self.add_builtin(
+            "total_search",
+            None,
+            "search the pattern in the whole tree, without time limit",
+        );
+        self.add_builtin(
+            "version",
+            None,
+            "show the version of broot and how it was built",
+        );
         for verb_conf in &conf.verbs {
             match Verb::create_external(
                 &verb_conf.invocation,
@@ -158,7 +334,26 @@ impl VerbStore {
                 verb_conf.leave_broot.unwrap_or(true),
                 verb_conf.confirm.unwrap_or(false),
             ) {
-                Ok(v) => {
+                Ok(mut v) => {
+                    if verb_conf.confirm.is_none() {
+                        v.confirm = v.execution.is_confirmed_by_default();
+                    }
+                    v.working_dir = verb_conf.working_dir.clone();
+                    v.env = verb_conf.env.clone();
+                    if verb_conf.key.is_some() && v.args_parser.is_some() {
+                        // there would be no way to give the arguments
+                        eprintln!("Verb error: {:?} takes arguments, it can't have a key", verb_conf.invocation);
+                    } else {
+                        if let Some(key) = verb_conf.key {
+                            // a key bound in conf replaces the default one of a built-in verb
+                            for builtin in self.verbs.iter_mut().filter(|b| b.key == Some(key)) {
+                                if let VerbExecution::Internal(_) = builtin.execution {
+                                    builtin.key = None;
+                                }
+                            }
+                        }
+                        v.key = verb_conf.key;
+                    }
                     self.verbs.push(v);
                 }
                 Err(e) => {
@@ -168,16 +363,14 @@ impl VerbStore {
         }
     }
     pub fn search(&self, prefix: &str) -> PrefixSearchResult<&Verb> {
-        let mut found_index = 0;
-        let mut nb_found = 0;
-        for (index, verb) in self.verbs.iter().enumerate() {
+        let mut found = Vec::new();
+        for verb in &self.verbs {
             if let Some(shortcut) = &verb.shortcut {
                 if shortcut.starts_with(prefix) {
                     if shortcut == prefix {
                         return PrefixSearchResult::Match(&verb);
                     }
-                    found_index = index;
-                    nb_found += 1;
+                    found.push(verb);
                     continue;
                 }
             }
@@ -185,16 +378,123 @@ impl VerbStore {
                 if verb.invocation.key == prefix {
                     return PrefixSearchResult::Match(&verb);
                 }
-                found_index = index;
-                nb_found += 1;
+                found.push(verb);
             }
         }
-        match nb_found {
+        match found.len() {
             0 => PrefixSearchResult::NoMatch,
-            1 => PrefixSearchResult::Match(&self.verbs[found_index]),
-            _ => PrefixSearchResult::TooManyMatches,
+            1 => PrefixSearchResult::Match(found[0]),
+            _ => PrefixSearchResult::TooManyMatches(found),
         }
     }
+    /// the verb executed by the given hot key, if any
+    pub fn hot_verb(&self, key: Key) -> Option<&Verb> {
+        self.verbs.iter().find(|verb| verb.key == Some(key))
+    }
+    /// the keys doing the verb of the given name: the ones handled
+    ///  by the input (eg <esc> for "back"), then the hot keys bound in
+    ///  conf, possibly to another verb calling the same internal
+    pub fn bindings_for(&self, verb: &str) -> Vec<KeyDescription> {
+        let mut keys: Vec<KeyDescription> = self
+            .input_keys
+            .iter()
+            .filter(|(_, name)| *name == verb)
+            .map(|(key, _)| *key)
+            .collect();
+        keys.extend(
+            self.verbs
+                .iter()
+                .filter(|v| {
+                    v.invocation.key == verb
+                        || match v.execution {
+                            VerbExecution::Internal(internal) => internal.key() == verb,
+                            VerbExecution::External(_) => false,
+                        }
+                })
+                .filter_map(|v| v.key)
+                .map(KeyDescription::Key),
+        );
+        keys
+    }
+    /// the first key doing the verb of the given name, if any
+    pub fn binding_for(&self, verb: &str) -> Option<KeyDescription> {
+        self.bindings_for(verb).into_iter().next()
+    }
+    /// the keys handled by the input which don't do a verb, with
+    ///  the name of their action, eg ("ctrl-f", "flags_menu")
+    pub fn action_keys(&self) -> Vec<(KeyDescription, &'static str)> {
+        self.input_keys
+            .iter()
+            .filter(|(_, name)| !self.verbs.iter().any(|v| v.invocation.key == *name))
+            .cloned()
+            .collect()
+    }
+    /// how a status hint tells to do the verb: with its key when
+    ///  there's one (eg "<esc>"), else with its name (eg "':total_search'")
+    pub fn hint_for(&self, verb: &str) -> String {
+        match self.binding_for(verb) {
+            Some(key) => key.hint(),
+            None => format!("':{}'", verb),
+        }
+    }
+    /// the status to display while a verb invocation is being typed,
+    ///  telling what <enter> would do on the given file. Err is for
+    ///  a status to display as an error.
+    /// The paths given to the shell are relative to base, when it's given
+    pub fn edition_status(
+        &self,
+        invocation: &VerbInvocation,
+        file: &Path,
+        root: &Path,
+        base: Option<&Path>,
+    ) -> Result<String, String> {
+        match self.search(&invocation.key) {
+            PrefixSearchResult::NoMatch => {
+                Err("No matching verb (':?' for the list of verbs)".to_string())
+            }
+            PrefixSearchResult::Match(verb) => match verb.match_error(invocation) {
+                Some(err) => Err(err),
+                None => match verb.program_error(file, root, &invocation.args) {
+                    Some(err) if !invocation.forced => Err(format!("warning: {}", err)),
+                    _ => Ok(verb.preview(file, root, &invocation.args, base)),
+                },
+            },
+            PrefixSearchResult::TooManyMatches(_) if invocation.key.is_empty() => {
+                Ok("Type a verb then <enter> to execute it (':?' for the list of verbs)".to_string())
+            }
+            PrefixSearchResult::TooManyMatches(verbs) => {
+                let candidates: Vec<String> = verbs
+                    .iter()
+                    .map(|v| match &v.description {
+                        Some(description) => format!(
+                            "{} ({})",
+                            v.invocation.key,
+                            status::fit(description, MAX_CANDIDATE_DESCRIPTION_LEN),
+                        ),
+                        None => v.invocation.key.clone(),
+                    })
+                    .collect();
+                Ok(format!("Possible verbs: {}", candidates.join(", ")))
+            }
+        }
+    }
+    /// the status to display while the verb of the invocation waits
+    ///  for the user to confirm it
+    pub fn confirmation_status(
+        &self,
+        invocation: &VerbInvocation,
+        file: &Path,
+        root: &Path,
+        base: Option<&Path>,
+    ) -> String {
+        let command = match self.search(&invocation.key) {
+            PrefixSearchResult::Match(verb) => {
+                verb.command_preview(file, root, &invocation.args, base)
+            }
+            _ => invocation.key.clone(),
+        };
+        format!("confirm: {} — press y to proceed, any other key to cancel", command)
+    }
     // return the index of the verb having the long key. This function is meant
     // for internal access when it's sure it can't failed (i.e. for a builtin)
     // It looks for verbs by key, starting from the builtins, to
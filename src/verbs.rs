@@ -7,13 +7,18 @@ use std::collections::HashMap;
 use std::fs::OpenOptions;
 use std::io::{self, Write};
 use std::path::{Path, PathBuf};
+use termion::event::Key;
 
 use crate::app::AppStateCmdResult;
 use crate::app_context::AppContext;
+use crate::commands::{Action, Command, KeyDescription};
 use crate::errors::ConfError;
 use crate::external;
+use crate::internals::Internal;
+use crate::permissions::ChmodArgs;
 use crate::screens::Screen;
 use crate::verb_invocation::VerbInvocation;
+use crate::verb_store::PrefixSearchResult;
 
 // what makes a verb.
 //
@@ -26,11 +31,80 @@ pub struct Verb {
     pub invocation: VerbInvocation,// how the verb is supposed to be called (key may be replaced by shortcut)
     pub args_parser: Option<Regex>,
     pub shortcut: Option<String>,  // a shortcut, eg "c"
-    pub execution: String,         // a pattern usable for execution, eg ":quit" or "less {file}"
+    pub execution: VerbExecution,  // eg Internal(Internal::Quit) or External("less {file}")
     pub description: Option<String>, // a description for the user
     pub from_shell: bool, // whether it must be launched from the parent shell (eg because it's a shell function)
     pub leave_broot: bool, // only defined for external
-    pub confirm: bool,
+    pub confirm: bool, // whether the user must confirm the execution with a 'y'
+    pub working_dir: Option<String>, // a pattern for the directory of execution, eg "{root}"
+    pub env: Vec<(String, String)>, // variables added to the environment of the program
+    pub key: Option<Key>, // a key executing the verb, even while a pattern is typed
+}
+
+// the built-in behaviors which ask for a confirmation, unless
+//  the conf of the verb says otherwise (:chmod only when recursive)
+const CONFIRMED_INTERNALS: &[Internal] = &[
+    Internal::Chmod,
+    Internal::Hardlink,
+    Internal::PasteOverwrite,
+];
+
+// the built-in behaviors whose argument may be omitted, eg
+//  `:focus` applying to the selection when no path is given
+const OPTIONAL_ARGS_INTERNALS: &[Internal] = &[Internal::Focus];
+
+// the internals launching something or printing a path, which
+//  are disabled in picker mode
+const LAUNCHING_INTERNALS: &[Internal] = &[
+    Internal::EditConf,
+    Internal::Open,
+    Internal::OpenLater,
+    Internal::OpenLeave,
+    Internal::OpenStay,
+    Internal::PrintPath,
+    Internal::PrintRelativePath,
+];
+
+/// what a verb does: either a behavior of broot, or the
+///  execution of an external program (from a pattern)
+#[derive(Debug, Clone)]
+pub enum VerbExecution {
+    Internal(Internal),
+    External(String),
+}
+
+impl VerbExecution {
+    /// parse the execution of a verb, as written in conf, eg ":quit" or "less {file}"
+    pub fn from(execution: &str) -> Result<VerbExecution, ConfError> {
+        if execution.starts_with(':') {
+            Ok(VerbExecution::Internal(execution[1..].parse()?))
+        } else {
+            Ok(VerbExecution::External(execution.to_string()))
+        }
+    }
+    /// the execution, as written in conf
+    pub fn as_str(&self) -> &str {
+        match self {
+            VerbExecution::Internal(internal) => internal.execution(),
+            VerbExecution::External(pattern) => &pattern,
+        }
+    }
+    /// whether a verb doing this must be confirmed when its conf
+    ///  doesn't tell
+    pub fn is_confirmed_by_default(&self) -> bool {
+        match self {
+            VerbExecution::Internal(internal) => CONFIRMED_INTERNALS.contains(internal),
+            VerbExecution::External(_) => false,
+        }
+    }
+    /// whether a verb doing this launches a program, or quits
+    ///  printing a path
+    pub fn is_launching(&self) -> bool {
+        match self {
+            VerbExecution::Internal(internal) => LAUNCHING_INTERNALS.contains(internal),
+            VerbExecution::External(_) => true,
+        }
+    }
 }
 
 lazy_static! {
@@ -39,19 +113,87 @@ lazy_static! {
 
 pub trait VerbExecutor {
     fn execute_verb(
-        &self,
+        &mut self,
         verb: &Verb,
         invocation: &VerbInvocation,
         screen: &mut Screen,
         con: &AppContext,
     ) -> io::Result<AppStateCmdResult>;
+    /// execute the verb, unless it must be confirmed first: the command
+    ///  then waits for the key answering the confirmation
+    fn execute_or_confirm(
+        &mut self,
+        verb: &Verb,
+        invocation: &VerbInvocation,
+        cmd: &mut Command,
+        screen: &mut Screen,
+        con: &AppContext,
+    ) -> io::Result<AppStateCmdResult> {
+        if con.launch_args.picker && verb.execution.is_launching() {
+            Ok(AppStateCmdResult::disabled_in_picker(&invocation.key))
+        } else if verb.must_confirm(invocation) && verb.match_error(invocation).is_none() {
+            cmd.action = Action::Confirm(invocation.clone());
+            Ok(AppStateCmdResult::Keep)
+        } else {
+            self.execute_verb(verb, invocation, screen, con)
+        }
+    }
+    /// apply the actions handled the same way in all states: the
+    ///  answers to a confirmation, the hot keys, and the keys or
+    ///  chords bound to nothing
+    fn apply_verb_action(
+        &mut self,
+        cmd: &mut Command,
+        screen: &mut Screen,
+        con: &AppContext,
+    ) -> io::Result<AppStateCmdResult> {
+        Ok(match &cmd.action {
+            Action::ConfirmAnswer(invocation, true) => match con.verb_store.search(&invocation.key) {
+                PrefixSearchResult::Match(verb) => {
+                    let invocation = invocation.clone();
+                    self.execute_verb(verb, &invocation, screen, con)?
+                }
+                _ => AppStateCmdResult::verb_not_found(&invocation.key),
+            },
+            Action::ConfirmAnswer(invocation, false) => {
+                AppStateCmdResult::DisplayMessage(format!(":{} cancelled", invocation.key))
+            }
+            Action::HotKey(key) => match con.verb_store.hot_verb(*key) {
+                Some(verb) => {
+                    let invocation = VerbInvocation::from(&verb.invocation.key);
+                    self.execute_or_confirm(verb, &invocation, cmd, screen, con)?
+                }
+                None => AppStateCmdResult::unbound_key(&KeyDescription::Key(*key).to_string()),
+            },
+            Action::UnboundKey(key) => AppStateCmdResult::unbound_key(key),
+            Action::UnboundChord(leader, c) => {
+                AppStateCmdResult::DisplayError(format!("unbound chord {} {}", leader, c))
+            }
+            _ => AppStateCmdResult::Keep,
+        })
+    }
 }
 
-fn make_invocation_args_regex(spec: &str) -> Result<Regex, ConfError> {
+fn make_invocation_args_regex(spec: &str, optional: bool) -> Result<Regex, ConfError> {
     let spec = GROUP.replace_all(spec, r"(?P<$1>.+)");
-    let spec = format!("^{}$", spec);
+    let spec = if optional {
+        format!("^(?:{})?$", spec)
+    } else {
+        format!("^{}$", spec)
+    };
     Regex::new(&spec.to_string()).or_else(|_| Err(ConfError::InvalidVerbInvocation{invocation: spec}))
 }
+// replace the {name} groups found in the map, leaving the other ones untouched
+fn replace_groups(pattern: &str, map: &HashMap<String, String>) -> String {
+    GROUP.replace_all(pattern, |ec: &Captures| {
+        let name = ec.get(1).unwrap().as_str();
+        if let Some(cap) = map.get(name) {
+            cap.as_str().to_string()
+        } else {
+            format!("{{{}}}", name)
+        }
+    }).to_string()
+}
 fn path_to_string(path: &Path, for_shell: bool) -> String {
     if for_shell {
         external::escape_for_shell(path)
@@ -77,39 +219,50 @@ impl Verb {
             });
         }
         let args_parser = match &invocation.args {
-            Some(ref args) => Some(make_invocation_args_regex(&args)?),
+            Some(ref args) => Some(make_invocation_args_regex(&args, false)?),
             None => None,
         };
         Ok(Verb {
             invocation,
             args_parser,
             shortcut,
-            execution,
+            execution: VerbExecution::from(&execution)?,
             description,
             from_shell,
             leave_broot,
             confirm,
+            working_dir: None,
+            env: Vec::new(),
+            key: None,
         })
     }
     // built-ins are verbs offering a logic other than the execution
-    //  based on exec_pattern. They mostly modify the appstate
+    //  based on exec_pattern. They mostly modify the appstate.
+    // The invocation may declare arguments, eg "chmod {mode}"
     pub fn create_builtin(
-        key: &str,
+        invocation_str: &str,
         shortcut: Option<String>,
         description: &str,
     ) -> Verb {
+        let invocation = VerbInvocation::from(invocation_str);
+        let internal = invocation.key.parse().expect("unknown built-in verb");
+        let args_parser = invocation.args.as_ref().map(|args| {
+            make_invocation_args_regex(&args, OPTIONAL_ARGS_INTERNALS.contains(&internal))
+                .expect("invalid built-in verb invocation")
+        });
+        let execution = VerbExecution::Internal(internal);
         Verb {
-            invocation: VerbInvocation{
-                key:key.to_string(),
-                args:None,
-            },
-            args_parser: None,
+            confirm: execution.is_confirmed_by_default(),
+            execution,
+            invocation,
+            args_parser,
             shortcut,
-            execution: format!(":{}", key),
             description: Some(description.to_string()),
             from_shell: false,
             leave_broot: true, // ignored
-            confirm: false, // ignored
+            working_dir: None, // ignored
+            env: Vec::new(), // ignored
+            key: None,
         }
     }
 
@@ -132,16 +285,29 @@ impl Verb {
         }
     }
 
-    fn replacement_map(&self, file: &Path, args: &Option<String>, for_shell: bool) -> HashMap<String, String> {
+    // the paths are made relative to base when it's given
+    fn replacement_map(
+        &self,
+        file: &Path,
+        root: &Path,
+        args: &Option<String>,
+        for_shell: bool,
+        base: Option<&Path>,
+    ) -> HashMap<String, String> {
         let mut map = HashMap::new();
-        // first we add the replacements computed from the given path
+        // first we add the replacements computed from the given paths
+        let path_to_string = |path: &Path| match base {
+            Some(base) => path_to_string(&external::relative_path(path, base), for_shell),
+            None => path_to_string(path, for_shell),
+        };
         let parent = file.parent().unwrap();
-        let file_str = path_to_string(file, for_shell);
-        let parent_str = path_to_string(parent, for_shell);
+        let file_str = path_to_string(file);
+        let parent_str = path_to_string(parent);
         map.insert("file".to_string(), file_str.to_string());
         map.insert("parent".to_string(), parent_str.to_string());
         let dir_str = if file.is_dir() { file_str } else { parent_str };
         map.insert("directory".to_string(), dir_str.to_string());
+        map.insert("root".to_string(), path_to_string(root));
         // then the ones computed from the user input
         if let Some(args) = args {
             if let Some(r) = &self.args_parser {
@@ -159,47 +325,174 @@ impl Verb {
         }
         map
     }
-    pub fn description_for(&self, path: PathBuf, args: &Option<String>) -> String {
+    /// the description given in conf (or for a built-in), or else
+    ///  the execution, as written in conf
+    pub fn description_or_execution(&self) -> &str {
+        match &self.description {
+            Some(description) => description,
+            None => self.execution.as_str(),
+        }
+    }
+    pub fn description_for(
+        &self,
+        path: PathBuf,
+        root: &Path,
+        args: &Option<String>,
+        base: Option<&Path>,
+    ) -> String {
         if let Some(s) = &self.description {
             s.clone()
         } else {
-            self.shell_exec_string(&path, args)
+            self.shell_exec_string(&path, root, args, base)
+        }
+    }
+    /// tell what the verb would do on the file, without executing it. The
+    ///  paths given to the shell are relative to base, when it's given
+    pub fn preview(
+        &self,
+        file: &Path,
+        root: &Path,
+        args: &Option<String>,
+        base: Option<&Path>,
+    ) -> String {
+        match &self.execution {
+            VerbExecution::Internal(_) => format!(
+                "Hit <enter> to {} : {}",
+                &self.invocation.key,
+                self.description_for(file.to_path_buf(), root, args, base),
+            ),
+            VerbExecution::External(_) => format!(
+                "Hit <enter> to {} : {}will run `{}` {}",
+                &self.invocation.key,
+                match &self.description {
+                    Some(description) => format!("{}, ", description),
+                    None => String::new(),
+                },
+                self.command_preview(file, root, args, base),
+                if self.from_shell {
+                    "in your shell, quitting broot"
+                } else if self.leave_broot {
+                    "and quit broot"
+                } else {
+                    "then come back to broot"
+                },
+            ),
+        }
+    }
+    /// whether this invocation must be confirmed before its execution.
+    /// A :chmod changing a single entry isn't
+    pub fn must_confirm(&self, invocation: &VerbInvocation) -> bool {
+        match self.execution {
+            VerbExecution::Internal(Internal::Chmod) => {
+                self.confirm
+                    && ChmodArgs::from(&invocation.args).map_or(false, |args| args.recursive)
+            }
+            _ => self.confirm,
+        }
+    }
+    /// the command the verb would run on the file, its placeholders
+    ///  replaced, or its invocation and the file for a built-in
+    pub fn command_preview(
+        &self,
+        file: &Path,
+        root: &Path,
+        args: &Option<String>,
+        base: Option<&Path>,
+    ) -> String {
+        match &self.execution {
+            VerbExecution::Internal(_) => {
+                let file = match base {
+                    Some(base) => external::relative_path(file, base),
+                    None => file.to_path_buf(),
+                };
+                match args {
+                    Some(args) => format!(":{} {} on {}", self.invocation.key, args, file.to_string_lossy()),
+                    None => format!(":{} on {}", self.invocation.key, file.to_string_lossy()),
+                }
+            }
+            VerbExecution::External(_) if self.from_shell => {
+                // the shell will resolve the env variables
+                self.shell_exec_string(file, root, args, base)
+            }
+            VerbExecution::External(_) => self
+                .exec_token(file, root, args)
+                .into_iter()
+                .map(external::resolve_env_variable)
+                .collect::<Vec<String>>()
+                .join(" "),
         }
     }
     // build the token which can be used to launch en executable.
     // This doesn't make sense for a built-in.
-    pub fn exec_token(&self, file: &Path, args: &Option<String>) -> Vec<String> {
-        let map = self.replacement_map(file, args, false);
+    pub fn exec_token(&self, file: &Path, root: &Path, args: &Option<String>) -> Vec<String> {
+        let map = self.replacement_map(file, root, args, false, None);
         self.execution
+            .as_str()
             .split_whitespace()
-            .map(|token| {
-                GROUP.replace_all(token, |ec:&Captures| {
-                    let name = ec.get(1).unwrap().as_str();
-                    if let Some(cap) = map.get(name) {
-                        cap.as_str().to_string()
-                    } else {
-                        format!("{{{}}}", name)
-                    }
-                }).to_string()
-            })
+            .map(|token| replace_groups(token, &map))
             .collect()
     }
-    // build a shell compatible command, with escapings
-    pub fn shell_exec_string(&self, file: &Path, args: &Option<String>) -> String {
-        let map = self.replacement_map(file, args, true);
-        GROUP.replace_all(&self.execution, |ec:&Captures| {
-            let name = ec.get(1).unwrap().as_str();
-            if let Some(cap) = map.get(name) {
-                cap.as_str().to_string()
-            } else {
-                format!("{{{}}}", name)
+    // the directory in which the program must be executed: the closest
+    //  directory of the selection, unless the verb has a working_dir
+    //  pattern (a relative one being resolved from the selection)
+    pub fn exec_dir(&self, file: &Path, root: &Path, args: &Option<String>) -> PathBuf {
+        let dir = if file.is_dir() { file } else { file.parent().unwrap() };
+        match &self.working_dir {
+            Some(pattern) => {
+                let map = self.replacement_map(file, root, args, false, None);
+                dir.join(replace_groups(pattern, &map))
             }
-        })
-        .to_string()
-        .split_whitespace()
-        .map(|token| {
+            None => dir.to_path_buf(),
+        }
+    }
+    /// tell why the program of the verb can't be launched, if it's not
+    ///  found. The verbs executed by the shell aren't checked, as they may
+    ///  be shell functions
+    pub fn program_error(&self, file: &Path, root: &Path, args: &Option<String>) -> Option<String> {
+        if self.from_shell {
+            return None;
+        }
+        match &self.execution {
+            VerbExecution::Internal(_) => None,
+            VerbExecution::External(_) => {
+                let exe = self.exec_token(file, root, args).into_iter().next()?;
+                external::Launchable::resolve_program(&exe).err()
+            }
+        }
+    }
+    // build the launchable of a verb defined with an exec pattern.
+    pub fn to_launchable(
+        &self,
+        file: &Path,
+        root: &Path,
+        args: &Option<String>,
+    ) -> io::Result<external::Launchable> {
+        external::Launchable::program(
+            self.exec_token(file, root, args),
+            Some(self.exec_dir(file, root, args)),
+            self.env.clone(),
+        )
+    }
+    // build a shell compatible command, with escapings. The variables
+    //  of the verb's environment are given as assignments before it.
+    // As the shell runs in the launch directory, the paths may be made
+    //  relative to it (base), else they're canonicalized
+    pub fn shell_exec_string(
+        &self,
+        file: &Path,
+        root: &Path,
+        args: &Option<String>,
+        base: Option<&Path>,
+    ) -> String {
+        let map = self.replacement_map(file, root, args, true, base);
+        let assignments = self
+            .env
+            .iter()
+            .map(|(k, v)| format!("{}={}", k, external::escape_for_shell(Path::new(v))));
+        let execution = replace_groups(self.execution.as_str(), &map);
+        let tokens = execution.split_whitespace().map(|token| {
             let path = Path::new(token);
-            if path.exists() {
+            if base.is_none() && path.exists() {
                 if let Ok(path) = path.canonicalize() {
                     if let Some(path) = path.to_str() {
                         return path.to_string();
@@ -207,44 +500,62 @@ impl Verb {
                 }
             }
             token.to_string()
-        })
-        .collect::<Vec<String>>()
-        .join(" ")
+        });
+        assignments.chain(tokens).collect::<Vec<String>>().join(" ")
     }
     // build the cmd result for a verb defined with an exec pattern.
     // Calling this function on a built-in doesn't make sense
     pub fn to_cmd_result(
         &self,
         file: &Path,
-        args: &Option<String>,
+        root: &Path,
+        invocation: &VerbInvocation,
         _screen: &mut Screen,
         con: &AppContext
     ) -> io::Result<AppStateCmdResult> {
+        let args = &invocation.args;
+        if !invocation.forced {
+            // broot stays open, rather than quitting to a failed launch
+            if let Some(err) = self.program_error(file, root, args) {
+                return Ok(AppStateCmdResult::DisplayError(format!(
+                    "{}: type '{}!' to launch it anyway",
+                    err, &invocation.key,
+                )));
+            }
+        }
         Ok(if self.from_shell {
             if let Some(ref export_path) = con.launch_args.cmd_export_path {
                 // new version of the br function: the whole command is exported
                 // in the passed file
                 let f = OpenOptions::new().append(true).open(export_path)?;
-                writeln!(&f, "{}", self.shell_exec_string(file, args))?;
-                AppStateCmdResult::Quit
+                writeln!(&f, "{}", self.shell_exec_string(file, root, args, con.relative_base()))?;
+                AppStateCmdResult::QuitWithExport
             } else if let Some(ref export_path) = con.launch_args.file_export_path {
                 // old version of the br function: only the file is exported
                 // in the passed file
-                let f = OpenOptions::new().append(true).open(export_path)?;
-                writeln!(&f, "{}", file.to_string_lossy())?;
-                AppStateCmdResult::Quit
+                let file = match con.relative_base() {
+                    Some(base) => external::relative_path(file, base),
+                    None => file.to_path_buf(),
+                };
+                match external::export_line(export_path, &file.to_string_lossy()) {
+                    Ok(()) => AppStateCmdResult::QuitWithExport,
+                    Err(e) => AppStateCmdResult::DisplayError(e.to_string()),
+                }
             } else {
                 AppStateCmdResult::DisplayError(
                     "this verb needs broot to be launched as `br`. Try `broot --install` if necessary.".to_string()
                 )
             }
         } else {
-            let launchable = external::Launchable::program(self.exec_token(file, args))?;
+            let launchable = self.to_launchable(file, root, args)?;
             if self.leave_broot {
-                AppStateCmdResult::Launch(launchable)
+                AppStateCmdResult::Launch {
+                    launchable,
+                    quit: true,
+                }
             } else {
                 info!("Executing not leaving, launchable {:?}", launchable);
-                let execution = launchable.execute();
+                let execution = launchable.execute_staying();
                 match execution {
                     Ok(()) => {
                         info!("ok");
@@ -260,3 +571,57 @@ impl Verb {
     }
 }
 
+
+#[cfg(test)]
+mod working_dir_tests {
+    use super::*;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+    use tempfile::{tempdir, TempDir};
+
+    // a tree with a script writing its working directory in the file
+    //  given as argument, and a sub directory containing a file
+    fn pwd_tree() -> (TempDir, PathBuf) {
+        let dir = tempdir().unwrap();
+        let script = dir.path().join("pwd.sh");
+        fs::write(&script, "#!/bin/sh\npwd -P > \"$1\"\n").unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+        fs::create_dir_all(dir.path().join("sub/deeper")).unwrap();
+        fs::write(dir.path().join("sub/file.txt"), "").unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        (dir, root)
+    }
+
+    // execute the verb on the selection and return the directory seen by the script
+    fn executed_dir(verb: &Verb, selection: &Path, root: &Path) -> PathBuf {
+        let launchable = verb.to_launchable(selection, root, &None).unwrap();
+        launchable.execute().unwrap();
+        let output = fs::read_to_string(root.join("pwd.txt")).unwrap();
+        PathBuf::from(output.trim_end())
+    }
+
+    fn pwd_verb(root: &Path, working_dir: Option<&str>) -> Verb {
+        let execution = format!("{} {}", root.join("pwd.sh").display(), root.join("pwd.txt").display());
+        let mut verb = Verb::create_external("pwd", None, execution, None, false, true, false).unwrap();
+        verb.working_dir = working_dir.map(|s| s.to_string());
+        verb
+    }
+
+    #[test]
+    fn selection_dir() {
+        let (_dir, root) = pwd_tree();
+        let verb = pwd_verb(&root, None);
+        assert_eq!(executed_dir(&verb, &root.join("sub/file.txt"), &root), root.join("sub"));
+        assert_eq!(executed_dir(&verb, &root.join("sub/deeper"), &root), root.join("sub/deeper"));
+    }
+
+    #[test]
+    fn configured_dir() {
+        let (_dir, root) = pwd_tree();
+        let selection = root.join("sub/file.txt");
+        let verb = pwd_verb(&root, Some("{root}"));
+        assert_eq!(executed_dir(&verb, &selection, &root), root);
+        let verb = pwd_verb(&root, Some("deeper"));
+        assert_eq!(executed_dir(&verb, &selection, &root), root.join("sub/deeper"));
+    }
+}
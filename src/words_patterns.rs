@@ -0,0 +1,80 @@
+//! a pattern made of several words, each one being a fuzzy pattern,
+//!  which must all be found in the candidate, in any order.
+//! With "app rs", both "app.rs" and "rs_app" match.
+
+use crate::fuzzy_patterns::FuzzyPattern;
+use crate::patterns::Match;
+use std::fmt;
+
+#[derive(Debug, Clone)]
+pub struct WordsPattern {
+    words: Vec<FuzzyPattern>,
+}
+
+impl fmt::Display for WordsPattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (idx, word) in self.words.iter().enumerate() {
+            if idx > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{}", word)?;
+        }
+        Ok(())
+    }
+}
+
+impl WordsPattern {
+    /// build a pattern from whitespace separated words
+    pub fn from(pat: &str) -> WordsPattern {
+        WordsPattern {
+            words: pat.split_whitespace().map(FuzzyPattern::from).collect(),
+        }
+    }
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+    // return a match if all the words can be found in the candidate.
+    // The score is the sum of the scores of the words, and the positions
+    //  are the union of their positions, sorted, so that the chars matched
+    //  by several words are wrapped only once.
+    pub fn find(&self, candidate: &str) -> Option<Match> {
+        if self.words.is_empty() {
+            return None;
+        }
+        let mut score = 0;
+        let mut pos = Vec::new();
+        for word in &self.words {
+            let m = word.find(candidate)?;
+            score += m.score;
+            pos.extend(m.pos);
+        }
+        pos.sort();
+        pos.dedup();
+        Some(Match { score, pos })
+    }
+    // return the number of results we should find before starting to
+    //  sort them (unless time is runing out).
+    pub(crate) fn optimal_result_number(&self, targeted_size: usize) -> usize {
+        20 * targeted_size
+    }
+}
+
+#[cfg(test)]
+mod words_patterns_tests {
+
+    use super::*;
+
+    #[test]
+    fn words_match_in_any_order() {
+        let wp = WordsPattern::from("app rs");
+        assert!(wp.find("app.rs").is_some());
+        assert!(wp.find("rs_app").is_some());
+        assert!(wp.find("app.toml").is_none());
+        let m = wp.find("rs_app").unwrap();
+        assert_eq!(m.pos, vec![0, 1, 3, 4, 5]);
+        assert_eq!(
+            m.wrap_matching_chars("rs_app", "<", ">"),
+            "<r><s>_<a><p><p>",
+        );
+    }
+}
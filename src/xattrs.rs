@@ -0,0 +1,130 @@
+//! The extended attributes of files, which are signaled, like ls does,
+//!  with a char after the mode: '+' for a POSIX ACL, '.' for a SELinux
+//!  context, and '@' (like on macOS) for the other attributes.
+
+use std::ffi::CString;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+use std::ptr;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExtendedAttributes {
+    None,    // or not read
+    Acl,     // a POSIX ACL, which may give more rights than the mode tells
+    SeLinux, // a SELinux security context, and nothing else
+    Other,   // other attributes, eg in the "user" namespace
+}
+
+impl ExtendedAttributes {
+    /// read the names of the extended attributes of the file (not following
+    /// symlinks). Any failure (eg attributes not supported) is read as None
+    pub fn of(path: &Path) -> ExtendedAttributes {
+        let c_path = match CString::new(path.as_os_str().as_bytes()) {
+            Ok(c_path) => c_path,
+            Err(_) => {
+                return ExtendedAttributes::None;
+            }
+        };
+        // a first call gives the size of the list
+        let size = unsafe { libc::llistxattr(c_path.as_ptr(), ptr::null_mut(), 0) };
+        if size <= 0 {
+            return ExtendedAttributes::None;
+        }
+        let mut names = vec![0u8; size as usize];
+        let size = unsafe {
+            libc::llistxattr(
+                c_path.as_ptr(),
+                names.as_mut_ptr() as *mut libc::c_char,
+                names.len(),
+            )
+        };
+        if size <= 0 {
+            return ExtendedAttributes::None;
+        }
+        names.truncate(size as usize);
+        ExtendedAttributes::from_names(
+            names
+                .split(|&b| b == 0)
+                .filter(|name| !name.is_empty())
+                .map(|name| String::from_utf8_lossy(name).to_string()),
+        )
+    }
+    /// the most important kind of attribute among the given names
+    pub(crate) fn from_names<I: Iterator<Item = String>>(names: I) -> ExtendedAttributes {
+        let mut xattrs = ExtendedAttributes::None;
+        for name in names {
+            match name.as_str() {
+                "system.posix_acl_access" | "system.posix_acl_default" => {
+                    return ExtendedAttributes::Acl;
+                }
+                "security.selinux" => {
+                    if xattrs == ExtendedAttributes::None {
+                        xattrs = ExtendedAttributes::SeLinux;
+                    }
+                }
+                _ => {
+                    xattrs = ExtendedAttributes::Other;
+                }
+            }
+        }
+        xattrs
+    }
+    /// the char written after the mode
+    pub fn mark(self) -> char {
+        match self {
+            ExtendedAttributes::None => ' ',
+            ExtendedAttributes::Acl => '+',
+            ExtendedAttributes::SeLinux => '.',
+            ExtendedAttributes::Other => '@',
+        }
+    }
+    /// what the mark means, to be displayed in the status
+    pub fn description(self) -> Option<&'static str> {
+        match self {
+            ExtendedAttributes::None => None,
+            ExtendedAttributes::Acl => Some("'+': a POSIX ACL may change the permissions"),
+            ExtendedAttributes::SeLinux => Some("'.': the file has a SELinux security context"),
+            ExtendedAttributes::Other => Some("'@': the file has extended attributes"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod xattrs_tests {
+    use super::*;
+
+    fn from_names(names: &[&str]) -> ExtendedAttributes {
+        ExtendedAttributes::from_names(names.iter().map(|s| s.to_string()))
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(from_names(&[]), ExtendedAttributes::None);
+        assert_eq!(from_names(&["security.selinux"]), ExtendedAttributes::SeLinux);
+        assert_eq!(
+            from_names(&["security.selinux", "user.xdg.origin.url"]),
+            ExtendedAttributes::Other,
+        );
+        assert_eq!(
+            from_names(&["user.comment", "system.posix_acl_access", "security.selinux"]),
+            ExtendedAttributes::Acl,
+        );
+        assert_eq!(from_names(&["system.posix_acl_default"]), ExtendedAttributes::Acl);
+    }
+
+    #[test]
+    fn plain_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("plain");
+        std::fs::write(&path, "").unwrap();
+        // a fresh file may only have a SELinux context
+        assert!(match ExtendedAttributes::of(&path) {
+            ExtendedAttributes::None | ExtendedAttributes::SeLinux => true,
+            _ => false,
+        });
+        assert_eq!(
+            ExtendedAttributes::of(&dir.path().join("missing")),
+            ExtendedAttributes::None
+        );
+    }
+}
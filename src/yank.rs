@@ -0,0 +1,402 @@
+//! The paths yanked with :cp or :mv, waiting for a :paste,
+//!  and the job doing the copy or move.
+//! The yank is kept out of the states so that it's still
+//!  there after a focus in another directory.
+
+use std::fs;
+use std::io;
+use std::os::unix;
+use std::os::unix::fs::FileTypeExt;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::Mutex;
+
+use crate::task_sync::TaskLifetime;
+
+// the error code of a rename which would cross filesystems
+const EXDEV: i32 = 18;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum YankMode {
+    Copy,
+    Move,
+}
+
+#[derive(Debug, Clone)]
+pub struct Yank {
+    pub mode: YankMode,
+    pub paths: Vec<PathBuf>,
+}
+
+/// what to do when a pasted entry has the name of an existing one
+/// (without the "!" asking for an overwrite)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PasteConflict {
+    Ask,    // refuse, telling the user about :paste!
+    Rename, // add a numeric suffix to the name
+}
+
+lazy_static! {
+    static ref YANK_MUTEX: Mutex<Option<Yank>> = Mutex::new(None);
+}
+
+pub fn set(yank: Yank) {
+    *YANK_MUTEX.lock().unwrap() = Some(yank);
+}
+
+pub fn get() -> Option<Yank> {
+    YANK_MUTEX.lock().unwrap().clone()
+}
+
+pub fn clear() {
+    *YANK_MUTEX.lock().unwrap() = None;
+}
+
+/// a short text for the flags area, eg "1 path yanked"
+pub fn description() -> Option<String> {
+    get().map(|yank| match yank.paths.len() {
+        1 => "1 path yanked".to_string(),
+        n => format!("{} paths yanked", n),
+    })
+}
+
+// the first non existing path made from the given one by adding
+//  a numeric suffix to the stem, eg "file_2.txt"
+fn free_path(path: &Path) -> PathBuf {
+    let parent = path.parent().unwrap_or_else(|| Path::new("/"));
+    let stem = path
+        .file_stem()
+        .map_or(String::new(), |s| s.to_string_lossy().to_string());
+    let ext = path
+        .extension()
+        .map_or(String::new(), |e| format!(".{}", e.to_string_lossy()));
+    let mut i = 1;
+    loop {
+        let candidate = parent.join(format!("{}_{}{}", stem, i, ext));
+        if fs::symlink_metadata(&candidate).is_err() {
+            return candidate;
+        }
+        i += 1;
+    }
+}
+
+// whether the entry is a fifo, a socket or a device
+fn is_special(ft: fs::FileType) -> bool {
+    ft.is_fifo() || ft.is_socket() || ft.is_block_device() || ft.is_char_device()
+}
+
+fn remove(path: &Path) -> io::Result<()> {
+    if fs::symlink_metadata(path)?.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    }
+}
+
+/// a free hidden name next to the given path, for an entry
+///  which must be written before it replaces this path
+pub fn temp_path(path: &Path) -> PathBuf {
+    let parent = path.parent().unwrap_or_else(|| Path::new("/"));
+    let name = path
+        .file_name()
+        .map_or(String::new(), |n| n.to_string_lossy().to_string());
+    let mut i = 0;
+    loop {
+        let candidate = parent.join(format!(".{}.broot-{}-{}", name, process::id(), i));
+        if fs::symlink_metadata(&candidate).is_err() {
+            return candidate;
+        }
+        i += 1;
+    }
+}
+
+// replace the entry at dst with the one at src, the old one
+//  being removed only when the new one is in place
+fn replace(src: &Path, dst: &Path) -> io::Result<()> {
+    let old = temp_path(dst);
+    fs::rename(dst, &old)?;
+    if let Err(e) = fs::rename(src, dst) {
+        let _ = fs::rename(&old, dst);
+        return Err(e);
+    }
+    remove(&old)
+}
+
+/// the copy or move of the yanked paths into a directory,
+///  done in as many steps as necessary
+pub struct PasteJob {
+    mode: YankMode,
+    copies: Vec<(PathBuf, PathBuf)>,       // the copies which remain to be done
+    to_remove: Vec<PathBuf>,               // sources of moves done by copying
+    replacements: Vec<(PathBuf, PathBuf)>, // temporary copies, with the entries they overwrite
+    dir_permissions: Vec<(PathBuf, fs::Permissions)>, // set when the content is copied
+    pub targets: Vec<PathBuf>,             // the top level pasted entries
+    pub nb_copied: usize,                  // number of copied files
+    pub nb_errors: usize,
+}
+
+impl PasteJob {
+    /// prepare the job, checking the conflicts. The moves which
+    ///  can be done by a simple rename are done immediately.
+    pub fn new(
+        yank: &Yank,
+        dir: &Path,
+        overwrite: bool,
+        conflict: PasteConflict,
+    ) -> Result<PasteJob, String> {
+        let mut job = PasteJob {
+            mode: yank.mode,
+            copies: Vec::new(),
+            to_remove: Vec::new(),
+            replacements: Vec::new(),
+            dir_permissions: Vec::new(),
+            targets: Vec::new(),
+            nb_copied: 0,
+            nb_errors: 0,
+        };
+        for src in &yank.paths {
+            let name = match src.file_name() {
+                Some(name) => name,
+                None => {
+                    return Err(format!("{:?} can't be pasted", src));
+                }
+            };
+            if dir.starts_with(src) {
+                return Err(format!("{:?} can't be pasted into itself", src));
+            }
+            let mut dst = dir.join(name);
+            if src.starts_with(&dst) && &dst != src {
+                return Err(format!("{:?} can't be pasted over its parent", src));
+            }
+            let mut replaced = false;
+            if &dst == src {
+                if yank.mode == YankMode::Move {
+                    return Err(format!("{:?} is already there", src));
+                }
+                dst = free_path(&dst);
+            } else if fs::symlink_metadata(&dst).is_ok() {
+                if overwrite {
+                    replaced = true;
+                } else if conflict == PasteConflict::Rename {
+                    dst = free_path(&dst);
+                } else {
+                    return Err(format!(
+                        "{:?} already exists: use `:paste!` to overwrite it",
+                        name
+                    ));
+                }
+            }
+            if yank.mode == YankMode::Move {
+                let moved = if replaced {
+                    replace(src, &dst)
+                } else {
+                    fs::rename(src, &dst)
+                };
+                match moved {
+                    Ok(()) => {
+                        job.targets.push(dst);
+                        continue;
+                    }
+                    Err(ref e) if e.raw_os_error() == Some(EXDEV) => {
+                        // another filesystem: we'll copy then remove
+                        job.to_remove.push(src.to_path_buf());
+                    }
+                    Err(e) => {
+                        return Err(format!("Unable to move {:?}: {}", src, e));
+                    }
+                }
+            }
+            if replaced {
+                // the existing entry is kept until the copy is complete
+                let tmp = temp_path(&dst);
+                job.copies.push((src.to_path_buf(), tmp.clone()));
+                job.replacements.push((tmp, dst.clone()));
+            } else {
+                job.copies.push((src.to_path_buf(), dst.clone()));
+            }
+            job.targets.push(dst);
+        }
+        Ok(job)
+    }
+    pub fn is_done(&self) -> bool {
+        self.copies.is_empty()
+    }
+    fn copy(&mut self, src: &Path, dst: &Path) -> io::Result<()> {
+        let md = fs::symlink_metadata(src)?;
+        if md.file_type().is_symlink() {
+            unix::fs::symlink(fs::read_link(src)?, dst)?;
+        } else if md.is_dir() {
+            fs::create_dir(dst)?;
+            // a read-only directory must stay writable until filled
+            self.dir_permissions.push((dst.to_path_buf(), md.permissions()));
+            for e in fs::read_dir(src)? {
+                let e = e?;
+                self.copies.push((e.path(), dst.join(e.file_name())));
+            }
+        } else if is_special(md.file_type()) {
+            // opening a fifo or a device would block, or read forever
+            warn!("special file {:?} not copied", src);
+            self.nb_errors += 1;
+        } else {
+            fs::copy(src, dst)?;
+            self.nb_copied += 1;
+        }
+        Ok(())
+    }
+    /// copy until it's done or the lifetime expires. When it's done,
+    ///  the overwritten entries are replaced and the sources of
+    ///  cross-filesystem moves are removed, unless there was an error
+    pub fn advance(&mut self, tl: &TaskLifetime) {
+        while let Some((src, dst)) = self.copies.pop() {
+            if tl.is_expired() {
+                self.copies.push((src, dst));
+                return;
+            }
+            if let Err(e) = self.copy(&src, &dst) {
+                warn!("copy of {:?} to {:?} failed: {:?}", src, dst, e);
+                self.nb_errors += 1;
+            }
+        }
+        if self.nb_errors > 0 {
+            // the overwritten entries are kept
+            self.abort();
+        }
+        // the deepest directories are the last ones
+        for (path, permissions) in self.dir_permissions.drain(..).rev() {
+            if let Err(e) = fs::set_permissions(&path, permissions) {
+                warn!("setting permissions of {:?} failed: {:?}", path, e);
+                self.nb_errors += 1;
+            }
+        }
+        for (tmp, dst) in self.replacements.drain(..) {
+            if let Err(e) = replace(&tmp, &dst) {
+                warn!("replacement of {:?} failed: {:?}", dst, e);
+                self.nb_errors += 1;
+            }
+        }
+        if self.nb_errors == 0 {
+            for src in self.to_remove.drain(..) {
+                if let Err(e) = remove(&src) {
+                    warn!("removal of {:?} failed: {:?}", src, e);
+                    self.nb_errors += 1;
+                }
+            }
+        }
+    }
+    /// stop the job, removing the temporary copies so that
+    ///  the entries they'd overwrite are left untouched
+    pub fn abort(&mut self) {
+        self.copies.clear();
+        for (tmp, _) in self.replacements.drain(..) {
+            if let Err(e) = remove(&tmp) {
+                warn!("removal of {:?} failed: {:?}", tmp, e);
+            }
+            self.dir_permissions.retain(|(path, _)| !path.starts_with(&tmp));
+        }
+    }
+    pub fn is_move(&self) -> bool {
+        self.mode == YankMode::Move
+    }
+}
+
+#[cfg(test)]
+mod paste_tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+    use std::os::unix::net::UnixListener;
+    use tempfile::tempdir;
+
+    fn paste(paths: Vec<PathBuf>, dir: &Path) -> Result<PasteJob, String> {
+        let yank = Yank {
+            mode: YankMode::Copy,
+            paths,
+        };
+        let mut job = PasteJob::new(&yank, dir, true, PasteConflict::Ask)?;
+        job.advance(&TaskLifetime::unlimited());
+        Ok(job)
+    }
+
+    fn names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn overwrite_replaces_the_entry() {
+        let dir = tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("src/d")).unwrap();
+        fs::write(root.join("src/d/new.txt"), "new").unwrap();
+        fs::create_dir_all(root.join("dst/d")).unwrap();
+        fs::write(root.join("dst/d/old.txt"), "old").unwrap();
+        let job = paste(vec![root.join("src/d")], &root.join("dst")).unwrap();
+        assert_eq!(job.nb_errors, 0);
+        assert_eq!(names(&root.join("dst")), vec!["d"]);
+        assert_eq!(names(&root.join("dst/d")), vec!["new.txt"]);
+        // a paste over an ancestor of the pasted entry is refused
+        fs::create_dir_all(root.join("dst/d/dst")).unwrap();
+        assert!(paste(vec![root.join("dst/d/dst")], root).is_err());
+        assert!(root.join("dst/d/new.txt").exists());
+    }
+
+    #[test]
+    fn failed_copy_keeps_the_overwritten_entry() {
+        let dir = tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("src/d")).unwrap();
+        fs::write(root.join("src/d/new.txt"), "new").unwrap();
+        // a socket can't be copied
+        let _listener = UnixListener::bind(root.join("src/d/socket")).unwrap();
+        fs::create_dir_all(root.join("dst/d")).unwrap();
+        fs::write(root.join("dst/d/old.txt"), "old").unwrap();
+        let job = paste(vec![root.join("src/d")], &root.join("dst")).unwrap();
+        assert_eq!(job.nb_errors, 1);
+        assert_eq!(names(&root.join("dst")), vec!["d"]);
+        assert_eq!(names(&root.join("dst/d")), vec!["old.txt"]);
+    }
+
+    #[test]
+    fn fifo_in_a_pasted_directory_is_skipped() {
+        let dir = tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("src/d")).unwrap();
+        fs::write(root.join("src/d/a.txt"), "a").unwrap();
+        let made = std::process::Command::new("mkfifo")
+            .arg(root.join("src/d/pipe"))
+            .status()
+            .unwrap();
+        assert!(made.success());
+        fs::create_dir(root.join("dst")).unwrap();
+        // the copy doesn't wait for a writer of the fifo
+        let job = paste(vec![root.join("src/d")], &root.join("dst")).unwrap();
+        assert!(job.is_done());
+        assert_eq!(job.nb_errors, 1);
+        assert_eq!(names(&root.join("dst/d")), vec!["a.txt"]);
+    }
+
+    #[test]
+    fn read_only_directory_is_copied() {
+        let dir = tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("src/d/sub")).unwrap();
+        fs::write(root.join("src/d/sub/a.txt"), "a").unwrap();
+        fs::create_dir(root.join("dst")).unwrap();
+        let read_only = fs::Permissions::from_mode(0o555);
+        fs::set_permissions(root.join("src/d/sub"), read_only.clone()).unwrap();
+        fs::set_permissions(root.join("src/d"), read_only).unwrap();
+        let job = paste(vec![root.join("src/d")], &root.join("dst")).unwrap();
+        assert_eq!(job.nb_errors, 0);
+        assert_eq!(fs::read_to_string(root.join("dst/d/sub/a.txt")).unwrap(), "a");
+        let mode = |p: &str| fs::metadata(root.join(p)).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode("dst/d"), 0o555);
+        assert_eq!(mode("dst/d/sub"), 0o555);
+        // so that the temporary directory can be removed
+        for p in &["src/d", "src/d/sub", "dst/d", "dst/d/sub"] {
+            fs::set_permissions(root.join(p), fs::Permissions::from_mode(0o755)).unwrap();
+        }
+    }
+}
@@ -0,0 +1,34 @@
+//! broot's interface needs stderr to be a terminal. These tests run
+//!  the binary with piped streams, as a script would.
+
+use std::process::{Command, Stdio};
+use tempfile::tempdir;
+
+#[test]
+fn exits_when_stderr_is_not_a_terminal() {
+    let home = tempdir().unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_broot"))
+        .arg(home.path())
+        .env("HOME", home.path())
+        .env_remove("XDG_CONFIG_HOME")
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("broot needs a terminal"), "stderr: {:?}", stderr);
+    // nothing was drawn, and nothing was written on stdout
+    assert!(!stderr.contains('\u{1b}'));
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn help_needs_no_terminal() {
+    let output = Command::new(env!("CARGO_BIN_EXE_broot"))
+        .arg("--help")
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("broot"));
+}